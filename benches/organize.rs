@@ -0,0 +1,152 @@
+//! Benchmarks for the hot paths: scanning, classification, planning, moving (with each
+//! backend) and hashing.
+//!
+//! Trees of 10k files are generated by default; set `FILE_ORGANIZER_BENCH_LARGE=1` to also
+//! run the 100k-file trees (slow to create, so they're opt-in).
+//!
+//! Track regressions against a saved baseline:
+//!
+//! ```text
+//! cargo bench -- --save-baseline main    # on the base branch
+//! cargo bench -- --baseline main         # on your branch
+//! ```
+
+use std::fs;
+use std::hint::black_box;
+use std::path::{Path, PathBuf};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use tempfile::TempDir;
+
+use file_organizer::organizer::file_types::FileTypeClassifier;
+use file_organizer::organizer::hashing::hash_file;
+use file_organizer::organizer::backend::Backend;
+use file_organizer::organizer::config::Config;
+use file_organizer::organizer::{FileOrganizer, OrganizeMode, OrganizeOptions};
+
+const EXTENSIONS: [&str; 12] = [
+    "jpg", "png", "pdf", "txt", "mp4", "mp3", "zip", "rs", "xlsx", "ttf", "epub", "xyz",
+];
+
+/// Files per generated directory
+const FILES_PER_DIR: usize = 100;
+
+/// A generated directory tree that is removed when dropped
+struct Tree {
+    dir: TempDir,
+    files: Vec<PathBuf>,
+}
+
+impl Tree {
+    fn generate(count: usize) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let mut files = Vec::with_capacity(count);
+        for i in 0..count {
+            let folder = dir.path().join(format!("dir-{:04}", i / FILES_PER_DIR));
+            if i % FILES_PER_DIR == 0 {
+                fs::create_dir_all(&folder).unwrap();
+            }
+            let path = folder.join(format!("file-{}.{}", i, EXTENSIONS[i % EXTENSIONS.len()]));
+            fs::write(&path, format!("contents of file {}", i)).unwrap();
+            files.push(path);
+        }
+        Self { dir, files }
+    }
+
+    fn root(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+fn tree_sizes() -> Vec<usize> {
+    if std::env::var_os("FILE_ORGANIZER_BENCH_LARGE").is_some() {
+        vec![10_000, 100_000]
+    } else {
+        vec![10_000]
+    }
+}
+
+fn bench_trees(c: &mut Criterion) {
+    for size in tree_sizes() {
+        let tree = Tree::generate(size);
+        let organizer = FileOrganizer::new();
+
+        let mut group = c.benchmark_group("tree");
+        group.sample_size(10);
+        group.throughput(Throughput::Elements(size as u64));
+
+        // A filter that matches nothing makes `plan` stop right after the scan
+        let no_match = vec!["no-such-extension".to_string()];
+        group.bench_with_input(BenchmarkId::new("scan", size), &tree, |b, tree| {
+            b.iter(|| organizer.plan(tree.root(), &OrganizeMode::Extension, true, Some(&no_match)).unwrap())
+        });
+
+        let classifier = FileTypeClassifier::new();
+        group.bench_with_input(BenchmarkId::new("classify", size), &tree, |b, tree| {
+            b.iter(|| {
+                for file in &tree.files {
+                    black_box(classifier.classify(file));
+                }
+            })
+        });
+
+        for (name, mode) in [("plan_extension", OrganizeMode::Extension), ("plan_size", OrganizeMode::Size)] {
+            group.bench_with_input(BenchmarkId::new(name, size), &tree, |b, tree| {
+                b.iter(|| organizer.plan(tree.root(), &mode, true, None).unwrap())
+            });
+        }
+
+        group.bench_with_input(BenchmarkId::new("hash_small_files", size), &tree, |b, tree| {
+            b.iter(|| {
+                for file in &tree.files {
+                    black_box(hash_file(file).unwrap());
+                }
+            })
+        });
+
+        group.finish();
+    }
+}
+
+/// Moving a planned tree with each backend; every iteration gets a fresh tree
+fn bench_backends(c: &mut Criterion) {
+    const SIZE: usize = 5_000;
+    let mut group = c.benchmark_group("execute");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(SIZE as u64));
+
+    for (name, backend) in [("portable", Backend::Portable), ("uring", Backend::Uring)] {
+        let options = OrganizeOptions { backend, ..OrganizeOptions::default() };
+        let organizer = FileOrganizer::with_config(options, &Config::default()).unwrap();
+        group.bench_function(BenchmarkId::new(name, SIZE), |b| {
+            b.iter_batched(
+                || {
+                    let tree = Tree::generate(SIZE);
+                    let plan = organizer.plan(tree.root(), &OrganizeMode::Extension, true, None).unwrap();
+                    (tree, plan)
+                },
+                |(tree, plan)| {
+                    organizer.execute(&plan.operations).unwrap();
+                    tree
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_hash_throughput(c: &mut Criterion) {
+    const SIZE: usize = 16 * 1024 * 1024;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("large.bin");
+    let data: Vec<u8> = (0..SIZE).map(|i| (i % 251) as u8).collect();
+    fs::write(&path, data).unwrap();
+
+    let mut group = c.benchmark_group("hash");
+    group.throughput(Throughput::Bytes(SIZE as u64));
+    group.bench_function("16MiB", |b| b.iter(|| hash_file(black_box(&path)).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_trees, bench_backends, bench_hash_throughput);
+criterion_main!(benches);
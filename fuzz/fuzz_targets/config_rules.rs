@@ -0,0 +1,45 @@
+//! Config files and the rules in them are user input: parsing and evaluating them must
+//! return errors, never panic.
+
+#![no_main]
+
+use std::path::Path;
+use libfuzzer_sys::fuzz_target;
+
+use file_organizer::organizer::config::Config;
+use file_organizer::organizer::rules::RuleSet;
+
+const SAMPLE_FILES: [&str; 5] = [
+    "invoice-2024.pdf",
+    "photos/IMG_0042.JPG",
+    "no-extension",
+    ".hidden.tar.gz",
+    "ünïcödé 文件.txt",
+];
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(contents) = std::str::from_utf8(data) else {
+        return;
+    };
+    let Ok(config) = Config::parse(contents) else {
+        return;
+    };
+
+    // Never run external commands or plugins from fuzzer input
+    let rules: Vec<_> = config
+        .rules
+        .into_iter()
+        .filter(|rule| rule.classify_command.is_none() && rule.plugin.is_none())
+        .collect();
+    let Ok(rules) = RuleSet::new(&rules, &config.plugins) else {
+        return;
+    };
+
+    for file in SAMPLE_FILES {
+        if let Ok(Some(destination)) = rules.destination(Path::new(file)) {
+            // Rule destinations are relative and can't climb out of the target directory
+            assert!(destination.is_relative());
+            assert!(destination.components().all(|c| matches!(c, std::path::Component::Normal(_))));
+        }
+    }
+});
@@ -0,0 +1,23 @@
+//! Date folder templates from the config: parsing must not panic, and whatever parses must
+//! render to a relative path that stays inside the organized directory.
+
+#![no_main]
+
+use std::path::Component;
+use chrono::DateTime;
+use libfuzzer_sys::fuzz_target;
+
+use file_organizer::organizer::templates::FolderTemplate;
+
+fuzz_target!(|input: (&str, i64)| {
+    let (template, timestamp) = input;
+    let Ok(template) = FolderTemplate::parse(template) else {
+        return;
+    };
+    let Some(date) = DateTime::from_timestamp(timestamp % 400_000_000_000, 0) else {
+        return;
+    };
+
+    let folder = template.render(&date.naive_utc());
+    assert!(folder.components().all(|component| matches!(component, Component::Normal(_))), "{:?}", folder);
+});
@@ -0,0 +1,20 @@
+//! Size and duration arguments (`1.5GB`, `2h30m`) must parse or error, never panic, and
+//! whatever parses must format back into something that parses.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use file_organizer::organizer::units::{ByteSize, HumanDuration};
+
+fuzz_target!(|input: &str| {
+    if let Ok(size) = input.parse::<ByteSize>() {
+        let formatted = size.to_string();
+        assert!(formatted.parse::<ByteSize>().is_ok(), "{} formatted as {}", input, formatted);
+    }
+
+    if let Ok(duration) = input.parse::<HumanDuration>() {
+        let formatted = duration.to_string();
+        assert_eq!(formatted.parse::<HumanDuration>().ok(), Some(duration), "{} formatted as {}", input, formatted);
+    }
+});
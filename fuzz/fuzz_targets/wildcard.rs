@@ -0,0 +1,18 @@
+//! Rule `pattern` globs against arbitrary file names.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use file_organizer::organizer::rules::wildcard_match;
+
+fuzz_target!(|input: (&str, &str)| {
+    let (pattern, text) = input;
+    let matched = wildcard_match(pattern, text);
+
+    // A pattern without wildcards only matches itself
+    if !pattern.contains(['*', '?']) {
+        assert_eq!(matched, pattern == text);
+    }
+    assert!(wildcard_match("*", text));
+});
@@ -0,0 +1,576 @@
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use colored::Colorize;
+
+use file_organizer::organizer::conflicts::{ConflictChoice, ConflictKind, ConflictPolicy, PlannedConflict};
+use file_organizer::organizer::dates::DateKind;
+use file_organizer::organizer::observer::{CategoryChoice, Notice, OrganizeObserver};
+use file_organizer::organizer::skips::{ScanError, SkipReason, SkippedFile};
+use file_organizer::organizer::stats::ExtensionStats;
+use file_organizer::organizer::timings::PhaseTimings;
+use file_organizer::organizer::units::ByteSize;
+use file_organizer::organizer::{FileOperation, OrganizationSummary, OrganizeMode, PlannedDeletion};
+
+use crate::pager;
+
+/// How many folders the preview lists unless told otherwise
+pub const PREVIEW_FOLDERS: usize = 30;
+
+/// How many example files the preview shows per folder
+const PREVIEW_EXAMPLES: usize = 3;
+
+/// How much of the plan the preview prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewSize {
+    /// This many folders, with a few example files each
+    Limit(usize),
+    /// Every folder and every file
+    Full,
+}
+
+impl Default for PreviewSize {
+    fn default() -> Self {
+        PreviewSize::Limit(PREVIEW_FOLDERS)
+    }
+}
+
+/// Prints organizer progress to the terminal
+#[derive(Default)]
+pub struct ConsoleObserver {
+    moved: Cell<usize>,
+    /// Shown at the end of the preview rather than before it
+    conflicts: RefCell<Option<(Vec<PlannedConflict>, ConflictPolicy)>>,
+    /// Files `delete` rules delete, and whether for good; also shown at the end
+    deletions: RefCell<Option<(Vec<PlannedDeletion>, bool)>>,
+    /// List every skipped file, not just how many were skipped for each reason
+    verbose: bool,
+    /// Print errors and warnings to stdout, for when stderr carries NDJSON events
+    errors_to_stdout: bool,
+    preview: PreviewSize,
+}
+
+impl ConsoleObserver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn verbose(self, verbose: bool) -> Self {
+        Self { verbose, ..self }
+    }
+
+    pub fn errors_to_stdout(self, errors_to_stdout: bool) -> Self {
+        Self { errors_to_stdout, ..self }
+    }
+
+    pub fn preview(self, preview: PreviewSize) -> Self {
+        Self { preview, ..self }
+    }
+
+    /// Print an error or warning line, to stderr unless that's taken by NDJSON events
+    fn error(&self, line: String) {
+        if self.errors_to_stdout {
+            println!("{}", line);
+        } else {
+            eprintln!("{}", line);
+        }
+    }
+
+    /// What the scan couldn't read, so a smaller plan than expected doesn't go unexplained
+    fn print_scan_errors(&self, errors: &[ScanError]) {
+        if errors.is_empty() {
+            return;
+        }
+        let heading = tr!("Couldn't read {} paths, so their files weren't organized (--strict-scan stops instead):", errors.len());
+        self.error(format!("\n{}", format!("⚠️  {}", heading).yellow()));
+        for error in errors {
+            self.error(format!("   {} {}: {}", "•".yellow(), error.path.display(), error.reason));
+        }
+    }
+}
+
+/// Ask a yes/no question; anything but yes (including no terminal) is no
+pub fn confirm(question: &str) -> bool {
+    print!("{} {} ", question, tr!("[y/N]"));
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).is_ok() && matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Ask for a line of text; `None` once there's no more input (no terminal, or end of file)
+pub fn ask(question: &str) -> Option<String> {
+    print!("{} ", question);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    match io::stdin().lock().read_line(&mut answer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(answer.trim().to_string()),
+    }
+}
+
+/// The "⚠️ Conflicts" section of the preview
+fn write_conflicts(out: &mut String, conflicts: &[PlannedConflict], policy: ConflictPolicy) {
+    let outcome = match policy {
+        ConflictPolicy::Refuse => tr!("will fail"),
+        ConflictPolicy::Skip => tr!("will be skipped"),
+        ConflictPolicy::Rename => tr!("will be renamed"),
+        ConflictPolicy::Ask => tr!("you'll be asked"),
+    };
+
+    let _ = writeln!(out, "\n{}", format!("⚠️  {}", tr!("Conflicts ({}):", conflicts.len())).bold().yellow());
+    for conflict in conflicts {
+        let operation = &conflict.operation;
+        let problem = match &conflict.kind {
+            ConflictKind::Exists => tr!("already exists").to_string(),
+            ConflictKind::SameDestination { source } => tr!("is also where {} goes", source.display()),
+            ConflictKind::CaseClash { other } => tr!("differs only in case from {}", other.display()),
+        };
+        let result = if conflict.kind.blocks() { outcome } else { tr!("clashes on case-insensitive filesystems") };
+        let _ = writeln!(
+            out,
+            "   {} {} → {} {}: {}",
+            "•".yellow(),
+            operation.source.display(),
+            operation.destination.display(),
+            problem,
+            result.red()
+        );
+    }
+}
+
+/// The "🗑️ Deletions" section of the preview, every file listed: nothing is deleted unseen
+fn write_deletions(out: &mut String, deletions: &[PlannedDeletion], permanent: bool) {
+    let heading = if permanent {
+        tr!("Deleted for good by rules ({}):", deletions.len())
+    } else {
+        tr!("Moved to the trash by rules ({}):", deletions.len())
+    };
+    let heading = format!("🗑️  {}", heading).bold();
+    let _ = writeln!(out, "\n{}", if permanent { heading.red() } else { heading.yellow() });
+    for deletion in deletions {
+        let _ = writeln!(out, "   {} {} {}", "•".red(), deletion.path.display(), tr!("(rule '{}')", deletion.rule).dimmed());
+    }
+}
+
+/// What the date fallback notice calls a timestamp
+fn date_kind(kind: DateKind) -> &'static str {
+    match kind {
+        DateKind::Created => tr!("creation time"),
+        DateKind::Exif => tr!("EXIF capture time"),
+        DateKind::Modified => tr!("modification time"),
+    }
+}
+
+/// What the summary calls files skipped for `reason`: "3 hidden"
+fn skip_reason(reason: SkipReason) -> &'static str {
+    match reason {
+        SkipReason::Hidden => tr!("hidden"),
+        SkipReason::System => tr!("system"),
+        SkipReason::Pinned => tr!("pinned"),
+        SkipReason::Placeholder => tr!("online-only"),
+        SkipReason::Filtered => tr!("filtered out"),
+        SkipReason::Excluded => tr!("excluded"),
+        SkipReason::NoExtension => tr!("without an extension"),
+        SkipReason::Rule => tr!("by rules"),
+        SkipReason::IncompleteArchive => tr!("incomplete archive parts"),
+        SkipReason::Conflict => tr!("conflicts"),
+        SkipReason::Pending => tr!("still pending"),
+    }
+}
+
+/// `Skipped: 14 hidden, 3 pinned, 2 conflicts`, if anything was
+fn print_skipped(skipped: &BTreeMap<SkipReason, usize>) {
+    if skipped.is_empty() {
+        return;
+    }
+    let reasons: Vec<String> = skipped.iter().map(|(reason, files)| format!("{} {}", files, skip_reason(*reason))).collect();
+    say!("   {}", tr!("Skipped: {}", reasons.join(", ").dimmed()));
+}
+
+
+/// `Scan 0.12s, planning 0.03s, moves 1.20s (850 files/s, 12.5MB/s)`, for --verbose
+fn print_timings(timings: &PhaseTimings, dry_run: bool) {
+    let secs = |secs: f64| format!("{:.2}s", secs);
+    let phases = if dry_run {
+        tr!("Scan {}, planning {}", secs(timings.scan_secs), secs(timings.plan_secs))
+    } else {
+        tr!("Scan {}, planning {}, moves {}", secs(timings.scan_secs), secs(timings.plan_secs), secs(timings.execute_secs))
+    };
+    let rates = match (timings.files_per_sec(), timings.bytes_per_sec()) {
+        (Some(files), Some(bytes)) => format!(" ({})", tr!("{} files/s, {}/s", format!("{:.0}", files), ByteSize(bytes as u64))),
+        _ => String::new(),
+    };
+    say!("   ⏱️  {}{}", phases, rates.dimmed());
+}
+
+impl OrganizeObserver for ConsoleObserver {
+    fn on_scan_started(&self, directory: &Path) {
+        say!("🔍 {}", tr!("Scanning directory: {}", directory.display().to_string().cyan()));
+    }
+
+    fn on_scan_finished(&self, files_found: usize, files_selected: usize) {
+        say!("📁 {}", tr!("Found {} files to process", files_found.to_string().yellow()));
+        if files_selected == 0 {
+            say!("ℹ️  {}", tr!("No files to organize after filtering"));
+        } else {
+            say!("🎯 {}", tr!("Processing {} files after filtering", files_selected.to_string().green()));
+        }
+    }
+
+    fn on_files_skipped(&self, skipped: &[SkippedFile]) {
+        if !self.verbose || skipped.is_empty() {
+            return;
+        }
+        say!("⏭️  {}", tr!("Skipped files:"));
+        for file in skipped {
+            say!("   {} {} ({})", "•".dimmed(), file.path.display(), skip_reason(file.reason));
+        }
+    }
+
+    fn on_notice(&self, notice: &Notice) {
+        match notice {
+            Notice::PlaceholdersSkipped(files) => {
+                say!(
+                    "☁️  {}",
+                    tr!("{} cloud files aren't downloaded and stay put; --hydrate downloads and organizes them", files.to_string().cyan())
+                );
+            }
+            Notice::NoCustomRules => {
+                say!("{}", format!("⚠️  {}", tr!("No custom rules configured - falling back to extension mode")).yellow());
+            }
+            Notice::SkippedByRules(files) => {
+                say!("⏭️  {}", tr!("{} files stay put because a skip rule matched them", files.to_string().cyan()));
+            }
+            Notice::BurstsGrouped(bursts) => {
+                say!("📸 {}", tr!("Grouped {} photo bursts", bursts.to_string().cyan()));
+            }
+            Notice::IncompleteArchives(groups) => {
+                say!("\n{}", format!("⚠️  {}", tr!("Incomplete multi-part archives (not moved):")).bold().yellow());
+                for group in groups {
+                    say!(
+                        "   {} {}",
+                        "•".yellow(),
+                        tr!("{} ({} parts present, missing: {})", group.name, group.present.len(), group.missing.join(", ").red())
+                    );
+                }
+            }
+            Notice::LazyAccessTimes { mount_option } => {
+                let detail = if mount_option == "noatime" {
+                    tr!("access times are never updated")
+                } else {
+                    tr!("access times are updated at most once a day")
+                };
+                say!(
+                    "{}",
+                    format!(
+                        "⚠️  {}",
+                        tr!("This filesystem is mounted with {}: {}, so Accessed mode is approximate", mount_option, detail)
+                    )
+                    .yellow()
+                );
+            }
+            Notice::Conflicts { conflicts, policy } => {
+                *self.conflicts.borrow_mut() = Some((conflicts.clone(), *policy));
+            }
+            Notice::Deletions { deletions, permanent } => {
+                *self.deletions.borrow_mut() = Some((deletions.clone(), *permanent));
+            }
+            Notice::Resumed { moved, remaining, vanished } => {
+                say!(
+                    "⏯️  {}",
+                    tr!(
+                        "Resuming an interrupted run: {} files already moved, {} to go",
+                        moved.to_string().green(),
+                        remaining.to_string().yellow()
+                    )
+                );
+                if *vanished > 0 {
+                    say!("{}", format!("⚠️  {}", tr!("{} planned files no longer exist and were dropped", vanished)).yellow());
+                }
+            }
+            Notice::TwinFolders(twins) => {
+                say!("\n{}", format!("⚠️  {}", tr!("These folders already exist under another name, so they're reused:")).bold().yellow());
+                for twin in twins {
+                    let name = |path: &Path| path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                    say!(
+                        "   {} {}",
+                        "•".yellow(),
+                        tr!("{} files for {} go to {}", twin.files, name(&twin.planned), name(&twin.existing).green())
+                    );
+                }
+            }
+            Notice::LowSpace(shortage) => {
+                say!(
+                    "{}",
+                    format!("⚠️  {}", tr!("Not enough room: {}; the real run would refuse to start", shortage)).yellow()
+                );
+            }
+            Notice::DateFallbacks { preferred, fallbacks } => {
+                let mut used = Vec::new();
+                if fallbacks.from_exif > 0 {
+                    used.push(tr!("EXIF capture time for {}", fallbacks.from_exif));
+                }
+                if fallbacks.from_modified > 0 {
+                    used.push(tr!("modification time for {}", fallbacks.from_modified));
+                }
+                say!(
+                    "{}",
+                    format!(
+                        "⚠️  {}",
+                        tr!(
+                            "{} files have no {}; used {}",
+                            fallbacks.total(),
+                            date_kind(*preferred),
+                            used.join(tr!(" and "))
+                        )
+                    )
+                    .yellow()
+                );
+            }
+        }
+    }
+
+    fn on_plan_ready(&self, operations: &[FileOperation], mode: &OrganizeMode) {
+        // Written out first, so a preview taller than the terminal can go through a pager
+        let mut out = String::new();
+        let _ = writeln!(out, "\n{}", format!("📋 {}", tr!("Organization Preview:")).bold().blue());
+        let _ = writeln!(out, "{}", tr!("Mode: {}", format!("{:?}", mode)));
+
+        // Group operations by destination folder
+        let mut folder_groups: BTreeMap<String, Vec<&FileOperation>> = BTreeMap::new();
+
+        for op in operations {
+            if let Some(parent) = op.destination.parent() {
+                if let Some(folder_name) = parent.file_name() {
+                    folder_groups.entry(folder_name.to_string_lossy().into_owned())
+                        .or_default()
+                        .push(op);
+                }
+            }
+        }
+
+        let mut folder_groups: Vec<(String, Vec<&FileOperation>)> = folder_groups.into_iter().collect();
+        folder_groups.sort_by(|a, b| by_folder_name(&a.0, &b.0));
+        let (folders_shown, examples) = match self.preview {
+            PreviewSize::Limit(folders) => (folders, PREVIEW_EXAMPLES),
+            PreviewSize::Full => (usize::MAX, usize::MAX),
+        };
+        for (folder_name, ops) in folder_groups.iter().take(folders_shown) {
+            // Sizes as the scan saw them, so a surprising total shows up before anything moves
+            let sizes: Vec<u64> = ops.iter().map(|op| op.size).collect();
+            let total = ByteSize(sizes.iter().sum()).to_string();
+            let _ = writeln!(out, "\n📁 {}", tr!("{} ({} files, {})", folder_name.green(), ops.len().to_string().yellow(), total.cyan()));
+
+            // Show first few files as examples
+            for (op, size) in ops.iter().zip(&sizes).take(examples) {
+                if let Some(file_name) = op.source.file_name() {
+                    let mut name = file_name.to_string_lossy().into_owned();
+                    // Renamed on conflict
+                    if let Some(renamed) = op.destination.file_name().filter(|renamed| *renamed != file_name) {
+                        name = tr!("{} as {}", name, renamed.to_string_lossy());
+                    }
+                    let _ = writeln!(out, "   {} {} {}", "→".cyan(), name, format!("({})", ByteSize(*size)).dimmed());
+                }
+            }
+
+            if ops.len() > examples {
+                let _ = writeln!(out, "   {} {}", "...".dimmed(), tr!("and {} more files...", (ops.len() - examples).to_string().dimmed()));
+            }
+        }
+        if folder_groups.len() > folders_shown {
+            let more = tr!("and {} more folders (--preview-full lists them all)", folder_groups.len() - folders_shown);
+            let _ = writeln!(out, "\n{} {}", "...".dimmed(), more.dimmed());
+        }
+
+        let _ = writeln!(out, "\n{} {}", tr!("Total:").bold(), tr!("{} files will be organized", operations.len().to_string().yellow()));
+        if let Some((conflicts, policy)) = self.conflicts.take() {
+            write_conflicts(&mut out, &conflicts, policy);
+        }
+        if let Some((deletions, permanent)) = self.deletions.take() {
+            write_deletions(&mut out, &deletions, permanent);
+        }
+        pager::show(&out);
+    }
+
+    fn on_category_review(&self, folder: &str, files: usize) -> CategoryChoice {
+        let stdin = io::stdin();
+        loop {
+            print!("{} ", tr!("Move {} files to {}? [y/n/a/q]", files.to_string().yellow(), folder.green()));
+            let _ = io::stdout().flush();
+
+            let mut answer = String::new();
+            // No terminal (or end of input): move nothing more
+            if stdin.lock().read_line(&mut answer).unwrap_or(0) == 0 {
+                println!();
+                return CategoryChoice::Quit;
+            }
+            match answer.trim().to_lowercase().as_str() {
+                "y" | "yes" => return CategoryChoice::Yes,
+                "n" | "no" => return CategoryChoice::No,
+                "a" | "all" => return CategoryChoice::All,
+                "q" | "quit" => return CategoryChoice::Quit,
+                _ => println!("{}", tr!("Please answer y (move), n (leave), a (move this and all the rest) or q (leave the rest)")),
+            }
+        }
+    }
+
+    fn on_deletion_review(&self, files: usize, permanent: bool) -> bool {
+        let stdin = io::stdin();
+        loop {
+            let question = match permanent {
+                true => tr!("Delete {} files for good? [y/n]", files.to_string().yellow()),
+                false => tr!("Move {} files to the trash? [y/n]", files.to_string().yellow()),
+            };
+            print!("{} ", question);
+            let _ = io::stdout().flush();
+
+            let mut answer = String::new();
+            if stdin.lock().read_line(&mut answer).unwrap_or(0) == 0 {
+                println!();
+                return false;
+            }
+            match answer.trim().to_lowercase().as_str() {
+                "y" | "yes" => return true,
+                "n" | "no" => return false,
+                _ => println!("{}", tr!("Please answer y (delete) or n (keep)")),
+            }
+        }
+    }
+
+    fn on_execution_started(&self, _operations: usize) {
+        self.moved.set(0);
+        say!("\n{}", format!("🚀 {}", tr!("Executing file operations...")).bold().green());
+    }
+
+    fn on_directories_created(&self, count: usize) {
+        say!("📁 {}", tr!("Created {} directories", count.to_string().cyan()));
+    }
+
+    fn on_file_moved(&self, _operation: &FileOperation) {
+        let moved = self.moved.get() + 1;
+        self.moved.set(moved);
+        if moved.is_multiple_of(10) {
+            say!("📦 {}", tr!("Moved {} files...", moved.to_string().green()));
+        }
+    }
+
+    fn on_conflict(&self, operation: &FileOperation) -> ConflictChoice {
+        let stdin = io::stdin();
+        loop {
+            print!(
+                "⚠️  {} ",
+                tr!(
+                    "{} already exists. [s]kip or [r]ename {}?",
+                    operation.destination.display(),
+                    operation.source.display()
+                )
+            );
+            let _ = io::stdout().flush();
+
+            let mut answer = String::new();
+            // No terminal (or end of input): don't guess
+            if stdin.lock().read_line(&mut answer).unwrap_or(0) == 0 {
+                println!();
+                return ConflictChoice::Skip;
+            }
+            match answer.trim().to_lowercase().as_str() {
+                "s" | "skip" => return ConflictChoice::Skip,
+                "r" | "rename" => return ConflictChoice::Rename,
+                _ => println!("{}", tr!("Please answer s or r")),
+            }
+        }
+    }
+
+    fn on_file_skipped(&self, operation: &FileOperation) {
+        say!(
+            "⏭️  {}",
+            tr!("Skipped {}: {} already exists", operation.source.display(), operation.destination.display())
+        );
+    }
+
+    fn on_error(&self, operation: &FileOperation, error: &io::Error) {
+        self.error(format!("❌ {}", tr!("Failed to move {}: {}", operation.source.file_name().unwrap_or_default().to_string_lossy(), error)));
+    }
+
+    fn on_execution_stopped(&self, not_attempted: usize) {
+        self.error(format!("⛔ {}", tr!("Stopped at the first failure; {} files were not attempted", not_attempted)).red().to_string());
+    }
+
+    fn on_rolled_back(&self, restored: usize, failed: usize) {
+        say!("↩️  {}", tr!("Rolled back {} moved files", restored.to_string().yellow()));
+        if failed > 0 {
+            self.error(format!("⚠️  {}", tr!("{} files couldn't be put back; run undo to restore them", failed.to_string().red())));
+        }
+    }
+
+    fn on_execution_finished(&self, moved: usize, failed: usize) {
+        say!("✅ {}", tr!("Successfully moved {} files", moved.to_string().green()));
+        if failed > 0 {
+            self.error(format!("⚠️  {}", tr!("{} files failed to move", failed.to_string().red())));
+        }
+    }
+
+    fn on_finished(&self, summary: &OrganizationSummary, dry_run: bool) {
+        self.print_scan_errors(&summary.scan_errors);
+        if dry_run {
+            print_skipped(&summary.skipped);
+            if self.verbose {
+                print_timings(&summary.timings, dry_run);
+            }
+            say!("🔍 {} {}", tr!("DRY RUN:").bold().yellow(), tr!("This was a dry run - no files were moved"));
+            return;
+        }
+
+        say!("\n{}", format!("🎉 {}", tr!("Organization Complete!")).bold().green());
+        say!("📊 {}", tr!("Summary:"));
+        say!("   {}", tr!("Files processed: {}", summary.total_files.to_string().cyan()));
+        say!("   {}", tr!("Folders created: {}", summary.folders_created.to_string().cyan()));
+        if summary.folders_reused > 0 {
+            say!("   {}", tr!("Existing folders reused: {}", summary.folders_reused.to_string().cyan()));
+        }
+        print_skipped(&summary.skipped);
+        if self.verbose {
+            print_timings(&summary.timings, dry_run);
+        }
+
+        if !summary.categories.is_empty() {
+            say!("   {}", tr!("Categories:"));
+            let mut categories: Vec<(&String, &usize)> = summary.categories.iter().collect();
+            categories.sort_by(|a, b| by_folder_name(a.0, b.0));
+            for (category, count) in categories {
+                say!("     {} {}: {}", "📁".cyan(), category, count.to_string().yellow());
+            }
+        }
+
+        if !summary.other_extensions.is_empty() {
+            let other: Vec<(&str, ExtensionStats)> =
+                summary.other_extensions.iter().map(|(extension, stats)| (extension.as_str(), *stats)).collect();
+            say!("   {}", tr!("Unknown types by extension:"));
+            print_other_extensions(&other, OTHER_EXTENSIONS_SHOWN);
+            say!("   💡 {}", tr!("File them with [categories.extensions] in the config, or run with --learn-unknown"));
+        }
+    }
+}
+
+/// Order folders by name, ignoring their emoji (`🎵 Audio` before `📄 Documents`), with the
+/// full names settling ties
+fn by_folder_name(a: &str, b: &str) -> std::cmp::Ordering {
+    let bare = |name: &str| name.trim_start_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+    bare(a).cmp(&bare(b)).then_with(|| a.cmp(b))
+}
+
+/// How many extensions the summary lists under Other; `stats` shows them all
+const OTHER_EXTENSIONS_SHOWN: usize = 10;
+
+/// One line per extension that went to Other, at most `limit` of them, most common first
+pub fn print_other_extensions(extensions: &[(&str, ExtensionStats)], limit: usize) {
+    for (extension, stats) in extensions.iter().take(limit) {
+        let name = if extension.is_empty() { tr!("(no extension)").to_string() } else { format!(".{}", extension) };
+        say!("     {:<16} {:>6} {:>10}", name, tr!("{} files", stats.files), ByteSize(stats.bytes).to_string());
+    }
+    if extensions.len() > limit {
+        say!("     {} {}", "...".dimmed(), tr!("and {} more extensions", extensions.len() - limit));
+    }
+}
@@ -0,0 +1,206 @@
+//! Translations of the CLI's messages, gettext style: the English text is the message id, and
+//! `locales/<language>.po` maps it to a translation. Messages without one stay in English.
+//!
+//! The language comes from `LANGUAGE`, `LC_ALL`, `LC_MESSAGES` or `LANG`, like other command
+//! line tools. Placeholders are `{}` in order, or `{0}`, `{1}`, ... where a translation needs
+//! the values in another order.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// Translation catalogs shipped with the binary, by language code
+const CATALOGS: &[(&str, &str)] = &[("es", include_str!("../locales/es.po"))];
+
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// `tr!("Moved {} files", count)`: the translation of a message, with its placeholders filled
+macro_rules! tr {
+    ($msgid:literal) => {
+        $crate::i18n::translate($msgid)
+    };
+    ($msgid:literal, $($arg:expr),+ $(,)?) => {
+        $crate::i18n::fill($crate::i18n::translate($msgid), &[$(&$arg as &dyn std::fmt::Display),+])
+    };
+}
+
+/// The translation of `msgid` for the user's language, or `msgid` itself
+pub fn translate(msgid: &'static str) -> &'static str {
+    let catalog = CATALOG.get_or_init(|| {
+        let language = language(|name| std::env::var(name).ok());
+        CATALOGS
+            .iter()
+            .find(|(code, _)| Some(*code) == language.as_deref())
+            .map(|(_, po)| parse_po(po))
+            .unwrap_or_default()
+    });
+    catalog.get(msgid).map(String::as_str).unwrap_or(msgid)
+}
+
+/// The language code (`es` for `es_ES.UTF-8`) of the first locale variable that's set; `None`
+/// for the `C`/`POSIX` locale
+fn language(var: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let value = ["LANGUAGE", "LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(var)
+        .find(|value| !value.is_empty())?;
+    // `LANGUAGE` is a list in order of preference; the first that ships is used
+    let candidates = value.split(':').map(|locale| {
+        locale.split(['_', '.', '@']).next().unwrap_or_default().to_lowercase()
+    });
+    let mut fallback = None;
+    for candidate in candidates {
+        if candidate == "c" || candidate == "posix" || candidate.is_empty() {
+            continue;
+        }
+        if CATALOGS.iter().any(|(code, _)| *code == candidate) {
+            return Some(candidate);
+        }
+        fallback.get_or_insert(candidate);
+    }
+    fallback
+}
+
+/// Replace the placeholders in `template` with `args`; `{{` and `}}` are literal braces
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let (mut chars, mut next) = (template.chars().peekable(), 0);
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut index = String::new();
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                    index.push(c);
+                }
+                let index = if index.is_empty() {
+                    next += 1;
+                    next - 1
+                } else {
+                    index.parse().unwrap_or(usize::MAX)
+                };
+                if let Some(arg) = args.get(index) {
+                    out.push_str(&arg.to_string());
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// The `msgid`/`msgstr` pairs of a `.po` file, leaving out untranslated ones
+fn parse_po(po: &str) -> HashMap<String, String> {
+    let mut catalog = HashMap::new();
+    let (mut msgid, mut msgstr) = (None::<String>, None::<String>);
+    let mut finish = |msgid: &mut Option<String>, msgstr: &mut Option<String>| {
+        if let (Some(id), Some(text)) = (msgid.take(), msgstr.take()) {
+            if !id.is_empty() && !text.is_empty() {
+                catalog.insert(id, text);
+            }
+        }
+    };
+    for line in po.lines().map(str::trim) {
+        if let Some(rest) = line.strip_prefix("msgid ") {
+            finish(&mut msgid, &mut msgstr);
+            msgid = Some(unquote(rest));
+        } else if let Some(rest) = line.strip_prefix("msgstr ") {
+            msgstr = Some(unquote(rest));
+        } else if line.starts_with('"') {
+            // Continues the string on the line before
+            if let Some(text) = msgstr.as_mut().or(msgid.as_mut()) {
+                text.push_str(&unquote(line));
+            }
+        }
+    }
+    finish(&mut msgid, &mut msgstr);
+    catalog
+}
+
+/// The contents of a `"quoted"` `.po` string, with `\"`, `\\`, `\n` and `\t` unescaped
+fn unquote(quoted: &str) -> String {
+    let inner = quoted.trim().trim_start_matches('"').strip_suffix('"').unwrap_or_default();
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(message: &str) -> usize {
+        message.replace("{{", "").matches('{').count()
+    }
+
+    #[test]
+    fn test_language() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+        };
+        assert_eq!(language(env(&[("LANG", "es_ES.UTF-8")])).as_deref(), Some("es"));
+        assert_eq!(language(env(&[("LC_ALL", "C"), ("LANG", "es_ES.UTF-8")])), None);
+        assert_eq!(language(env(&[("LANGUAGE", "fr:es"), ("LANG", "fr_FR")])).as_deref(), Some("es"));
+        assert_eq!(language(env(&[("LANGUAGE", ""), ("LC_MESSAGES", "de_DE@euro")])).as_deref(), Some("de"));
+        assert_eq!(language(env(&[])), None);
+    }
+
+    #[test]
+    fn test_fill_and_parse() {
+        assert_eq!(fill("Moved {} of {} files", &[&3, &"5"]), "Moved 3 of 5 files");
+        assert_eq!(fill("{1} ← {0} {{x}}", &[&"a", &"b"]), "b ← a {x}");
+
+        let catalog = parse_po(
+            "# comment\nmsgid \"\"\nmsgstr \"Language: es\\n\"\n\nmsgid \"Say \\\"hi\\\"\"\nmsgstr \"\"\n\"Di \\\"hola\\\"\"\n\nmsgid \"Untranslated\"\nmsgstr \"\"\n",
+        );
+        assert_eq!(catalog.len(), 1);
+        assert_eq!(catalog["Say \"hi\""], "Di \"hola\"");
+    }
+
+    /// Every message the CLI shows has a Spanish translation with the same placeholders
+    #[test]
+    fn test_spanish_catalog_is_complete() {
+        let catalog = parse_po(CATALOGS[0].1);
+        for source in [include_str!("main.rs"), include_str!("console.rs")] {
+            for call in source.split("tr!(\"").skip(1) {
+                let mut end = 0;
+                let mut escaped = false;
+                for (i, c) in call.char_indices() {
+                    match c {
+                        '"' if !escaped => {
+                            end = i;
+                            break;
+                        }
+                        '\\' => escaped = !escaped,
+                        _ => escaped = false,
+                    }
+                }
+                let msgid = unquote(&format!("\"{}\"", &call[..end]));
+                let translation = catalog.get(&msgid).unwrap_or_else(|| panic!("no translation for {:?}", msgid));
+                assert_eq!(placeholders(translation), placeholders(&msgid), "{:?}", msgid);
+            }
+        }
+    }
+}
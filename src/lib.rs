@@ -0,0 +1,5 @@
+//! 🦀 Organize files by type, size, date, or custom rules.
+//!
+//! The `file-organizer` binary is a thin CLI over [`organizer::FileOrganizer`].
+
+pub mod organizer;
@@ -1,8 +1,44 @@
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use clap::{Args, Parser, Subcommand};
+use std::collections::{BTreeSet, HashSet};
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 
-mod organizer;
-use organizer::FileOrganizer;
+#[macro_use]
+mod i18n;
+#[macro_use]
+mod output;
+mod console;
+mod pager;
+mod progress;
+mod update;
+
+use console::{ConsoleObserver, PreviewSize, PREVIEW_FOLDERS};
+use progress::{NdjsonObserver, ProgressFormat};
+use file_organizer::organizer::backend::Backend;
+use file_organizer::organizer::budget::{Budget, EvictionStrategy};
+use file_organizer::organizer::clean;
+use file_organizer::organizer::config::{self, default_config_path, Config, NoExtension};
+use file_organizer::organizer::conflicts::ConflictPolicy;
+use file_organizer::organizer::control::{self, Control, ControlReply, ControlRequest, ControlServer, WatchStatus};
+use file_organizer::organizer::dates::{DateBucket, DateSource};
+use file_organizer::organizer::file_events::{FileEvents, Written};
+use file_organizer::organizer::file_types::FileCategory;
+use file_organizer::organizer::hashing::HashAlgorithm;
+use file_organizer::organizer::journal;
+use file_organizer::organizer::manifest::Manifest;
+use file_organizer::organizer::media_import;
+#[cfg(feature = "metrics")]
+use file_organizer::organizer::metrics::Metrics;
+use file_organizer::organizer::names::{self, NamePredicates};
+use file_organizer::organizer::store::{self, HistoryFilter};
+use file_organizer::organizer::pins;
+use file_organizer::organizer::priority;
+use file_organizer::organizer::rules;
+use file_organizer::organizer::stats::RunStats;
+use file_organizer::organizer::syncs;
+use file_organizer::organizer::units::{ByteSize, HumanDuration};
+use file_organizer::organizer::{ErrorPolicy, FileOrganizer, OrganizeMode, OrganizeOptions, PENDING_DIR};
 
 /// 🦀 A powerful CLI tool to organize files by type, size, date, or custom rules
 #[derive(Parser)]
@@ -11,14 +47,24 @@ use organizer::FileOrganizer;
 #[command(version = "0.1.0")]
 #[command(author = "Janmesh Shewale")]
 struct Cli {
-    /// Enable verbose output
-    #[arg(short, long)]
-    verbose: bool,
+    /// More output: -v lists details and logs what's being done, -vv adds debug logs,
+    /// -vvv trace logs
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Print and log only errors
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
 
     /// Configuration file path
     #[arg(short, long)]
     config: Option<PathBuf>,
 
+    /// Run at the lowest CPU and I/O priority, so scheduled runs don't slow down the machine
+    /// while it's in use
+    #[arg(long)]
+    background: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -26,56 +72,223 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Organize files in a directory
-    Organize {
-        /// Directory to organize (default: current directory)
+    Organize(OrganizeArgs),
+
+    /// Keep organizing a directory as files arrive, pausing while a sync client (Syncthing,
+    /// Dropbox, Resilio Sync) is writing into it
+    Watch(WatchArgs),
+
+    /// Manage running watches without restarting them
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonAction,
+    },
+
+    /// Undo the last organization operation
+    Undo {
+        /// Directory to undo organization
         #[arg(value_name = "DIRECTORY")]
         path: Option<PathBuf>,
 
-        /// Organization mode
-        #[arg(short, long, value_enum, default_value_t = OrganizeMode::Extension)]
-        mode: OrganizeMode,
+        /// Undo this operation (as printed after each run and listed by `history`) instead
+        /// of the most recent one
+        #[arg(long, value_name = "ID")]
+        operation: Option<String>,
 
-        /// Preview changes without applying them
+        /// Show what would be undone without doing it
         #[arg(short = 'n', long)]
         dry_run: bool,
 
-        /// Force operation without confirmation
+        /// What to do when a file's original location is taken again
+        #[arg(long, value_enum, default_value_t = ConflictPolicy::Refuse)]
+        on_conflict: ConflictPolicy,
+    },
+
+    /// Show organization history
+    #[command(args_conflicts_with_subcommands = true)]
+    History {
+        #[command(subcommand)]
+        action: Option<HistoryAction>,
+
+        /// Directory to show history for (with --global, only directories inside it)
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+
+        /// Number of recent operations to show
+        #[arg(short, long, default_value_t = 10)]
+        limit: usize,
+
+        /// Show operations from every directory ever organized
         #[arg(short, long)]
-        force: bool,
+        global: bool,
+
+        /// Only operations on or after this day (YYYY-MM-DD)
+        #[arg(long, value_name = "DATE")]
+        since: Option<NaiveDate>,
+
+        /// Only operations on or before this day (YYYY-MM-DD)
+        #[arg(long, value_name = "DATE")]
+        until: Option<NaiveDate>,
 
-        /// Filter specific file types
-        #[arg(long, value_delimiter = ',')]
-        filter: Option<Vec<String>>,
+        /// Only operations made in this mode
+        #[arg(long, value_enum)]
+        mode: Option<OrganizeMode>,
+    },
+
+    /// Mark files that must never be moved
+    Pin {
+        /// Files to pin
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<PathBuf>,
 
-        /// Create backup before organizing
+        /// Unpin the files instead
         #[arg(short, long)]
-        backup: bool,
+        remove: bool,
+    },
+
+    /// Show what organizing a directory has moved
+    Stats {
+        /// Directory to show statistics for
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
 
-        /// Recursive organization (include subdirectories)
+        /// Show how the directory evolved over recent runs instead of just the last one
         #[arg(short, long)]
-        recursive: bool,
+        trend: bool,
+
+        /// Number of recent runs in the trend
+        #[arg(short, long, default_value_t = 10)]
+        limit: usize,
     },
 
-    /// Undo the last organization operation
-    Undo {
-        /// Directory to undo organization
+    /// Copy new photos and videos from an SD card or camera into date folders
+    Import {
+        /// The mounted card or camera
+        #[arg(value_name = "MEDIA")]
+        media: PathBuf,
+
+        /// Where the date folders go (default: current directory)
+        #[arg(value_name = "DESTINATION")]
+        destination: Option<PathBuf>,
+
+        /// Show what would be copied without copying
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Folder granularity, by EXIF capture date
+        #[arg(long, value_enum, default_value_t = DateBucket::Month)]
+        date_buckets: DateBucket,
+
+        /// What to do when a different file with the same name is already in the destination
+        #[arg(long, value_enum, default_value_t = ConflictPolicy::Rename)]
+        on_conflict: ConflictPolicy,
+
+        /// Unmount and eject the media once everything was copied
+        #[arg(long, conflicts_with = "dry_run")]
+        eject: bool,
+    },
+
+    /// Fold one organized tree into another, keeping each file's place in the tree
+    Merge {
+        /// Tree to move files out of
+        #[arg(value_name = "SOURCE")]
+        source: PathBuf,
+
+        /// Tree to move them into
+        #[arg(value_name = "DESTINATION")]
+        destination: PathBuf,
+
+        /// Show what would be moved without moving anything
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+
+    /// Keep a directory within a size or file count budget, archiving or trashing files until
+    /// it fits
+    Enforce {
+        /// Directory to keep within the budget
         #[arg(value_name = "DIRECTORY")]
         path: Option<PathBuf>,
 
-        /// Show what would be undone without doing it
+        /// Most the directory may hold, e.g. 50GB
+        #[arg(long, value_name = "SIZE", required_unless_present = "keep_newest")]
+        max_size: Option<ByteSize>,
+
+        /// Keep only the newest N files of each folder (e.g. Screenshots)
+        #[arg(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        keep_newest: Option<usize>,
+
+        /// Which files go, and where
+        #[arg(long, value_enum, default_value_t)]
+        strategy: EvictionStrategy,
+
+        /// Where archived files go (default: an Archive folder in the directory, whose files
+        /// don't count)
+        #[arg(long, value_name = "DIR")]
+        archive_dir: Option<PathBuf>,
+
+        /// Count the files of subdirectories too
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// Also count and evict hidden files; system files always stay
+        #[arg(long)]
+        include_hidden: bool,
+
+        /// Also count and evict cloud placeholders, which downloads them first
+        #[arg(long)]
+        hydrate: bool,
+
+        /// Show what would be evicted without moving anything
         #[arg(short = 'n', long)]
         dry_run: bool,
     },
 
-    /// Show organization history
-    History {
-        /// Directory to show history for
+    /// Find duplicate files, or whole duplicate folders, and optionally keep one copy
+    Dedupe {
+        /// Directory to search, including subdirectories
         #[arg(value_name = "DIRECTORY")]
         path: Option<PathBuf>,
 
-        /// Number of recent operations to show
-        #[arg(short, long, default_value_t = 10)]
-        limit: usize,
+        /// Look for whole folders with the same files (e.g. an archive unzipped twice)
+        #[arg(long)]
+        folders: bool,
+
+        /// Delete every copy except the one with the shortest path
+        #[arg(long)]
+        remove: bool,
+
+        /// With --remove, don't ask for confirmation
+        #[arg(short, long, requires = "remove")]
+        force: bool,
+
+        /// Checksum to compare files by
+        #[arg(long = "hash", value_enum, default_value_t = HashAlgorithm::Blake3)]
+        hash_algorithm: HashAlgorithm,
+    },
+
+    /// Check the custom rules in the config
+    Rules {
+        #[command(subcommand)]
+        action: RulesAction,
+    },
+
+    /// Record every file of a library with its hash, or check a library against such a record
+    Manifest {
+        #[command(subcommand)]
+        action: ManifestAction,
+    },
+
+    /// Report files that need attention
+    Find {
+        #[command(subcommand)]
+        report: FindReport,
+    },
+
+    /// Manage the cache of file hashes that dedupe and manifests reuse between runs
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
     },
 
     /// Clean empty directories
@@ -87,6 +300,18 @@ enum Commands {
         /// Preview what would be cleaned
         #[arg(short = 'n', long)]
         dry_run: bool,
+
+        /// Only remove folders the organizer made (category folders, and folders its runs
+        /// moved files into); keep empty folders of your own, like mount points
+        #[arg(long)]
+        only_organizer_dirs: bool,
+    },
+
+    /// Update file-organizer to the latest GitHub release
+    SelfUpdate {
+        /// Only report whether a newer release exists (e.g. from cron); install nothing
+        #[arg(long)]
+        check_only: bool,
     },
 
     /// Generate shell completions
@@ -97,21 +322,367 @@ enum Commands {
     },
 }
 
-#[derive(clap::ValueEnum, Clone, Debug)]
-enum OrganizeMode {
-    /// Organize by file extension (default)
-    Extension,
-    /// Organize by file size
-    Size,
-    /// Organize by creation date
-    Date,
-    /// Organize by modification date
-    Modified,
-    /// Use custom rules from config file
-    Custom,
+#[derive(Subcommand)]
+enum HistoryAction {
+    /// Rewrite journals from older versions of the tool in the current format
+    Migrate {
+        /// Directory whose history to migrate
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+
+        /// Show which journals would be rewritten without changing them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+
+    /// Save a directory's history to a JSON file
+    Export {
+        /// File to write
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Directory whose history to export
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+    },
+
+    /// Add the history in an exported JSON file to a directory's history
+    Import {
+        /// File written by `history export`
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Directory whose history to add to
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DaemonAction {
+    /// Show what each running watch is doing
+    Status,
+    /// Stop organizing until resumed (every watch, or the one of DIRECTORY)
+    Pause {
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+    },
+    /// Organize again after a pause
+    Resume {
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+    },
+    /// Organize everything now, even while paused or syncing
+    Flush {
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+    },
+    /// Read the config file again
+    Reload {
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Forget every cached hash, so files are read and hashed again
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum RulesAction {
+    /// Report rule conditions that don't parse, contradict themselves or can never be reached
+    Lint,
+}
+
+#[derive(Subcommand)]
+enum ManifestAction {
+    /// Write the path, size, hash and category of every file to FILE (CSV if it ends in .csv,
+    /// JSON otherwise)
+    Export {
+        /// File to write
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Library to list, including subdirectories
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+
+        /// Checksum to record; `check` uses the one the manifest was written with
+        #[arg(long = "hash", value_enum, default_value_t = HashAlgorithm::Blake3)]
+        hash_algorithm: HashAlgorithm,
+    },
+
+    /// Report files that are missing, changed or moved since the manifest was written
+    Check {
+        /// File written by `manifest export`
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Library to check
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum FindReport {
+    /// Files with very long names or non-ASCII/control characters in their names, which
+    /// sync tools and other platforms often can't handle
+    WeirdNames {
+        /// Directory to search, including subdirectories
+        #[arg(value_name = "DIRECTORY")]
+        path: Option<PathBuf>,
+
+        /// Report names with more characters than this
+        #[arg(long, value_name = "N", default_value_t = 128)]
+        longer_than: usize,
+    },
+}
+
+#[derive(Args)]
+#[command(after_help = "File selection: a file is organized if its extension is in --filter (when given) \
+and its category is in --only-categories (when given) and its name matches --name-longer-than or \
+--weird-names (when given), unless --exclude-ext or --exclude-categories \
+matches it; exclusions always win. Options that rule each other out entirely, such as \
+--filter jpg --exclude-categories Images, are rejected.")]
+struct OrganizeArgs {
+    /// Directory to organize (default: current directory)
+    #[arg(value_name = "DIRECTORY")]
+    path: Option<PathBuf>,
+
+    /// Organization mode
+    #[arg(short, long, value_enum, default_value_t = OrganizeMode::Extension)]
+    mode: OrganizeMode,
+
+    /// Preview changes without applying them
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Like --dry-run, but guaranteed not to write anything at all (no folders, history or
+    /// resume file), so it's safe on read-only mounts
+    #[arg(long, conflicts_with_all = ["resume", "ask_per_category"])]
+    analyze: bool,
+
+    /// Like --dry-run, but exit with an error if any file would be moved, for checking in CI
+    /// or scripts that a folder is fully organized
+    #[arg(long, conflicts_with_all = ["resume", "ask_per_category"])]
+    check: bool,
+
+    /// Only carry out the plan a dry run printed this ID for; if the files changed since and
+    /// the plan comes out differently, nothing is moved
+    #[arg(long, value_name = "ID", conflicts_with = "resume")]
+    plan_id: Option<String>,
+
+    /// Force operation without confirmation
+    #[arg(short, long)]
+    force: bool,
+
+    /// Filter specific file types
+    #[arg(long, value_delimiter = ',')]
+    filter: Option<Vec<String>>,
+
+    /// Only organize files of these categories, e.g. Images,Videos (by extension, in any mode)
+    #[arg(long, value_delimiter = ',', value_name = "CATEGORIES")]
+    only_categories: Vec<FileCategory>,
+
+    /// Never organize files with these extensions, e.g. rs,zip
+    #[arg(long, value_delimiter = ',', value_name = "EXTENSIONS")]
+    exclude_ext: Vec<String>,
+
+    /// Never organize files of these categories, e.g. Code,Archives
+    #[arg(long, value_delimiter = ',', value_name = "CATEGORIES")]
+    exclude_categories: Vec<FileCategory>,
+
+    /// Only organize files whose names have more than N characters
+    #[arg(long, value_name = "N")]
+    name_longer_than: Option<usize>,
+
+    /// Only organize files whose names have non-ASCII or control characters
+    #[arg(long)]
+    weird_names: bool,
+
+    /// Create backup before organizing
+    #[arg(short, long)]
+    backup: bool,
+
+    /// Also organize hidden files (dot files, and files with the hidden attribute on Windows
+    /// and macOS); system files are always left alone
+    #[arg(long)]
+    include_hidden: bool,
+
+    /// Also organize cloud placeholders (OneDrive, Dropbox and iCloud files that aren't
+    /// downloaded), which downloads them first
+    #[arg(long)]
+    hydrate: bool,
+
+    /// For folders on network shares (SMB, NFS): never read file contents, only names and
+    /// metadata. This turns off content hashing (duplicate fonts aren't detected), font family,
+    /// EPUB author/title and EXIF capture time reads (font and book folders come from file
+    /// names, dates from file times, and --group-bursts does nothing), and the first-bytes
+    /// check of .ts/.key/.md files and files without an extension (default: the config's
+    /// [execution] network_friendly)
+    #[arg(long)]
+    network_friendly: bool,
+
+    /// What happens to files without an extension (default: the config's [categories]
+    /// no_extension, or other)
+    #[arg(long, value_enum, value_name = "MODE")]
+    no_extension: Option<NoExtension>,
+
+    /// Stop if part of the directory can't be read (an unreadable subdirectory, say) instead
+    /// of organizing the rest and listing what was left out
+    #[arg(long)]
+    strict_scan: bool,
+
+    /// Don't descend into other filesystems in recursive runs, like `find -xdev`: mount
+    /// points below the directory (backup drives, network mounts) are left out
+    #[arg(long, short = 'x')]
+    one_file_system: bool,
+
+    /// Flush each moved file, its folders and the progress file to disk before the next
+    /// move, so a power loss can't lose files or the undo history; slower (default: the
+    /// config's [execution] durable)
+    #[arg(long)]
+    durable: bool,
+
+    /// How files are moved: portable (one at a time) or uring (batched through io_uring, for
+    /// hundreds of thousands of small files; Linux only)
+    #[arg(long, value_enum, value_name = "BACKEND", default_value_t = Backend::Portable)]
+    backend: Backend,
+
+    /// Tag every organized file in Finder with its category ("Images", "Documents"), so the
+    /// tag sidebar mirrors the folders (macOS)
+    #[arg(long)]
+    finder_tags: bool,
+
+    /// Recursive organization (include subdirectories)
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// With --recursive, keep each file's subdirectories beneath its category folder
+    #[arg(long, requires = "recursive")]
+    preserve_structure: bool,
+
+    /// With --recursive, organize each subdirectory in place instead of moving files to the top
+    #[arg(long, requires = "recursive", conflicts_with = "preserve_structure")]
+    per_directory: bool,
+
+    /// What to do when a file with the same name is already at the destination
+    #[arg(long, value_enum, default_value_t = ConflictPolicy::Refuse)]
+    on_conflict: ConflictPolicy,
+
+    /// What a failed move does to the rest of the run (default: keep-going, or the config's
+    /// [execution] on_error)
+    #[arg(long, value_enum)]
+    on_error: Option<ErrorPolicy>,
+
+    /// Stop at the first file that can't be moved (same as --on-error fail-fast)
+    #[arg(long, conflicts_with = "on_error")]
+    fail_fast: bool,
+
+    /// Continue a run that was interrupted (killed, crashed, rebooted) with its original plan
+    #[arg(long, conflicts_with_all = ["dry_run", "ask_per_category"])]
+    resume: bool,
+
+    /// Refuse to run if less than SIZE (e.g. 1GB) would stay free on the filesystem
+    /// (default: the config's [execution] min_free)
+    #[arg(long, value_name = "SIZE")]
+    min_free: Option<ByteSize>,
+
+    /// Save progress for --resume every N files
+    #[arg(long, value_name = "N", default_value_t = 100)]
+    checkpoint_every: usize,
+
+    /// After the preview, ask before moving the files of each destination folder
+    #[arg(long, conflicts_with = "dry_run")]
+    ask_per_category: bool,
+
+    /// Sort ebooks into Books/{author}/{title}/ using EPUB metadata or file names
+    #[arg(long)]
+    books_by_author: bool,
+
+    /// Sort fonts into Fonts/{family}/, with web fonts and duplicate faces kept apart
+    #[arg(long)]
+    fonts_by_family: bool,
+
+    /// Timestamp Date mode sorts by
+    #[arg(long, value_enum, default_value_t = DateSource::Auto)]
+    date_source: DateSource,
+
+    /// Folder granularity for the date, modified and accessed modes
+    #[arg(long, value_enum, default_value_t = DateBucket::Month)]
+    date_buckets: DateBucket,
+
+    /// Group photos taken within DURATION (e.g. 2s, 1m) of each other into Bursts/<timestamp>/
+    #[arg(long, value_name = "DURATION")]
+    group_bursts: Option<HumanDuration>,
+
+    /// Move files modified within DURATION (e.g. 7d) to Pending/ first, and file them away
+    /// once they've been left unchanged that long
+    #[arg(long, value_name = "DURATION")]
+    pending_for: Option<HumanDuration>,
+
+    /// Delete what `delete` rules match for good, instead of moving it to the trash
+    #[arg(long)]
+    permanent: bool,
+
+    /// Afterwards, list the extensions of unknown type and ask which category each belongs
+    /// to, saving the answers to the config for future runs
+    #[arg(long, conflicts_with = "check")]
+    learn_unknown: bool,
+
+    /// With ndjson, also write one JSON event per line to stderr (scan_started, file_planned,
+    /// file_moved, file_skipped, error, summary) for wrappers and GUIs
+    #[arg(long, value_enum, default_value_t = ProgressFormat::Pretty)]
+    progress_format: ProgressFormat,
+
+    /// How many destination folders the preview lists, with three example files each
+    #[arg(long, value_name = "N", default_value_t = PREVIEW_FOLDERS)]
+    preview_limit: usize,
+
+    /// List every folder and every file in the preview
+    #[arg(long, conflicts_with = "preview_limit")]
+    preview_full: bool,
+}
+
+#[derive(Args)]
+struct WatchArgs {
+    /// Directory to watch (default: current directory)
+    #[arg(value_name = "DIRECTORY")]
+    path: Option<PathBuf>,
+
+    /// Organization mode
+    #[arg(short, long, value_enum, default_value_t = OrganizeMode::Extension)]
+    mode: OrganizeMode,
+
+    /// Include subdirectories
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// How often to look for new files where they can't be noticed as they're closed (on
+    /// Linux they can)
+    #[arg(long, value_name = "DURATION", default_value = "30s")]
+    interval: HumanDuration,
+
+    /// How long a sync client's temporary files have to stay unchanged before organizing
+    /// resumes
+    #[arg(long, value_name = "DURATION", default_value = "1m")]
+    quiet_period: HumanDuration,
+
+    /// What to do when a file with the same name is already at the destination
+    #[arg(long, value_enum, default_value_t = ConflictPolicy::Refuse)]
+    on_conflict: ConflictPolicy,
+
+    /// Serve Prometheus metrics at http://ADDRESS/metrics, e.g. 0.0.0.0:9464
+    #[cfg(feature = "metrics")]
+    #[arg(long, value_name = "ADDRESS")]
+    metrics_listen: Option<std::net::SocketAddr>,
 }
 
 #[derive(clap::ValueEnum, Clone, Debug)]
+#[allow(clippy::enum_variant_names)]
 enum Shell {
     Bash,
     Zsh,
@@ -120,156 +691,1474 @@ enum Shell {
 }
 
 fn main() {
-    // Initialize logger
-    env_logger::init();
-
     // Parse CLI arguments
     let cli = Cli::parse();
-
-    // Set up logging level based on verbose flag
-    if cli.verbose {
-        println!("🦀 File Organizer CLI - Verbose mode enabled");
+    // Stderr is left to the JSON events, so the rest goes to stdout
+    let ndjson = matches!(&cli.command, Commands::Organize(args) if args.progress_format == ProgressFormat::Ndjson);
+    init_logger(cli.verbose, cli.quiet, ndjson);
+    output::set_quiet(cli.quiet);
+    let verbose = cli.verbose > 0;
+    if cli.background {
+        match priority::lower() {
+            Ok(()) => log::info!("Running at background priority"),
+            Err(e) => log::warn!("Couldn't lower the priority: {}", e),
+        }
     }
 
     // Handle the command
     let result = match cli.command {
-        Commands::Organize {
-            path,
-            mode,
-            dry_run,
-            force,
-            filter,
-            backup,
-            recursive,
-        } => handle_organize(path, mode, dry_run, force, filter, backup, recursive, cli.config),
+        Commands::Organize(args) => handle_organize(args, verbose, cli.config),
+
+        Commands::Watch(args) => handle_watch(args, cli.config),
+
+        Commands::Daemon { action } => match action {
+            DaemonAction::Status => handle_daemon(Control::Status, None),
+            DaemonAction::Pause { path } => handle_daemon(Control::Pause, path),
+            DaemonAction::Resume { path } => handle_daemon(Control::Resume, path),
+            DaemonAction::Flush { path } => handle_daemon(Control::Flush, path),
+            DaemonAction::Reload { path } => handle_daemon(Control::Reload, path),
+        },
+
+        Commands::Undo { path, operation, dry_run, on_conflict } => {
+            handle_undo(path, operation, dry_run, on_conflict, cli.config)
+        }
+
+        Commands::History { action: Some(HistoryAction::Migrate { path, dry_run }), .. } => {
+            handle_history_migrate(path, dry_run)
+        }
+
+        Commands::History { action: Some(HistoryAction::Export { file, path }), .. } => {
+            handle_history_export(file, path, cli.config)
+        }
+
+        Commands::History { action: Some(HistoryAction::Import { file, path }), .. } => {
+            handle_history_import(file, path, cli.config)
+        }
+
+        Commands::History { action: None, path, limit, global, since, until, mode } => {
+            let filter = HistoryFilter {
+                within: None,
+                since: since.map(start_of_day),
+                until: until.and_then(|day| day.succ_opt()).map(start_of_day),
+                mode: mode.map(|mode| format!("{:?}", mode)),
+            };
+            if global {
+                handle_history_global(HistoryFilter { within: path, ..filter }, limit, cli.config)
+            } else {
+                handle_history(path, limit, filter, cli.config)
+            }
+        }
+
+        Commands::Pin { files, remove } => handle_pin(&files, remove),
+
+        Commands::Stats { path, trend, limit } => handle_stats(path, trend, limit, cli.config),
 
-        Commands::Undo { path, dry_run } => handle_undo(path, dry_run),
+        Commands::Import { media, destination, dry_run, date_buckets, on_conflict, eject } => {
+            handle_import(media, destination, dry_run, date_buckets, on_conflict, eject, cli.config)
+        }
 
-        Commands::History { path, limit } => handle_history(path, limit),
+        Commands::Merge { source, destination, dry_run } => handle_merge(source, destination, dry_run, cli.config),
 
-        Commands::Clean { path, dry_run } => handle_clean(path, dry_run),
+        Commands::Enforce { path, max_size, keep_newest, strategy, archive_dir, recursive, include_hidden, hydrate, dry_run } => {
+            let budget = Budget { max_size: max_size.map(|size| size.0), keep_newest, strategy, archive_dir, recursive };
+            handle_enforce(path, budget, include_hidden, hydrate, dry_run, cli.config)
+        }
+
+        Commands::Dedupe { path, folders, remove, force, hash_algorithm } => {
+            handle_dedupe(path, folders, remove, force, hash_algorithm, verbose, cli.config)
+        }
+
+        Commands::Rules { action: RulesAction::Lint } => handle_rules_lint(cli.config),
+
+        Commands::Manifest { action: ManifestAction::Export { file, path, hash_algorithm } } => {
+            handle_manifest_export(file, path, hash_algorithm, verbose, cli.config)
+        }
+
+        Commands::Manifest { action: ManifestAction::Check { file, path } } => {
+            handle_manifest_check(file, path, verbose, cli.config)
+        }
+
+        Commands::Find { report: FindReport::WeirdNames { path, longer_than } } => handle_find_weird_names(path, longer_than),
+
+        Commands::Cache { action: CacheAction::Clear } => handle_cache_clear(cli.config),
+
+        Commands::Clean { path, dry_run, only_organizer_dirs } => {
+            handle_clean(path, dry_run, only_organizer_dirs, cli.config)
+        }
+
+        Commands::SelfUpdate { check_only } => handle_self_update(check_only),
 
         Commands::Completions { shell } => handle_completions(shell),
     };
 
     // Handle any errors
     if let Err(e) = result {
-        eprintln!("❌ Error: {}", e);
+        if ndjson {
+            println!("❌ {}", tr!("Error: {}", e));
+            progress::emit_run_error(&e.to_string());
+        } else {
+            eprintln!("❌ {}", tr!("Error: {}", e));
+        }
         std::process::exit(1);
     }
 }
 
+/// Log warnings by default, or what `-v`/`-vv`/`-vvv` and `--quiet` ask for. Without either,
+/// `RUST_LOG` can choose; the flags only raise this tool's own logs, not its dependencies'.
+/// Logs go to stderr unless that carries NDJSON progress.
+fn init_logger(verbose: u8, quiet: bool, to_stdout: bool) {
+    let mut builder = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"));
+    let level = match verbose {
+        0 => None,
+        1 => Some(log::LevelFilter::Info),
+        2 => Some(log::LevelFilter::Debug),
+        _ => Some(log::LevelFilter::Trace),
+    };
+    if quiet {
+        builder.filter_level(log::LevelFilter::Error);
+    } else if let Some(level) = level {
+        builder.filter_level(log::LevelFilter::Warn).filter_module("file_organizer", level);
+    }
+    if to_stdout {
+        builder.target(env_logger::Target::Stdout);
+    }
+    builder.init();
+}
+
 /// Handle the organize command
-fn handle_organize(
-    path: Option<PathBuf>,
-    mode: OrganizeMode,
-    dry_run: bool,
-    _force: bool,
-    filter: Option<Vec<String>>,
-    backup: bool,
-    recursive: bool,
-    config: Option<PathBuf>,
-) -> anyhow::Result<()> {
+fn handle_organize(args: OrganizeArgs, verbose: bool, config: Option<PathBuf>) -> anyhow::Result<()> {
     use colored::Colorize;
+
+    let OrganizeArgs {
+        path,
+        mode,
+        dry_run,
+        analyze,
+        check,
+        plan_id,
+        force: _force,
+        filter,
+        only_categories,
+        exclude_ext,
+        exclude_categories,
+        name_longer_than,
+        weird_names,
+        include_hidden,
+        hydrate,
+        network_friendly,
+        no_extension,
+        strict_scan,
+        one_file_system,
+        durable,
+        backend,
+        finder_tags,
+        backup,
+        recursive,
+        preserve_structure,
+        per_directory,
+        on_conflict,
+        on_error,
+        fail_fast,
+        ask_per_category,
+        resume,
+        min_free,
+        checkpoint_every,
+        books_by_author,
+        fonts_by_family,
+        date_source,
+        date_buckets,
+        group_bursts,
+        pending_for,
+        permanent,
+        learn_unknown,
+        progress_format,
+        preview_limit,
+        preview_full,
+    } = args;
     
     let target_path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
     
     // Verify target directory exists
     if !target_path.exists() {
-        return Err(anyhow::anyhow!("Directory does not exist: {}", target_path.display()));
+        return Err(anyhow::anyhow!(tr!("Directory does not exist: {}", target_path.display())));
     }
     
     if !target_path.is_dir() {
-        return Err(anyhow::anyhow!("Path is not a directory: {}", target_path.display()));
+        return Err(anyhow::anyhow!(tr!("Path is not a directory: {}", target_path.display())));
     }
     
     // Show operation details
-    println!("{}", "🦀 File Organizer CLI".bold().cyan());
-    println!("🎯 Target directory: {}", target_path.display().to_string().green());
-    println!("📋 Organization mode: {:?}", mode);
+    say!("{}", format!("🦀 {}", tr!("File Organizer CLI")).bold().cyan());
+    say!("🎯 {}", tr!("Target directory: {}", target_path.display().to_string().green()));
+    say!("📋 {}", tr!("Organization mode: {}", format!("{:?}", mode)));
     
-    if dry_run {
-        println!("{}", "🔍 DRY RUN MODE - No changes will be made".yellow());
+    let dry_run = dry_run || check;
+    if analyze {
+        say!("{}", format!("🔬 {}", tr!("ANALYSIS MODE - Read-only, nothing will be written")).yellow());
+    } else if check {
+        say!("{}", format!("🔍 {}", tr!("CHECK MODE - No changes will be made")).yellow());
+    } else if dry_run {
+        say!("{}", format!("🔍 {}", tr!("DRY RUN MODE - No changes will be made")).yellow());
     }
     
     if let Some(filters) = &filter {
-        println!("🔧 File filters: {}", filters.join(", ").cyan());
+        say!("🔧 {}", tr!("File filters: {}", filters.join(", ").cyan()));
+    }
+
+    if !only_categories.is_empty() {
+        let names: Vec<&str> = only_categories.iter().map(FileCategory::folder_name).collect();
+        say!("🔧 {}", tr!("Only categories: {}", names.join(", ").cyan()));
+    }
+
+    if !exclude_ext.is_empty() || !exclude_categories.is_empty() {
+        let names: Vec<&str> = exclude_ext
+            .iter()
+            .map(String::as_str)
+            .chain(exclude_categories.iter().map(|category| category.folder_name()))
+            .collect();
+        say!("🚫 {}", tr!("Excluding: {}", names.join(", ").cyan()));
+    }
+
+    let name_predicates = NamePredicates { longer_than: name_longer_than, unusual_characters: weird_names };
+    if let Some(limit) = name_longer_than {
+        say!("🔧 {}", tr!("Only names longer than {} characters", limit.to_string().cyan()));
+    }
+    if weird_names {
+        say!("🔧 {}", tr!("Only names with non-ASCII or control characters"));
     }
     
     if backup {
-        println!("{}", "💾 Backup mode enabled (TODO: Not implemented yet)".yellow());
+        say!("{}", format!("💾 {}", tr!("Backup mode enabled (TODO: Not implemented yet)")).yellow());
     }
     
     if recursive {
-        println!("{}", "🔄 Recursive mode enabled".green());
+        say!("{}", format!("🔄 {}", tr!("Recursive mode enabled")).green());
+    }
+
+    if preserve_structure {
+        say!("{}", format!("🌳 {}", tr!("Subdirectories will be kept beneath each category")).green());
+    }
+
+    if per_directory {
+        say!("{}", format!("📂 {}", tr!("Each subdirectory will be organized in place")).green());
+    }
+
+    if books_by_author {
+        say!("{}", format!("📚 {}", tr!("Ebooks will be sorted by author and title")).green());
+    }
+
+    if fonts_by_family {
+        say!("{}", format!("🔤 {}", tr!("Fonts will be sorted by family")).green());
+    }
+
+    if finder_tags && !cfg!(target_os = "macos") {
+        say!("{}", format!("⚠️  {}", tr!("Finder tags only exist on macOS, so --finder-tags is ignored")).yellow());
+    }
+    let finder_tags = finder_tags && cfg!(target_os = "macos");
+    if finder_tags {
+        say!("{}", format!("🏷️  {}", tr!("Files will be tagged in Finder with their category")).green());
+    }
+
+    if let Some(duration) = pending_for {
+        say!("⏳ {}", tr!("Files changed within {} wait in {}/ first", duration.to_string().cyan(), PENDING_DIR));
+    }
+
+    if let Some(window) = group_bursts {
+        say!("📸 {}", tr!("Grouping photos taken within {} of each other into bursts", window.to_string().cyan()));
     }
     
-    if let Some(config_path) = config {
-        println!("⚙️ Using config: {} {}", config_path.display(), "(TODO: Not implemented yet)".yellow());
+    let (mut config, config_path) = Config::load_or_default(config.as_deref())?;
+    if let Some(config_path) = &config_path {
+        log::info!("Using config {}", config_path.display());
+    }
+    if let Some(no_extension) = no_extension {
+        config.categories.no_extension = no_extension;
     }
     
-    println!(); // Empty line for better formatting
+    let network_friendly = network_friendly || config.execution.network_friendly;
+    if network_friendly && group_bursts.is_some() {
+        say!("{}", format!("⚠️  {}", tr!("Bursts are found by EXIF capture times, which --network-friendly doesn't read, so --group-bursts is ignored")).yellow());
+    }
+
+    let on_error = if fail_fast { ErrorPolicy::FailFast } else { on_error.unwrap_or(config.execution.on_error) };
+    if on_error != ErrorPolicy::KeepGoing {
+        say!("{}", format!("⛔ {}", tr!("Execution stops at the first file that can't be moved")).yellow());
+    }
+
+    say!(); // Empty line for better formatting
     
     // Create and run the organizer
-    let mut organizer = FileOrganizer::new();
-    let _summary = organizer.organize(
-        &target_path,
-        &mode,
-        recursive,
-        filter.as_ref(),
-        dry_run,
+    let organizer = FileOrganizer::with_config(
+        OrganizeOptions {
+            books_by_author,
+            fonts_by_family,
+            burst_window: group_bursts.map(|window| window.as_secs()),
+            date_source,
+            date_buckets,
+            preserve_structure,
+            per_directory,
+            on_conflict,
+            ask_per_category,
+            on_error,
+            only_categories,
+            exclude_extensions: exclude_ext,
+            exclude_categories,
+            name_predicates,
+            checkpoint_every: Some(checkpoint_every),
+            min_free: min_free.or(config.execution.min_free).map(|size| size.0),
+            analyze,
+            include_hidden,
+            hydrate,
+            network_friendly,
+            hash_algorithm: HashAlgorithm::default(),
+            strict_scan,
+            one_file_system,
+            durable: durable || config.execution.durable,
+            backend,
+            finder_tags,
+            plan_id,
+            pending_for: pending_for.map(|duration| duration.0),
+            permanent_delete: permanent,
+        },
+        &config,
     )?;
+    let preview = if preview_full { PreviewSize::Full } else { PreviewSize::Limit(preview_limit) };
+    let console = ConsoleObserver::new()
+        .verbose(verbose)
+        .errors_to_stdout(progress_format == ProgressFormat::Ndjson)
+        .preview(preview);
+    let mut organizer = match progress_format {
+        ProgressFormat::Pretty => organizer.with_observer(console),
+        ProgressFormat::Ndjson => organizer.with_observer(NdjsonObserver::new(console)),
+    };
+    let report = if resume {
+        let Some(report) = organizer.resume(&target_path)? else {
+            say!("ℹ️  {}", tr!("No interrupted run to resume"));
+            return Ok(());
+        };
+        report
+    } else {
+        organizer.organize(
+            &target_path,
+            &mode,
+            recursive,
+            filter.as_ref(),
+            dry_run,
+        )?
+    };
+
+    if check {
+        if !report.plan.is_empty() || !report.deletions.is_empty() {
+            let files = report.plan.len() + report.deletions.len();
+            return Err(anyhow::anyhow!(tr!("{} files in {} need organizing", files, target_path.display())));
+        }
+        say!("\n{}", format!("✅ {}", tr!("Everything is already organized")).bold().green());
+        return Ok(());
+    }
+
+    if report.dry_run && (!report.plan.is_empty() || !report.deletions.is_empty()) {
+        let id = &report.plan_id;
+        say!("\n🔖 {}", tr!("Plan {} — run with `--plan-id {}` to carry out exactly this plan", id.cyan(), id));
+    }
+
+    // Even after a failure: whatever did move can be put back
+    if let Some(id) = &report.operation_id {
+        let undo = format!("file-organizer undo {} --operation {}", target_path.display(), id);
+        say!("\n📜 {}", tr!("Operation #{} — run `{}` to revert", id.cyan(), undo));
+    }
+
+    let deleted = report.deleted.iter().filter(|outcome| outcome.error.is_none()).count();
+    if deleted > 0 {
+        let message = if permanent { tr!("Deleted {} files for good", deleted) } else { tr!("Moved {} files to the trash", deleted) };
+        say!("🗑️  {}", message);
+    }
+    let undeleted = report.deleted.len() - deleted;
+    if undeleted > 0 && report.failed() == 0 {
+        return Err(anyhow::anyhow!(tr!("{} of {} files could not be deleted", undeleted, report.deleted.len())));
+    }
+
+    if report.failed() > 0 {
+        if report.not_attempted() == 0 && report.rolled_back() == 0 && report.deletions_not_attempted() == 0 {
+            return Err(anyhow::anyhow!(tr!("{} of {} files could not be moved", report.failed(), report.outcomes.len())));
+        }
+        let mut message = tr!("Stopped after a file could not be moved; {} files were not attempted", report.not_attempted());
+        if report.rolled_back() > 0 {
+            message.push_str(&tr!(" and {} moved files were put back", report.rolled_back()));
+        }
+        if report.deletions_not_attempted() > 0 {
+            message.push_str(&tr!("; {} files were not deleted", report.deletions_not_attempted()));
+        }
+        return Err(anyhow::anyhow!(message));
+    }
     
-    println!("\n{}", "🎉 File organization completed successfully!".bold().green());
+    say!("\n{}", format!("🎉 {}", tr!("File organization completed successfully!")).bold().green());
+
+    if learn_unknown {
+        learn_unknown_extensions(organizer.unknown_extensions(&report.plan), config_path)?;
+    }
     
     Ok(())
 }
 
+/// Longest a watch takes to answer a daemon command between rounds
+const CONTROL_TICK: Duration = Duration::from_secs(1);
+
+/// Handle the watch command: organize now, then each file once it has been written (on Linux)
+/// or everything every `interval`, except while a sync client has written into the
+/// directory within `quiet_period`
+fn handle_watch(args: WatchArgs, config: Option<PathBuf>) -> anyhow::Result<()> {
+    use colored::Colorize;
+
+    let WatchArgs { path, mode, recursive, interval, quiet_period, on_conflict, .. } = args;
+    let target_path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    if !target_path.is_dir() {
+        return Err(anyhow::anyhow!(tr!("Path is not a directory: {}", target_path.display())));
+    }
+
+    let load_organizer = |config: Option<&std::path::Path>| -> anyhow::Result<FileOrganizer> {
+        let (config, _) = Config::load_or_default(config)?;
+        let options =
+            OrganizeOptions { on_conflict, min_free: config.execution.min_free.map(|size| size.0), ..OrganizeOptions::default() };
+        Ok(FileOrganizer::with_config(options, &config)?)
+    };
+    let mut organizer = load_organizer(config.as_deref())?;
+    let mut events = FileEvents::new(&target_path, recursive, interval.0);
+    if events.reports_files() {
+        say!("👀 {}", tr!("Watching {} (files are organized once they're closed)", target_path.display().to_string().green()));
+    } else {
+        say!("👀 {}", tr!("Watching {} (checking every {})", target_path.display().to_string().green(), interval));
+    }
+    let server = ControlServer::start(&target_path)
+        .inspect_err(|e| log::warn!("Can't listen for daemon commands, so `daemon` won't reach this watch: {}", e))
+        .ok();
+    #[cfg(feature = "metrics")]
+    let metrics = match args.metrics_listen {
+        Some(address) => {
+            let metrics = Metrics::serve(address).map_err(|e| anyhow::anyhow!(tr!("Can't serve metrics at {}: {}", address, e)))?;
+            say!("📈 {}", tr!("Metrics at http://{}/metrics", metrics.local_addr().unwrap_or(address)));
+            Some(metrics)
+        }
+        None => None,
+    };
+
+    let mut status = WatchStatus {
+        pid: std::process::id(),
+        directory: target_path.canonicalize().unwrap_or_else(|_| target_path.clone()),
+        ..WatchStatus::default()
+    };
+    let mut paused_by = None;
+    // Everything at first; after that, on Linux, just the files written since
+    let mut rescan = true;
+    let mut written = BTreeSet::new();
+    let mut moved_here = HashSet::new();
+    let mut flushes = Vec::new();
+    loop {
+        for request in server.iter().flat_map(ControlServer::requests) {
+            let (message, error) = match request.control {
+                _ if !request.applies => (None, None),
+                Control::Status => (None, None),
+                Control::Pause => {
+                    status.paused = true;
+                    (Some(tr!("Paused until `daemon resume`").to_string()), None)
+                }
+                Control::Resume => {
+                    status.paused = false;
+                    (Some(tr!("Resumed").to_string()), None)
+                }
+                Control::Flush => {
+                    flushes.push(request);
+                    continue;
+                }
+                Control::Reload => match load_organizer(config.as_deref()) {
+                    Ok(reloaded) => {
+                        organizer = reloaded;
+                        (Some(tr!("Reloaded the config").to_string()), None)
+                    }
+                    Err(e) => (None, Some(e.to_string())),
+                },
+            };
+            if let Some(message) = &message {
+                say!("🎛️  {}", message);
+            }
+            request.answer(ControlReply { status: status.clone(), message, error, busy: false });
+        }
+        if let Some(server) = &server {
+            server.publish(&status);
+        }
+
+        let flushing = !flushes.is_empty();
+        if flushing || (!status.paused && (rescan || !written.is_empty())) {
+            let since = SystemTime::now().checked_sub(quiet_period.0).unwrap_or(SystemTime::UNIX_EPOCH);
+            // Flushing organizes right away, syncing or not
+            let activity = if flushing { None } else { syncs::activity_since(&target_path, recursive, since) };
+            if let Some(activity) = activity {
+                if paused_by != Some(activity.client) {
+                    say!(
+                        "⏸️  {}",
+                        tr!("{} is syncing ({}), waiting until it has been quiet for {}", activity.client, activity.path.display(), quiet_period)
+                    );
+                    paused_by = Some(activity.client);
+                    status.waiting_for = Some(activity.client.to_string());
+                }
+            } else {
+                if paused_by.take().is_some() {
+                    say!("▶️  {}", tr!("Syncing has finished, organizing again"));
+                    status.waiting_for = None;
+                }
+                let result = if rescan || flushing {
+                    organizer.organize(&target_path, &mode, recursive, None, false)
+                } else {
+                    organizer.organize_files(&target_path, &mode, recursive, &written.iter().cloned().collect::<Vec<_>>())
+                };
+                (rescan, written) = (false, BTreeSet::new());
+                let (message, error) = match result {
+                    Ok(report) => {
+                        // Watched folders report the files moved into them; they're in place already
+                        moved_here = report
+                            .outcomes
+                            .iter()
+                            .filter(|outcome| outcome.succeeded())
+                            .map(|outcome| outcome.operation.destination.clone())
+                            .collect();
+                        #[cfg(feature = "metrics")]
+                        if let Some(metrics) = &metrics {
+                            metrics.update(&status.directory, |counters| {
+                                counters.files_organized += report.moved() as u64;
+                                counters.bytes_moved += report.summary.timings.bytes;
+                                counters.errors += report.failed() as u64;
+                            });
+                        }
+                        if report.moved() > 0 {
+                            status.rounds += 1;
+                            status.moved += report.moved() as u64;
+                            status.last_moved = Some(chrono::Local::now());
+                        }
+                        if report.failed() > 0 {
+                            let failed = tr!("{} of {} files could not be moved", report.failed(), report.outcomes.len());
+                            say!("⚠️  {}", failed);
+                            (None, Some(failed))
+                        } else {
+                            let moved = tr!("Moved {} files", report.moved());
+                            if report.moved() > 0 {
+                                say!("📦 {}", moved);
+                            }
+                            (Some(moved), None)
+                        }
+                    }
+                    // A file vanishing mid-scan, say; the next round tries again
+                    Err(e) => {
+                        eprintln!("❌ {}", tr!("Error: {}", e));
+                        #[cfg(feature = "metrics")]
+                        if let Some(metrics) = &metrics {
+                            metrics.update(&status.directory, |counters| counters.errors += 1);
+                        }
+                        (None, Some(e.to_string()))
+                    }
+                };
+                for flush in flushes.drain(..) {
+                    flush.answer(ControlReply { status: status.clone(), message: message.clone(), error: error.clone(), busy: false });
+                }
+            }
+        }
+
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &metrics {
+            metrics.update(&status.directory, |counters| counters.queue_depth = written.len() as u64);
+        }
+        // Short waits, so daemon commands are answered promptly
+        match events.wait(CONTROL_TICK) {
+            Written::Files(files) => written.extend(files.into_iter().filter(|file| !moved_here.remove(file))),
+            Written::Anything => rescan = true,
+        }
+    }
+}
+
+/// Handle the daemon commands: send `control` to every running watch (or just the one of
+/// `directory`) and show what they say
+fn handle_daemon(control: Control, directory: Option<PathBuf>) -> anyhow::Result<()> {
+    use colored::Colorize;
+
+    let replies = control::send_all(&ControlRequest { control, directory: directory.clone() });
+    if replies.is_empty() {
+        return Err(anyhow::anyhow!(tr!("No watch is running")));
+    }
+    let mut reached = false;
+    let mut failed = false;
+    for reply in &replies {
+        let acted = reply.busy || reply.message.is_some() || reply.error.is_some();
+        if control != Control::Status && !acted {
+            continue;
+        }
+        reached = true;
+        let status = &reply.status;
+        let state = if status.paused {
+            tr!("paused").yellow()
+        } else if let Some(client) = &status.waiting_for {
+            tr!("waiting for {}", client).yellow()
+        } else {
+            tr!("watching").green()
+        };
+        say!("👀 {} (pid {}): {}", status.directory.display(), status.pid, state);
+        if control == Control::Status {
+            let last = status.last_moved.map(|time| time.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_else(|| "-".to_string());
+            say!("   {}", tr!("{} files moved in {} rounds, last at {}", status.moved, status.rounds, last));
+        }
+        if let Some(message) = &reply.message {
+            say!("   {}", message);
+        }
+        if reply.busy {
+            say!("   {}", tr!("Busy with a round; this is done once it's over"));
+        }
+        if let Some(error) = &reply.error {
+            say!("   ❌ {}", error);
+            failed = true;
+        }
+    }
+    if !reached {
+        let directory = directory.unwrap_or_default();
+        return Err(anyhow::anyhow!(tr!("No watch is running for {}", directory.display())));
+    }
+    if failed {
+        return Err(anyhow::anyhow!(tr!("A watch couldn't do that")));
+    }
+    Ok(())
+}
+
+/// With --learn-unknown: ask for the category of each unknown extension and add the answers
+/// to the config. Without answers (no terminal), print a config snippet instead.
+fn learn_unknown_extensions(unknown: Vec<(String, usize)>, config_path: Option<PathBuf>) -> anyhow::Result<()> {
+    if unknown.is_empty() {
+        say!("\nℹ️  {}", tr!("Every file had a known type"));
+        return Ok(());
+    }
+    let Some(config_path) = config_path.or_else(default_config_path) else {
+        return Err(anyhow::anyhow!(tr!("Can't tell where the config file goes; pass --config")));
+    };
+
+    say!("\n❓ {}", tr!("Files of unknown type, by extension:"));
+    for (extension, files) in &unknown {
+        say!("   .{}  {}", extension, tr!("{} files", files));
+    }
+    say!("💡 {}", tr!("Name a category for each (Images, Documents, Code, ...), or press Enter to skip one"));
+
+    let mut learned = Vec::new();
+    let mut unanswered = Vec::new();
+    let mut remaining = unknown.into_iter();
+    for (extension, files) in remaining.by_ref() {
+        let Some(answer) = console::ask(&tr!("Category for .{}:", extension)) else {
+            unanswered.push((extension, files));
+            break;
+        };
+        if answer.is_empty() {
+            continue;
+        }
+        match answer.parse::<FileCategory>() {
+            Ok(category) => learned.push((extension, category)),
+            Err(e) => say!("⚠️  {}", tr!("Skipping .{}: {}", extension, e)),
+        }
+    }
+    unanswered.extend(remaining);
+
+    if !learned.is_empty() {
+        config::save_extensions(&config_path, &learned)?;
+        say!("✅ {}", tr!("Saved {} extensions to {}", learned.len(), config_path.display()));
+    }
+    if !unanswered.is_empty() {
+        say!("\n📝 {}", tr!("To sort them from now on, add this to {} with the right categories:", config_path.display()));
+        let rows: Vec<(&str, String)> =
+            unanswered.iter().map(|(extension, files)| (extension.as_str(), tr!("{} files", files))).collect();
+        print_extensions_snippet(&rows);
+    }
+    Ok(())
+}
+
+/// Print a `[categories.extensions]` table filing every extension under Other, for the user to
+/// correct and paste into their config, with a comment after each line
+fn print_extensions_snippet(extensions: &[(&str, String)]) {
+    say!("[categories.extensions]");
+    for (extension, comment) in extensions {
+        // `tar.xyz` would be a dotted key unquoted
+        let key = if extension.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            extension.to_string()
+        } else {
+            format!("{:?}", extension)
+        };
+        say!("{} = \"Other\"  # {}", key, comment);
+    }
+}
+
 /// Handle the undo command
-fn handle_undo(path: Option<PathBuf>, dry_run: bool) -> anyhow::Result<()> {
+fn handle_undo(
+    path: Option<PathBuf>,
+    operation: Option<String>,
+    dry_run: bool,
+    on_conflict: ConflictPolicy,
+    config: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    use colored::Colorize;
+
     let target_path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
     
-    println!("↩️  Undoing organization in: {}", target_path.display());
+    say!("↩️  {}", tr!("Undoing organization in: {}", target_path.display()));
     
     if dry_run {
-        println!("🔍 DRY RUN MODE - Showing what would be undone");
+        say!("🔍 {}", tr!("DRY RUN MODE - Showing what would be undone"));
+    }
+
+    let organizer = FileOrganizer::with_config(
+        OrganizeOptions {
+            on_conflict,
+            ..OrganizeOptions::default()
+        },
+        &Config::load_or_default(config.as_deref())?.0,
+    )?
+    .with_observer(ConsoleObserver::new());
+    let report = match &operation {
+        Some(id) => organizer.undo_operation(&target_path, id, dry_run)?,
+        None => match organizer.undo(&target_path, dry_run)? {
+            Some(report) => report,
+            None => {
+                say!("ℹ️  {}", tr!("Nothing to undo"));
+                return Ok(());
+            }
+        },
+    };
+    say!("📜 {}", tr!("Operation {}", report.operation_id.cyan()));
+
+    for entry in &report.missing {
+        eprintln!(
+            "{} {}",
+            format!("❌ {}", tr!("Can't restore")).red(),
+            tr!("{} is no longer at {}", entry.source.display(), entry.destination.display())
+        );
+    }
+
+    if dry_run {
+        for op in &report.operations {
+            let taken = if op.destination.exists() { format!(" ⚠️  {}", tr!("(already taken)")).yellow().to_string() } else { String::new() };
+            say!("   {} → {}{}", op.source.display(), op.destination.display(), taken);
+        }
+        let conflicts = report.conflicts().count();
+        say!("\n{} {}", tr!("Total:").bold(), tr!("{} files would be restored", report.operations.len().to_string().yellow()));
+        if conflicts > 0 {
+            say!("{}", format!("⚠️  {}", tr!("{} original locations are taken; see --on-conflict", conflicts)).yellow());
+        }
+        return Ok(());
+    }
+
+    for outcome in report.outcomes.iter().filter(|outcome| outcome.error.is_some()) {
+        eprintln!(
+            "{} {}: {}",
+            format!("❌ {}", tr!("Can't restore")).red(),
+            outcome.operation.destination.display(),
+            outcome.error.as_deref().unwrap_or_default()
+        );
+    }
+    if report.skipped() > 0 {
+        say!("⏭️  {}", tr!("Skipped {} files whose original location is taken", report.skipped().to_string().yellow()));
+    }
+
+    match report.unrestorable() {
+        0 => {
+            say!("✅ {}", tr!("Undo completed!"));
+            Ok(())
+        }
+        unrestorable => Err(anyhow::anyhow!(tr!(
+            "{} of {} files could not be restored",
+            unrestorable,
+            report.operations.len() + report.missing.len()
+        ))),
     }
-    
-    // TODO: Implement undo logic
-    println!("✅ Undo completed!");
-    
-    Ok(())
 }
 
 /// Handle the history command
-fn handle_history(path: Option<PathBuf>, limit: usize) -> anyhow::Result<()> {
+fn handle_history(
+    path: Option<PathBuf>,
+    limit: usize,
+    filter: HistoryFilter,
+    config: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    use colored::Colorize;
+
     let target_path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
     
-    println!("📚 Showing history for: {}", target_path.display());
-    println!("📊 Limit: {} operations", limit);
-    
-    // TODO: Implement history logic
-    println!("ℹ️  No operations found in history");
+    say!("📚 {}", tr!("Showing history for: {}", target_path.display()));
+    say!("📊 {}", tr!("Limit: {} operations", limit));
+
+    let (config, _) = Config::load_or_default(config.as_deref())?;
+    let mut journals = store::open(&config.history)?.journals(&target_path)?;
+    journals.retain(|journal| filter.matches(journal));
+    if journals.is_empty() {
+        say!("ℹ️  {}", tr!("No operations found in history"));
+        return Ok(());
+    }
+
+    for journal in journals.iter().rev().take(limit) {
+        say!(
+            "   {} {}  {}",
+            journal.id.cyan(),
+            journal.created_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+            tr!("{} mode, {} files", journal.mode, journal.moves.len().to_string().yellow())
+        );
+    }
     
     Ok(())
 }
 
+/// Handle the history command with --global
+fn handle_history_global(filter: HistoryFilter, limit: usize, config: Option<PathBuf>) -> anyhow::Result<()> {
+    use colored::Colorize;
+
+    match &filter.within {
+        Some(within) => say!("📚 {}", tr!("Showing history for every directory in: {}", within.display())),
+        None => say!("📚 {}", tr!("Showing history for every organized directory")),
+    }
+    say!("📊 {}", tr!("Limit: {} operations", limit));
+
+    let (config, _) = Config::load_or_default(config.as_deref())?;
+    let runs = store::global_history(store::open(&config.history)?.as_ref(), &filter)?;
+    if runs.is_empty() {
+        say!("ℹ️  {}", tr!("No operations found in history"));
+        return Ok(());
+    }
+
+    for (directory, journal) in runs.iter().take(limit) {
+        say!(
+            "   {} {}  {}  {}",
+            journal.id.cyan(),
+            journal.created_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+            directory.display(),
+            tr!("{} mode, {} files", journal.mode, journal.moves.len().to_string().yellow())
+        );
+    }
+
+    Ok(())
+}
+
+/// Local midnight at the start of `day`, for the history date filters
+fn start_of_day(day: NaiveDate) -> DateTime<Utc> {
+    let midnight = day.and_time(NaiveTime::MIN);
+    // Midnight can be skipped by a DST change; fall back to reading it as UTC
+    match Local.from_local_datetime(&midnight).earliest() {
+        Some(local) => local.with_timezone(&Utc),
+        None => midnight.and_utc(),
+    }
+}
+
+/// Handle the history export command
+fn handle_history_export(file: PathBuf, path: Option<PathBuf>, config: Option<PathBuf>) -> anyhow::Result<()> {
+    let target_path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    let (config, _) = Config::load_or_default(config.as_deref())?;
+
+    let count = store::export(store::open(&config.history)?.as_ref(), &target_path, &file)?;
+    say!("✅ {}", tr!("Exported {} operations to {}", count, file.display()));
+    Ok(())
+}
+
+/// Handle the history import command
+fn handle_history_import(file: PathBuf, path: Option<PathBuf>, config: Option<PathBuf>) -> anyhow::Result<()> {
+    let target_path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    let (config, _) = Config::load_or_default(config.as_deref())?;
+
+    let count = store::import(store::open(&config.history)?.as_ref(), &target_path, &file)?;
+    say!("✅ {}", tr!("Imported {} operations into the history of {}", count, target_path.display()));
+    Ok(())
+}
+
+/// Handle the history migrate command
+fn handle_history_migrate(path: Option<PathBuf>, dry_run: bool) -> anyhow::Result<()> {
+    let target_path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
+
+    say!("📚 {}", tr!("Migrating history in: {}", target_path.display()));
+    let migrations = journal::migrate(&target_path, dry_run)?;
+    for migration in &migrations {
+        say!(
+            "   {} ({})",
+            migration.path.display(),
+            tr!("format {} → {}", migration.from_version, journal::JOURNAL_VERSION)
+        );
+    }
+
+    match (migrations.len(), dry_run) {
+        (0, _) => say!("✅ {}", tr!("History is already up to date")),
+        (count, true) => say!("🔍 {}", tr!("{} journals would be migrated", count)),
+        (count, false) => say!("✅ {}", tr!("Migrated {} journals", count)),
+    }
+    Ok(())
+}
+
 /// Handle the clean command
-fn handle_clean(path: Option<PathBuf>, dry_run: bool) -> anyhow::Result<()> {
+/// Handle the pin command
+fn handle_pin(files: &[PathBuf], remove: bool) -> anyhow::Result<()> {
+    for file in files {
+        if !remove {
+            pins::pin(file)?;
+            say!("📌 {}", tr!("Pinned {}", file.display()));
+        } else if pins::unpin(file)? {
+            say!("✅ {}", tr!("Unpinned {}", file.display()));
+        } else {
+            say!("ℹ️  {}", tr!("{} wasn't pinned", file.display()));
+        }
+    }
+    Ok(())
+}
+
+/// Handle the import command
+fn handle_import(
+    media: PathBuf,
+    destination: Option<PathBuf>,
+    dry_run: bool,
+    date_buckets: DateBucket,
+    on_conflict: ConflictPolicy,
+    eject: bool,
+    config: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    use colored::Colorize;
+
+    if !media.is_dir() {
+        return Err(anyhow::anyhow!(tr!("Not a directory: {}", media.display())));
+    }
+    let destination = destination.unwrap_or_else(|| std::env::current_dir().unwrap());
+    say!("📥 {}", tr!("Importing from {} into {}", media.display(), destination.display().to_string().green()));
+    if dry_run {
+        say!("{}", format!("🔍 {}", tr!("DRY RUN MODE - Nothing will be copied")).yellow());
+    }
+
+    let (config, _) = Config::load_or_default(config.as_deref())?;
+    let mut organizer = FileOrganizer::with_config(
+        OrganizeOptions {
+            date_buckets,
+            on_conflict,
+            min_free: config.execution.min_free.map(|size| size.0),
+            ..OrganizeOptions::default()
+        },
+        &config,
+    )?
+    .with_observer(ConsoleObserver::new());
+    let report = organizer.import_media(&media, &destination, dry_run)?;
+    if let Some(last_import) = report.last_import {
+        say!("🕒 {}", tr!("Last import: {}", last_import.with_timezone(&Local).format("%Y-%m-%d %H:%M")));
+    }
+
+    if dry_run {
+        say!(
+            "\n{} {}",
+            tr!("Total:").bold(),
+            tr!("{} new files would be copied, {} were imported before", report.plan.len().to_string().yellow(), report.already_imported)
+        );
+        return Ok(());
+    }
+    for outcome in report.outcomes.iter().filter(|outcome| outcome.error.is_some()) {
+        eprintln!(
+            "{} {}: {}",
+            format!("❌ {}", tr!("Can't copy")).red(),
+            outcome.operation.source.display(),
+            outcome.error.as_deref().unwrap_or_default()
+        );
+    }
+    say!(
+        "✅ {}",
+        tr!("Copied {} new files, {} were imported before", report.copied().to_string().green(), report.already_imported)
+    );
+    if report.failed() > 0 {
+        return Err(anyhow::anyhow!(tr!("{} files could not be copied; the media was left mounted", report.failed())));
+    }
+
+    if eject {
+        media_import::eject(&media)?;
+        say!("⏏️  {}", tr!("Ejected {}; the media can be removed", media.display()));
+    }
+    Ok(())
+}
+
+/// Handle the merge command
+fn handle_merge(source: PathBuf, destination: PathBuf, dry_run: bool, config: Option<PathBuf>) -> anyhow::Result<()> {
+    use colored::Colorize;
+
+    say!("🔀 {}", tr!("Merging {} into {}", source.display(), destination.display().to_string().green()));
+    if dry_run {
+        say!("{}", format!("🔍 {}", tr!("DRY RUN MODE - No changes will be made")).yellow());
+    }
+
+    let (config, _) = Config::load_or_default(config.as_deref())?;
+    let mut organizer = FileOrganizer::with_config(
+        OrganizeOptions {
+            // Only files that appear at the destination during the merge get here
+            on_conflict: ConflictPolicy::Rename,
+            min_free: config.execution.min_free.map(|size| size.0),
+            ..OrganizeOptions::default()
+        },
+        &config,
+    )?
+    .with_observer(ConsoleObserver::new());
+    let report = organizer.merge(&source, &destination, dry_run)?;
+
+    let relative = |path: &std::path::Path, root: &std::path::Path| path.strip_prefix(root).unwrap_or(path).display().to_string();
+    if dry_run {
+        for op in &report.plan {
+            say!("   {} → {}", relative(&op.source, &source), relative(&op.destination, &destination));
+        }
+        say!(
+            "\n{} {}",
+            tr!("Total:").bold(),
+            tr!(
+                "{} files would be moved ({} renamed), {} identical files would stay in {}",
+                report.plan.len().to_string().yellow(),
+                report.renamed,
+                report.identical.len(),
+                source.display()
+            )
+        );
+        return Ok(());
+    }
+
+    for outcome in report.outcomes.iter().filter(|outcome| outcome.error.is_some()) {
+        eprintln!(
+            "{} {}: {}",
+            format!("❌ {}", tr!("Can't move")).red(),
+            outcome.operation.source.display(),
+            outcome.error.as_deref().unwrap_or_default()
+        );
+    }
+    say!(
+        "✅ {}",
+        tr!(
+            "Moved {} files ({} renamed); {} identical files were left in {}",
+            report.moved().to_string().green(),
+            report.renamed,
+            report.identical.len(),
+            source.display()
+        )
+    );
+    if let Some(id) = &report.operation_id {
+        say!("↩️  {}", tr!("Undo with: file-organizer undo {} --operation {}", destination.display(), id.cyan()));
+    }
+    if report.failed() > 0 {
+        return Err(anyhow::anyhow!(tr!("{} of {} files could not be moved", report.failed(), report.outcomes.len())));
+    }
+    Ok(())
+}
+
+/// Handle the enforce command
+fn handle_enforce(
+    path: Option<PathBuf>,
+    budget: Budget,
+    include_hidden: bool,
+    hydrate: bool,
+    dry_run: bool,
+    config: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    use colored::Colorize;
+
+    let target_path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    if let Some(max_size) = budget.max_size {
+        say!("📏 {}", tr!("Keeping {} within {}", target_path.display(), ByteSize(max_size).to_string().green()));
+    }
+    if let Some(keep) = budget.keep_newest {
+        say!("📏 {}", tr!("Keeping the newest {} files of each folder in {}", keep.to_string().green(), target_path.display()));
+    }
+    if dry_run {
+        say!("{}", format!("🔍 {}", tr!("DRY RUN MODE - No changes will be made")).yellow());
+    }
+
+    let (config, _) = Config::load_or_default(config.as_deref())?;
+    let mut organizer = FileOrganizer::with_config(
+        OrganizeOptions {
+            on_conflict: ConflictPolicy::Rename,
+            min_free: config.execution.min_free.map(|size| size.0),
+            include_hidden,
+            hydrate,
+            ..OrganizeOptions::default()
+        },
+        &config,
+    )?
+    .with_observer(ConsoleObserver::new());
+    let report = organizer.enforce(&target_path, &budget, dry_run)?;
+    if report.evictions.is_empty() {
+        say!("✅ {}", tr!("{} holds {}, within the budget", target_path.display(), ByteSize(report.size)));
+        return Ok(());
+    }
+
+    let root = target_path.canonicalize().unwrap_or(target_path.clone());
+    for eviction in &report.evictions {
+        let shown = eviction.path.strip_prefix(&root).unwrap_or(&eviction.path).display();
+        match &eviction.destination {
+            Some(destination) => {
+                let destination = destination.strip_prefix(&root).unwrap_or(destination);
+                say!("   {} ({}) → {}", shown, ByteSize(eviction.bytes), destination.display());
+            }
+            None => say!("   🗑️  {} ({})", shown, ByteSize(eviction.bytes)),
+        }
+    }
+    let remaining = ByteSize(report.size - report.freed());
+    if dry_run {
+        let total = match budget.strategy {
+            EvictionStrategy::Archive => tr!(
+                "{} files ({}) would be archived, leaving {}",
+                report.evictions.len().to_string().yellow(),
+                ByteSize(report.freed()),
+                remaining
+            ),
+            _ => tr!(
+                "{} files ({}) would be moved to the trash, leaving {}",
+                report.evictions.len().to_string().yellow(),
+                ByteSize(report.freed()),
+                remaining
+            ),
+        };
+        say!("\n{} {}", tr!("Total:").bold(), total);
+        return Ok(());
+    }
+
+    for (file, error) in &report.failures {
+        eprintln!("{} {}: {}", format!("❌ {}", tr!("Can't move")).red(), file.display(), error);
+    }
+    match budget.strategy {
+        EvictionStrategy::Archive => say!(
+            "✅ {}",
+            tr!("Archived {} files ({}), leaving {}", report.evicted().to_string().green(), ByteSize(report.freed()), remaining)
+        ),
+        _ => say!(
+            "✅ {}",
+            tr!("Moved {} files ({}) to the trash, leaving {}", report.evicted().to_string().green(), ByteSize(report.freed()), remaining)
+        ),
+    }
+    if let Some(id) = &report.operation_id {
+        say!("↩️  {}", tr!("Undo with: file-organizer undo {} --operation {}", target_path.display(), id.cyan()));
+    }
+    if !report.failures.is_empty() {
+        return Err(anyhow::anyhow!(tr!("{} of {} files could not be moved", report.failures.len(), report.evictions.len())));
+    }
+    Ok(())
+}
+
+/// Handle the dedupe command
+fn handle_dedupe(
+    path: Option<PathBuf>,
+    folders: bool,
+    remove: bool,
+    force: bool,
+    hash_algorithm: HashAlgorithm,
+    verbose: bool,
+    config: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    use colored::Colorize;
+
+    let target_path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    let looking = if folders {
+        tr!("Looking for duplicate folders in {}", target_path.display())
+    } else {
+        tr!("Looking for duplicate files in {}", target_path.display())
+    };
+    say!("🔎 {}", looking);
+
+    let (config, _) = Config::load_or_default(config.as_deref())?;
+    let organizer = FileOrganizer::with_config(OrganizeOptions { hash_algorithm, ..OrganizeOptions::default() }, &config)?;
+    let groups = if folders { organizer.duplicate_folders(&target_path)? } else { organizer.duplicate_files(&target_path)? };
+    if verbose {
+        print_hash_cache_stats(&organizer);
+    }
+    if groups.is_empty() {
+        say!("✅ {}", if folders { tr!("No duplicate folders found") } else { tr!("No duplicate files found") });
+        return Ok(());
+    }
+
+    let relative = |path: &std::path::Path| path.strip_prefix(&target_path).unwrap_or(path).display().to_string();
+    for group in &groups {
+        say!("\n📁 {}", tr!("{} copies of {} each", group.paths.len().to_string().yellow(), ByteSize(group.bytes)));
+        say!("   {} {}", tr!("keep").green(), relative(group.canonical()));
+        for extra in group.extras() {
+            say!("   {} {}", tr!("copy").red(), relative(extra));
+        }
+    }
+    let reclaimable: u64 = groups.iter().map(|group| group.reclaimable()).sum();
+    let extras: usize = groups.iter().map(|group| group.extras().len()).sum();
+    say!("\n💾 {}", tr!("Keeping one copy of each would reclaim {}", ByteSize(reclaimable).to_string().cyan()));
+    if !remove {
+        say!("💡 {}", tr!("Run with --remove to delete the {} extra copies", extras));
+        return Ok(());
+    }
+
+    if !force && !console::confirm(&tr!("Delete {} extra copies, reclaiming {}?", extras, ByteSize(reclaimable))) {
+        say!("ℹ️  {}", tr!("Nothing was deleted"));
+        return Ok(());
+    }
+    for group in &groups {
+        organizer.remove_duplicates(group)?;
+    }
+    say!("✅ {}", tr!("Deleted {} extra copies", extras.to_string().green()));
+    Ok(())
+}
+
+/// Handle the rules lint command
+fn handle_rules_lint(config: Option<PathBuf>) -> anyhow::Result<()> {
+    use colored::Colorize;
+
+    // Show the whole chain, down to the column of a bad condition
+    let (config, path) = Config::load_or_default(config.as_deref()).map_err(|e| anyhow::anyhow!("{:#}", e))?;
+    let Some(path) = path else {
+        say!("ℹ️  {}", tr!("No config file, so there are no custom rules to check"));
+        return Ok(());
+    };
+
+    let warnings = rules::lint(&config.rules);
+    for warning in &warnings {
+        say!("⚠️  {}", tr!("Rule '{}' {}", warning.rule.yellow(), warning.message));
+    }
+    if !warnings.is_empty() {
+        return Err(anyhow::anyhow!(tr!("{} of {} rules in {} need attention", warnings.len(), config.rules.len(), path.display())));
+    }
+    say!("✅ {}", tr!("{} rules in {} look fine", config.rules.len(), path.display()));
+    Ok(())
+}
+
+/// Handle the manifest export command
+fn handle_manifest_export(
+    file: PathBuf,
+    path: Option<PathBuf>,
+    hash_algorithm: HashAlgorithm,
+    verbose: bool,
+    config: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let target_path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    let (config, _) = Config::load_or_default(config.as_deref())?;
+
+    let organizer = FileOrganizer::with_config(OrganizeOptions { hash_algorithm, ..OrganizeOptions::default() }, &config)?;
+    let manifest = organizer.manifest(&target_path)?;
+    if verbose {
+        print_hash_cache_stats(&organizer);
+    }
+    manifest.save(&file)?;
+    say!("✅ {}", tr!("Listed {} files ({}) in {}", manifest.entries.len(), ByteSize(manifest.bytes()), file.display()));
+    Ok(())
+}
+
+/// Handle the manifest check command
+fn handle_manifest_check(file: PathBuf, path: Option<PathBuf>, verbose: bool, config: Option<PathBuf>) -> anyhow::Result<()> {
+    use colored::Colorize;
+
+    let target_path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    let (config, _) = Config::load_or_default(config.as_deref())?;
+
+    let manifest = Manifest::load(&file)?;
+    let organizer = FileOrganizer::with_config(OrganizeOptions { analyze: true, ..OrganizeOptions::default() }, &config)?;
+    let check = organizer.check_manifest(&manifest, &target_path)?;
+    if verbose {
+        print_hash_cache_stats(&organizer);
+    }
+    say!("🔎 {}", tr!("Checking {} against {}", target_path.display(), file.display()));
+    for path in &check.missing {
+        say!("   {} {}", tr!("missing").red(), path);
+    }
+    for path in &check.changed {
+        say!("   {} {}", tr!("changed").red(), path);
+    }
+    for (from, to) in &check.moved {
+        say!("   {}   {} → {}", tr!("moved").yellow(), from, to);
+    }
+    for path in &check.added {
+        say!("   {}     {}", tr!("new").cyan(), path);
+    }
+
+    say!(
+        "\n{} {}",
+        tr!("Total:").bold(),
+        tr!(
+            "{} intact, {} missing, {} changed, {} moved, {} new",
+            check.matched.to_string().green(),
+            check.missing.len(),
+            check.changed.len(),
+            check.moved.len(),
+            check.added.len()
+        )
+    );
+    if !check.is_intact() {
+        return Err(anyhow::anyhow!(tr!("{} doesn't match {}", target_path.display(), file.display())));
+    }
+    say!("✅ {}", tr!("Every file in the manifest is intact"));
+    Ok(())
+}
+
+/// Handle the cache clear command
+fn handle_cache_clear(config: Option<PathBuf>) -> anyhow::Result<()> {
+    let (config, _) = Config::load_or_default(config.as_deref())?;
+    let organizer = FileOrganizer::with_config(OrganizeOptions::default(), &config)?;
+    let cleared = organizer.clear_hash_cache()?;
+    say!("✅ {}", tr!("Forgot {} cached hashes", cleared));
+    Ok(())
+}
+
+/// With --verbose: how much hashing the cache saved
+fn print_hash_cache_stats(organizer: &FileOrganizer) {
+    let stats = organizer.hash_cache_stats();
+    say!("🗃️  {}", tr!("Hash cache: {} hits, {} files hashed", stats.hits(), stats.misses()));
+}
+
+/// Handle the find weird-names command
+fn handle_find_weird_names(path: Option<PathBuf>, longer_than: usize) -> anyhow::Result<()> {
+    use colored::Colorize;
+
+    let target_path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    let predicates = NamePredicates { longer_than: Some(longer_than), unusual_characters: true };
+    let found = names::find(&target_path, &predicates)?;
+    if found.is_empty() {
+        say!("✅ {}", tr!("No weird file names in {}", target_path.display()));
+        return Ok(());
+    }
+
+    say!("⚠️  {}", tr!("{} files with weird names in {}:", found.len().to_string().yellow(), target_path.display()));
+    for (file, issues) in &found {
+        let relative = file.strip_prefix(&target_path).unwrap_or(file);
+        let issues: Vec<String> = issues.iter().map(ToString::to_string).collect();
+        // Debug formatting escapes control characters so they can't garble the terminal
+        say!("   {:?}  ({})", relative, issues.join(", "));
+    }
+    Ok(())
+}
+
+/// Handle the stats command
+fn handle_stats(path: Option<PathBuf>, trend: bool, limit: usize, config: Option<PathBuf>) -> anyhow::Result<()> {
+    use colored::Colorize;
+
+    let target_path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
+    say!("📈 {}", tr!("Statistics for: {}", target_path.display()));
+
+    let (config, _) = Config::load_or_default(config.as_deref())?;
+    // Statistics only read the history, so they work on read-only mounts too
+    let journals = store::open_read_only(&config.history)?.journals(&target_path)?;
+    // Journals from before statistics were recorded have nothing to show
+    let runs: Vec<(&journal::Journal, &RunStats)> =
+        journals.iter().filter_map(|journal| journal.stats.as_ref().map(|stats| (journal, stats))).collect();
+    let Some((last, last_stats)) = runs.last() else {
+        say!("ℹ️  {}", tr!("No runs with statistics found in history"));
+        return Ok(());
+    };
+
+    if !trend {
+        say!(
+            "   {}",
+            tr!(
+                "Last run {} moved {} files ({}), {} of an unknown type",
+                last.created_at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                last_stats.files.to_string().yellow(),
+                ByteSize(last_stats.total_bytes),
+                last_stats.other_files
+            )
+        );
+        for (category, count) in last_stats.top_categories(usize::MAX) {
+            say!("   {:>6}  {}", count, category);
+        }
+        let other = last_stats.other_by_extension();
+        if !other.is_empty() {
+            say!("\n❓ {}", tr!("Unknown types by extension:"));
+            console::print_other_extensions(&other, usize::MAX);
+            let named: Vec<(&str, String)> = other
+                .iter()
+                .filter(|(extension, _)| !extension.is_empty())
+                .map(|(extension, stats)| (*extension, format!("{}, {}", tr!("{} files", stats.files), ByteSize(stats.bytes))))
+                .collect();
+            if !named.is_empty() {
+                say!("\n📝 {}", tr!("To sort them from now on, add this to your config with the right categories:"));
+                print_extensions_snippet(&named);
+            }
+        }
+        return Ok(());
+    }
+
+    let recent = &runs[runs.len().saturating_sub(limit)..];
+    let mut previous: Option<usize> = None;
+    for (journal, stats) in recent {
+        let change = match previous {
+            Some(previous) if stats.files < previous => format!("▼ {}", previous - stats.files).green(),
+            Some(previous) if stats.files > previous => format!("▲ {}", stats.files - previous).red(),
+            Some(_) => "=".normal(),
+            None => "".normal(),
+        };
+        let top = stats
+            .top_categories(3)
+            .iter()
+            .map(|(category, count)| format!("{} {}", category, count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        say!(
+            "   {}  {:>5} {} {:>10}  {:>4} {}  {:<7} {}",
+            journal.created_at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            stats.files,
+            tr!("files"),
+            ByteSize(stats.total_bytes).to_string(),
+            stats.other_files,
+            tr!("other"),
+            change,
+            top
+        );
+        previous = Some(stats.files);
+    }
+
+    let first_stats = recent[0].1;
+    if recent.len() > 1 {
+        say!(
+            "📊 {}",
+            tr!("Files per run went from {} to {} over {} runs", first_stats.files, last_stats.files, recent.len())
+        );
+    }
+    Ok(())
+}
+
+fn handle_clean(path: Option<PathBuf>, dry_run: bool, only_organizer_dirs: bool, config: Option<PathBuf>) -> anyhow::Result<()> {
     let target_path = path.unwrap_or_else(|| std::env::current_dir().unwrap());
     
-    println!("🧹 Cleaning empty directories in: {}", target_path.display());
+    say!("🧹 {}", tr!("Cleaning empty directories in: {}", target_path.display()));
     
     if dry_run {
-        println!("🔍 DRY RUN MODE - Showing what would be cleaned");
+        say!("🔍 {}", tr!("DRY RUN MODE - Showing what would be cleaned"));
     }
     
-    // TODO: Implement clean logic
-    println!("✅ Cleaning completed!");
+    let only = if only_organizer_dirs {
+        let (config, _) = Config::load_or_default(config.as_deref())?;
+        let journals = store::open_read_only(&config.history)?.journals(&target_path)?;
+        Some(clean::OrganizerDirs::new(&target_path, &journals))
+    } else {
+        None
+    };
+    let removed = clean::remove_empty_dirs(&target_path, only.as_ref(), dry_run)?;
+    for dir in &removed {
+        let shown = dir.strip_prefix(&target_path).unwrap_or(dir);
+        say!("   🗑️  {}", shown.display());
+    }
+
+    if dry_run {
+        say!("✅ {}", tr!("{} empty directories would be removed", removed.len()));
+    } else {
+        say!("✅ {}", tr!("Removed {} empty directories", removed.len()));
+    }
     
     Ok(())
 }
 
+/// Handle the self-update command
+fn handle_self_update(check_only: bool) -> anyhow::Result<()> {
+    use colored::Colorize;
+
+    say!("🔎 {}", tr!("Checking for a newer release..."));
+    let update = update::check()?;
+    if !update.is_newer() {
+        say!("✅ {}", tr!("file-organizer {} is the latest release", update.current));
+        return Ok(());
+    }
+    say!(
+        "⬆️  {}",
+        tr!("file-organizer {} is available (you have {})", update.latest.green(), update.current)
+    );
+    if check_only {
+        say!("💡 {}", tr!("Run file-organizer self-update to install it"));
+        return Ok(());
+    }
+
+    let replaced = update::install(&update)?;
+    say!("✅ {}", tr!("Installed file-organizer {} at {}", update.latest.green(), replaced.display()));
+    Ok(())
+}
+
 /// Handle shell completions generation
 fn handle_completions(shell: Shell) -> anyhow::Result<()> {
     use clap::CommandFactory;
@@ -0,0 +1,158 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_util::sync::CancellationToken;
+
+use super::error::Result;
+use super::observer::OrganizeObserver;
+use super::progress::ProgressEvent;
+use super::{FileOperation, FileOrganizer, OrganizeMode, OrganizeReport};
+
+/// A running organization started with [`FileOrganizer::organize_async`]
+pub struct OrganizeTask {
+    /// Progress events; the stream ends when the organization finishes
+    pub events: UnboundedReceiverStream<ProgressEvent>,
+    /// Resolves to the report, or [`OrganizerError::Cancelled`](super::error::OrganizerError::Cancelled)
+    pub handle: JoinHandle<Result<OrganizeReport>>,
+}
+
+impl FileOrganizer {
+    /// Organize a directory on tokio's blocking pool without blocking the caller.
+    ///
+    /// File operations run on a blocking thread; progress is streamed as it happens and
+    /// `cancel` stops the run before the next file is planned or moved.
+    pub fn organize_async(
+        mut self,
+        target_dir: PathBuf,
+        mode: OrganizeMode,
+        recursive: bool,
+        filters: Option<Vec<String>>,
+        dry_run: bool,
+        cancel: CancellationToken,
+    ) -> OrganizeTask {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.observer = Box::new(ChannelObserver { sender });
+        self.cancelled = Some(Box::new(move || cancel.is_cancelled()));
+
+        let handle = tokio::task::spawn_blocking(move || {
+            self.organize(&target_dir, &mode, recursive, filters.as_ref(), dry_run)
+        });
+
+        OrganizeTask {
+            events: UnboundedReceiverStream::new(receiver),
+            handle,
+        }
+    }
+}
+
+/// Forwards observer callbacks to an [`OrganizeTask`]'s event stream
+struct ChannelObserver {
+    sender: mpsc::UnboundedSender<ProgressEvent>,
+}
+
+impl ChannelObserver {
+    fn send(&self, event: ProgressEvent) {
+        // The receiver going away just means nobody is listening any more
+        let _ = self.sender.send(event);
+    }
+}
+
+impl OrganizeObserver for ChannelObserver {
+    fn on_scan_started(&self, directory: &Path) {
+        self.send(ProgressEvent::ScanStarted {
+            directory: directory.to_path_buf(),
+        });
+    }
+
+    fn on_scan_finished(&self, files_found: usize, _files_selected: usize) {
+        self.send(ProgressEvent::ScanFinished { files_found });
+    }
+
+    fn on_plan_ready(&self, plan: &[FileOperation], _mode: &OrganizeMode) {
+        self.send(ProgressEvent::PlanReady { operations: plan.len() });
+    }
+
+    fn on_file_moved(&self, operation: &FileOperation) {
+        self.send(ProgressEvent::FileMoved {
+            source: operation.source.clone(),
+            destination: operation.destination.clone(),
+        });
+    }
+
+    fn on_error(&self, operation: &FileOperation, error: &io::Error) {
+        self.send(ProgressEvent::FileFailed {
+            source: operation.source.clone(),
+            error: error.to_string(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::error::OrganizerError;
+    use std::fs;
+    use tokio_stream::StreamExt;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap()
+    }
+
+    #[test]
+    fn test_organize_async_streams_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("photo.jpg"), b"jpg").unwrap();
+        fs::write(dir.path().join("notes.txt"), b"txt").unwrap();
+
+        runtime().block_on(async {
+            let task = FileOrganizer::new().organize_async(
+                dir.path().to_path_buf(),
+                OrganizeMode::Extension,
+                false,
+                None,
+                false,
+                CancellationToken::new(),
+            );
+
+            let events: Vec<ProgressEvent> = task.events.collect().await;
+            let report = task.handle.await.unwrap().unwrap();
+
+            assert_eq!(report.summary.total_files, 2);
+            assert_eq!(report.moved(), 2);
+            assert!(matches!(events.first(), Some(ProgressEvent::ScanStarted { .. })));
+            let moved = events
+                .iter()
+                .filter(|event| matches!(event, ProgressEvent::FileMoved { .. }))
+                .count();
+            assert_eq!(moved, 2);
+        });
+
+        assert!(dir.path().join("🖼️ Images").join("photo.jpg").exists());
+    }
+
+    #[test]
+    fn test_organize_async_can_be_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("photo.jpg"), b"jpg").unwrap();
+
+        runtime().block_on(async {
+            let cancel = CancellationToken::new();
+            cancel.cancel();
+            let task = FileOrganizer::new().organize_async(
+                dir.path().to_path_buf(),
+                OrganizeMode::Extension,
+                false,
+                None,
+                false,
+                cancel,
+            );
+
+            let error = task.handle.await.unwrap().unwrap_err();
+            assert!(matches!(error, OrganizerError::Cancelled));
+        });
+
+        assert!(dir.path().join("photo.jpg").exists());
+    }
+}
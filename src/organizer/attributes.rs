@@ -0,0 +1,104 @@
+//! File attributes that the name doesn't show: the hidden and system flags of Windows (and the
+//! hidden flag of macOS), and cloud placeholders such as OneDrive's online-only files, whose
+//! contents would be downloaded just to move them.
+//!
+//! Placeholders are recognized by their Windows attributes (recall on access, offline, or
+//! an unpinned reparse point as the Cloud Files API makes them), by the macOS dataless flag,
+//! and by the `.name.icloud` stubs older iCloud Drive leaves in place of evicted files.
+
+use std::fs;
+use std::path::Path;
+
+/// What the filesystem says about a file besides its name
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileAttributes {
+    pub hidden: bool,
+    pub system: bool,
+    /// The contents live in the cloud (OneDrive, iCloud Drive, ...) rather than on disk
+    pub placeholder: bool,
+}
+
+impl FileAttributes {
+    /// The attributes of `file_path`, read from its `metadata` without touching the file again;
+    /// all false where the platform has none
+    pub fn from_metadata(file_path: &Path, metadata: &fs::Metadata) -> Self {
+        let mut attributes = platform_attributes(metadata);
+        attributes.placeholder |= is_icloud_stub(file_path);
+        attributes
+    }
+}
+
+/// `.report.pdf.icloud`, which stands in for `report.pdf` until it's downloaded
+fn is_icloud_stub(file_path: &Path) -> bool {
+    file_path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .is_some_and(|name| name.starts_with('.') && name.len() > ".icloud".len() + 1 && name.ends_with(".icloud"))
+}
+
+const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x4_0000;
+const FILE_ATTRIBUTE_UNPINNED: u32 = 0x10_0000;
+const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x40_0000;
+
+/// Decode Windows' `dwFileAttributes`
+#[cfg_attr(not(any(windows, test)), allow(dead_code))]
+fn from_windows_attributes(bits: u32) -> FileAttributes {
+    FileAttributes {
+        hidden: bits & FILE_ATTRIBUTE_HIDDEN != 0,
+        system: bits & FILE_ATTRIBUTE_SYSTEM != 0,
+        placeholder: bits & (FILE_ATTRIBUTE_OFFLINE | FILE_ATTRIBUTE_RECALL_ON_OPEN | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS) != 0
+            || bits & (FILE_ATTRIBUTE_REPARSE_POINT | FILE_ATTRIBUTE_UNPINNED) == FILE_ATTRIBUTE_REPARSE_POINT | FILE_ATTRIBUTE_UNPINNED,
+    }
+}
+
+#[cfg(windows)]
+fn platform_attributes(metadata: &fs::Metadata) -> FileAttributes {
+    use std::os::windows::fs::MetadataExt;
+    from_windows_attributes(metadata.file_attributes())
+}
+
+#[cfg(target_os = "macos")]
+fn platform_attributes(metadata: &fs::Metadata) -> FileAttributes {
+    use std::os::macos::fs::MetadataExt;
+    // `chflags hidden`, and files iCloud Drive has evicted ("dataless")
+    const UF_HIDDEN: u32 = 0x8000;
+    const SF_DATALESS: u32 = 0x4000_0000;
+    let flags = metadata.st_flags();
+    FileAttributes { hidden: flags & UF_HIDDEN != 0, system: false, placeholder: flags & SF_DATALESS != 0 }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn platform_attributes(_metadata: &fs::Metadata) -> FileAttributes {
+    FileAttributes::default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windows_attributes() {
+        assert_eq!(from_windows_attributes(0x20), FileAttributes::default());
+        assert_eq!(
+            from_windows_attributes(FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM | 0x20),
+            FileAttributes { hidden: true, system: true, placeholder: false }
+        );
+        // OneDrive's online-only files
+        assert!(from_windows_attributes(0x20 | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS).placeholder);
+        assert!(from_windows_attributes(FILE_ATTRIBUTE_OFFLINE).placeholder);
+        assert!(from_windows_attributes(FILE_ATTRIBUTE_REPARSE_POINT | FILE_ATTRIBUTE_UNPINNED).placeholder);
+        // Symlinks and deduplicated files are reparse points too
+        assert!(!from_windows_attributes(FILE_ATTRIBUTE_REPARSE_POINT).placeholder);
+    }
+
+    #[test]
+    fn test_icloud_stubs() {
+        assert!(is_icloud_stub(Path::new("docs/.report.pdf.icloud")));
+        assert!(!is_icloud_stub(Path::new("docs/report.icloud")));
+        assert!(!is_icloud_stub(Path::new(".icloud")));
+    }
+}
@@ -0,0 +1,106 @@
+//! How moves reach the filesystem. The portable backend renames one file at a time; on Linux,
+//! `--backend uring` hands the kernel batches of renames through io_uring instead (see
+//! [`uring`](super::uring)), for trees of hundreds of thousands of small files where the
+//! system calls themselves are the cost. `cargo bench` compares the two.
+//!
+//! Either way a run does the same thing: conflicts are settled and directories made before a
+//! batch goes out, and moves across filesystems fall back to copying one by one. The one
+//! difference is when a move fails and the run stops: files already sent in the same batch
+//! are still moved (and recorded, so undo and rollback cover them).
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Renames per io_uring batch
+#[cfg(target_os = "linux")]
+const URING_BATCH: u32 = 256;
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// One rename() per file, on every platform
+    #[default]
+    Portable,
+    /// Batched renames through io_uring (Linux 5.11 or later); elsewhere the portable backend
+    /// is used
+    Uring,
+}
+
+/// Renames files in batches the way the chosen backend does
+pub struct Renamer {
+    #[cfg(target_os = "linux")]
+    ring: Option<super::uring::Ring>,
+}
+
+impl Renamer {
+    pub fn new(backend: Backend) -> Self {
+        if backend == Backend::Portable {
+            return Self::portable();
+        }
+        #[cfg(target_os = "linux")]
+        match super::uring::Ring::new(URING_BATCH) {
+            Ok(ring) => return Self { ring: Some(ring) },
+            Err(e) => log::warn!("io_uring isn't available ({}), so files are moved one at a time", e),
+        }
+        #[cfg(not(target_os = "linux"))]
+        log::warn!("io_uring is only available on Linux, so files are moved one at a time");
+        Self::portable()
+    }
+
+    fn portable() -> Self {
+        Self {
+            #[cfg(target_os = "linux")]
+            ring: None,
+        }
+    }
+
+    /// How many renames go out together
+    pub fn batch_size(&self) -> usize {
+        #[cfg(target_os = "linux")]
+        if let Some(ring) = &self.ring {
+            return ring.entries();
+        }
+        1
+    }
+
+    /// Rename each `(source, destination)` pair (at most [`Renamer::batch_size`] of them),
+    /// returning each one's result in order
+    pub fn rename_all(&mut self, pairs: &[(&Path, &Path)]) -> Vec<io::Result<()>> {
+        #[cfg(target_os = "linux")]
+        if let Some(ring) = &mut self.ring {
+            let results = ring.rename_all(pairs);
+            if ring.is_broken() {
+                log::warn!("io_uring stopped working, so the rest of the files are moved one at a time");
+                self.ring = None;
+            }
+            return results;
+        }
+        pairs.iter().map(|(source, destination)| fs::rename(source, destination)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backends_rename_alike() {
+        for backend in [Backend::Portable, Backend::Uring] {
+            let dir = tempfile::tempdir().unwrap();
+            let mut renamer = Renamer::new(backend);
+            let pairs: Vec<_> = (0..renamer.batch_size().min(3))
+                .map(|i| {
+                    let source = dir.path().join(format!("{}.txt", i));
+                    fs::write(&source, b"x").unwrap();
+                    (source, dir.path().join(format!("{}.moved", i)))
+                })
+                .collect();
+            let borrowed: Vec<(&Path, &Path)> = pairs.iter().map(|(from, to)| (from.as_path(), to.as_path())).collect();
+
+            let results = renamer.rename_all(&borrowed);
+            assert!(results.iter().all(|result| result.is_ok()), "{:?}", backend);
+            assert!(pairs.iter().all(|(from, to)| to.exists() && !from.exists()));
+        }
+    }
+}
@@ -0,0 +1,363 @@
+//! Keeping a directory within a size budget, the way a cache evicts entries: `enforce
+//! --max-size 50GB` picks files until what's left fits, and archives or trashes them. Download
+//! folders that only ever grow are the case in mind. A budget can also be a number of files:
+//! `--keep-newest 200` keeps the newest files of each folder, e.g. of Screenshots, and evicts
+//! the rest.
+//!
+//! Archiving moves the oldest files to an archive folder, keeping their place in the tree, and
+//! is journaled so `undo` brings them back. Trashing takes the oldest or the largest files
+//! (the fewest files for the space) and sends them to the trash, which isn't part of the
+//! history. Pinned files are never evicted, and neither are the files a scan leaves alone:
+//! system files, and unless `include_hidden` and `hydrate` say otherwise, hidden files and
+//! cloud placeholders, which moving would download. Of all these, only pinned files still count
+//! toward the budget.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use walkdir::WalkDir;
+
+use super::conflicts;
+use super::error::{IoContext, OrganizerError, Result};
+use super::journal::{self, METADATA_DIR};
+use super::skips::SkipReason;
+use super::{trash, FileOperation, FileOrganizer, OperationType};
+
+/// Mode enforcement is recorded with in the history
+pub const ENFORCE_MODE: &str = "Enforce";
+
+/// Folder archived files go to by default, inside the directory; nothing in it counts
+pub const ARCHIVE_DIR: &str = "Archive";
+
+/// Which files go, and where, when a directory is over its budget
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvictionStrategy {
+    /// Move the oldest files to the archive folder
+    #[default]
+    Archive,
+    /// Move the oldest files to the trash
+    TrashOldest,
+    /// Move the largest files to the trash, freeing the space with the fewest files
+    TrashLargest,
+}
+
+/// What a directory may hold
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    /// Most bytes the directory may hold, counting what [`Budget::keep_newest`] leaves
+    pub max_size: Option<u64>,
+    /// Most files each folder may hold; the newest ones stay
+    pub keep_newest: Option<usize>,
+    pub strategy: EvictionStrategy,
+    /// Where archived files go; `None` for [`ARCHIVE_DIR`] in the directory
+    pub archive_dir: Option<PathBuf>,
+    /// Count the files of subdirectories too
+    pub recursive: bool,
+}
+
+/// A file picked to make room
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Eviction {
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// Where it's archived to; `None` when it's trashed
+    pub destination: Option<PathBuf>,
+}
+
+/// What [`FileOrganizer::enforce`] did
+#[derive(Debug)]
+pub struct EnforceReport {
+    /// Size of the directory before anything was evicted
+    pub size: u64,
+    /// Files picked, in the order they go
+    pub evictions: Vec<Eviction>,
+    /// Evicted files that couldn't be moved, with why; empty for a dry run
+    pub failures: Vec<(PathBuf, String)>,
+    pub dry_run: bool,
+    /// Journal id for `undo`, if anything was archived
+    pub operation_id: Option<String>,
+}
+
+impl EnforceReport {
+    /// Bytes the evictions free, or would free in a dry run
+    pub fn freed(&self) -> u64 {
+        let failed = |eviction: &&Eviction| self.failures.iter().any(|(path, _)| *path == eviction.path);
+        self.evictions.iter().filter(|eviction| !failed(eviction)).map(|eviction| eviction.bytes).sum()
+    }
+
+    /// Files evicted; none for a dry run
+    pub fn evicted(&self) -> usize {
+        if self.dry_run {
+            0
+        } else {
+            self.evictions.len() - self.failures.len()
+        }
+    }
+}
+
+/// A file that counts towards the budget
+struct Candidate {
+    path: PathBuf,
+    bytes: u64,
+    modified: SystemTime,
+}
+
+impl FileOrganizer {
+    /// Evict files from `dir` until it fits `budget`
+    pub fn enforce(&mut self, dir: &Path, budget: &Budget, dry_run: bool) -> Result<EnforceReport> {
+        let dry_run = dry_run || self.options.analyze;
+        let dir = dir.canonicalize().scan_context(dir, format!("Can't find {}", dir.display()))?;
+        journal::refuse_metadata_dir(&dir)?;
+        let archive_dir = match &budget.archive_dir {
+            Some(archive) => std::path::absolute(archive).scan_context(archive, format!("Can't find {}", archive.display()))?,
+            None => dir.join(ARCHIVE_DIR),
+        };
+        // Compared with the walked paths, which start at the canonical directory
+        let archive_dir = archive_dir.canonicalize().unwrap_or(archive_dir);
+        self.observer.on_scan_started(&dir);
+
+        let mut candidates = Vec::new();
+        let walker = WalkDir::new(&dir)
+            .min_depth(1)
+            .max_depth(if budget.recursive { usize::MAX } else { 1 })
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| e.file_name() != METADATA_DIR && e.path() != archive_dir);
+        for entry in walker {
+            self.check_cancelled()?;
+            let entry = entry.map_err(|e| OrganizerError::walk(&dir, e))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let metadata = entry.metadata().map_err(|e| OrganizerError::walk(&dir, e))?;
+            // Left alone the way the scan would, except that pinned files still take up room
+            if let Some(reason) = self.skip_reason(entry.path(), &metadata).filter(|reason| *reason != SkipReason::Pinned) {
+                log::debug!("Not counting {:?} toward the budget: {:?}", entry.path(), reason);
+                continue;
+            }
+            let modified = metadata.modified().scan_context(entry.path(), "Failed to get file modification time")?;
+            candidates.push(Candidate { path: entry.into_path(), bytes: metadata.len(), modified });
+            self.observer.on_scan_progress(candidates.len());
+        }
+        let (found, size) = (candidates.len(), candidates.iter().map(|candidate| candidate.bytes).sum());
+
+        let mut candidates: Vec<Candidate> = candidates.into_iter().filter(|candidate| !self.pins.contains(&candidate.path)).collect();
+        let mut picked = Vec::new();
+        if let Some(keep) = budget.keep_newest {
+            candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.modified));
+            let mut newer: HashMap<PathBuf, usize> = HashMap::new();
+            let (kept, mut overflow): (Vec<Candidate>, Vec<Candidate>) = candidates.into_iter().partition(|candidate| {
+                let count = newer.entry(candidate.path.parent().unwrap_or(&dir).to_path_buf()).or_default();
+                *count += 1;
+                *count <= keep
+            });
+            overflow.reverse();
+            candidates = kept;
+            picked = overflow;
+        }
+        let mut remaining = size - picked.iter().map(|candidate| candidate.bytes).sum::<u64>();
+        if let Some(max_size) = budget.max_size {
+            match budget.strategy {
+                EvictionStrategy::Archive | EvictionStrategy::TrashOldest => candidates.sort_by_key(|candidate| candidate.modified),
+                EvictionStrategy::TrashLargest => candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.bytes)),
+            }
+            for candidate in candidates {
+                if remaining <= max_size {
+                    break;
+                }
+                remaining -= candidate.bytes;
+                picked.push(candidate);
+            }
+        }
+
+        let mut evictions = Vec::new();
+        // A free name must be free of the earlier evictions' destinations too, not just on disk
+        let mut planned: HashSet<PathBuf> = HashSet::new();
+        for candidate in picked {
+            let destination = (budget.strategy == EvictionStrategy::Archive).then(|| {
+                let target = archive_dir.join(candidate.path.strip_prefix(&dir).unwrap_or(&candidate.path));
+                let taken = |path: &Path| path.exists() || planned.contains(path);
+                let target = if taken(&target) { conflicts::free_name_where(&target, taken) } else { target };
+                planned.insert(target.clone());
+                target
+            });
+            evictions.push(Eviction { path: candidate.path, bytes: candidate.bytes, destination });
+        }
+        self.observer.on_scan_finished(found, evictions.len());
+
+        let mut report = EnforceReport { size, evictions, failures: Vec::new(), dry_run, operation_id: None };
+        if report.evictions.is_empty() || dry_run {
+            return Ok(report);
+        }
+        if budget.strategy == EvictionStrategy::Archive {
+            self.archive(&dir, &archive_dir, &mut report)?;
+        } else {
+            for eviction in &report.evictions {
+                self.check_cancelled()?;
+                match trash::delete(&eviction.path, false) {
+                    Ok(()) => log::info!("Moved {:?} to the trash to stay within the budget", eviction.path),
+                    Err(e) => {
+                        log::warn!("Couldn't move {} to the trash: {}", eviction.path.display(), e);
+                        report.failures.push((eviction.path.clone(), e.to_string()));
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn archive(&self, dir: &Path, archive_dir: &Path, report: &mut EnforceReport) -> Result<()> {
+        let plan: Vec<FileOperation> = report
+            .evictions
+            .iter()
+            .filter_map(|eviction| {
+                let destination = eviction.destination.clone()?;
+                Some(FileOperation {
+                    source: eviction.path.clone(),
+                    destination,
+                    operation_type: OperationType::Move,
+                    size: eviction.bytes,
+                })
+            })
+            .collect();
+        if let Some(shortage) = self.space_shortage(archive_dir, &plan) {
+            return Err(OrganizerError::plan(
+                archive_dir,
+                format!("Not enough room in {}: {}; nothing was moved", archive_dir.display(), shortage),
+            ));
+        }
+        let outcomes = self.execute(&plan)?;
+        report.failures = outcomes
+            .iter()
+            .filter_map(|outcome| Some((outcome.operation.source.clone(), outcome.error.clone()?)))
+            .collect();
+        if outcomes.iter().any(|outcome| outcome.succeeded()) {
+            report.operation_id = self.journal(dir, ENFORCE_MODE, &outcomes);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Duration;
+    use super::super::config::Config;
+    use super::super::OrganizeOptions;
+
+    /// A file of `bytes` bytes, last changed `days_ago`
+    fn write(root: &Path, name: &str, bytes: usize, days_ago: u64) {
+        let path = root.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, vec![b'x'; bytes]).unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(days_ago * 86_400);
+        fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn test_enforce() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "old.iso", 400, 30);
+        write(dir.path(), "big.mkv", 900, 2);
+        write(dir.path(), "nested/older.zip", 300, 60);
+        write(dir.path(), "new.pdf", 100, 0);
+        let mut organizer = FileOrganizer::with_config(OrganizeOptions::default(), &Config::default()).unwrap();
+        let budget = Budget { max_size: Some(1000), ..Budget::default() };
+
+        // Only the top level counts without recursion
+        let preview = organizer.enforce(dir.path(), &budget, true).unwrap();
+        assert_eq!(preview.size, 1400);
+        let picked: Vec<&Path> = preview.evictions.iter().map(|eviction| eviction.path.strip_prefix(dir.path().canonicalize().unwrap()).unwrap()).collect();
+        assert_eq!(picked, [Path::new("old.iso")]);
+        assert!(dir.path().join("old.iso").exists());
+
+        let largest = Budget { strategy: EvictionStrategy::TrashLargest, recursive: true, ..budget.clone() };
+        let preview = organizer.enforce(dir.path(), &largest, true).unwrap();
+        assert_eq!((preview.size, preview.evictions.len(), preview.freed()), (1700, 1, 900));
+
+        let recursive = Budget { recursive: true, ..budget };
+        let report = organizer.enforce(dir.path(), &recursive, false).unwrap();
+        assert_eq!((report.evicted(), report.freed()), (2, 700));
+        assert!(dir.path().join("Archive/nested/older.zip").exists() && dir.path().join("Archive/old.iso").exists());
+        // The archive doesn't count, so the directory fits now
+        assert!(organizer.enforce(dir.path(), &recursive, true).unwrap().evictions.is_empty());
+
+        organizer.undo(dir.path(), false).unwrap().unwrap();
+        assert!(dir.path().join("nested/older.zip").exists() && dir.path().join("old.iso").exists());
+    }
+
+    #[test]
+    fn test_keep_newest() {
+        let dir = tempfile::tempdir().unwrap();
+        for day in 1..=4 {
+            write(dir.path(), &format!("Screenshot {}.png", day), 10, day);
+        }
+        write(dir.path(), "2023/Screenshot 9.png", 10, 400);
+        write(dir.path(), "2023/Screenshot 8.png", 10, 300);
+        let mut organizer = FileOrganizer::with_config(OrganizeOptions::default(), &Config::default()).unwrap();
+        let root = dir.path().canonicalize().unwrap();
+
+        // Each folder keeps its own newest files, and the overflow goes oldest first
+        let budget = Budget { keep_newest: Some(2), recursive: true, ..Budget::default() };
+        let preview = organizer.enforce(dir.path(), &budget, true).unwrap();
+        let picked: Vec<&Path> = preview.evictions.iter().map(|eviction| eviction.path.strip_prefix(&root).unwrap()).collect();
+        assert_eq!(picked, [Path::new("Screenshot 4.png"), Path::new("Screenshot 3.png")]);
+
+        // A size budget then applies to what's left
+        let both = Budget { max_size: Some(30), ..budget.clone() };
+        assert_eq!(organizer.enforce(dir.path(), &both, true).unwrap().evictions.len(), 3);
+
+        let report = organizer.enforce(dir.path(), &budget, false).unwrap();
+        assert_eq!(report.evicted(), 2);
+        assert!(root.join("Archive/Screenshot 4.png").exists() && root.join("Screenshot 1.png").exists());
+        assert!(organizer.enforce(dir.path(), &budget, true).unwrap().evictions.is_empty());
+    }
+
+    #[test]
+    fn test_scan_skips_stay() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "old.iso", 10, 30);
+        write(dir.path(), ".env", 10, 60);
+        write(dir.path(), "Thumbs.db", 10, 60);
+        write(dir.path(), ".report.pdf.icloud", 10, 90);
+        let root = dir.path().canonicalize().unwrap();
+        let budget = Budget { max_size: Some(0), ..Budget::default() };
+        let picked = |include_hidden: bool, hydrate: bool| {
+            let options = OrganizeOptions { include_hidden, hydrate, ..OrganizeOptions::default() };
+            let mut organizer = FileOrganizer::with_config(options, &Config::default()).unwrap();
+            let preview = organizer.enforce(dir.path(), &budget, true).unwrap();
+            let mut picked: Vec<PathBuf> =
+                preview.evictions.iter().map(|eviction| eviction.path.strip_prefix(&root).unwrap().to_path_buf()).collect();
+            picked.sort();
+            (preview.size, picked)
+        };
+
+        assert_eq!(picked(false, false), (10, vec![PathBuf::from("old.iso")]));
+        assert_eq!(picked(true, false), (20, vec![PathBuf::from(".env"), PathBuf::from("old.iso")]));
+        let everything = [".env", ".report.pdf.icloud", "old.iso"].map(PathBuf::from).to_vec();
+        assert_eq!(picked(true, true), (30, everything));
+    }
+
+    #[test]
+    fn test_archive_names_dont_collide() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "Archive/report.pdf", 10, 90);
+        write(dir.path(), "report.pdf", 10, 60);
+        write(dir.path(), "report (1).pdf", 10, 30);
+        let mut organizer = FileOrganizer::with_config(OrganizeOptions::default(), &Config::default()).unwrap();
+        let budget = Budget { max_size: Some(0), ..Budget::default() };
+
+        // The first takes `report (1).pdf` in the archive, so the second, already called that,
+        // has to be numbered as well
+        let preview = organizer.enforce(dir.path(), &budget, true).unwrap();
+        let destinations: HashSet<&PathBuf> = preview.evictions.iter().filter_map(|eviction| eviction.destination.as_ref()).collect();
+        assert_eq!(destinations.len(), 2);
+
+        let report = organizer.enforce(dir.path(), &budget, false).unwrap();
+        assert_eq!((report.evicted(), report.failures.len()), (2, 0));
+        let archived = fs::read_dir(dir.path().join("Archive")).unwrap().count();
+        assert_eq!(archived, 3);
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use chrono::NaiveDateTime;
+
+use super::FileOperation;
+
+/// Folder that holds one subfolder per burst inside a destination folder
+pub const BURSTS_FOLDER: &str = "Bursts";
+
+/// Move photos taken within `window_secs` of each other into `Bursts/<timestamp>/`.
+///
+/// `shot_times` maps operation indexes to the time each photo was taken. Photos are only
+/// grouped with others headed to the same destination folder, and a burst needs at least
+/// two photos.
+pub fn group_bursts(
+    operations: &mut [FileOperation],
+    shot_times: &HashMap<usize, NaiveDateTime>,
+    window_secs: u64,
+) -> usize {
+    let mut by_folder: HashMap<PathBuf, Vec<(NaiveDateTime, usize)>> = HashMap::new();
+    for (&index, &taken) in shot_times {
+        if let Some(parent) = operations[index].destination.parent() {
+            by_folder.entry(parent.to_path_buf()).or_default().push((taken, index));
+        }
+    }
+
+    let mut bursts = 0;
+    for (folder, mut photos) in by_folder {
+        photos.sort();
+
+        for burst in split_bursts(&photos, window_secs) {
+            let burst_folder = folder
+                .join(BURSTS_FOLDER)
+                .join(burst[0].0.format("%Y%m%d-%H%M%S").to_string());
+
+            for &(_, index) in burst {
+                let op = &mut operations[index];
+                if let Some(file_name) = op.source.file_name() {
+                    op.destination = burst_folder.join(file_name);
+                }
+            }
+            bursts += 1;
+        }
+    }
+
+    bursts
+}
+
+/// Split time-sorted photos into runs whose consecutive gaps are within the window
+fn split_bursts(photos: &[(NaiveDateTime, usize)], window_secs: u64) -> Vec<&[(NaiveDateTime, usize)]> {
+    let mut bursts = Vec::new();
+    let mut start = 0;
+
+    for i in 1..=photos.len() {
+        let continues = i < photos.len()
+            && (photos[i].0 - photos[i - 1].0).num_seconds().unsigned_abs() <= window_secs;
+        if !continues {
+            if i - start >= 2 {
+                bursts.push(&photos[start..i]);
+            }
+            start = i;
+        }
+    }
+
+    bursts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::OperationType;
+
+    #[test]
+    fn test_photos_close_in_time_are_grouped() {
+        let mut operations: Vec<FileOperation> = ["a.jpg", "b.jpg", "c.jpg", "d.jpg"]
+            .iter()
+            .map(|name| FileOperation {
+                source: PathBuf::from(name),
+                destination: PathBuf::from("Images").join(name),
+                operation_type: OperationType::Move,
+                size: 0,
+            })
+            .collect();
+
+        let time = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap();
+        let shot_times = HashMap::from([
+            (0, time("2024-06-01 10:00:00")),
+            (1, time("2024-06-01 10:00:02")),
+            (2, time("2024-06-01 10:00:03")),
+            (3, time("2024-06-01 10:05:00")),
+        ]);
+
+        assert_eq!(group_bursts(&mut operations, &shot_times, 2), 1);
+        assert_eq!(
+            operations[2].destination,
+            PathBuf::from("Images/Bursts/20240601-100000/c.jpg")
+        );
+        assert_eq!(operations[3].destination, PathBuf::from("Images/d.jpg"));
+    }
+}
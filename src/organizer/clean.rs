@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::error::{IoContext, OrganizerError, Result};
+use super::extensionless::NO_EXTENSION_FOLDER;
+use super::file_types::{FileCategory, FileSizeCategory};
+use super::journal::{self, Journal, METADATA_DIR};
+
+/// Directories the organizer made, for `clean --only-organizer-dirs`: every folder a journal
+/// moved a file into, and anything named like a category folder
+pub struct OrganizerDirs {
+    created: HashSet<PathBuf>,
+    names: HashSet<String>,
+}
+
+impl OrganizerDirs {
+    /// The organizer's directories below `root`, going by its `journals`
+    pub fn new(root: &Path, journals: &[Journal]) -> Self {
+        let created = journals
+            .iter()
+            .flat_map(|journal| &journal.moves)
+            .flat_map(|entry| entry.destination.ancestors().skip(1))
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(|dir| root.join(dir))
+            .collect();
+        let sizes = [
+            FileSizeCategory::Tiny,
+            FileSizeCategory::Small,
+            FileSizeCategory::Medium,
+            FileSizeCategory::Large,
+            FileSizeCategory::Huge,
+        ];
+        let names = FileCategory::all()
+            .iter()
+            .map(FileCategory::folder)
+            .chain(sizes.iter().map(|size| size.folder_name().to_string()))
+            .chain([NO_EXTENSION_FOLDER.to_string()])
+            .collect();
+        Self { created, names }
+    }
+
+    pub fn contains(&self, dir: &Path) -> bool {
+        self.created.contains(dir)
+            || dir.file_name().is_some_and(|name| self.names.contains(name.to_string_lossy().as_ref()))
+    }
+}
+
+/// Remove empty directories below `root` (never `root` itself), deepest first.
+///
+/// A directory that only contains empty directories counts as empty, but the tool's own
+/// metadata folder and everything in it are left alone. With `only` set, directories it
+/// doesn't contain are kept even when empty, and so are the folders around them. Returns the
+/// removed directories; with `dry_run` nothing is removed and the would-be list is returned.
+pub fn remove_empty_dirs(root: &Path, only: Option<&OrganizerDirs>, dry_run: bool) -> Result<Vec<PathBuf>> {
+    journal::refuse_metadata_dir(root)?;
+    let mut removed: Vec<PathBuf> = Vec::new();
+
+    for entry in WalkDir::new(root).min_depth(1).contents_first(true) {
+        let entry = entry.map_err(|e| OrganizerError::walk(root, e))?;
+        // Contents come before their folder, so the metadata folder can't be pruned up front
+        let in_metadata = entry.path().strip_prefix(root).is_ok_and(|path| path.iter().any(|name| name == METADATA_DIR));
+        if !entry.file_type().is_dir() || in_metadata {
+            continue;
+        }
+
+        let dir = entry.path();
+        if only.is_some_and(|only| !only.contains(dir)) {
+            continue;
+        }
+        let mut children = fs::read_dir(dir).scan_context(dir, format!("Failed to read {:?}", dir))?;
+        // In a dry run nothing was actually removed, so look at what would be left
+        let is_empty = children.all(|child| {
+            child
+                .map(|child| dry_run && removed.contains(&child.path()))
+                .unwrap_or(false)
+        });
+
+        if is_empty {
+            if !dry_run {
+                fs::remove_dir(dir).io_context(dir, format!("Failed to remove {:?}", dir))?;
+            }
+            removed.push(dir.to_path_buf());
+        }
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remove_empty_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("empty/nested")).unwrap();
+        fs::create_dir_all(root.join("kept")).unwrap();
+        fs::write(root.join("kept/file.txt"), b"x").unwrap();
+
+        let preview = remove_empty_dirs(root, None, true).unwrap();
+        assert_eq!(preview, [root.join("empty/nested"), root.join("empty")]);
+        assert!(root.join("empty/nested").exists());
+
+        let removed = remove_empty_dirs(root, None, false).unwrap();
+        assert_eq!(removed, preview);
+        assert!(!root.join("empty").exists());
+        assert!(root.join("kept/file.txt").exists());
+        assert!(root.exists());
+    }
+
+    #[test]
+    fn test_metadata_dir_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join(".file-organizer/history")).unwrap();
+        fs::create_dir_all(root.join("sub/.file-organizer")).unwrap();
+
+        assert_eq!(remove_empty_dirs(root, None, false).unwrap(), Vec::<PathBuf>::new());
+        assert!(root.join(".file-organizer/history").exists());
+        let error = remove_empty_dirs(&root.join(".file-organizer"), None, true).unwrap_err();
+        assert!(matches!(error, OrganizerError::Plan { .. }));
+    }
+
+    #[test]
+    fn test_only_organizer_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("2024/06")).unwrap();
+        fs::create_dir_all(root.join("📄 Documents")).unwrap();
+        fs::create_dir_all(root.join("mnt/usb")).unwrap();
+        fs::create_dir_all(root.join("project/src")).unwrap();
+        let journal: Journal = serde_json::from_str(
+            r#"{"version": 2, "id": "2024-06-01-0001", "created_at": "2024-06-01T10:00:00Z", "mode": "Date",
+                "moves": [{ "source": "a.jpg", "destination": "2024/06/a.jpg" }]}"#,
+        )
+        .unwrap();
+
+        let only = OrganizerDirs::new(root, &[journal]);
+        let removed = remove_empty_dirs(root, Some(&only), false).unwrap();
+        assert_eq!(removed.len(), 3);
+        assert!(!root.join("2024").exists());
+        assert!(!root.join("📄 Documents").exists());
+        assert!(root.join("mnt/usb").exists());
+        assert!(root.join("project/src").exists());
+    }
+}
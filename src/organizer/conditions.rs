@@ -0,0 +1,647 @@
+//! Compound conditions for custom rules, written in the config as a small expression:
+//!
+//! ```text
+//! ext in [jpg, png] AND size > 5MB AND age < 30d
+//! name = "invoice*" AND NOT origin = github.com
+//! (ext = pdf OR ext = docx) AND age >= 52w
+//! ```
+//!
+//! Tests are `ext`, `name` (a glob, case-insensitive), `origin` (download domain), `size`
+//! (units as in `1.5GB`) and `age` (time since the last modification, units as in `2h30m`
+//! or `7d`). `AND` binds tighter than `OR`; keywords are case-insensitive.
+
+use std::cell::OnceCell;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::SystemTime;
+use serde::{Deserialize, Deserializer};
+
+use super::origin;
+use super::rules::wildcard_match;
+use super::units::{ByteSize, HumanDuration};
+
+/// A parsed rule condition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    /// Every condition holds (an empty list always does)
+    All(Vec<Condition>),
+    /// At least one condition holds
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+    Test(Test),
+}
+
+/// A single test on a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Test {
+    /// Lowercase extensions without the dot
+    Extension(Vec<String>),
+    /// Lowercase glob matched against the lowercased file name
+    Name(String),
+    /// Download origin domain; subdomains match too
+    Origin(String),
+    /// Size in bytes
+    Size(Comparison, u64),
+    /// Seconds since the last modification
+    Age(Comparison, u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    fn holds(&self, value: u64, limit: u64) -> bool {
+        match self {
+            Comparison::Less => value < limit,
+            Comparison::LessOrEqual => value <= limit,
+            Comparison::Greater => value > limit,
+            Comparison::GreaterOrEqual => value >= limit,
+            Comparison::Equal => value == limit,
+            Comparison::NotEqual => value != limit,
+        }
+    }
+
+    /// The values that pass as an inclusive range, empty when `start > end`; `None` for `!=`
+    fn range(&self, limit: u64) -> Option<(u64, u64)> {
+        match self {
+            Comparison::Less => Some(match limit.checked_sub(1) {
+                Some(end) => (0, end),
+                None => (1, 0),
+            }),
+            Comparison::LessOrEqual => Some((0, limit)),
+            Comparison::Greater => Some(match limit.checked_add(1) {
+                Some(start) => (start, u64::MAX),
+                None => (1, 0),
+            }),
+            Comparison::GreaterOrEqual => Some((limit, u64::MAX)),
+            Comparison::Equal => Some((limit, limit)),
+            Comparison::NotEqual => None,
+        }
+    }
+}
+
+/// Error for a condition that couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseConditionError {
+    input: String,
+    /// Character offset of the problem, counting from 1
+    column: usize,
+    reason: String,
+}
+
+impl fmt::Display for ParseConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid condition '{}': {} (at column {})", self.input, self.reason, self.column)
+    }
+}
+
+impl std::error::Error for ParseConditionError {}
+
+impl FromStr for Condition {
+    type Err = ParseConditionError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { input, tokens, position: 0 };
+        let condition = parser.any()?;
+        match parser.peek() {
+            None => Ok(condition),
+            Some(token) => Err(parser.error_at(token, format!("unexpected '{}'; expected AND or OR", token.text))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// What a condition may ask about a file; the metadata is read at most once, and only
+/// if a size or age test needs it
+pub struct FileFacts<'a> {
+    path: &'a Path,
+    metadata: OnceCell<Option<fs::Metadata>>,
+}
+
+impl<'a> FileFacts<'a> {
+    pub fn new(path: &'a Path) -> Self {
+        Self { path, metadata: OnceCell::new() }
+    }
+
+    /// Facts about a file whose metadata was read already, e.g. while scanning
+    pub fn with_metadata(path: &'a Path, metadata: fs::Metadata) -> Self {
+        Self { path, metadata: OnceCell::from(Some(metadata)) }
+    }
+
+    pub fn path(&self) -> &'a Path {
+        self.path
+    }
+
+    pub fn metadata(&self) -> Option<&fs::Metadata> {
+        self.metadata.get_or_init(|| fs::metadata(self.path).ok()).as_ref()
+    }
+
+    fn age(&self) -> Option<u64> {
+        let modified = self.metadata()?.modified().ok()?;
+        // Files from the future are brand new
+        Some(SystemTime::now().duration_since(modified).map(|age| age.as_secs()).unwrap_or(0))
+    }
+}
+
+impl Condition {
+    /// Whether the file passes. Size and age tests fail for files without metadata.
+    pub fn matches(&self, file: &FileFacts) -> bool {
+        match self {
+            Condition::All(conditions) => conditions.iter().all(|condition| condition.matches(file)),
+            Condition::Any(conditions) => conditions.iter().any(|condition| condition.matches(file)),
+            Condition::Not(condition) => !condition.matches(file),
+            Condition::Test(test) => test.matches(file),
+        }
+    }
+
+    /// Whether no file can pass, as with `size > 5MB AND size < 1MB`. Only obvious
+    /// contradictions are found; `false` doesn't promise that anything matches.
+    pub fn never_matches(&self) -> bool {
+        match self {
+            Condition::All(conditions) => {
+                if conditions.iter().any(Condition::never_matches) {
+                    return true;
+                }
+                let tests: Vec<&Test> = conditions
+                    .iter()
+                    .filter_map(|condition| match condition {
+                        Condition::Test(test) => Some(test),
+                        _ => None,
+                    })
+                    .collect();
+                let empty = |field: fn(&Test) -> Option<(u64, u64)>| {
+                    let ranges: Vec<_> = tests.iter().filter_map(|test| field(test)).collect();
+                    !ranges.is_empty() && {
+                        let start = ranges.iter().map(|range| range.0).max().unwrap_or(0);
+                        let end = ranges.iter().map(|range| range.1).min().unwrap_or(u64::MAX);
+                        start > end
+                    }
+                };
+                let extensions: Vec<&Vec<String>> = tests
+                    .iter()
+                    .filter_map(|test| match test {
+                        Test::Extension(extensions) => Some(extensions),
+                        _ => None,
+                    })
+                    .collect();
+                let no_common_extension = extensions
+                    .first()
+                    .is_some_and(|first| !first.iter().any(|ext| extensions.iter().all(|other| other.contains(ext))));
+                empty(Test::size_range) || empty(Test::age_range) || no_common_extension
+            }
+            Condition::Any(conditions) => conditions.iter().all(Condition::never_matches),
+            Condition::Not(_) => false,
+            Condition::Test(test) => test.never_matches(),
+        }
+    }
+
+    /// Whether every file passing this condition also passes `other`. Like
+    /// [`Condition::never_matches`], it errs on the side of `false`.
+    pub fn implies(&self, other: &Condition) -> bool {
+        if self == other {
+            return true;
+        }
+        match (self, other) {
+            (_, Condition::All(others)) => others.iter().all(|other| self.implies(other)),
+            (Condition::Any(conditions), _) => conditions.iter().all(|condition| condition.implies(other)),
+            (_, Condition::Any(others)) => others.iter().any(|other| self.implies(other)),
+            (Condition::All(conditions), _) => conditions.iter().any(|condition| condition.implies(other)),
+            (Condition::Test(test), Condition::Test(other)) => test.implies(other),
+            _ => false,
+        }
+    }
+}
+
+impl Test {
+    fn matches(&self, file: &FileFacts) -> bool {
+        match self {
+            Test::Extension(extensions) => {
+                let ext = file.path.extension().map(|ext| ext.to_string_lossy().to_lowercase()).unwrap_or_default();
+                extensions.contains(&ext)
+            }
+            Test::Name(pattern) => {
+                let name = file.path.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+                wildcard_match(pattern, &name)
+            }
+            Test::Origin(wanted) => origin::origin_domain(file.path)
+                .is_some_and(|domain| domain == *wanted || domain.ends_with(&format!(".{}", wanted))),
+            Test::Size(comparison, limit) => file.metadata().is_some_and(|metadata| comparison.holds(metadata.len(), *limit)),
+            Test::Age(comparison, limit) => file.age().is_some_and(|age| comparison.holds(age, *limit)),
+        }
+    }
+
+    fn size_range(&self) -> Option<(u64, u64)> {
+        match self {
+            Test::Size(comparison, limit) => comparison.range(*limit),
+            _ => None,
+        }
+    }
+
+    fn age_range(&self) -> Option<(u64, u64)> {
+        match self {
+            Test::Age(comparison, limit) => comparison.range(*limit),
+            _ => None,
+        }
+    }
+
+    fn never_matches(&self) -> bool {
+        match self {
+            Test::Extension(extensions) => extensions.is_empty(),
+            _ => [self.size_range(), self.age_range()].into_iter().flatten().any(|(start, end)| start > end),
+        }
+    }
+
+    fn implies(&self, other: &Test) -> bool {
+        let within = |inner: Option<(u64, u64)>, outer: Option<(u64, u64)>| match (inner, outer) {
+            (Some(inner), Some(outer)) => inner.0 >= outer.0 && inner.1 <= outer.1,
+            _ => false,
+        };
+        match (self, other) {
+            (Test::Extension(mine), Test::Extension(theirs)) => mine.iter().all(|ext| theirs.contains(ext)),
+            (Test::Name(_), Test::Name(theirs)) => theirs.chars().all(|c| c == '*') && !theirs.is_empty(),
+            (Test::Origin(mine), Test::Origin(theirs)) => mine.ends_with(&format!(".{}", theirs)),
+            (Test::Size(..), Test::Size(..)) => within(self.size_range(), other.size_range()),
+            (Test::Age(..), Test::Age(..)) => within(self.age_range(), other.age_range()),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    /// Formats the condition back into the expression syntax, with parentheses where needed
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter<'_>, conditions: &[Condition], keyword: &str| -> fmt::Result {
+            for (i, condition) in conditions.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {} ", keyword)?;
+                }
+                match condition {
+                    Condition::Any(_) | Condition::All(_) => write!(f, "({})", condition)?,
+                    _ => write!(f, "{}", condition)?,
+                }
+            }
+            Ok(())
+        };
+        match self {
+            Condition::All(conditions) => join(f, conditions, "AND"),
+            Condition::Any(conditions) => join(f, conditions, "OR"),
+            Condition::Not(condition) => match **condition {
+                Condition::Test(_) | Condition::Not(_) => write!(f, "NOT {}", condition),
+                _ => write!(f, "NOT ({})", condition),
+            },
+            Condition::Test(Test::Extension(extensions)) => write!(f, "ext in [{}]", extensions.join(", ")),
+            Condition::Test(Test::Name(pattern)) if pattern.contains('"') => write!(f, "name = '{}'", pattern),
+            Condition::Test(Test::Name(pattern)) => write!(f, "name = \"{}\"", pattern),
+            Condition::Test(Test::Origin(domain)) => write!(f, "origin = {}", domain),
+            Condition::Test(Test::Size(comparison, limit)) => write!(f, "size {} {}", comparison, ByteSize(*limit)),
+            Condition::Test(Test::Age(comparison, limit)) => {
+                write!(f, "age {} {}", comparison, HumanDuration(std::time::Duration::from_secs(*limit)))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self {
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Equal => "=",
+            Comparison::NotEqual => "!=",
+        };
+        f.write_str(operator)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    Word,
+    Quoted,
+    Symbol,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    column: usize,
+}
+
+impl Token {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Word && self.text.eq_ignore_ascii_case(keyword)
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        self.kind == TokenKind::Symbol && self.text == symbol
+    }
+}
+
+const SYMBOLS: [&str; 12] = ["<=", ">=", "!=", "==", "<", ">", "=", "(", ")", "[", "]", ","];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseConditionError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' || c == '\'' {
+            let end = chars[i + 1..].iter().position(|&other| other == c).map(|offset| i + 1 + offset).ok_or_else(|| {
+                ParseConditionError { input: input.to_string(), column, reason: "unterminated quote".to_string() }
+            })?;
+            tokens.push(Token { kind: TokenKind::Quoted, text: chars[i + 1..end].iter().collect(), column });
+            i = end + 1;
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| {
+            let symbol: Vec<char> = symbol.chars().collect();
+            chars[i..].starts_with(&symbol)
+        }) {
+            tokens.push(Token { kind: TokenKind::Symbol, text: symbol.to_string(), column });
+            i += symbol.chars().count();
+        } else if c == '!' {
+            let reason = "'!' must be followed by '=' (use NOT to negate)".to_string();
+            return Err(ParseConditionError { input: input.to_string(), column, reason });
+        } else {
+            let end = chars[i..]
+                .iter()
+                .position(|&other| other.is_whitespace() || "()[],<>=!\"'".contains(other))
+                .map_or(chars.len(), |offset| i + offset);
+            tokens.push(Token { kind: TokenKind::Word, text: chars[i..end].iter().collect(), column });
+            i = end;
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, ParseConditionError> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => Err(self.error_at_end(format!("expected {}", expected))),
+        }
+    }
+
+    fn error_at(&self, token: &Token, reason: String) -> ParseConditionError {
+        ParseConditionError { input: self.input.to_string(), column: token.column, reason }
+    }
+
+    fn error_at_end(&self, reason: String) -> ParseConditionError {
+        let column = self.input.chars().count() + 1;
+        ParseConditionError { input: self.input.to_string(), column, reason }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek().is_some_and(|token| token.is_keyword(keyword));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    /// `all (OR all)*`
+    fn any(&mut self) -> Result<Condition, ParseConditionError> {
+        let mut conditions = vec![self.all()?];
+        while self.eat_keyword("or") {
+            conditions.push(self.all()?);
+        }
+        Ok(if conditions.len() == 1 { conditions.remove(0) } else { Condition::Any(conditions) })
+    }
+
+    /// `not (AND not)*`
+    fn all(&mut self) -> Result<Condition, ParseConditionError> {
+        let mut conditions = vec![self.not()?];
+        while self.eat_keyword("and") {
+            conditions.push(self.not()?);
+        }
+        Ok(if conditions.len() == 1 { conditions.remove(0) } else { Condition::All(conditions) })
+    }
+
+    /// `NOT not | ( any ) | test`
+    fn not(&mut self) -> Result<Condition, ParseConditionError> {
+        if self.eat_keyword("not") {
+            return Ok(Condition::Not(Box::new(self.not()?)));
+        }
+        let token = self.next("a test such as `ext = pdf` or `size > 5MB`")?;
+        if token.is_symbol("(") {
+            let condition = self.any()?;
+            let close = self.next("')'")?;
+            if !close.is_symbol(")") {
+                return Err(self.error_at(&close, format!("expected ')' but found '{}'", close.text)));
+            }
+            return Ok(condition);
+        }
+        self.test(token)
+    }
+
+    fn test(&mut self, field: Token) -> Result<Condition, ParseConditionError> {
+        if field.kind != TokenKind::Word {
+            return Err(self.error_at(&field, format!("expected a test such as `ext = pdf` but found '{}'", field.text)));
+        }
+        let name = field.text.to_lowercase();
+        let operator = self.next(&format!("an operator after '{}'", field.text))?;
+        let negated = operator.is_symbol("!=");
+        let test = match name.as_str() {
+            "ext" | "extension" => {
+                let values = if operator.is_keyword("in") {
+                    self.list()?
+                } else if self.is_equality(&operator) {
+                    vec![self.value("an extension")?.1]
+                } else {
+                    return Err(self.error_at(&operator, format!("ext takes =, != or in, not '{}'", operator.text)));
+                };
+                let extensions = values.iter().map(|ext| ext.trim_start_matches('.').to_lowercase()).collect();
+                return Ok(negate(Condition::Test(Test::Extension(extensions)), negated));
+            }
+            "name" | "origin" => {
+                if !self.is_equality(&operator) {
+                    return Err(self.error_at(&operator, format!("{} takes = or !=, not '{}'", name, operator.text)));
+                }
+                let value = self.value(if name == "name" { "a file name pattern" } else { "a domain" })?.1.to_lowercase();
+                let test = if name == "name" { Test::Name(value) } else { Test::Origin(value) };
+                return Ok(negate(Condition::Test(test), negated));
+            }
+            "size" | "age" => {
+                let comparison = self.comparison(&operator)?;
+                if name == "size" {
+                    let (token, value) = self.value("a size such as 5MB")?;
+                    let size: ByteSize = value.parse().map_err(|e: super::units::ParseUnitError| self.error_at(&token, e.to_string()))?;
+                    Test::Size(comparison, size.0)
+                } else {
+                    let (token, value) = self.value("a duration such as 30d")?;
+                    let age: HumanDuration = value.parse().map_err(|e: super::units::ParseUnitError| self.error_at(&token, e.to_string()))?;
+                    Test::Age(comparison, age.as_secs())
+                }
+            }
+            _ => {
+                let reason = format!("unknown test '{}' (expected ext, name, origin, size or age)", field.text);
+                return Err(self.error_at(&field, reason));
+            }
+        };
+        Ok(Condition::Test(test))
+    }
+
+    fn is_equality(&self, operator: &Token) -> bool {
+        ["=", "==", "!="].iter().any(|symbol| operator.is_symbol(symbol))
+    }
+
+    fn comparison(&self, operator: &Token) -> Result<Comparison, ParseConditionError> {
+        let comparison = match operator.text.as_str() {
+            "<" => Comparison::Less,
+            "<=" => Comparison::LessOrEqual,
+            ">" => Comparison::Greater,
+            ">=" => Comparison::GreaterOrEqual,
+            "=" | "==" => Comparison::Equal,
+            "!=" => Comparison::NotEqual,
+            _ => return Err(self.error_at(operator, format!("expected <, <=, >, >=, = or != but found '{}'", operator.text))),
+        };
+        if operator.kind != TokenKind::Symbol {
+            return Err(self.error_at(operator, format!("expected <, <=, >, >=, = or != but found '{}'", operator.text)));
+        }
+        Ok(comparison)
+    }
+
+    fn value(&mut self, expected: &str) -> Result<(Token, String), ParseConditionError> {
+        let token = self.next(expected)?;
+        if token.kind == TokenKind::Symbol {
+            return Err(self.error_at(&token, format!("expected {} but found '{}'", expected, token.text)));
+        }
+        let text = token.text.clone();
+        Ok((token, text))
+    }
+
+    /// `[ value (, value)* ]`
+    fn list(&mut self) -> Result<Vec<String>, ParseConditionError> {
+        let open = self.next("'['")?;
+        if !open.is_symbol("[") {
+            return Err(self.error_at(&open, format!("expected '[' but found '{}'", open.text)));
+        }
+        let mut values = Vec::new();
+        loop {
+            values.push(self.value("an extension")?.1);
+            let separator = self.next("',' or ']'")?;
+            if separator.is_symbol("]") {
+                return Ok(values);
+            }
+            if !separator.is_symbol(",") {
+                return Err(self.error_at(&separator, format!("expected ',' or ']' but found '{}'", separator.text)));
+            }
+        }
+    }
+}
+
+fn negate(condition: Condition, negated: bool) -> Condition {
+    if negated {
+        Condition::Not(Box::new(condition))
+    } else {
+        condition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Condition {
+        input.parse().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    #[test]
+    fn test_parse_conditions() {
+        let condition = parse("ext in [JPG, .png] and size > 5MB AND age < 30d OR name = 'scan *'");
+        assert_eq!(
+            condition,
+            Condition::Any(vec![
+                Condition::All(vec![
+                    Condition::Test(Test::Extension(vec!["jpg".to_string(), "png".to_string()])),
+                    Condition::Test(Test::Size(Comparison::Greater, 5 << 20)),
+                    Condition::Test(Test::Age(Comparison::Less, 30 * 24 * 60 * 60)),
+                ]),
+                Condition::Test(Test::Name("scan *".to_string())),
+            ])
+        );
+        assert_eq!(condition.to_string(), "(ext in [jpg, png] AND size > 5MB AND age < 30d) OR name = \"scan *\"");
+        assert_eq!(parse(&condition.to_string()), condition);
+        assert_eq!(
+            parse("NOT (ext = pdf OR origin != github.com)"),
+            Condition::Not(Box::new(Condition::Any(vec![
+                Condition::Test(Test::Extension(vec!["pdf".to_string()])),
+                Condition::Not(Box::new(Condition::Test(Test::Origin("github.com".to_string())))),
+            ])))
+        );
+
+        let error = |input: &str| input.parse::<Condition>().unwrap_err().to_string();
+        assert_eq!(
+            error("size > 5XB"),
+            "invalid condition 'size > 5XB': invalid size '5XB': unknown unit 'XB' (expected B, KB, MB, GB or TB) (at column 8)"
+        );
+        assert!(error("ext in [jpg, png").contains("expected ',' or ']' (at column 17)"), "{}", error("ext in [jpg, png"));
+        assert!(error("colour = red").contains("unknown test 'colour'"));
+        assert!(error("ext = pdf size > 1MB").contains("unexpected 'size'; expected AND or OR (at column 11)"));
+        assert!(error("name > a").contains("name takes = or !="));
+        assert!(error("(ext = pdf").contains("expected ')'"));
+    }
+
+    #[test]
+    fn test_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let big = dir.path().join("Holiday.JPG");
+        fs::write(&big, vec![0u8; 2048]).unwrap();
+        let facts = FileFacts::new(&big);
+
+        assert!(parse("ext in [jpg, png] AND size > 1KB AND age < 1d").matches(&facts));
+        assert!(parse("name = 'holiday*' AND NOT size >= 1MB").matches(&facts));
+        assert!(!parse("ext = jpg AND age > 1d").matches(&facts));
+        assert!(!parse("size < 1KB").matches(&FileFacts::new(&dir.path().join("missing.jpg"))));
+        // Metadata from the scan is used as is, without reading the file again
+        let moved = dir.path().join("moved.jpg");
+        assert!(parse("size > 1KB").matches(&FileFacts::with_metadata(&moved, fs::metadata(&big).unwrap())));
+    }
+
+    #[test]
+    fn test_never_matches_and_implies() {
+        assert!(parse("size > 5MB AND size < 1MB").never_matches());
+        assert!(parse("ext = pdf AND ext in [jpg, png]").never_matches());
+        assert!(parse("size < 0").never_matches());
+        assert!(!parse("size > 1MB AND size < 5MB").never_matches());
+        assert!(!parse("NOT size > 0").never_matches());
+
+        let broad = parse("ext in [jpg, png] AND size > 1MB");
+        assert!(parse("ext = jpg AND size > 5MB AND age < 30d").implies(&broad));
+        assert!(parse("ext = jpg AND size > 5MB OR ext = png AND size >= 2MB").implies(&broad));
+        assert!(!parse("ext = jpg").implies(&broad));
+        assert!(!parse("ext = gif AND size > 5MB").implies(&broad));
+        assert!(parse("origin = api.github.com").implies(&parse("origin = github.com")));
+    }
+}
@@ -0,0 +1,547 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+
+use super::ambiguous::AMBIGUOUS_EXTENSIONS;
+use super::conditions::Condition;
+use super::error::{Context, OrganizerError, Result};
+use super::file_types::{CategoryNames, FileCategory};
+use super::rules;
+use super::ErrorPolicy;
+use super::templates::FolderTemplate;
+use super::units::ByteSize;
+
+/// User configuration loaded from a TOML file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub categories: CategoryConfig,
+    pub sidecars: SidecarConfig,
+    /// Custom rules used by `--mode custom`, evaluated in order
+    pub rules: Vec<RuleConfig>,
+    /// What `--mode custom` does with files no rule places
+    pub default_rule: DefaultRuleConfig,
+    /// WASM classifier plugins that rules can call (needs the `plugins` feature)
+    pub plugins: PluginConfig,
+    /// Custom buckets for `--mode size`, smallest first (default: Tiny/Small/Medium/Large/Huge)
+    pub size_buckets: Vec<SizeBucketConfig>,
+    pub dates: DateConfig,
+    pub history: HistoryConfig,
+    pub pins: PinConfig,
+    pub execution: ExecutionConfig,
+}
+
+/// Settings that change how files are assigned to categories
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CategoryConfig {
+    /// Put installers and disk images (iso, dmg, msi, deb, ...) in their own
+    /// Installers folder instead of Archives/Executables
+    pub split_installers: bool,
+    /// Language of the category folder names (`es` for Imágenes, Documentos, ...)
+    pub language: FolderLanguage,
+    /// Folder names for single categories, e.g. `Images = "Fotos"`; the emoji stays
+    pub names: HashMap<FileCategory, String>,
+    /// Where web pages and structured text (html, json, yaml, toml, xml) go
+    pub structured_text: StructuredText,
+    /// Categories for extensions the built-in list doesn't know, or files differently, e.g.
+    /// `xyz = "Documents"`; `organize --learn-unknown` adds to them
+    pub extensions: HashMap<String, FileCategory>,
+    /// A fixed category for an ambiguous extension, e.g. `ts = "Videos"`, instead of looking
+    /// at each file's contents
+    pub ambiguous: HashMap<String, FileCategory>,
+    /// What happens to files without an extension
+    pub no_extension: NoExtension,
+}
+
+impl Default for CategoryConfig {
+    fn default() -> Self {
+        Self {
+            split_installers: true,
+            language: FolderLanguage::default(),
+            names: HashMap::new(),
+            structured_text: StructuredText::default(),
+            extensions: HashMap::new(),
+            ambiguous: HashMap::new(),
+            no_extension: NoExtension::default(),
+        }
+    }
+}
+
+/// Language of the built-in category folder names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum FolderLanguage {
+    #[default]
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "es")]
+    Spanish,
+}
+
+/// The category of web pages and structured text files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StructuredText {
+    /// With the other documents
+    #[default]
+    Documents,
+    /// With the source code they usually belong to
+    Code,
+    /// In folders of their own: pages in Web, json/yaml/toml/xml in Data
+    Data,
+}
+
+/// What happens to files without an extension. Scripts with a `#!` line go to Code with
+/// all but `skip`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoExtension {
+    /// With the files of unknown type
+    #[default]
+    Other,
+    /// By their contents: images, PDFs, archives, executables, ... by their first bytes,
+    /// build files (`Makefile`, `Dockerfile`) as Code and plain text as Documents
+    Sniff,
+    /// In a `No Extension` folder of their own
+    Folder,
+    /// Left where they are
+    Skip,
+}
+
+/// Sidecar files (subtitles, .nfo, .xmp, ...) that are kept next to their primary file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SidecarConfig {
+    pub enabled: bool,
+    /// Primary extension -> extensions of sidecars that follow it
+    pub pairs: HashMap<String, Vec<String>>,
+}
+
+impl Default for SidecarConfig {
+    fn default() -> Self {
+        let video_sidecars = ["srt", "ass", "ssa", "sub", "idx", "vtt", "nfo"];
+        let photo_sidecars = ["xmp", "aae"];
+
+        let mut pairs = HashMap::new();
+        for ext in ["mkv", "mp4", "avi", "mov", "m4v", "webm", "wmv"] {
+            pairs.insert(ext.to_string(), video_sidecars.iter().map(|s| s.to_string()).collect());
+        }
+        for ext in ["raw", "cr2", "nef", "arw", "dng", "jpg", "jpeg", "heic"] {
+            pairs.insert(ext.to_string(), photo_sidecars.iter().map(|s| s.to_string()).collect());
+        }
+
+        Self {
+            enabled: true,
+            pairs,
+        }
+    }
+}
+
+/// A custom organization rule.
+///
+/// All predicates that are set must match. The destination is either a folder (filled in
+/// from the regex's groups, if the rule has one) or the output of `classify_command`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleConfig {
+    pub name: String,
+    /// File extensions (without the dot) this rule applies to
+    pub extensions: Option<Vec<String>>,
+    /// Glob (`*`, `?`) matched case-insensitively against the file name
+    pub pattern: Option<String>,
+    /// Regular expression searched for in the file name; its groups can be used in the
+    /// destination, as in `Invoices/{client}/{year}`
+    pub regex: Option<String>,
+    /// Download origin domain, e.g. `github.com` (subdomains match too)
+    pub origin: Option<String>,
+    /// Compound condition such as `ext in [jpg, png] AND size > 5MB AND age < 30d`
+    pub when: Option<Condition>,
+    /// Folder, relative to the organized directory, that matching files move to; may use
+    /// placeholders such as `Photos/{yyyy}/{mm}`
+    pub destination: Option<String>,
+    /// New name for matching files, such as `{yyyy}{mm}{dd}_{hhmmss}.{ext}`
+    pub rename: Option<String>,
+    /// External program called with the file path; the first line it prints is the destination
+    pub classify_command: Option<String>,
+    /// Name of a registered WASM plugin that picks the destination
+    pub plugin: Option<String>,
+    /// `skip` (or `ignore`) leaves matching files where they are, `delete` deletes them
+    pub action: RuleAction,
+    /// Keep evaluating later rules after this one matched; they can set what this one
+    /// didn't (e.g. this rule renames, a later one picks the folder) or override it
+    #[serde(rename = "continue")]
+    pub fall_through: bool,
+}
+
+/// What happens to files that no custom rule places
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DefaultRuleConfig {
+    pub action: DefaultAction,
+    /// Folder `quarantine` moves files to
+    pub destination: String,
+}
+
+impl Default for DefaultRuleConfig {
+    fn default() -> Self {
+        Self {
+            action: DefaultAction::Extension,
+            destination: "Unsorted".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DefaultAction {
+    /// Organize them by extension
+    #[default]
+    Extension,
+    /// Leave them where they are
+    Skip,
+    /// Move them all to the default rule's destination, to be sorted by hand
+    Quarantine,
+}
+
+/// What a rule does with the files it matches
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// Move them to the rule's destination
+    #[default]
+    Move,
+    /// Leave them in place; later rules and extension mode don't see them either
+    #[serde(alias = "ignore")]
+    Skip,
+    /// Move them to the trash (or delete them for good with `--permanent`); needs
+    /// `allow_delete = true` in `[execution]`
+    Delete,
+}
+
+/// A size bucket: files up to `max` that didn't fit a smaller bucket
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SizeBucketConfig {
+    /// Folder name; generated from the thresholds (e.g. `100MB-2GB`) if not set
+    pub name: Option<String>,
+    /// Largest size in the bucket (`"100MB"`); only the last bucket may leave it out
+    pub max: Option<ByteSize>,
+}
+
+/// Folder naming for the date modes (date, modified, accessed)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DateConfig {
+    /// Template like `"{year}/{quarter}"` that replaces the `📅 2024-06` style folders
+    pub folder_template: Option<String>,
+}
+
+/// Files that are never moved, besides the ones marked with `file-organizer pin`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PinConfig {
+    /// Globs (`*`, `?`) matched case-insensitively against file names
+    pub patterns: Vec<String>,
+}
+
+/// Defaults for how runs are carried out
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExecutionConfig {
+    /// What a failed move does to the rest of the run (`--on-error` overrides it)
+    pub on_error: ErrorPolicy,
+    /// Free space a run must leave on the filesystem (`--min-free` overrides it)
+    pub min_free: Option<ByteSize>,
+    /// Never read file contents, for folders on network shares (same as `--network-friendly`)
+    pub network_friendly: bool,
+    /// Flush every move to disk before the next (same as `--durable`)
+    pub durable: bool,
+    /// Let rules with `action = "delete"` delete files; without it such rules are refused
+    pub allow_delete: bool,
+}
+
+/// Where undo history is kept
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    pub store: HistoryBackend,
+    /// Database file for the SQLite store
+    /// (default: `~/.local/share/file-organizer/history.sqlite3`)
+    pub database: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryBackend {
+    /// One JSON file per run in `<directory>/.file-organizer/history/`
+    #[default]
+    Json,
+    /// One database for all directories, with a hash cache (needs the `sqlite` feature)
+    Sqlite,
+}
+
+/// Where plugins live and which ones are registered
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PluginConfig {
+    /// Directory relative plugin files are resolved against
+    /// (default: `~/.config/file-organizer/plugins`)
+    pub directory: Option<PathBuf>,
+    pub classifiers: Vec<PluginClassifierConfig>,
+}
+
+impl PluginConfig {
+    /// Configured plugin directory, or the default one next to the config file
+    pub fn directory(&self) -> Option<PathBuf> {
+        self.directory
+            .clone()
+            .or_else(|| config_dir().map(|dir| dir.join("file-organizer").join("plugins")))
+    }
+}
+
+/// One registered WASM classifier
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PluginClassifierConfig {
+    pub name: String,
+    /// `.wasm` (or `.wat`) file, relative to the plugin directory
+    pub file: PathBuf,
+    /// Maximum time a single classification may take
+    pub timeout_ms: u64,
+    /// How many leading bytes of each file the plugin gets to see
+    pub max_bytes: usize,
+}
+
+impl Default for PluginClassifierConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            file: PathBuf::new(),
+            timeout_ms: 1000,
+            max_bytes: 4096,
+        }
+    }
+}
+
+impl Config {
+    /// Load configuration from a TOML file
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .config_context(format!("Failed to read config file {}", path.display()))?;
+        Self::parse(&contents).config_context(format!("Invalid config file {}", path.display()))
+    }
+
+    /// Parse configuration from TOML text
+    pub fn parse(contents: &str) -> Result<Self> {
+        let config: Self = toml::from_str(contents).map_err(|e| OrganizerError::config(e.to_string()))?;
+        for plugin in &config.plugins.classifiers {
+            if plugin.name.is_empty() || plugin.file.as_os_str().is_empty() {
+                return Err(OrganizerError::config("Every [[plugins.classifiers]] entry needs a name and a file"));
+            }
+        }
+        for rule in &config.rules {
+            rules::validate_rule(rule, &config.plugins)?;
+            if rule.action == RuleAction::Delete && !config.execution.allow_delete {
+                return Err(OrganizerError::config(format!(
+                    "Rule '{}' deletes files, which needs `allow_delete = true` in [execution]",
+                    rule.name
+                )));
+            }
+        }
+        validate_size_buckets(&config.size_buckets)?;
+        CategoryNames::from_config(&config.categories)?;
+        if config.categories.extensions.keys().any(|extension| extension.trim_start_matches('.').trim().is_empty()) {
+            return Err(OrganizerError::config("[categories.extensions] has an empty extension"));
+        }
+        for extension in config.categories.ambiguous.keys() {
+            if !AMBIGUOUS_EXTENSIONS.contains(&extension.trim_start_matches('.').to_lowercase().as_str()) {
+                return Err(OrganizerError::config(format!(
+                    "[categories.ambiguous] only settles {}; '{}' isn't ambiguous",
+                    AMBIGUOUS_EXTENSIONS.join(", "),
+                    extension
+                )));
+            }
+        }
+        if let Some(template) = &config.dates.folder_template {
+            FolderTemplate::parse(template)?;
+        }
+        if config.history.store == HistoryBackend::Sqlite && cfg!(not(feature = "sqlite")) {
+            return Err(OrganizerError::config("`history.store = \"sqlite\"` needs a build with the `sqlite` feature"));
+        }
+        Ok(config)
+    }
+
+    /// Load the given config file, or the default one if it exists, or built-in defaults
+    pub fn load_or_default(path: Option<&Path>) -> Result<(Self, Option<PathBuf>)> {
+        if let Some(path) = path {
+            return Ok((Self::load(path)?, Some(path.to_path_buf())));
+        }
+
+        match default_config_path() {
+            Some(path) if path.is_file() => Ok((Self::load(&path)?, Some(path))),
+            _ => Ok((Self::default(), None)),
+        }
+    }
+}
+
+/// Add `[categories.extensions]` entries to the config file at `path` (created if needed),
+/// leaving the rest of the file as it was
+pub fn save_extensions(path: &Path, extensions: &[(String, FileCategory)]) -> Result<()> {
+    let contents = if path.exists() {
+        fs::read_to_string(path).config_context(format!("Failed to read config file {}", path.display()))?
+    } else {
+        String::new()
+    };
+    let mut document: toml_edit::DocumentMut =
+        contents.parse().config_context(format!("Invalid config file {}", path.display()))?;
+    // Without a [categories] header of its own when there wasn't one
+    let categories = document.entry("categories").or_insert_with(|| {
+        let mut categories = toml_edit::Table::new();
+        categories.set_implicit(true);
+        toml_edit::Item::Table(categories)
+    });
+    let table = categories
+        .as_table_mut()
+        .and_then(|categories| categories.entry("extensions").or_insert(toml_edit::table()).as_table_mut())
+        .ok_or_else(|| OrganizerError::config(format!("[categories.extensions] in {} isn't a table", path.display())))?;
+    for (extension, category) in extensions {
+        table[extension.as_str()] = toml_edit::value(category.folder_name());
+    }
+
+    let updated = document.to_string();
+    Config::parse(&updated).config_context(format!("The updated config for {} would be invalid", path.display()))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).config_context(format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, updated).config_context(format!("Failed to write config file {}", path.display()))
+}
+
+fn validate_size_buckets(buckets: &[SizeBucketConfig]) -> Result<()> {
+    let mut previous: Option<ByteSize> = None;
+    for (index, bucket) in buckets.iter().enumerate() {
+        if bucket.name.as_deref().is_some_and(|name| name.trim().is_empty()) {
+            return Err(OrganizerError::config(format!("Size bucket {} has an empty name", index + 1)));
+        }
+        match bucket.max {
+            Some(max) if previous.is_some_and(|previous| max <= previous) => {
+                return Err(OrganizerError::config(format!("Size buckets must be in increasing order, but {} comes after {}", max, previous.unwrap())))
+            }
+            Some(max) => previous = Some(max),
+            None if index + 1 < buckets.len() => {
+                return Err(OrganizerError::config("Only the last size bucket can leave out `max`"))
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Default config location: `$XDG_CONFIG_HOME/file-organizer/config.toml`
+/// (or `~/.config/file-organizer/config.toml`, `%APPDATA%` on Windows)
+pub fn default_config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("file-organizer").join("config.toml"))
+}
+
+fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    if cfg!(windows) {
+        if let Some(dir) = std::env::var_os("APPDATA") {
+            return Some(PathBuf::from(dir));
+        }
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        assert!(Config::parse("").unwrap().categories.split_installers);
+
+        let config = Config::parse("[categories]\nsplit_installers = false\n").unwrap();
+        assert!(!config.categories.split_installers);
+
+        assert!(Config::parse("[categories]\nsplit_installer = false\n").is_err());
+
+        let config = Config::parse("[categories]\nlanguage = \"es\"\n[categories.names]\nebooks = \"Novelas\"\n").unwrap();
+        assert_eq!(config.categories.language, FolderLanguage::Spanish);
+        assert_eq!(config.categories.names[&FileCategory::Ebooks], "Novelas");
+        assert!(Config::parse("[categories]\nlanguage = \"xx\"\n").is_err());
+        assert!(Config::parse("[categories.names]\nPictures = \"Fotos\"\n").is_err());
+        let error = format!("{:#}", Config::parse("[categories.names]\nImages = \"Stuff\"\nVideos = \"stuff\"\n").unwrap_err());
+        assert!(error.contains("both named"), "{}", error);
+        let config = Config::parse("[categories]\nstructured_text = \"data\"\n").unwrap();
+        assert_eq!(config.categories.structured_text, StructuredText::Data);
+        assert!(Config::parse("[categories]\nstructured_text = \"web\"\n").is_err());
+        let config = Config::parse("[categories.ambiguous]\nts = \"Videos\"\n").unwrap();
+        assert_eq!(config.categories.ambiguous["ts"], FileCategory::Videos);
+        let error = Config::parse("[categories.ambiguous]\nrs = \"Documents\"\n").unwrap_err();
+        assert!(error.to_string().contains("'rs' isn't ambiguous"), "{}", error);
+
+        let config = Config::parse("[sidecars.pairs]\nmkv = [\"srt\"]\n").unwrap();
+        assert!(config.sidecars.enabled);
+        assert_eq!(config.sidecars.pairs.len(), 1);
+
+        let config = Config::parse(
+            "[[rules]]\nname = \"invoices\"\npattern = \"invoice*\"\ndestination = \"Invoices\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.rules[0].destination.as_deref(), Some("Invoices"));
+        assert!(Config::parse("[[rules]]\nname = \"broken\"\n").is_err());
+        let config = Config::parse("[[rules]]\nname = \"big\"\nwhen = \"size > 5MB\"\ndestination = \"Big\"\n").unwrap();
+        assert!(config.rules[0].when.is_some());
+        let error = Config::parse("[[rules]]\nname = \"bad\"\nwhen = \"size >\"\ndestination = \"Big\"\n").unwrap_err();
+        assert!(error.to_string().contains("expected a size such as 5MB"), "{}", error);
+
+        let delete = "[[rules]]\nname = \"temp\"\npattern = \"*.tmp\"\nwhen = \"age > 30d\"\naction = \"delete\"\n";
+        let error = Config::parse(delete).unwrap_err();
+        assert!(error.to_string().contains("needs `allow_delete = true`"), "{}", error);
+        let config = Config::parse(&format!("[execution]\nallow_delete = true\n{}", delete)).unwrap();
+        assert_eq!(config.rules[0].action, RuleAction::Delete);
+    }
+
+    #[test]
+    fn test_parse_size_buckets() {
+        let config = Config::parse(
+            "[[size_buckets]]\nmax = \"100MB\"\n[[size_buckets]]\nname = \"Long\"\nmax = 2147483648\n[[size_buckets]]\n",
+        )
+        .unwrap();
+        assert_eq!(config.size_buckets[1].max, Some(ByteSize(2 << 30)));
+        assert_eq!(config.size_buckets[1].name.as_deref(), Some("Long"));
+
+        let error = |toml: &str| format!("{:#}", Config::parse(toml).unwrap_err());
+        assert!(error("[[size_buckets]]\nmax = \"2GB\"\n[[size_buckets]]\nmax = \"1GB\"\n").contains("increasing order"));
+        assert!(error("[[size_buckets]]\n[[size_buckets]]\nmax = \"1GB\"\n").contains("Only the last"));
+        assert!(error("[[size_buckets]]\nmax = \"1XB\"\n").contains("unknown unit"));
+    }
+
+    #[test]
+    fn test_parse_folder_template() {
+        let config = Config::parse("[dates]\nfolder_template = \"{year}/{week}\"\n").unwrap();
+        assert_eq!(config.dates.folder_template.as_deref(), Some("{year}/{week}"));
+
+        let error = format!("{:#}", Config::parse("[dates]\nfolder_template = \"{semester}\"\n").unwrap_err());
+        assert!(error.contains("Unknown placeholder '{semester}'"), "{}", error);
+    }
+
+    #[test]
+    fn test_save_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file-organizer/config.toml");
+        save_extensions(&path, &[("xyz".to_string(), FileCategory::Documents)]).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[categories.extensions]\nxyz = \"Documents\"\n");
+
+        fs::write(&path, "# Mine\n[categories]\nlanguage = \"es\" # Spanish\n\n[categories.extensions]\nabc = \"Code\"\n").unwrap();
+        save_extensions(&path, &[("kdbx".to_string(), FileCategory::Other), ("abc".to_string(), FileCategory::Data)]).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with("# Mine\n[categories]\nlanguage = \"es\" # Spanish\n"), "{}", contents);
+        let config = Config::parse(&contents).unwrap();
+        assert_eq!(config.categories.extensions["abc"], FileCategory::Data);
+        assert_eq!(config.categories.extensions["kdbx"], FileCategory::Other);
+    }
+}
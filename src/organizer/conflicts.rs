@@ -0,0 +1,243 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use super::FileOperation;
+
+/// What to do when a file is about to land where another file already is.
+///
+/// With `Ask`, [`OrganizeObserver::on_conflict`](super::observer::OrganizeObserver::on_conflict)
+/// answers each conflict.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Leave the file where it is and report it as failed
+    #[default]
+    Refuse,
+    /// Leave the file where it is without treating it as a failure
+    Skip,
+    /// Move it next to the existing file as `name (1).ext`
+    Rename,
+    /// Ask what to do for every conflict
+    Ask,
+}
+
+/// Answer to a single conflict under [`ConflictPolicy::Ask`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictChoice {
+    Skip,
+    Rename,
+}
+
+/// A planned move that won't go through as planned, found before anything is moved
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PlannedConflict {
+    pub operation: FileOperation,
+    pub kind: ConflictKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// A file is already at the destination
+    Exists,
+    /// An earlier operation in the plan moves `source` to the same destination
+    SameDestination { source: PathBuf },
+    /// The destination differs only in case from `other` (an existing file or another planned
+    /// destination), so the two collide on case-insensitive filesystems
+    CaseClash { other: PathBuf },
+}
+
+impl ConflictKind {
+    /// The conflict makes the move hit the conflict policy on every filesystem
+    pub fn blocks(&self) -> bool {
+        !matches!(self, ConflictKind::CaseClash { .. })
+    }
+}
+
+/// Every conflict in `operations`, in plan order.
+///
+/// Operations are taken to run in order, so of two files headed to the same place only the
+/// second one conflicts.
+pub fn detect(operations: &[FileOperation]) -> Vec<PlannedConflict> {
+    let mut planned: HashMap<&Path, &Path> = HashMap::new();
+    let mut planned_folded: HashMap<String, &Path> = HashMap::new();
+    // Existing entries per destination directory, by lowercased name
+    let mut listings: HashMap<&Path, HashMap<String, PathBuf>> = HashMap::new();
+    let mut conflicts = Vec::new();
+
+    for op in operations {
+        let destination = op.destination.as_path();
+        let folded = destination.to_string_lossy().to_lowercase();
+        let kind = if destination != op.source && fs::symlink_metadata(destination).is_ok() {
+            Some(ConflictKind::Exists)
+        } else if let Some(source) = planned.get(destination) {
+            Some(ConflictKind::SameDestination { source: source.to_path_buf() })
+        } else if let Some(other) = planned_folded.get(&folded) {
+            Some(ConflictKind::CaseClash { other: other.to_path_buf() })
+        } else {
+            let listing = destination
+                .parent()
+                .map(|dir| listings.entry(dir).or_insert_with(|| list_folded(dir)));
+            let name = destination.file_name().map(|name| name.to_string_lossy().to_lowercase());
+            listing
+                .zip(name)
+                .and_then(|(listing, name)| listing.get(&name))
+                .map(|other| ConflictKind::CaseClash { other: other.clone() })
+        };
+
+        if let Some(kind) = kind {
+            conflicts.push(PlannedConflict { operation: op.clone(), kind });
+        }
+        planned.entry(destination).or_insert(&op.source);
+        planned_folded.entry(folded).or_insert(destination);
+    }
+    conflicts
+}
+
+/// Give every move with a blocking conflict the `name (N).ext` it would be renamed to, so the
+/// plan already says where `--on-conflict rename` puts it
+pub fn rename_planned(operations: &mut [FileOperation], conflicts: &[PlannedConflict]) {
+    let renamed: HashSet<&Path> = conflicts
+        .iter()
+        .filter(|conflict| conflict.kind.blocks())
+        .map(|conflict| conflict.operation.source.as_path())
+        .collect();
+    let mut planned: HashSet<PathBuf> = operations.iter().map(|op| op.destination.clone()).collect();
+    for op in operations.iter_mut().filter(|op| renamed.contains(op.source.as_path())) {
+        op.destination = free_name_where(&op.destination, |path| path.exists() || planned.contains(path));
+        planned.insert(op.destination.clone());
+    }
+}
+
+/// Entries of `dir` keyed by lowercased name; empty if it doesn't exist yet
+fn list_folded(dir: &Path) -> HashMap<String, PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return HashMap::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| (entry.file_name().to_string_lossy().to_lowercase(), entry.path()))
+        .collect()
+}
+
+/// First `name (N).ext` next to `path` that doesn't exist yet
+pub fn free_name(path: &Path) -> PathBuf {
+    free_name_where(path, Path::exists)
+}
+
+/// First `name (N).ext` next to `path` that isn't `taken`
+pub fn free_name_where(path: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    // Built from the name's own bytes, so names that aren't valid UTF-8 keep them
+    let numbered = |n: usize| {
+        let mut name = path.file_stem().map(OsString::from).unwrap_or_default();
+        name.push(format!(" ({})", n));
+        if let Some(extension) = path.extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        path.with_file_name(name)
+    };
+
+    (1..)
+        .map(numbered)
+        .find(|candidate| !taken(candidate))
+        .expect("some numbered name is free")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::OperationType;
+
+    #[test]
+    fn test_free_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let photo = dir.path().join("photo.jpg");
+        fs::write(&photo, b"a").unwrap();
+        assert_eq!(free_name(&photo), dir.path().join("photo (1).jpg"));
+
+        fs::write(dir.path().join("photo (1).jpg"), b"b").unwrap();
+        assert_eq!(free_name(&photo), dir.path().join("photo (2).jpg"));
+        assert_eq!(free_name(&dir.path().join("README")), dir.path().join("README (1)"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let latin1 = dir.path().join(std::ffi::OsStr::from_bytes(b"caf\xe9.jpg"));
+            let renamed = dir.path().join(std::ffi::OsStr::from_bytes(b"caf\xe9 (1).jpg"));
+            assert_eq!(free_name(&latin1), renamed);
+        }
+    }
+
+    #[test]
+    fn test_detect_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("Images")).unwrap();
+        fs::write(root.join("Images/taken.jpg"), b"old").unwrap();
+        fs::write(root.join("Images/Holiday.jpg"), b"old").unwrap();
+        let op = |source: &str, destination: &str| FileOperation {
+            source: root.join(source),
+            destination: root.join(destination),
+            operation_type: OperationType::Move,
+            size: 0,
+        };
+        let operations = [
+            op("taken.jpg", "Images/taken.jpg"),
+            op("a/photo.jpg", "Images/photo.jpg"),
+            op("b/photo.jpg", "Images/photo.jpg"),
+            op("PHOTO.jpg", "Images/PHOTO.jpg"),
+            op("holiday.jpg", "Images/holiday.jpg"),
+            op("fine.jpg", "Images/fine.jpg"),
+        ];
+
+        let kinds: Vec<_> = detect(&operations).into_iter().map(|c| (c.operation.source, c.kind)).collect();
+        // A case-insensitive filesystem reports the case variants as existing files
+        let case_insensitive = root.join("images").exists();
+        let clash = |other: &str| match case_insensitive {
+            true => ConflictKind::Exists,
+            false => ConflictKind::CaseClash { other: root.join(other) },
+        };
+        let expected = vec![
+            (root.join("taken.jpg"), ConflictKind::Exists),
+            (root.join("b/photo.jpg"), ConflictKind::SameDestination { source: root.join("a/photo.jpg") }),
+            (root.join("PHOTO.jpg"), ConflictKind::CaseClash { other: root.join("Images/photo.jpg") }),
+            (root.join("holiday.jpg"), clash("Images/Holiday.jpg")),
+        ];
+        assert_eq!(kinds, expected);
+        assert!(!ConflictKind::CaseClash { other: PathBuf::new() }.blocks());
+    }
+
+    #[test]
+    fn test_rename_planned() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("Images")).unwrap();
+        fs::write(root.join("Images/photo.jpg"), b"old").unwrap();
+        fs::write(root.join("Images/photo (1).jpg"), b"old").unwrap();
+        let op = |source: &str, destination: &str| FileOperation {
+            source: root.join(source),
+            destination: root.join(destination),
+            operation_type: OperationType::Move,
+            size: 0,
+        };
+        let mut operations = vec![
+            op("photo.jpg", "Images/photo.jpg"),
+            op("a/photo.jpg", "Images/photo.jpg"),
+            op("photo (3).jpg", "Images/photo (3).jpg"),
+            op("fine.jpg", "Images/fine.jpg"),
+        ];
+
+        let conflicts = detect(&operations);
+        rename_planned(&mut operations, &conflicts);
+        let destinations: Vec<_> = operations.iter().map(|op| op.destination.strip_prefix(root).unwrap().to_path_buf()).collect();
+        let expected: Vec<PathBuf> = ["Images/photo (2).jpg", "Images/photo (4).jpg", "Images/photo (3).jpg", "Images/fine.jpg"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(destinations, expected);
+        assert!(detect(&operations).is_empty());
+    }
+}
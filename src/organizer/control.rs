@@ -0,0 +1,378 @@
+//! The control channel of a running `watch`, so it can be managed without restarting it
+//! (`daemon status`, `pause`, `resume`, `flush`, `reload`). Each watch listens on its own
+//! endpoint named after its process ID: a unix socket in `$XDG_RUNTIME_DIR/file-organizer/`
+//! (or the data directory where there's no runtime directory), a named pipe on Windows. A
+//! request is one line of JSON and so is the reply. A watch in the middle of a round answers
+//! that it's busy, with the status from before the round, rather than keeping the client
+//! waiting; the request is still taken up once the round is over.
+//!
+//! Requests go out to every running watch; one naming a directory only acts on the watch of
+//! that directory, and the others just report their status.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+/// Start of every endpoint's name, followed by the process ID
+const ENDPOINT_PREFIX: &str = "file-organizer-watch-";
+
+/// How long a request waits for the watch before the answer is that it's busy. Between
+/// rounds requests are taken up every second.
+const BUSY_AFTER: Duration = Duration::from_secs(3);
+
+/// For reading and writing a request or a reply, so a stuck client or watch gives up
+#[cfg(unix)]
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Control {
+    /// Just report the status
+    Status,
+    /// Stop organizing until resumed; written files are still noted
+    Pause,
+    Resume,
+    /// Organize everything now, paused or not
+    Flush,
+    /// Read the config again
+    Reload,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControlRequest {
+    pub control: Control,
+    /// Only the watch of this directory acts on it; `None` for all of them
+    pub directory: Option<PathBuf>,
+}
+
+/// What a watch is up to
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchStatus {
+    pub pid: u32,
+    pub directory: PathBuf,
+    /// Paused with `daemon pause`
+    pub paused: bool,
+    /// The sync client it's waiting for, if one is writing into the directory
+    pub waiting_for: Option<String>,
+    /// Rounds that moved files
+    pub rounds: u64,
+    pub moved: u64,
+    pub last_moved: Option<DateTime<Local>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControlReply {
+    pub status: WatchStatus,
+    /// What the request did; `None` if it was for another directory or only asked for the
+    /// status
+    pub message: Option<String>,
+    pub error: Option<String>,
+    /// In the middle of a round, so the request is taken up once it's over; the status is
+    /// from before the round
+    #[serde(default)]
+    pub busy: bool,
+}
+
+/// A request for the watch to act on, with the way to answer it
+pub struct Request {
+    pub control: Control,
+    /// False if the request names another directory: only the status is wanted then
+    pub applies: bool,
+    reply: mpsc::Sender<ControlReply>,
+}
+
+impl Request {
+    pub fn answer(self, reply: ControlReply) {
+        // The client may have given up
+        let _ = self.reply.send(reply);
+    }
+}
+
+/// The listening end, taking requests on a thread of its own
+pub struct ControlServer {
+    requests: mpsc::Receiver<Request>,
+    /// What busy answers report
+    status: Arc<Mutex<WatchStatus>>,
+    #[cfg(unix)]
+    socket: PathBuf,
+}
+
+impl ControlServer {
+    /// Listen for requests to the watch of `directory`
+    pub fn start(directory: &Path) -> io::Result<Self> {
+        let directory = directory.canonicalize()?;
+        let (sender, requests) = mpsc::channel();
+        let status = Arc::new(Mutex::new(WatchStatus::default()));
+        let published = Arc::clone(&status);
+        #[cfg(unix)]
+        {
+            use std::os::unix::net::UnixListener;
+
+            let socket = endpoint_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no runtime or home directory"))?;
+            create_private_dir(&socket)?;
+            let socket = socket.join(format!("{}{}", ENDPOINT_PREFIX, std::process::id()));
+            // A watch that died with this process ID
+            let _ = std::fs::remove_file(&socket);
+            let listener = UnixListener::bind(&socket)?;
+            std::thread::spawn(move || {
+                for stream in listener.incoming().filter_map(Result::ok) {
+                    // A client that stops halfway mustn't keep the others out
+                    if stream.set_read_timeout(Some(IO_TIMEOUT)).and_then(|()| stream.set_write_timeout(Some(IO_TIMEOUT))).is_ok() {
+                        serve(&stream, &stream, &directory, &sender, &published);
+                    }
+                }
+            });
+            Ok(Self { requests, status, socket })
+        }
+        #[cfg(windows)]
+        {
+            let name = format!(r"\\.\pipe\{}{}", ENDPOINT_PREFIX, std::process::id());
+            // Make the first pipe instance here, so errors reach the caller
+            let mut pipe = windows::create_pipe(&name)?;
+            std::thread::spawn(move || loop {
+                if let Ok(connected) = windows::connect(pipe) {
+                    // Pipes have no timeouts, so a client that stops halfway only holds up its
+                    // own thread
+                    let (directory, sender, published) = (directory.clone(), sender.clone(), Arc::clone(&published));
+                    std::thread::spawn(move || serve(&connected, &connected, &directory, &sender, &published));
+                }
+                match windows::create_pipe(&name) {
+                    Ok(next) => pipe = next,
+                    Err(e) => return log::warn!("The control pipe stopped working: {}", e),
+                }
+            });
+            Ok(Self { requests, status })
+        }
+    }
+
+    /// Requests that have come in since the last call
+    pub fn requests(&self) -> impl Iterator<Item = Request> + '_ {
+        self.requests.try_iter()
+    }
+
+    /// Note the status to answer with while the watch is busy
+    pub fn publish(&self, status: &WatchStatus) {
+        if let Ok(mut published) = self.status.lock() {
+            published.clone_from(status);
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
+
+/// Read one request from a client, hand it to the watch and write back its reply, or that
+/// it's busy if it doesn't answer within [`BUSY_AFTER`]
+fn serve(input: impl io::Read, mut output: impl io::Write, directory: &Path, sender: &mpsc::Sender<Request>, status: &Mutex<WatchStatus>) {
+    let mut line = String::new();
+    if BufReader::new(input).read_line(&mut line).is_err() {
+        return;
+    }
+    let request: ControlRequest = match serde_json::from_str(&line) {
+        Ok(request) => request,
+        Err(e) => return log::warn!("Ignoring a malformed control request: {}", e),
+    };
+    let applies = match &request.directory {
+        Some(requested) => requested.canonicalize().is_ok_and(|requested| requested == directory),
+        None => true,
+    };
+    let (reply, replies) = mpsc::channel();
+    if sender.send(Request { control: request.control, applies, reply }).is_err() {
+        return;
+    }
+    // Flushing answers once the round is done, which may well be too late
+    let reply = match replies.recv_timeout(BUSY_AFTER) {
+        Ok(reply) => reply,
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            let status = status.lock().map(|status| status.clone()).unwrap_or_default();
+            // Requests for other directories only want the status
+            ControlReply { status, message: None, error: None, busy: applies }
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => return,
+    };
+    if let Ok(json) = serde_json::to_string(&reply) {
+        let _ = writeln!(output, "{}", json);
+    }
+}
+
+/// Send `request` to every running watch, returning their replies. Endpoints left behind by
+/// watches that are gone are cleaned up.
+pub fn send_all(request: &ControlRequest) -> Vec<ControlReply> {
+    endpoints()
+        .into_iter()
+        .filter_map(|endpoint| match send(&endpoint, request) {
+            Ok(reply) => Some(reply),
+            Err(e) => {
+                if e.kind() == io::ErrorKind::ConnectionRefused {
+                    let _ = std::fs::remove_file(&endpoint);
+                } else {
+                    log::warn!("Couldn't reach the watch at {}: {}", endpoint.display(), e);
+                }
+                None
+            }
+        })
+        .collect()
+}
+
+fn send(endpoint: &Path, request: &ControlRequest) -> io::Result<ControlReply> {
+    #[cfg(unix)]
+    let (input, mut output) = {
+        let stream = std::os::unix::net::UnixStream::connect(endpoint)?;
+        // The watch answers within BUSY_AFTER, busy or not
+        stream.set_read_timeout(Some(BUSY_AFTER + IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        (stream.try_clone()?, stream)
+    };
+    #[cfg(windows)]
+    let (input, mut output) = {
+        let pipe = windows::open(endpoint)?;
+        (pipe.try_clone()?, pipe)
+    };
+    writeln!(output, "{}", serde_json::to_string(request)?)?;
+    let mut line = String::new();
+    BufReader::new(input).read_line(&mut line)?;
+    if line.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the watch closed the connection"));
+    }
+    Ok(serde_json::from_str(&line)?)
+}
+
+/// Endpoints of the watches running now, and of any that died without cleaning up
+fn endpoints() -> Vec<PathBuf> {
+    #[cfg(unix)]
+    let dir = endpoint_dir();
+    #[cfg(windows)]
+    let dir = Some(PathBuf::from(r"\\.\pipe\"));
+    let Some(entries) = dir.and_then(|dir| std::fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut endpoints: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(ENDPOINT_PREFIX))
+        .map(|entry| entry.path())
+        .collect();
+    endpoints.sort();
+    endpoints
+}
+
+#[cfg(unix)]
+fn endpoint_dir() -> Option<PathBuf> {
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => Some(PathBuf::from(dir).join("file-organizer")),
+        None => super::store::data_dir().map(|dir| dir.join("file-organizer").join("run")),
+    }
+}
+
+/// Other users mustn't be able to control this user's watches
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    std::fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::fs::File;
+    use std::io;
+    use std::os::windows::io::{FromRawHandle, RawHandle};
+    use std::path::Path;
+    use std::time::Duration;
+    use windows_sys::Win32::Foundation::{CloseHandle, ERROR_PIPE_BUSY, ERROR_PIPE_CONNECTED, HANDLE, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Storage::FileSystem::PIPE_ACCESS_DUPLEX;
+    use windows_sys::Win32::System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES,
+        PIPE_WAIT,
+    };
+
+    /// A pipe instance waiting for its client
+    pub(super) struct Pipe(HANDLE);
+
+    // SAFETY: the handle is only used by the thread it's moved to
+    unsafe impl Send for Pipe {}
+
+    pub(super) fn create_pipe(name: &str) -> io::Result<Pipe> {
+        let wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+        let mode = PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS;
+        // SAFETY: the name is NUL-terminated; the default security lets only this user and
+        // administrators in
+        let handle = unsafe { CreateNamedPipeW(wide.as_ptr(), PIPE_ACCESS_DUPLEX, mode, PIPE_UNLIMITED_INSTANCES, 4096, 4096, 0, std::ptr::null()) };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Pipe(handle))
+    }
+
+    /// Wait for a client, returning the connection as a file to read and write
+    pub(super) fn connect(pipe: Pipe) -> io::Result<File> {
+        // SAFETY: a pipe handle made by create_pipe, without overlapped I/O
+        if unsafe { ConnectNamedPipe(pipe.0, std::ptr::null_mut()) } == 0 {
+            let error = io::Error::last_os_error();
+            // A client that connected before the call is just as good
+            if error.raw_os_error() != Some(ERROR_PIPE_CONNECTED as i32) {
+                // SAFETY: the handle isn't used again
+                unsafe { CloseHandle(pipe.0) };
+                return Err(error);
+            }
+        }
+        // SAFETY: the file takes over the handle and closes it
+        Ok(unsafe { File::from_raw_handle(pipe.0 as RawHandle) })
+    }
+
+    /// Open a watch's pipe, waiting a little while it serves someone else
+    pub(super) fn open(endpoint: &Path) -> io::Result<File> {
+        let mut attempts = 0;
+        loop {
+            match File::options().read(true).write(true).open(endpoint) {
+                Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY as i32) && attempts < 20 => {
+                    attempts += 1;
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(io::ErrorKind::ConnectionRefused.into()),
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serve_busy() {
+        let dir = tempfile::tempdir().unwrap();
+        let directory = dir.path().canonicalize().unwrap();
+        let (sender, requests) = mpsc::channel();
+        let status = Mutex::new(WatchStatus { pid: 7, rounds: 2, ..WatchStatus::default() });
+        let request = |control: Control| format!("{}\n", serde_json::to_string(&ControlRequest { control, directory: None }).unwrap());
+
+        // Between rounds the watch answers itself
+        let answering = std::thread::spawn(move || {
+            let request: Request = requests.recv().unwrap();
+            request.answer(ControlReply { status: WatchStatus::default(), message: Some("Paused".to_string()), error: None, busy: false });
+            requests
+        });
+        let mut output = Vec::new();
+        serve(request(Control::Pause).as_bytes(), &mut output, &directory, &sender, &status);
+        let reply: ControlReply = serde_json::from_slice(&output).unwrap();
+        assert_eq!((reply.message.as_deref(), reply.busy), (Some("Paused"), false));
+
+        // In the middle of a round it's left unanswered, but still queued
+        let requests = answering.join().unwrap();
+        let mut output = Vec::new();
+        serve(request(Control::Flush).as_bytes(), &mut output, &directory, &sender, &status);
+        let reply: ControlReply = serde_json::from_slice(&output).unwrap();
+        assert!(reply.busy);
+        assert_eq!((reply.status.pid, reply.status.rounds, reply.message), (7, 2, None));
+        assert_eq!(requests.try_iter().map(|request| request.control).collect::<Vec<_>>(), [Control::Flush]);
+    }
+}
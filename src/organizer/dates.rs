@@ -0,0 +1,266 @@
+use std::fs::Metadata;
+use std::path::Path;
+use std::time::SystemTime;
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+use chrono::{DateTime, Datelike, NaiveDateTime, Utc};
+
+use super::exif_time;
+
+/// Which timestamp Date mode sorts by
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DateSource {
+    /// Creation time, then EXIF capture time, then modification time
+    #[default]
+    Auto,
+    /// Creation (birth) time, falling back to modification time
+    Created,
+    /// EXIF capture time, falling back to modification time
+    Exif,
+    /// Modification time only
+    Modified,
+}
+
+impl DateSource {
+    /// Timestamps to try, in order
+    fn chain(self) -> &'static [DateKind] {
+        match self {
+            DateSource::Auto => &[DateKind::Created, DateKind::Exif, DateKind::Modified],
+            DateSource::Created => &[DateKind::Created, DateKind::Modified],
+            DateSource::Exif => &[DateKind::Exif, DateKind::Modified],
+            DateSource::Modified => &[DateKind::Modified],
+        }
+    }
+
+    /// The timestamp this source would use if every file had it
+    pub fn preferred(self) -> DateKind {
+        self.chain()[0]
+    }
+
+    /// The same source without EXIF capture times, which have to be read from the file
+    pub fn without_exif(self) -> Self {
+        match self {
+            DateSource::Auto => DateSource::Created,
+            DateSource::Exif => DateSource::Modified,
+            other => other,
+        }
+    }
+}
+
+/// How finely the date modes (date, modified, accessed) split files into folders
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DateBucket {
+    /// `2024`
+    Year,
+    /// `2024-Q2`
+    Quarter,
+    /// `2024-06`
+    #[default]
+    Month,
+    /// ISO week, `2024-W23`
+    Week,
+    /// `2024-06-05`
+    Day,
+}
+
+impl DateBucket {
+    /// Folder label for a date, without the mode's emoji
+    pub fn label(self, date: &NaiveDateTime) -> String {
+        match self {
+            DateBucket::Year => date.format("%Y").to_string(),
+            DateBucket::Quarter => format!("{}-Q{}", date.format("%Y"), date.month0() / 3 + 1),
+            DateBucket::Month => date.format("%Y-%m").to_string(),
+            DateBucket::Week => date.format("%G-W%V").to_string(),
+            DateBucket::Day => date.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// A timestamp a file's date can come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateKind {
+    Created,
+    Exif,
+    Modified,
+}
+
+impl DateKind {
+    pub fn description(self) -> &'static str {
+        match self {
+            DateKind::Created => "creation time",
+            DateKind::Exif => "EXIF capture time",
+            DateKind::Modified => "modification time",
+        }
+    }
+}
+
+/// How many files in a run didn't have the preferred timestamp, by what was used instead
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct DateFallbacks {
+    pub from_exif: usize,
+    pub from_modified: usize,
+}
+
+impl DateFallbacks {
+    pub fn total(&self) -> usize {
+        self.from_exif + self.from_modified
+    }
+}
+
+/// Date of a file according to `source`, or `None` if it has none of the timestamps.
+///
+/// Fallbacks (the preferred timestamp was missing) are counted in `fallbacks`.
+pub fn file_date(
+    file_path: &Path,
+    metadata: &Metadata,
+    source: DateSource,
+    fallbacks: &mut DateFallbacks,
+) -> Option<NaiveDateTime> {
+    let found = pick_date(source, |kind| match kind {
+        DateKind::Created => birth_time(file_path, metadata).map(utc),
+        DateKind::Exif => exif_time::exif_datetime(file_path),
+        DateKind::Modified => metadata.modified().ok().map(utc),
+    });
+
+    match found {
+        Some((_, kind)) if kind == source.preferred() => {}
+        Some((_, DateKind::Exif)) => fallbacks.from_exif += 1,
+        Some((_, _)) => fallbacks.from_modified += 1,
+        None => {}
+    }
+    found.map(|(date, _)| date)
+}
+
+/// When the file was created, if the platform and filesystem record it
+pub fn birth_time(file_path: &Path, metadata: &Metadata) -> Option<SystemTime> {
+    #[cfg(target_os = "linux")]
+    if let Some(btime) = statx_btime(file_path) {
+        return Some(btime);
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = file_path;
+
+    metadata.created().ok()
+}
+
+/// Ask statx() for just the birth time, without forcing network filesystems to sync.
+///
+/// Returns `None` on kernels before 4.11, under seccomp filters that block statx, and on
+/// filesystems that don't record birth times (the kernel then clears `STATX_BTIME`).
+#[cfg(target_os = "linux")]
+fn statx_btime(file_path: &Path) -> Option<SystemTime> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(file_path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statx only writes into `buffer`, and `c_path` is a valid NUL-terminated string
+    // that outlives the call
+    let (result, buffer) = unsafe {
+        let mut buffer: libc::statx = std::mem::zeroed();
+        let result = libc::statx(
+            libc::AT_FDCWD,
+            c_path.as_ptr(),
+            libc::AT_STATX_DONT_SYNC,
+            libc::STATX_BTIME,
+            &mut buffer,
+        );
+        (result, buffer)
+    };
+
+    if result != 0 || buffer.stx_mask & libc::STATX_BTIME == 0 {
+        return None;
+    }
+    let seconds = u64::try_from(buffer.stx_btime.tv_sec).ok()?;
+    SystemTime::UNIX_EPOCH.checked_add(Duration::new(seconds, buffer.stx_btime.tv_nsec))
+}
+
+/// First timestamp in the source's chain that `lookup` can provide
+fn pick_date(
+    source: DateSource,
+    mut lookup: impl FnMut(DateKind) -> Option<NaiveDateTime>,
+) -> Option<(NaiveDateTime, DateKind)> {
+    source
+        .chain()
+        .iter()
+        .find_map(|&kind| lookup(kind).map(|date| (date, kind)))
+}
+
+fn utc(time: SystemTime) -> NaiveDateTime {
+    DateTime::<Utc>::from(time).naive_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn date(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 6, day).unwrap().and_hms_opt(12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_pick_date_follows_chain() {
+        // A filesystem without birth times (e.g. older Linux kernels)
+        let no_btime = |kind| match kind {
+            DateKind::Created => None,
+            DateKind::Exif => Some(date(1)),
+            DateKind::Modified => Some(date(2)),
+        };
+
+        assert_eq!(pick_date(DateSource::Auto, no_btime), Some((date(1), DateKind::Exif)));
+        assert_eq!(pick_date(DateSource::Created, no_btime), Some((date(2), DateKind::Modified)));
+        assert_eq!(pick_date(DateSource::Exif, no_btime), Some((date(1), DateKind::Exif)));
+        assert_eq!(pick_date(DateSource::Modified, |_| None), None);
+        for source in [DateSource::Auto, DateSource::Created, DateSource::Exif, DateSource::Modified] {
+            assert!(!source.without_exif().chain().contains(&DateKind::Exif), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn test_date_bucket_labels() {
+        let label = |bucket: DateBucket, year, month, day| {
+            let date = NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(0, 0, 0).unwrap();
+            bucket.label(&date)
+        };
+        assert_eq!(label(DateBucket::Year, 2024, 6, 5), "2024");
+        assert_eq!(label(DateBucket::Quarter, 2024, 6, 5), "2024-Q2");
+        assert_eq!(label(DateBucket::Quarter, 2024, 10, 1), "2024-Q4");
+        assert_eq!(label(DateBucket::Month, 2024, 6, 5), "2024-06");
+        assert_eq!(label(DateBucket::Week, 2024, 6, 5), "2024-W23");
+        // Late December can belong to the next ISO year, early January to the previous one
+        assert_eq!(label(DateBucket::Week, 2024, 12, 30), "2025-W01");
+        assert_eq!(label(DateBucket::Week, 2021, 1, 3), "2020-W53");
+        assert_eq!(label(DateBucket::Day, 2024, 6, 5), "2024-06-05");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_statx_btime_agrees_with_std() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.txt");
+        std::fs::write(&path, b"x").unwrap();
+
+        // std uses statx too, so both see a birth time or neither does
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(statx_btime(&path), metadata.created().ok());
+        assert_eq!(statx_btime(&dir.path().join("missing")), None);
+    }
+
+    #[test]
+    fn test_file_date_counts_fallbacks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, b"no exif here").unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+
+        let mut fallbacks = DateFallbacks::default();
+        let modified = file_date(&path, &metadata, DateSource::Modified, &mut fallbacks);
+        assert_eq!(modified, Some(utc(metadata.modified().unwrap())));
+        assert_eq!(fallbacks.total(), 0);
+
+        // Plain text has no EXIF, so this always falls back to the modification time
+        assert_eq!(file_date(&path, &metadata, DateSource::Exif, &mut fallbacks), modified);
+        assert_eq!(fallbacks, DateFallbacks { from_modified: 1, ..DateFallbacks::default() });
+    }
+}
@@ -0,0 +1,297 @@
+//! Duplicate files and duplicate folders.
+//!
+//! Two folders are duplicates when they hold the same files by content (their names may
+//! differ), as after unzipping the same archive twice. Each group keeps one canonical copy:
+//! the one with the shortest path, so `photos/` wins over `photos (1)/` and `old/photos/`.
+//!
+//! Only files of the same size are hashed, and big ones only when their ends match too.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::error::{IoContext, OrganizerError, Result};
+use super::hashing;
+use super::journal::{self, METADATA_DIR};
+use super::FileOrganizer;
+
+/// Copies of the same content; the first path is the one to keep
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub paths: Vec<PathBuf>,
+    /// Size of one copy
+    pub bytes: u64,
+}
+
+impl DuplicateGroup {
+    pub fn canonical(&self) -> &Path {
+        &self.paths[0]
+    }
+
+    /// The copies that could go
+    pub fn extras(&self) -> &[PathBuf] {
+        &self.paths[1..]
+    }
+
+    /// Space freed by keeping only the canonical copy
+    pub fn reclaimable(&self) -> u64 {
+        self.bytes * self.extras().len() as u64
+    }
+}
+
+impl FileOrganizer {
+    /// Files below `root` with identical content, biggest first
+    pub fn duplicate_files(&self, root: &Path) -> Result<Vec<DuplicateGroup>> {
+        let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+        for (path, size) in scan(root)? {
+            if size > 0 {
+                by_size.entry(size).or_default().push(path);
+            }
+        }
+
+        let mut groups = Vec::new();
+        for (size, paths) in by_size.into_iter().filter(|(_, paths)| paths.len() > 1) {
+            // Big files that differ near either end are told apart without reading them whole
+            let candidates = if size > 2 * hashing::QUICK_HASH_BYTES {
+                let mut by_quick_hash: HashMap<String, Vec<PathBuf>> = HashMap::new();
+                for path in paths {
+                    self.check_cancelled()?;
+                    by_quick_hash.entry(hashing::quick_hash(&path)?).or_default().push(path);
+                }
+                by_quick_hash.into_values().filter(|paths| paths.len() > 1).collect()
+            } else {
+                vec![paths]
+            };
+
+            for paths in candidates {
+                let mut by_hash: HashMap<String, Vec<PathBuf>> = HashMap::new();
+                for path in paths {
+                    self.check_cancelled()?;
+                    by_hash.entry(self.hash_file(&path)?).or_default().push(path);
+                }
+                groups.extend(by_hash.into_values().filter(|paths| paths.len() > 1).map(|paths| group(paths, size)));
+            }
+        }
+        sort_groups(&mut groups);
+        Ok(groups)
+    }
+
+    /// Folders below `root` holding the same files by content, biggest first.
+    ///
+    /// Subfolders of duplicates aren't reported separately, and a folder is never paired
+    /// with one inside it.
+    pub fn duplicate_folders(&self, root: &Path) -> Result<Vec<DuplicateGroup>> {
+        let files = scan(root)?;
+
+        // Only folders that share their file count and total size can be duplicates, so
+        // files elsewhere are never hashed
+        let mut totals: HashMap<PathBuf, (usize, u64)> = HashMap::new();
+        for (path, size) in &files {
+            for folder in folders_above(root, path) {
+                let total = totals.entry(folder.to_path_buf()).or_default();
+                total.0 += 1;
+                total.1 += size;
+            }
+        }
+        let mut shared: HashMap<(usize, u64), usize> = HashMap::new();
+        for total in totals.values() {
+            *shared.entry(*total).or_default() += 1;
+        }
+        let candidates: HashMap<&Path, (usize, u64)> = totals
+            .iter()
+            .filter(|(_, total)| shared[*total] > 1)
+            .map(|(folder, total)| (folder.as_path(), *total))
+            .collect();
+
+        let mut contents: HashMap<&Path, Vec<String>> = HashMap::new();
+        for (path, _) in &files {
+            let candidate_folders: Vec<&Path> =
+                folders_above(root, path).filter(|folder| candidates.contains_key(folder)).collect();
+            if candidate_folders.is_empty() {
+                continue;
+            }
+            self.check_cancelled()?;
+            let hash = self.hash_file(path)?;
+            for folder in candidate_folders {
+                contents.entry(folder).or_default().push(hash.clone());
+            }
+        }
+
+        let mut by_signature: BTreeMap<(Vec<String>, u64), Vec<PathBuf>> = BTreeMap::new();
+        for (folder, mut hashes) in contents {
+            hashes.sort();
+            by_signature.entry((hashes, candidates[folder].1)).or_default().push(folder.to_path_buf());
+        }
+        let mut groups = Vec::new();
+        for ((_, bytes), folders) in by_signature {
+            // A folder whose only content is another member (`old/` around `old/photos/`)
+            // isn't a copy of its own
+            let innermost: Vec<PathBuf> = folders
+                .iter()
+                .filter(|outer| !folders.iter().any(|inner| inner != *outer && inner.starts_with(outer)))
+                .cloned()
+                .collect();
+            if innermost.len() > 1 {
+                groups.push(group(innermost, bytes));
+            }
+        }
+        sort_groups(&mut groups);
+
+        let mut reported: Vec<PathBuf> = Vec::new();
+        groups.retain(|group| {
+            let nested = group.paths.iter().all(|path| reported.iter().any(|outer| path.starts_with(outer)));
+            if !nested {
+                reported.extend(group.paths.iter().cloned());
+            }
+            !nested
+        });
+        Ok(groups)
+    }
+
+    /// Delete every copy but the canonical one, after checking that none of them changed
+    /// since they were found
+    pub fn remove_duplicates(&self, group: &DuplicateGroup) -> Result<()> {
+        let fingerprint = |path: &Path| -> Result<Vec<String>> {
+            let mut hashes = Vec::new();
+            if path.is_dir() {
+                for (file, _) in scan(path)? {
+                    hashes.push(self.hash_file(&file)?);
+                }
+            } else {
+                hashes.push(self.hash_file(path)?);
+            }
+            hashes.sort();
+            Ok(hashes)
+        };
+
+        let expected = fingerprint(group.canonical())?;
+        for extra in group.extras() {
+            if fingerprint(extra)? != expected {
+                return Err(OrganizerError::plan(extra, format!("{} changed since it was found; nothing was removed", extra.display())));
+            }
+        }
+        for extra in group.extras() {
+            let result = if extra.is_dir() { fs::remove_dir_all(extra) } else { fs::remove_file(extra) };
+            result.io_context(extra, format!("Failed to remove {}", extra.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Every file below `root` with its size, leaving out the tool's own metadata
+fn scan(root: &Path) -> Result<Vec<(PathBuf, u64)>> {
+    journal::refuse_metadata_dir(root)?;
+    let mut files = Vec::new();
+    for entry in WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != METADATA_DIR)
+    {
+        let entry = entry.map_err(|e| OrganizerError::walk(root, e))?;
+        if entry.file_type().is_file() {
+            let size = entry.metadata().map_err(|e| OrganizerError::walk(root, e))?.len();
+            files.push((entry.into_path(), size));
+        }
+    }
+    Ok(files)
+}
+
+/// The folders between `root` (exclusive) and a file
+fn folders_above<'a>(root: &'a Path, file: &'a Path) -> impl Iterator<Item = &'a Path> {
+    file.ancestors().skip(1).take_while(move |folder| *folder != root && folder.starts_with(root))
+}
+
+/// Canonical copy first: fewest path components, then the shortest and alphabetically first
+fn group(mut paths: Vec<PathBuf>, bytes: u64) -> DuplicateGroup {
+    paths.sort_by_key(|path| (path.components().count(), path.as_os_str().len(), path.clone()));
+    DuplicateGroup { paths, bytes }
+}
+
+fn sort_groups(groups: &mut [DuplicateGroup]) {
+    groups.sort_by(|a, b| b.reclaimable().cmp(&a.reclaimable()).then_with(|| a.paths.cmp(&b.paths)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::config::Config;
+    use super::super::OrganizeOptions;
+
+    fn write(root: &Path, name: &str, contents: &str) {
+        let path = root.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for folder in ["photos", "photos (1)", "old/photos-copy"] {
+            write(root, &format!("{}/a.jpg", folder), "aaaa");
+            write(root, &format!("{}/trip/b.jpg", folder), "bb");
+        }
+        // Same totals but different content, and a renamed file that still matches
+        write(root, "other/a.jpg", "cccc");
+        write(root, "other/trip/b.jpg", "dd");
+        fs::rename(root.join("photos (1)/a.jpg"), root.join("photos (1)/renamed.jpg")).unwrap();
+        write(root, ".file-organizer/history/x.json", "aaaa");
+        let organizer = FileOrganizer::with_config(OrganizeOptions::default(), &Config::default()).unwrap();
+
+        let groups = organizer.duplicate_folders(root).unwrap();
+        assert_eq!(groups.len(), 1, "{:?}", groups);
+        let group = &groups[0];
+        assert_eq!(group.canonical(), root.join("photos"));
+        assert_eq!(group.extras(), [root.join("photos (1)"), root.join("old/photos-copy")]);
+        assert_eq!(group.reclaimable(), 12);
+
+        let files = organizer.duplicate_files(root).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].paths.len(), 3);
+
+        organizer.remove_duplicates(group).unwrap();
+        assert!(root.join("photos/trip/b.jpg").exists());
+        assert!(!root.join("photos (1)").exists());
+        assert!(!root.join("old/photos-copy").exists());
+        assert!(organizer.duplicate_folders(root).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_big_files_are_told_apart_by_their_ends() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let original = "x".repeat(3 * hashing::QUICK_HASH_BYTES as usize);
+        let changed_at = |at: usize| {
+            let mut contents = original.clone();
+            contents.replace_range(at..at + 1, "y");
+            contents
+        };
+        write(root, "a.mkv", &original);
+        write(root, "copy.mkv", &original);
+        write(root, "middle.mkv", &changed_at(original.len() / 2));
+        write(root, "end.mkv", &changed_at(original.len() - 1));
+        let organizer = FileOrganizer::with_config(OrganizeOptions::default(), &Config::default()).unwrap();
+
+        let groups = organizer.duplicate_files(root).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].paths, [root.join("a.mkv"), root.join("copy.mkv")]);
+        // end.mkv never needed a full hash
+        assert_eq!(organizer.hash_cache_stats().misses(), 3);
+    }
+
+    #[test]
+    fn test_changed_duplicates_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(root, "a/file.txt", "same");
+        write(root, "b/file.txt", "same");
+        let organizer = FileOrganizer::with_config(OrganizeOptions::default(), &Config::default()).unwrap();
+        let groups = organizer.duplicate_folders(root).unwrap();
+        assert_eq!(groups.len(), 1);
+
+        write(root, "b/file.txt", "diff");
+        assert!(organizer.remove_duplicates(&groups[0]).is_err());
+        assert!(root.join("b/file.txt").exists());
+    }
+}
@@ -0,0 +1,56 @@
+//! `--durable`: flushing each move to disk before the next, so a power loss right after a big
+//! run can't lose files or the undo history. A rename only reaches the disk with its
+//! directories, so those are flushed too: the destination's, and the source's it left.
+//!
+//! Directories can't be opened for flushing on Windows, where NTFS journals them anyway, so
+//! only files are flushed there.
+
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+
+/// Flush a moved (or copied) file, and the directories the move changed
+pub fn sync_move(source: &Path, destination: &Path) -> io::Result<()> {
+    sync_file(destination)?;
+    let from = source.parent();
+    let to = destination.parent();
+    if let Some(to) = to {
+        sync_dir(to)?;
+    }
+    match from {
+        Some(from) if Some(from) != to => sync_dir(from),
+        _ => Ok(()),
+    }
+}
+
+pub fn sync_file(path: &Path) -> io::Result<()> {
+    // Windows only flushes files opened for writing
+    OpenOptions::new().read(true).write(cfg!(windows)).open(path)?.sync_all()
+}
+
+/// Flush a directory's entries, so the files just put in it (or taken out) stay that way
+#[cfg(unix)]
+pub fn sync_dir(dir: &Path) -> io::Result<()> {
+    std::fs::File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+pub fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_move() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("Images")).unwrap();
+        std::fs::write(dir.path().join("Images/a.jpg"), b"jpg").unwrap();
+
+        sync_move(&dir.path().join("a.jpg"), &dir.path().join("Images/a.jpg")).unwrap();
+        let error = sync_move(&dir.path().join("a.jpg"), &dir.path().join("missing.jpg")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
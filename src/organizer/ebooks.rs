@@ -0,0 +1,170 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use super::error::{IoContext, OrganizerError, Result};
+
+/// Author and title of an ebook, used to build `Books/{author}/{title}/` folders
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookMetadata {
+    pub author: String,
+    pub title: String,
+}
+
+impl BookMetadata {
+    /// Read metadata for an ebook, preferring embedded EPUB metadata
+    /// and falling back to parsing the file name
+    pub fn read(file_path: &Path) -> Self {
+        let is_epub = file_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.eq_ignore_ascii_case("epub"))
+            .unwrap_or(false);
+
+        if is_epub {
+            match read_epub_metadata(file_path) {
+                Ok(Some(metadata)) => return metadata,
+                Ok(None) => {}
+                Err(e) => log::debug!("Could not read EPUB metadata from {:?}: {}", file_path, e),
+            }
+        }
+        Self::from_file_name(file_path)
+    }
+
+    /// Metadata from the file name alone, without opening the book
+    pub fn from_file_name(file_path: &Path) -> Self {
+        let stem = file_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Self::from_file_stem(&stem)
+    }
+
+    /// Parse `Author - Title` style file names
+    pub fn from_file_stem(stem: &str) -> Self {
+        match stem.split_once(" - ") {
+            Some((author, title)) if !author.trim().is_empty() && !title.trim().is_empty() => Self {
+                author: author.trim().to_string(),
+                title: title.trim().to_string(),
+            },
+            _ => Self {
+                author: "Unknown Author".to_string(),
+                title: stem.trim().to_string(),
+            },
+        }
+    }
+}
+
+/// Read author and title from the OPF package document inside an EPUB
+fn read_epub_metadata(file_path: &Path) -> Result<Option<BookMetadata>> {
+    let file = File::open(file_path).scan_context(file_path, "Failed to open EPUB")?;
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| OrganizerError::scan(file_path, "EPUB is not a valid zip archive", e.into()))?;
+
+    let container = read_entry(&mut archive, file_path, "META-INF/container.xml")?;
+    let opf_path = match parse_container(&container) {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+
+    let opf = read_entry(&mut archive, file_path, &opf_path)?;
+    Ok(parse_opf(&opf))
+}
+
+fn read_entry(archive: &mut zip::ZipArchive<File>, file_path: &Path, name: &str) -> Result<String> {
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|e| OrganizerError::scan(file_path, format!("EPUB is missing {}", name), e.into()))?;
+    let mut contents = String::new();
+    entry
+        .read_to_string(&mut contents)
+        .scan_context(file_path, format!("Failed to read {} from EPUB", name))?;
+    Ok(contents)
+}
+
+/// Find the OPF path declared by `META-INF/container.xml`
+fn parse_container(xml: &str) -> Option<String> {
+    let rootfile = &xml[xml.find("<rootfile")?..];
+    let attr_start = rootfile.find("full-path=")? + "full-path=".len();
+    let quote = rootfile[attr_start..].chars().next()?;
+    let value = &rootfile[attr_start + 1..];
+    let end = value.find(quote)?;
+    Some(value[..end].to_string())
+}
+
+/// Extract the first creator and title from an OPF package document
+fn parse_opf(xml: &str) -> Option<BookMetadata> {
+    let title = extract_element(xml, "dc:title")?;
+    let author = extract_element(xml, "dc:creator").unwrap_or_else(|| "Unknown Author".to_string());
+    Some(BookMetadata { author, title })
+}
+
+fn extract_element(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}", tag);
+    let mut search_from = 0;
+
+    while let Some(offset) = xml[search_from..].find(&open) {
+        let start = search_from + offset + open.len();
+        search_from = start;
+
+        // Make sure we matched `<dc:title>` or `<dc:title ...>`, not `<dc:titles>`
+        match xml[start..].chars().next() {
+            Some('>') | Some(' ') | Some('\t') | Some('\n') | Some('\r') => {}
+            _ => continue,
+        }
+
+        let content_start = start + xml[start..].find('>')? + 1;
+        let content_end = content_start + xml[content_start..].find(&format!("</{}>", tag))?;
+        let value = decode_entities(xml[content_start..content_end].trim());
+        if !value.is_empty() {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filename_fallback() {
+        assert_eq!(
+            BookMetadata::from_file_stem("Ursula K. Le Guin - The Dispossessed"),
+            BookMetadata {
+                author: "Ursula K. Le Guin".to_string(),
+                title: "The Dispossessed".to_string(),
+            }
+        );
+        assert_eq!(BookMetadata::from_file_stem("notes").author, "Unknown Author");
+    }
+
+    #[test]
+    fn test_parse_epub_metadata() {
+        let container = r#"<container><rootfiles>
+            <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+        </rootfiles></container>"#;
+        assert_eq!(parse_container(container), Some("OEBPS/content.opf".to_string()));
+
+        let opf = r#"<package><metadata>
+            <dc:title id="t1">Dune &amp; Beyond</dc:title>
+            <dc:creator opf:role="aut">Frank Herbert</dc:creator>
+        </metadata></package>"#;
+        assert_eq!(
+            parse_opf(opf),
+            Some(BookMetadata {
+                author: "Frank Herbert".to_string(),
+                title: "Dune & Beyond".to_string(),
+            })
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! The error type of the library. Embedders can match on what went wrong; the CLI prints the
+//! message followed by its causes.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::{FileOperation, OperationType};
+
+/// The result of everything in the library that can fail
+pub type Result<T, E = OrganizerError> = std::result::Result<T, E>;
+
+/// The cause kept by the errors that can come from anywhere, such as an invalid config
+pub type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Why an organization, undo or other library call failed
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum OrganizerError {
+    /// The target directory, or a file in it, couldn't be read
+    Scan { path: PathBuf, reason: String, source: io::Error },
+    /// What was asked for can't be planned: filters that rule out every file, too little free
+    /// space, a rule with no date for its template, ...
+    Plan { path: PathBuf, reason: String },
+    /// A file operation failed on disk and stopped the run
+    Execution { op: FileOperation, io_kind: io::ErrorKind, source: io::Error },
+    /// Something else on disk couldn't be changed, such as a duplicate removed or a file pinned
+    Io { path: PathBuf, reason: String, source: io::Error },
+    /// The config, or a rule, template or plugin it sets up, is invalid
+    Config { reason: String, source: Option<BoxError> },
+    /// The history couldn't be read or written: journals, resume files, the hash cache, manifests
+    Journal { reason: String, source: Option<BoxError> },
+    /// The caller cancelled the run before it finished
+    Cancelled,
+}
+
+impl OrganizerError {
+    pub(crate) fn scan(path: &Path, reason: impl Into<String>, source: io::Error) -> Self {
+        OrganizerError::Scan { path: path.to_path_buf(), reason: reason.into(), source }
+    }
+
+    /// A failure walking the directory tree under `root`, about the entry it happened on
+    pub(crate) fn walk(root: &Path, error: walkdir::Error) -> Self {
+        let path = error.path().unwrap_or(root).to_path_buf();
+        let reason = format!("Failed to scan {:?}", path);
+        OrganizerError::Scan { path, reason, source: error.into() }
+    }
+
+    pub(crate) fn plan(path: &Path, reason: impl Into<String>) -> Self {
+        OrganizerError::Plan { path: path.to_path_buf(), reason: reason.into() }
+    }
+
+    pub(crate) fn execution(op: &FileOperation, source: io::Error) -> Self {
+        OrganizerError::Execution { op: op.clone(), io_kind: source.kind(), source }
+    }
+
+    pub(crate) fn io(path: &Path, reason: impl Into<String>, source: io::Error) -> Self {
+        OrganizerError::Io { path: path.to_path_buf(), reason: reason.into(), source }
+    }
+
+    pub(crate) fn config(reason: impl Into<String>) -> Self {
+        OrganizerError::Config { reason: reason.into(), source: None }
+    }
+
+    pub(crate) fn journal(reason: impl Into<String>) -> Self {
+        OrganizerError::Journal { reason: reason.into(), source: None }
+    }
+}
+
+/// `{}` is the message alone; `{:#}` adds the causes, as `message: cause: cause`
+impl fmt::Display for OrganizerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_message(f)?;
+        if f.alternate() {
+            let mut cause = self.source();
+            while let Some(error) = cause {
+                write!(f, ": {}", error)?;
+                cause = error.source();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OrganizerError {
+    fn fmt_message(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrganizerError::Scan { reason, .. }
+            | OrganizerError::Plan { reason, .. }
+            | OrganizerError::Io { reason, .. }
+            | OrganizerError::Config { reason, .. }
+            | OrganizerError::Journal { reason, .. } => write!(f, "{}", reason),
+            OrganizerError::Execution { op, .. } => {
+                let verb = match op.operation_type {
+                    OperationType::Move => "move",
+                    OperationType::Copy => "copy",
+                };
+                write!(f, "Failed to {} {:?} to {:?}", verb, op.source, op.destination)
+            }
+            OrganizerError::Cancelled => write!(f, "Organization cancelled"),
+        }
+    }
+}
+
+impl Error for OrganizerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            OrganizerError::Scan { source, .. }
+            | OrganizerError::Execution { source, .. }
+            | OrganizerError::Io { source, .. } => Some(source),
+            OrganizerError::Config { source, .. } | OrganizerError::Journal { source, .. } => {
+                source.as_deref().map(|source| source as &(dyn Error + 'static))
+            }
+            OrganizerError::Plan { .. } | OrganizerError::Cancelled => None,
+        }
+    }
+}
+
+/// Say what was being done when an I/O call failed, the way anyhow's `context` does
+pub(crate) trait IoContext<T> {
+    /// Fail with [`OrganizerError::Scan`]: reading `path` didn't work
+    fn scan_context(self, path: &Path, reason: impl Into<String>) -> Result<T>;
+    /// Fail with [`OrganizerError::Io`]: changing `path` didn't work
+    fn io_context(self, path: &Path, reason: impl Into<String>) -> Result<T>;
+}
+
+impl<T> IoContext<T> for io::Result<T> {
+    fn scan_context(self, path: &Path, reason: impl Into<String>) -> Result<T> {
+        self.map_err(|source| OrganizerError::scan(path, reason, source))
+    }
+
+    fn io_context(self, path: &Path, reason: impl Into<String>) -> Result<T> {
+        self.map_err(|source| OrganizerError::io(path, reason, source))
+    }
+}
+
+/// Wrap any failure as a config or history error that says what was being done
+pub(crate) trait Context<T> {
+    fn config_context(self, reason: impl Into<String>) -> Result<T>;
+    fn journal_context(self, reason: impl Into<String>) -> Result<T>;
+}
+
+impl<T, E: Into<BoxError>> Context<T> for std::result::Result<T, E> {
+    fn config_context(self, reason: impl Into<String>) -> Result<T> {
+        self.map_err(|source| OrganizerError::Config { reason: reason.into(), source: Some(source.into()) })
+    }
+
+    fn journal_context(self, reason: impl Into<String>) -> Result<T> {
+        self.map_err(|source| OrganizerError::Journal { reason: reason.into(), source: Some(source.into()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_and_causes() {
+        let op = FileOperation {
+            source: PathBuf::from("a.jpg"),
+            destination: PathBuf::from("Images/a.jpg"),
+            operation_type: OperationType::Move,
+            size: 0,
+        };
+        let error = OrganizerError::execution(&op, io::Error::from(io::ErrorKind::PermissionDenied));
+        assert_eq!(error.to_string(), "Failed to move \"a.jpg\" to \"Images/a.jpg\"");
+        assert!(matches!(error, OrganizerError::Execution { io_kind: io::ErrorKind::PermissionDenied, .. }));
+
+        let parsed: Result<u32> = "x".parse::<u32>().config_context("Invalid size");
+        let error = parsed.unwrap_err();
+        assert_eq!(error.to_string(), "Invalid size");
+        assert_eq!(error.source().unwrap().to_string(), "invalid digit found in string");
+
+        let error = Err::<(), _>(error).journal_context("Failed to read history").unwrap_err();
+        assert!(matches!(error, OrganizerError::Journal { .. }));
+        assert_eq!(error.source().unwrap().source().unwrap().to_string(), "invalid digit found in string");
+        assert_eq!(format!("{:#}", error), "Failed to read history: Invalid size: invalid digit found in string");
+    }
+}
@@ -0,0 +1,44 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use chrono::{NaiveDate, NaiveDateTime};
+
+/// Read the time a photo was taken from its EXIF data.
+///
+/// Prefers `DateTimeOriginal`, then `DateTimeDigitized` and `DateTime`. Returns `None` for
+/// files without (valid) EXIF timestamps.
+pub fn exif_datetime(file_path: &Path) -> Option<NaiveDateTime> {
+    let file = File::open(file_path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+
+    [exif::Tag::DateTimeOriginal, exif::Tag::DateTimeDigitized, exif::Tag::DateTime]
+        .into_iter()
+        .filter_map(|tag| exif.get_field(tag, exif::In::PRIMARY))
+        .find_map(|field| match &field.value {
+            exif::Value::Ascii(values) => values.first().and_then(|raw| parse_exif_datetime(raw)),
+            _ => None,
+        })
+}
+
+/// Parse EXIF's `YYYY:MM:DD HH:MM:SS` format
+fn parse_exif_datetime(raw: &[u8]) -> Option<NaiveDateTime> {
+    let datetime = exif::DateTime::from_ascii(raw).ok()?;
+    NaiveDate::from_ymd_opt(datetime.year as i32, datetime.month as u32, datetime.day as u32)?
+        .and_hms_opt(datetime.hour as u32, datetime.minute as u32, datetime.second as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_exif_datetime() {
+        let parsed = parse_exif_datetime(b"2024:06:01 14:03:59").unwrap();
+        assert_eq!(parsed.to_string(), "2024-06-01 14:03:59");
+
+        assert_eq!(parse_exif_datetime(b"    :  :     :  :  "), None);
+        assert_eq!(parse_exif_datetime(b"2024:13:01 14:03:59"), None);
+    }
+}
@@ -0,0 +1,150 @@
+//! Files without an extension: `Makefile`, `LICENSE`, scripts with a `#!` line, downloads
+//! saved without one. `[categories] no_extension` picks what happens to them; whatever it
+//! is, a script with a shebang goes to Code unless they're skipped.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use super::config::NoExtension;
+use super::file_types::FileCategory;
+
+/// Where `no_extension = "folder"` puts files without an extension
+pub const NO_EXTENSION_FOLDER: &str = "❔ No Extension";
+
+/// How much of a file the checks read
+const SNIFF_BYTES: u64 = 512;
+
+/// Build files and the like, known by their whole name
+const CODE_NAMES: [&str; 9] = [
+    "makefile", "gnumakefile", "dockerfile", "containerfile", "rakefile", "gemfile",
+    "jenkinsfile", "vagrantfile", "justfile",
+];
+
+/// The category of a file without an extension, or `None` to leave it to the usual Other
+pub fn classify(policy: NoExtension, file_path: &Path) -> Option<FileCategory> {
+    if policy == NoExtension::Skip {
+        return None;
+    }
+    let head = read_head(file_path)?;
+    if head.starts_with(b"#!") {
+        return Some(FileCategory::Code);
+    }
+    if policy != NoExtension::Sniff {
+        return None;
+    }
+    let name = file_path.file_name()?.to_string_lossy().to_lowercase();
+    if CODE_NAMES.contains(&name.as_str()) {
+        return Some(FileCategory::Code);
+    }
+    sniff(&head)
+}
+
+/// [`classify`] by the name alone, without reading the file: only build files and the like
+/// are told apart, and only under `no_extension = "sniff"`
+pub fn classify_by_name(policy: NoExtension, file_path: &Path) -> Option<FileCategory> {
+    if policy != NoExtension::Sniff {
+        return None;
+    }
+    let name = file_path.file_name()?.to_string_lossy().to_lowercase();
+    CODE_NAMES.contains(&name.as_str()).then_some(FileCategory::Code)
+}
+
+fn read_head(file_path: &Path) -> Option<Vec<u8>> {
+    let mut head = Vec::new();
+    File::open(file_path).ok()?.take(SNIFF_BYTES).read_to_end(&mut head).ok()?;
+    Some(head)
+}
+
+/// Bytes a format has at an offset from the start of the file
+type Signature = &'static [(usize, &'static [u8])];
+
+/// Magic numbers of common formats. The first match wins, so `M4A ` audio comes before
+/// other `ftyp` (MP4, MOV) files.
+const MAGIC: &[(Signature, FileCategory)] = &[
+    (&[(0, b"\x89PNG")], FileCategory::Images),
+    (&[(0, b"\xff\xd8\xff")], FileCategory::Images),
+    (&[(0, b"GIF8")], FileCategory::Images),
+    (&[(0, b"II*\0")], FileCategory::Images),
+    (&[(0, b"MM\0*")], FileCategory::Images),
+    (&[(0, b"RIFF"), (8, b"WEBP")], FileCategory::Images),
+    (&[(0, b"%PDF")], FileCategory::Documents),
+    (&[(0, b"ID3")], FileCategory::Audio),
+    (&[(0, b"fLaC")], FileCategory::Audio),
+    (&[(0, b"OggS")], FileCategory::Audio),
+    (&[(0, b"RIFF"), (8, b"WAVE")], FileCategory::Audio),
+    (&[(4, b"ftypM4A")], FileCategory::Audio),
+    (&[(4, b"ftyp")], FileCategory::Videos),
+    (&[(0, b"\x1a\x45\xdf\xa3")], FileCategory::Videos),
+    (&[(0, b"RIFF"), (8, b"AVI ")], FileCategory::Videos),
+    (&[(0, b"PK\x03\x04")], FileCategory::Archives),
+    (&[(0, b"\x1f\x8b")], FileCategory::Archives),
+    (&[(0, b"7z\xbc\xaf\x27\x1c")], FileCategory::Archives),
+    (&[(0, b"Rar!")], FileCategory::Archives),
+    (&[(0, b"BZh")], FileCategory::Archives),
+    (&[(0, b"\xfd7zXZ\0")], FileCategory::Archives),
+    (&[(0, b"\x7fELF")], FileCategory::Executables),
+    (&[(0, b"MZ")], FileCategory::Executables),
+    (&[(0, b"\xfe\xed\xfa\xce")], FileCategory::Executables),
+    (&[(0, b"\xfe\xed\xfa\xcf")], FileCategory::Executables),
+    (&[(0, b"\xce\xfa\xed\xfe")], FileCategory::Executables),
+    (&[(0, b"\xcf\xfa\xed\xfe")], FileCategory::Executables),
+];
+
+/// What a file's first bytes say it is: a format with a known magic number, or plain text
+/// as a document
+fn sniff(head: &[u8]) -> Option<FileCategory> {
+    let at = |(offset, magic): &(usize, &[u8])| head.get(*offset..offset + magic.len()) == Some(*magic);
+    if let Some((_, category)) = MAGIC.iter().find(|(magic, _)| magic.iter().all(at)) {
+        return Some(category.clone());
+    }
+    (!head.is_empty() && !head.contains(&0) && is_utf8_prefix(head)).then_some(FileCategory::Documents)
+}
+
+/// Valid UTF-8, allowing for a character cut off at the end of what was read
+fn is_utf8_prefix(head: &[u8]) -> bool {
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(error) => error.error_len().is_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n"), Some(FileCategory::Images));
+        assert_eq!(sniff(b"%PDF-1.7"), Some(FileCategory::Documents));
+        assert_eq!(sniff(b"\0\0\0\x20ftypisom"), Some(FileCategory::Videos));
+        assert_eq!(sniff(b"\0\0\0\x20ftypM4A "), Some(FileCategory::Audio));
+        assert_eq!(sniff(b"PK\x03\x04"), Some(FileCategory::Archives));
+        assert_eq!(sniff(b"\x7fELF\x02\x01"), Some(FileCategory::Executables));
+        assert_eq!(sniff("MIT License\n\nCopyright © 2024".as_bytes()), Some(FileCategory::Documents));
+        assert_eq!(sniff(b"\x00\x01\x02\xff"), None);
+        assert_eq!(sniff(b""), None);
+    }
+
+    #[test]
+    fn test_classify() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, contents: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            path
+        };
+        let script = write("deploy", b"#!/bin/sh\necho hi\n");
+        let makefile = write("Makefile", b"all:\n\tcc main.c\n");
+        let photo = write("IMG_0042", b"\xff\xd8\xff\xe0");
+
+        for policy in [NoExtension::Other, NoExtension::Sniff, NoExtension::Folder] {
+            assert_eq!(classify(policy, &script), Some(FileCategory::Code));
+        }
+        assert_eq!(classify(NoExtension::Skip, &script), None);
+        assert_eq!(classify(NoExtension::Other, &photo), None);
+        assert_eq!(classify(NoExtension::Sniff, &photo), Some(FileCategory::Images));
+        assert_eq!(classify(NoExtension::Sniff, &makefile), Some(FileCategory::Code));
+        assert_eq!(classify(NoExtension::Sniff, &dir.path().join("missing")), None);
+    }
+}
@@ -0,0 +1,222 @@
+//! Files that have finished being written, for `watch`. On Linux inotify reports a file when
+//! the program writing it closes it (`IN_CLOSE_WRITE`) or renames it into place
+//! (`IN_MOVED_TO`, as browsers and torrent clients do with finished downloads), so files are
+//! never moved while they're still being written. Elsewhere, or where inotify can't be set
+//! up (say, the watch limit `fs.inotify.max_user_watches` is reached), the whole directory is
+//! rescanned every so often.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// What was written while waiting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Written {
+    /// These files, and nothing else
+    Files(Vec<PathBuf>),
+    /// Unknown (no inotify, or it lost events): the whole directory needs scanning
+    Anything,
+}
+
+pub struct FileEvents {
+    #[cfg(target_os = "linux")]
+    inotify: Option<linux::Inotify>,
+    /// How often to rescan without inotify
+    interval: Duration,
+    next_rescan: Instant,
+}
+
+impl FileEvents {
+    /// Watch `dir`, and its subdirectories if `recursive`, rescanning every `interval` where
+    /// files can't be reported as they're written
+    pub fn new(dir: &Path, recursive: bool, interval: Duration) -> Self {
+        let next_rescan = Instant::now() + interval;
+        #[cfg(target_os = "linux")]
+        {
+            let inotify = linux::Inotify::new(dir, recursive)
+                .inspect_err(|e| log::warn!("Can't watch {} with inotify ({}), so it's rescanned every so often", dir.display(), e))
+                .ok();
+            Self { inotify, interval, next_rescan }
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (dir, recursive);
+            Self { interval, next_rescan }
+        }
+    }
+
+    /// Whether files are reported as they're written, rather than every wait being
+    /// [`Written::Anything`]
+    pub fn reports_files(&self) -> bool {
+        #[cfg(target_os = "linux")]
+        return self.inotify.is_some();
+        #[cfg(not(target_os = "linux"))]
+        false
+    }
+
+    /// Wait up to `timeout` for files to be written, returning as soon as any are. Without
+    /// inotify that's [`Written::Anything`] once the rescan interval is up, and no files
+    /// before.
+    pub fn wait(&mut self, timeout: Duration) -> Written {
+        #[cfg(target_os = "linux")]
+        if let Some(inotify) = &mut self.inotify {
+            match inotify.wait(timeout) {
+                Ok(written) => return written,
+                Err(e) => {
+                    log::warn!("inotify stopped working ({}), so the directory is rescanned every so often", e);
+                    self.inotify = None;
+                }
+            }
+        }
+        std::thread::sleep(timeout.min(self.next_rescan.saturating_duration_since(Instant::now())));
+        if Instant::now() < self.next_rescan {
+            return Written::Files(Vec::new());
+        }
+        self.next_rescan = Instant::now() + self.interval;
+        Written::Anything
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::collections::HashMap;
+    use std::ffi::{CString, OsStr};
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    use walkdir::WalkDir;
+
+    use super::Written;
+    use crate::organizer::journal;
+
+    const EVENTS: u32 = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE;
+
+    pub(super) struct Inotify {
+        fd: OwnedFd,
+        recursive: bool,
+        /// Watched directory of each watch descriptor
+        dirs: HashMap<i32, PathBuf>,
+    }
+
+    impl Inotify {
+        pub(super) fn new(dir: &Path, recursive: bool) -> io::Result<Self> {
+            // SAFETY: a plain syscall; the descriptor is owned from here on
+            let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: `fd` was just opened and nothing else owns it
+            let mut inotify = Self { fd: unsafe { OwnedFd::from_raw_fd(fd) }, recursive, dirs: HashMap::new() };
+            inotify.add_tree(dir)?;
+            Ok(inotify)
+        }
+
+        /// Watch `dir` and, when recursive, every directory below it except the tool's own
+        fn add_tree(&mut self, dir: &Path) -> io::Result<()> {
+            if !self.recursive {
+                return self.add(dir);
+            }
+            let walker = WalkDir::new(dir).into_iter().filter_entry(|entry| entry.file_name() != journal::METADATA_DIR);
+            for entry in walker.filter_map(Result::ok).filter(|entry| entry.file_type().is_dir()) {
+                self.add(entry.path())?;
+            }
+            Ok(())
+        }
+
+        fn add(&mut self, dir: &Path) -> io::Result<()> {
+            let path = CString::new(dir.as_os_str().as_bytes()).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+            // SAFETY: the path is NUL-terminated and outlives the call
+            let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), EVENTS) };
+            if wd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            self.dirs.insert(wd, dir.to_path_buf());
+            Ok(())
+        }
+
+        pub(super) fn wait(&mut self, timeout: Duration) -> io::Result<Written> {
+            let mut poll = libc::pollfd { fd: self.fd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+            let millis = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+            // SAFETY: polls the one descriptor given
+            if unsafe { libc::poll(&mut poll, 1, millis) } < 0 {
+                let error = io::Error::last_os_error();
+                return if error.kind() == io::ErrorKind::Interrupted { Ok(Written::Files(Vec::new())) } else { Err(error) };
+            }
+
+            let mut files = Vec::new();
+            let mut buffer = vec![0u8; 64 * 1024];
+            loop {
+                // SAFETY: reads into a buffer of the length given
+                let read = unsafe { libc::read(self.fd.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len()) };
+                if read < 0 {
+                    let error = io::Error::last_os_error();
+                    if error.kind() == io::ErrorKind::WouldBlock {
+                        break;
+                    }
+                    return Err(error);
+                }
+                let mut offset = 0;
+                while offset + size_of::<libc::inotify_event>() <= read as usize {
+                    // SAFETY: the kernel writes whole events, each followed by `len` bytes of name
+                    let event = unsafe { buffer.as_ptr().add(offset).cast::<libc::inotify_event>().read_unaligned() };
+                    let name_start = offset + size_of::<libc::inotify_event>();
+                    let name = &buffer[name_start..name_start + event.len as usize];
+                    offset = name_start + event.len as usize;
+
+                    if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                        return Ok(Written::Anything);
+                    }
+                    if event.mask & libc::IN_IGNORED != 0 {
+                        self.dirs.remove(&event.wd);
+                        continue;
+                    }
+                    let Some(dir) = self.dirs.get(&event.wd) else {
+                        continue;
+                    };
+                    // The name is NUL-padded
+                    let name = OsStr::from_bytes(name.split(|&byte| byte == 0).next().unwrap_or_default());
+                    let path = dir.join(name);
+                    if event.mask & libc::IN_ISDIR != 0 {
+                        // New folders get watched too, and whatever landed in them before that
+                        // is picked up by a full scan
+                        if self.recursive && name != journal::METADATA_DIR {
+                            self.add_tree(&path)?;
+                            return Ok(Written::Anything);
+                        }
+                    } else if event.mask & (libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO) != 0 {
+                        files.push(path);
+                    }
+                }
+            }
+            files.sort();
+            files.dedup();
+            Ok(Written::Files(files))
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    #[test]
+    fn test_files_reported_once_closed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut events = FileEvents::new(dir.path(), false, Duration::from_secs(30));
+
+        let path = dir.path().join("download.iso");
+        let mut file = fs::File::create(&path).unwrap();
+        file.write_all(b"first half").unwrap();
+        assert_eq!(events.wait(Duration::from_millis(50)), Written::Files(Vec::new()));
+
+        drop(file);
+        fs::write(dir.path().join("partial.crdownload"), b"done").unwrap();
+        fs::rename(dir.path().join("partial.crdownload"), dir.path().join("report.pdf")).unwrap();
+        let expected = vec![path, dir.path().join("partial.crdownload"), dir.path().join("report.pdf")];
+        assert_eq!(events.wait(Duration::from_secs(5)), Written::Files(expected));
+    }
+}
@@ -1,8 +1,17 @@
 use std::collections::HashMap;
 use std::path::Path;
 
+use super::ambiguous;
+use super::extensionless;
+use super::config::{CategoryConfig, FolderLanguage, NoExtension, SizeBucketConfig, StructuredText};
+use super::error::{OrganizerError, Result};
+use super::multipart;
+use super::sanitize_folder_name;
+use super::syncs;
+use super::units::ByteSize;
+
 /// File categories for organization
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum FileCategory {
     Images,
     Documents,
@@ -14,10 +23,53 @@ pub enum FileCategory {
     Presentations,
     Executables,
     Fonts,
+    Ebooks,
+    Installers,
+    Models3D,
+    Design,
+    Web,
+    Data,
     Other,
 }
 
 impl FileCategory {
+    /// Every category, in display order
+    pub fn all() -> &'static [FileCategory] {
+        &[
+            FileCategory::Images,
+            FileCategory::Documents,
+            FileCategory::Videos,
+            FileCategory::Audio,
+            FileCategory::Archives,
+            FileCategory::Code,
+            FileCategory::Spreadsheets,
+            FileCategory::Presentations,
+            FileCategory::Executables,
+            FileCategory::Fonts,
+            FileCategory::Ebooks,
+            FileCategory::Installers,
+            FileCategory::Models3D,
+            FileCategory::Design,
+            FileCategory::Web,
+            FileCategory::Data,
+            FileCategory::Other,
+        ]
+    }
+
+    /// Look up a category by its folder name (case-insensitive), e.g. `images` or `Books`
+    pub fn from_name(name: &str) -> Option<FileCategory> {
+        let name = name.trim();
+        Self::all()
+            .iter()
+            .find(|category| category.folder_name().eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    /// Folder this category is organized into, e.g. `🖼️ Images`
+    pub fn folder(&self) -> String {
+        format!("{} {}", self.emoji(), self.folder_name())
+    }
+
     /// Get the folder name for this category
     pub fn folder_name(&self) -> &'static str {
         match self {
@@ -31,6 +83,12 @@ impl FileCategory {
             FileCategory::Presentations => "Presentations",
             FileCategory::Executables => "Executables",
             FileCategory::Fonts => "Fonts",
+            FileCategory::Ebooks => "Books",
+            FileCategory::Installers => "Installers",
+            FileCategory::Models3D => "3D & CAD",
+            FileCategory::Design => "Design",
+            FileCategory::Web => "Web",
+            FileCategory::Data => "Data",
             FileCategory::Other => "Other",
         }
     }
@@ -48,23 +106,168 @@ impl FileCategory {
             FileCategory::Presentations => "📈",
             FileCategory::Executables => "⚙️",
             FileCategory::Fonts => "🔤",
+            FileCategory::Ebooks => "📚",
+            FileCategory::Installers => "💿",
+            FileCategory::Models3D => "🧊",
+            FileCategory::Design => "🎨",
+            FileCategory::Web => "🌐",
+            FileCategory::Data => "🗄️",
             FileCategory::Other => "📂",
         }
     }
 }
 
+/// Parses folder names like [`FileCategory::from_name`], and also the variant names
+/// (`Ebooks`, `Models3D`) for folders whose names are awkward to type
+impl std::str::FromStr for FileCategory {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::from_name(name)
+            .or_else(|| {
+                Self::all()
+                    .iter()
+                    .find(|category| format!("{:?}", category).eq_ignore_ascii_case(name.trim()))
+                    .cloned()
+            })
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::all().iter().map(|category| category.folder_name()).collect();
+                format!("unknown category '{}' (expected one of: {})", name, names.join(", "))
+            })
+    }
+}
+
+/// Written as the folder name (`Books`), which reads back the same way
+impl serde::Serialize for FileCategory {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.folder_name())
+    }
+}
+
+/// Read from config keys like [`FromStr`](std::str::FromStr), e.g. `[categories.names]`
+impl<'de> serde::Deserialize<'de> for FileCategory {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Spanish folder names, for `[categories] language = "es"`
+fn spanish_name(category: &FileCategory) -> &'static str {
+    match category {
+        FileCategory::Images => "Imágenes",
+        FileCategory::Documents => "Documentos",
+        FileCategory::Videos => "Vídeos",
+        FileCategory::Audio => "Audio",
+        FileCategory::Archives => "Comprimidos",
+        FileCategory::Code => "Código",
+        FileCategory::Spreadsheets => "Hojas de cálculo",
+        FileCategory::Presentations => "Presentaciones",
+        FileCategory::Executables => "Ejecutables",
+        FileCategory::Fonts => "Fuentes",
+        FileCategory::Ebooks => "Libros",
+        FileCategory::Installers => "Instaladores",
+        FileCategory::Models3D => "3D y CAD",
+        FileCategory::Design => "Diseño",
+        FileCategory::Web => "Web",
+        FileCategory::Data => "Datos",
+        FileCategory::Other => "Otros",
+    }
+}
+
+/// The folder names categories are organized into: the built-in English ones, or the
+/// language and renames from `[categories]`.
+///
+/// Folders under the built-in names are still recognized, so a directory organized before
+/// the names changed isn't organized all over again.
+#[derive(Debug, Clone, Default)]
+pub struct CategoryNames {
+    names: HashMap<FileCategory, String>,
+}
+
+impl CategoryNames {
+    pub fn from_config(config: &CategoryConfig) -> Result<Self> {
+        let mut names = HashMap::new();
+        for category in FileCategory::all() {
+            let name = match (config.names.get(category), config.language) {
+                (Some(name), _) if name.trim().is_empty() => {
+                    return Err(OrganizerError::config(format!("[categories.names] gives {} an empty name", category.folder_name())))
+                }
+                (Some(name), _) => sanitize_folder_name(name.trim()),
+                (None, FolderLanguage::Spanish) => spanish_name(category).to_string(),
+                (None, FolderLanguage::English) => continue,
+            };
+            names.insert(category.clone(), name);
+        }
+        let names = Self { names };
+
+        let mut seen: HashMap<String, &FileCategory> = HashMap::new();
+        for category in FileCategory::all() {
+            if let Some(other) = seen.insert(names.name(category).to_lowercase(), category) {
+                return Err(OrganizerError::config(format!(
+                    "{} and {} are both named '{}'; category folders need different names",
+                    other.folder_name(),
+                    category.folder_name(),
+                    names.name(category)
+                )));
+            }
+        }
+        Ok(names)
+    }
+
+    /// Name of the category's folder without the emoji, e.g. `Imágenes`
+    pub fn name(&self, category: &FileCategory) -> &str {
+        self.names.get(category).map(String::as_str).unwrap_or(category.folder_name())
+    }
+
+    /// Folder the category is organized into, e.g. `🖼️ Imágenes`
+    pub fn folder(&self, category: &FileCategory) -> String {
+        format!("{} {}", category.emoji(), self.name(category))
+    }
+
+    /// The category with this name (without the emoji), current or built-in, ignoring case
+    pub fn by_name(&self, name: &str) -> Option<FileCategory> {
+        let name = name.trim().to_lowercase();
+        FileCategory::all()
+            .iter()
+            .find(|category| [self.name(category), category.folder_name()].into_iter().any(|known| known.to_lowercase() == name))
+            .cloned()
+    }
+
+    /// The category whose folder this is, under its current or its built-in name. Plain
+    /// `Images` folders are the user's own; only `🖼️ Images` counts.
+    pub fn category_of(&self, folder: &str) -> Option<FileCategory> {
+        let folder = folder.trim().to_lowercase();
+        FileCategory::all()
+            .iter()
+            .find(|category| folder.strip_prefix(category.emoji()).is_some_and(|name| self.by_name(name).as_ref() == Some(category)))
+            .cloned()
+    }
+}
+
 /// File type classifier
 pub struct FileTypeClassifier {
     extension_map: HashMap<String, FileCategory>,
+    /// Extensions the config gives a category, which are then classified without a look inside
+    settled: HashMap<String, FileCategory>,
+    names: CategoryNames,
+    no_extension: NoExtension,
+    /// Look at the first bytes of ambiguous extensions and files without one
+    content_checks: bool,
 }
 
 impl FileTypeClassifier {
     /// Create a new file type classifier with default mappings
     pub fn new() -> Self {
+        Self::with_config(&CategoryConfig::default())
+    }
+
+    /// Create a file type classifier using the category settings from the config
+    pub fn with_config(config: &CategoryConfig) -> Self {
         let mut extension_map = HashMap::new();
 
         // Images
-        let image_extensions = vec![
+        let image_extensions = [
             "jpg", "jpeg", "png", "gif", "bmp", "svg", "webp", "tiff", "tif",
             "ico", "raw", "cr2", "nef", "arw", "dng", "psd", "ai", "eps",
         ];
@@ -73,16 +276,29 @@ impl FileTypeClassifier {
         }
 
         // Documents
-        let document_extensions = vec![
+        let document_extensions = [
             "pdf", "doc", "docx", "txt", "md", "rtf", "odt", "pages", "tex",
-            "wps", "wpd", "html", "htm", "xml", "json", "yaml", "yml", "toml",
+            "wps", "wpd",
         ];
         for ext in document_extensions {
             extension_map.insert(ext.to_string(), FileCategory::Documents);
         }
 
+        // Web pages and structured text, wherever the config puts them
+        let (web_category, data_category) = match config.structured_text {
+            StructuredText::Documents => (FileCategory::Documents, FileCategory::Documents),
+            StructuredText::Code => (FileCategory::Code, FileCategory::Code),
+            StructuredText::Data => (FileCategory::Web, FileCategory::Data),
+        };
+        for ext in ["html", "htm", "xhtml"] {
+            extension_map.insert(ext.to_string(), web_category.clone());
+        }
+        for ext in ["xml", "json", "yaml", "yml", "toml"] {
+            extension_map.insert(ext.to_string(), data_category.clone());
+        }
+
         // Videos
-        let video_extensions = vec![
+        let video_extensions = [
             "mp4", "avi", "mkv", "mov", "wmv", "flv", "webm", "m4v", "3gp",
             "mpg", "mpeg", "ogv", "f4v", "asf", "rm", "rmvb", "vob",
         ];
@@ -91,7 +307,7 @@ impl FileTypeClassifier {
         }
 
         // Audio
-        let audio_extensions = vec![
+        let audio_extensions = [
             "mp3", "wav", "flac", "aac", "ogg", "wma", "m4a", "opus", "aiff",
             "au", "ra", "3ga", "amr", "awb", "dss", "dvf", "m4b", "m4p", "mmf",
         ];
@@ -99,17 +315,18 @@ impl FileTypeClassifier {
             extension_map.insert(ext.to_string(), FileCategory::Audio);
         }
 
-        // Archives
-        let archive_extensions = vec![
+        // Archives, compressed tarballs included
+        let archive_extensions = [
             "zip", "rar", "7z", "tar", "gz", "bz2", "xz", "z", "lzma", "cab",
             "iso", "dmg", "pkg", "deb", "rpm", "msi", "exe", "jar", "war",
+            "zst", "tar.gz", "tgz", "tar.bz2", "tbz2", "tar.xz", "txz", "tar.zst",
         ];
         for ext in archive_extensions {
             extension_map.insert(ext.to_string(), FileCategory::Archives);
         }
 
         // Code
-        let code_extensions = vec![
+        let code_extensions = [
             "rs", "py", "js", "ts", "java", "cpp", "c", "h", "hpp", "cs", "php",
             "rb", "go", "swift", "kt", "scala", "r", "m", "pl", "sh", "bash",
             "zsh", "fish", "ps1", "bat", "cmd", "sql", "css", "scss", "sass",
@@ -120,44 +337,121 @@ impl FileTypeClassifier {
         }
 
         // Spreadsheets
-        let spreadsheet_extensions = vec!["xlsx", "xls", "csv", "ods", "numbers", "tsv"];
+        let spreadsheet_extensions = ["xlsx", "xls", "csv", "ods", "numbers", "tsv"];
         for ext in spreadsheet_extensions {
             extension_map.insert(ext.to_string(), FileCategory::Spreadsheets);
         }
 
         // Presentations
-        let presentation_extensions = vec!["pptx", "ppt", "odp", "key"];
+        let presentation_extensions = ["pptx", "ppt", "odp", "key"];
         for ext in presentation_extensions {
             extension_map.insert(ext.to_string(), FileCategory::Presentations);
         }
 
         // Executables
-        let executable_extensions = vec!["exe", "msi", "app", "deb", "rpm", "pkg", "dmg"];
+        let executable_extensions = ["exe", "msi", "app", "deb", "rpm", "pkg", "dmg"];
         for ext in executable_extensions {
             extension_map.insert(ext.to_string(), FileCategory::Executables);
         }
 
         // Fonts
-        let font_extensions = vec!["ttf", "otf", "woff", "woff2", "eot"];
+        let font_extensions = ["ttf", "otf", "woff", "woff2", "eot"];
         for ext in font_extensions {
             extension_map.insert(ext.to_string(), FileCategory::Fonts);
         }
 
-        Self { extension_map }
+        // Ebooks
+        let ebook_extensions = ["epub", "mobi", "azw3", "fb2"];
+        for ext in ebook_extensions {
+            extension_map.insert(ext.to_string(), FileCategory::Ebooks);
+        }
+
+        // 3D models, CAD and 3D printing
+        let model_extensions = [
+            "stl", "obj", "fbx", "blend", "step", "stp", "3mf", "gcode", "gltf",
+            "glb", "dae", "ply", "dwg", "dxf", "iges", "igs",
+        ];
+        for ext in model_extensions {
+            extension_map.insert(ext.to_string(), FileCategory::Models3D);
+        }
+
+        // Design tool documents
+        let design_extensions = ["fig", "sketch", "xd", "afdesign"];
+        for ext in design_extensions {
+            extension_map.insert(ext.to_string(), FileCategory::Design);
+        }
+
+        // Installers and disk images are registered last so they take precedence
+        // over the overlapping Archives/Executables entries above
+        if config.split_installers {
+            let installer_extensions = [
+                "iso", "img", "dmg", "msi", "msix", "deb", "rpm", "pkg", "appimage",
+            ];
+            for ext in installer_extensions {
+                extension_map.insert(ext.to_string(), FileCategory::Installers);
+            }
+        }
+
+        let settled = config
+            .extensions
+            .iter()
+            .chain(&config.ambiguous)
+            .map(|(extension, category)| (extension.trim_start_matches('.').to_lowercase(), category.clone()))
+            .collect();
+        Self { extension_map, settled, names: CategoryNames::default(), no_extension: config.no_extension, content_checks: true }
+    }
+
+    /// Use these folder names instead of the built-in English ones
+    pub fn with_names(mut self, names: CategoryNames) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// Classify by name alone, never opening a file: ambiguous extensions go by their usual
+    /// category, and files without one are Other unless their name gives them away
+    pub fn without_content_checks(mut self) -> Self {
+        self.content_checks = false;
+        self
+    }
+
+    /// The configured category folder names
+    pub fn names(&self) -> &CategoryNames {
+        &self.names
     }
 
-    /// Classify a file based on its extension
+    /// What happens to files without an extension
+    pub fn no_extension(&self) -> NoExtension {
+        self.no_extension
+    }
+
+    /// Classify a file based on its extension, and for the few ambiguous extensions (`.ts`,
+    /// `.key`, `.md`) and files without one on its first bytes
     pub fn classify(&self, file_path: &Path) -> FileCategory {
-        if let Some(extension) = file_path.extension() {
-            if let Some(ext_str) = extension.to_str() {
-                let lowercase_ext = ext_str.to_lowercase();
-                return self.extension_map
-                    .get(&lowercase_ext)
-                    .cloned()
-                    .unwrap_or(FileCategory::Other);
+        // Volumes like `.r00` or `.7z.001` belong with the archive they split
+        if multipart::is_archive_part(file_path) {
+            return FileCategory::Archives;
+        }
+
+        if let Some(extension) = file_extension(file_path) {
+            if let Some(category) = self.settled.get(&extension) {
+                return category.clone();
             }
+            let sniffed = if self.content_checks { ambiguous::sniff(&extension, file_path) } else { None };
+            if let Some(category) = sniffed {
+                return category;
+            }
+            // A tarball compressed some other way goes by its compression (`tar.gpg` as `gpg`)
+            return self.extension_map
+                .get(&extension)
+                .or_else(|| extension.strip_prefix("tar.").and_then(|compression| self.extension_map.get(compression)))
+                .cloned()
+                .unwrap_or(FileCategory::Other);
         }
-        FileCategory::Other
+        let category = match self.content_checks {
+            true => extensionless::classify(self.no_extension, file_path),
+            false => extensionless::classify_by_name(self.no_extension, file_path),
+        };
+        category.unwrap_or(FileCategory::Other)
     }
 
     /// Get all supported extensions for a category
@@ -172,24 +466,48 @@ impl FileTypeClassifier {
     /// Check if file should be ignored (system files, hidden files, etc.)
     pub fn should_ignore(&self, file_path: &Path) -> bool {
         if let Some(file_name) = file_path.file_name() {
-            if let Some(name_str) = file_name.to_str() {
-                // Ignore hidden files (starting with .)
-                if name_str.starts_with('.') {
-                    return true;
-                }
-                
-                // Ignore common system files
-                let system_files = vec![
-                    "Thumbs.db", "Desktop.ini", ".DS_Store", "Icon\r",
-                    "desktop.ini", "thumbs.db", "ehthumbs.db",
-                ];
-                
-                if system_files.contains(&name_str) {
-                    return true;
-                }
+            // Ignore hidden files (starting with .)
+            if file_name.to_string_lossy().starts_with('.') {
+                return true;
             }
         }
-        false
+        self.is_system_file(file_path)
+    }
+
+    /// Check if file is one the OS or a file manager keeps for itself (`Thumbs.db`, `.DS_Store`),
+    /// or a sync client is still downloading (`.syncthing.report.pdf.tmp`)
+    pub fn is_system_file(&self, file_path: &Path) -> bool {
+        let system_files = [
+            "Thumbs.db", "Desktop.ini", ".DS_Store", "Icon\r",
+            "desktop.ini", "thumbs.db", "ehthumbs.db",
+        ];
+        file_path.file_name().is_some_and(|name| {
+            let name = name.to_string_lossy();
+            system_files.contains(&name.as_ref()) || syncs::temp_file_client(&name).is_some()
+        })
+    }
+}
+
+/// The lowercase extension of a file, `tar.gz` for compressed tarballs. A leading dot is part
+/// of the name, so `.gitignore` has no extension and `.eslintrc.json` is `json`. Names that
+/// aren't valid UTF-8 still have one: `caf\xe9.jpg` is a `jpg`.
+pub fn file_extension(file_path: &Path) -> Option<String> {
+    let name = file_path.file_name()?.to_string_lossy().to_lowercase();
+    let name = name.strip_prefix('.').unwrap_or(&name);
+    let mut parts = name.rsplitn(3, '.');
+    let (extension, before) = (parts.next()?, parts.next()?);
+    if extension.is_empty() {
+        return None;
+    }
+    match parts.next() {
+        Some(stem) if before == "tar" && !stem.is_empty() => Some(format!("tar.{}", extension)),
+        _ => Some(extension.to_string()),
+    }
+}
+
+impl Default for FileTypeClassifier {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -232,18 +550,65 @@ impl FileSizeCategory {
         const GB: u64 = 1_024 * MB;
 
         match size_bytes {
-            0..=1_048_576 => FileSizeCategory::Tiny,           // < 1 MB
-            1_048_577..=10_485_760 => FileSizeCategory::Small, // 1-10 MB
-            10_485_761..=104_857_600 => FileSizeCategory::Medium, // 10-100 MB
-            104_857_601..=1_073_741_824 => FileSizeCategory::Large, // 100 MB - 1 GB
-            _ => FileSizeCategory::Huge,                       // > 1 GB
+            s if s <= MB => FileSizeCategory::Tiny,         // < 1 MB
+            s if s <= 10 * MB => FileSizeCategory::Small,   // 1-10 MB
+            s if s <= 100 * MB => FileSizeCategory::Medium, // 10-100 MB
+            s if s <= GB => FileSizeCategory::Large,        // 100 MB - 1 GB
+            _ => FileSizeCategory::Huge,                    // > 1 GB
+        }
+    }
+}
+
+/// Size buckets from the config, used instead of [`FileSizeCategory`] when configured
+#[derive(Debug, Clone)]
+pub struct SizeBuckets {
+    /// Upper bound (inclusive) and folder name, smallest first; `None` is unbounded
+    buckets: Vec<(Option<u64>, String)>,
+}
+
+impl SizeBuckets {
+    /// Build from the config; `None` when no custom buckets are configured
+    pub fn from_config(config: &[SizeBucketConfig]) -> Option<Self> {
+        if config.is_empty() {
+            return None;
+        }
+
+        let mut buckets = Vec::with_capacity(config.len() + 1);
+        let mut previous: Option<ByteSize> = None;
+        for bucket in config {
+            let label = match (&bucket.name, previous, bucket.max) {
+                (Some(name), _, _) => sanitize_folder_name(name),
+                (None, None, Some(max)) => format!("Under {}", max),
+                (None, Some(min), Some(max)) => format!("{}-{}", min, max),
+                (None, Some(min), None) => format!("Over {}", min),
+                (None, None, None) => "All sizes".to_string(),
+            };
+            buckets.push((bucket.max.map(|max| max.0), label));
+            previous = bucket.max;
+        }
+
+        // Files bigger than every configured bucket still need somewhere to go
+        if let Some(max) = config.last().and_then(|last| last.max) {
+            buckets.push((None, format!("Over {}", max)));
         }
+
+        Some(Self { buckets })
+    }
+
+    /// Folder name for a file of the given size
+    pub fn folder(&self, size_bytes: u64) -> &str {
+        self.buckets
+            .iter()
+            .find(|(max, _)| max.is_none_or(|max| size_bytes <= max))
+            .map(|(_, label)| label.as_str())
+            .unwrap_or("Other")
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
     use std::path::PathBuf;
 
     #[test]
@@ -256,7 +621,125 @@ mod tests {
         assert_eq!(classifier.classify(&PathBuf::from("song.mp3")), FileCategory::Audio);
         assert_eq!(classifier.classify(&PathBuf::from("archive.zip")), FileCategory::Archives);
         assert_eq!(classifier.classify(&PathBuf::from("script.rs")), FileCategory::Code);
+        assert_eq!(classifier.classify(&PathBuf::from("novel.epub")), FileCategory::Ebooks);
         assert_eq!(classifier.classify(&PathBuf::from("unknown.xyz")), FileCategory::Other);
+        assert_eq!(classifier.classify(&PathBuf::from("ubuntu.iso")), FileCategory::Installers);
+        assert_eq!(classifier.classify(&PathBuf::from("setup.exe")), FileCategory::Executables);
+        assert_eq!(classifier.classify(&PathBuf::from("benchy.3mf")), FileCategory::Models3D);
+        assert_eq!(classifier.classify(&PathBuf::from("part.gcode")), FileCategory::Models3D);
+        assert_eq!(classifier.classify(&PathBuf::from("logo.afdesign")), FileCategory::Design);
+        assert_eq!(classifier.classify(&PathBuf::from("backup.7z.002")), FileCategory::Archives);
+    }
+
+    #[test]
+    fn test_installer_split_can_be_disabled() {
+        let classifier = FileTypeClassifier::with_config(&CategoryConfig {
+            split_installers: false,
+            ..CategoryConfig::default()
+        });
+
+        assert_eq!(classifier.classify(&PathBuf::from("ubuntu.iso")), FileCategory::Archives);
+        assert_eq!(classifier.classify(&PathBuf::from("app.dmg")), FileCategory::Executables);
+    }
+
+    #[test]
+    fn test_file_extension() {
+        let extension = |name: &str| file_extension(Path::new(name));
+        assert_eq!(extension("backup.tar.gz").as_deref(), Some("tar.gz"));
+        assert_eq!(extension("a.b.c.TAR.XZ").as_deref(), Some("tar.xz"));
+        assert_eq!(extension("photo.jpg").as_deref(), Some("jpg"));
+        assert_eq!(extension("dir/.eslintrc.json").as_deref(), Some("json"));
+        assert_eq!(extension(".tar.gz").as_deref(), Some("gz"));
+        assert_eq!(extension(".gitignore"), None);
+        assert_eq!(extension("Makefile"), None);
+        assert_eq!(extension("trailing."), None);
+
+        let classifier = FileTypeClassifier::new();
+        for name in ["a.b.c.tar.gz", "logs.tar.zst", "src.tgz", "old.tar.lzma", "data.tar"] {
+            assert_eq!(classifier.classify(Path::new(name)), FileCategory::Archives, "{}", name);
+        }
+        assert_eq!(classifier.classify(Path::new("secrets.tar.gpg")), FileCategory::Other);
+        assert_eq!(classifier.classify(Path::new(".gitignore")), FileCategory::Other);
+        assert_eq!(classifier.classify(Path::new(".prettierrc.yaml")), FileCategory::Documents);
+    }
+
+    #[test]
+    fn test_structured_text() {
+        let classify = |structured_text: StructuredText, file: &str| {
+            FileTypeClassifier::with_config(&CategoryConfig { structured_text, ..CategoryConfig::default() })
+                .classify(&PathBuf::from(file))
+        };
+        assert_eq!(classify(StructuredText::Documents, "page.html"), FileCategory::Documents);
+        assert_eq!(classify(StructuredText::Documents, "settings.json"), FileCategory::Documents);
+        assert_eq!(classify(StructuredText::Code, "Cargo.toml"), FileCategory::Code);
+        assert_eq!(classify(StructuredText::Data, "page.htm"), FileCategory::Web);
+        assert_eq!(classify(StructuredText::Data, "compose.yml"), FileCategory::Data);
+        assert_eq!(classify(StructuredText::Data, "notes.txt"), FileCategory::Documents);
+    }
+
+    #[test]
+    fn test_ambiguous_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut stream = vec![0u8; 4 * 188];
+        stream[0] = 0x47;
+        stream[188] = 0x47;
+        fs::write(dir.path().join("clip.ts"), &stream).unwrap();
+        fs::write(dir.path().join("app.ts"), "let x = 1;").unwrap();
+        let classifier = FileTypeClassifier::new();
+        assert_eq!(classifier.classify(&dir.path().join("clip.ts")), FileCategory::Videos);
+        assert_eq!(classifier.classify(&dir.path().join("app.ts")), FileCategory::Code);
+        // Without a file to look at, the extension decides
+        assert_eq!(classifier.classify(Path::new("file.ts")), FileCategory::Code);
+
+        let classifier = FileTypeClassifier::with_config(&CategoryConfig {
+            ambiguous: HashMap::from([(".TS".to_string(), FileCategory::Videos)]),
+            ..CategoryConfig::default()
+        });
+        assert_eq!(classifier.classify(&dir.path().join("app.ts")), FileCategory::Videos);
+
+        let classifier = FileTypeClassifier::with_config(&CategoryConfig {
+            extensions: HashMap::from([("kdbx".to_string(), FileCategory::Documents), ("md".to_string(), FileCategory::Code)]),
+            ..CategoryConfig::default()
+        });
+        assert_eq!(classifier.classify(Path::new("passwords.KDBX")), FileCategory::Documents);
+        assert_eq!(classifier.classify(Path::new("README.md")), FileCategory::Code);
+    }
+
+    #[test]
+    fn test_category_from_name() {
+        assert_eq!(FileCategory::from_name("images"), Some(FileCategory::Images));
+        assert_eq!(FileCategory::from_name("3D & CAD"), Some(FileCategory::Models3D));
+        assert_eq!(FileCategory::from_name("Pictures"), None);
+        assert_eq!("models3d".parse(), Ok(FileCategory::Models3D));
+        assert_eq!("Books".parse(), Ok(FileCategory::Ebooks));
+        assert!("Pictures".parse::<FileCategory>().unwrap_err().contains("expected one of: Images"));
+        for category in FileCategory::all() {
+            let json = serde_json::to_string(category).unwrap();
+            assert_eq!(&serde_json::from_str::<FileCategory>(&json).unwrap(), category);
+        }
+        assert_eq!(serde_json::to_string(&FileCategory::Ebooks).unwrap(), "\"Books\"");
+    }
+
+    #[test]
+    fn test_category_names() {
+        let default = CategoryNames::default();
+        assert_eq!(default.folder(&FileCategory::Images), "🖼️ Images");
+
+        let config = CategoryConfig {
+            language: FolderLanguage::Spanish,
+            names: HashMap::from([(FileCategory::Ebooks, "Novelas".to_string())]),
+            ..CategoryConfig::default()
+        };
+        let names = CategoryNames::from_config(&config).unwrap();
+        assert_eq!(names.folder(&FileCategory::Images), "🖼️ Imágenes");
+        assert_eq!(names.name(&FileCategory::Ebooks), "Novelas");
+        for folder in ["🖼️ Imágenes", "🖼️ imágenes", "🖼️ Images"] {
+            assert_eq!(names.category_of(folder), Some(FileCategory::Images), "{}", folder);
+        }
+        assert_eq!(names.category_of("📚 Books"), Some(FileCategory::Ebooks));
+        assert_eq!(names.category_of("📚 Libros"), None);
+        assert_eq!(names.category_of("Images"), None);
+        assert_eq!(names.by_name("documentos"), Some(FileCategory::Documents));
     }
 
     #[test]
@@ -268,6 +751,28 @@ mod tests {
         assert_eq!(FileSizeCategory::from_size(2_000_000_000), FileSizeCategory::Huge);
     }
 
+    #[test]
+    fn test_size_buckets() {
+        let bucket = |name: Option<&str>, max: Option<&str>| SizeBucketConfig {
+            name: name.map(str::to_string),
+            max: max.map(|max| max.parse().unwrap()),
+        };
+        let buckets = SizeBuckets::from_config(&[
+            bucket(None, Some("100MB")),
+            bucket(Some("Long: takes"), Some("2GB")),
+        ])
+        .unwrap();
+
+        assert_eq!(buckets.folder(0), "Under 100MB");
+        assert_eq!(buckets.folder(100 << 20), "Under 100MB");
+        assert_eq!(buckets.folder((100 << 20) + 1), "Long_ takes");
+        assert_eq!(buckets.folder(3 << 30), "Over 2GB");
+
+        let buckets = SizeBuckets::from_config(&[bucket(None, Some("1.5GB")), bucket(None, None)]).unwrap();
+        assert_eq!(buckets.folder(2 << 30), "Over 1.5GB");
+        assert!(SizeBuckets::from_config(&[]).is_none());
+    }
+
     #[test]
     fn test_should_ignore() {
         let classifier = FileTypeClassifier::new();
@@ -276,5 +781,8 @@ mod tests {
         assert!(classifier.should_ignore(&PathBuf::from(".DS_Store")));
         assert!(classifier.should_ignore(&PathBuf::from("Thumbs.db")));
         assert!(!classifier.should_ignore(&PathBuf::from("normal_file.txt")));
+        assert!(classifier.is_system_file(&PathBuf::from(".DS_Store")));
+        assert!(!classifier.is_system_file(&PathBuf::from(".hidden")));
+        assert!(classifier.is_system_file(&PathBuf::from("~syncthing~notes.txt.tmp")));
     }
 }
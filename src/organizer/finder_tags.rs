@@ -0,0 +1,181 @@
+//! Finder tags for organized files (`--finder-tags`), so Finder's tag sidebar on macOS lists
+//! files by category the way the folders do.
+//!
+//! Tags live in the `com.apple.metadata:_kMDItemUserTags` extended attribute as a binary plist
+//! holding an array of strings, each a tag name with an optional `\n` and color number. The
+//! codec here handles just that shape; a file's other tags are kept.
+
+use std::io;
+use std::path::Path;
+
+/// The extended attribute Finder keeps tags in
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+const TAGS_ATTRIBUTE: &str = "com.apple.metadata:_kMDItemUserTags";
+
+/// Add the tag `name` to a file, keeping the tags it has; adding a tag it has is a no-op
+#[cfg(target_os = "macos")]
+pub fn add_tag(file_path: &Path, name: &str) -> io::Result<()> {
+    let mut tags = match xattr::get(file_path, TAGS_ATTRIBUTE)? {
+        Some(data) => decode(&data).unwrap_or_default(),
+        None => Vec::new(),
+    };
+    if tags.iter().any(|tag| tag.split('\n').next() == Some(name)) {
+        return Ok(());
+    }
+    tags.push(name.to_string());
+    xattr::set(file_path, TAGS_ATTRIBUTE, &encode(&tags))
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn add_tag(_file_path: &Path, _name: &str) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Finder tags only exist on macOS"))
+}
+
+/// A binary plist of the array of `strings`
+#[cfg_attr(not(any(target_os = "macos", test)), allow(dead_code))]
+fn encode(strings: &[String]) -> Vec<u8> {
+    let count = strings.len() + 1;
+    let ref_size = if count < 256 { 1 } else { 2 };
+    let mut data = b"bplist00".to_vec();
+    let mut offsets = Vec::with_capacity(count);
+
+    offsets.push(data.len());
+    push_marker(&mut data, 0xA, strings.len());
+    for index in 1..count {
+        data.extend_from_slice(&index.to_be_bytes()[8 - ref_size..]);
+    }
+    for string in strings {
+        offsets.push(data.len());
+        if string.is_ascii() {
+            push_marker(&mut data, 0x5, string.len());
+            data.extend_from_slice(string.as_bytes());
+        } else {
+            let units: Vec<u16> = string.encode_utf16().collect();
+            push_marker(&mut data, 0x6, units.len());
+            data.extend(units.iter().flat_map(|unit| unit.to_be_bytes()));
+        }
+    }
+
+    let table = data.len();
+    let offset_size = [1, 2, 4, 8].into_iter().find(|size| *size == 8 || table < 1 << (8 * size)).unwrap_or(8);
+    for offset in offsets {
+        data.extend_from_slice(&offset.to_be_bytes()[8 - offset_size..]);
+    }
+    data.extend_from_slice(&[0; 6]);
+    data.extend_from_slice(&[offset_size as u8, ref_size as u8]);
+    data.extend_from_slice(&(count as u64).to_be_bytes());
+    data.extend_from_slice(&0u64.to_be_bytes());
+    data.extend_from_slice(&(table as u64).to_be_bytes());
+    data
+}
+
+/// An object marker: its type in the high nibble, and the length in the low one or, from 15
+/// on, in an integer after it
+fn push_marker(data: &mut Vec<u8>, kind: u8, len: usize) {
+    if len < 15 {
+        data.push(kind << 4 | len as u8);
+        return;
+    }
+    data.push(kind << 4 | 0xF);
+    match len {
+        len if len < 1 << 8 => data.extend_from_slice(&[0x10, len as u8]),
+        len if len < 1 << 16 => {
+            data.push(0x11);
+            data.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            data.push(0x12);
+            data.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+}
+
+/// The strings of a binary plist holding an array of them; `None` for anything else
+#[cfg_attr(not(any(target_os = "macos", test)), allow(dead_code))]
+fn decode(data: &[u8]) -> Option<Vec<String>> {
+    if !data.starts_with(b"bplist00") || data.len() < 8 + 32 {
+        return None;
+    }
+    let trailer = &data[data.len() - 32..];
+    let (offset_size, ref_size) = (trailer[6] as usize, trailer[7] as usize);
+    let count = read_int(&trailer[8..16])?;
+    let top = read_int(&trailer[16..24])?;
+    let table = read_int(&trailer[24..32])?;
+    let offset_of = |index: usize| {
+        let start = table.checked_add(index.checked_mul(offset_size)?)?;
+        read_int(data.get(start..start + offset_size)?)
+    };
+
+    let array = offset_of(top)?;
+    let (kind, len, body) = read_marker(data, array)?;
+    if kind != 0xA {
+        return None;
+    }
+    (0..len)
+        .map(|i| {
+            let at = body + i * ref_size;
+            let index = read_int(data.get(at..at + ref_size)?)?;
+            if index >= count {
+                return None;
+            }
+            let (kind, len, body) = read_marker(data, offset_of(index)?)?;
+            match kind {
+                0x5 => Some(String::from_utf8_lossy(data.get(body..body + len)?).into_owned()),
+                0x6 => {
+                    let units: Vec<u16> = data
+                        .get(body..body + 2 * len)?
+                        .chunks(2)
+                        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                        .collect();
+                    Some(String::from_utf16_lossy(&units))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// The type, length and start of the contents of the object at `offset`
+fn read_marker(data: &[u8], offset: usize) -> Option<(u8, usize, usize)> {
+    let marker = *data.get(offset)?;
+    let (kind, len) = (marker >> 4, (marker & 0xF) as usize);
+    if len < 15 {
+        return Some((kind, len, offset + 1));
+    }
+    let size = 1 << (data.get(offset + 1)? & 0xF);
+    let len = read_int(data.get(offset + 2..offset + 2 + size)?)?;
+    Some((kind, len, offset + 2 + size))
+}
+
+fn read_int(bytes: &[u8]) -> Option<usize> {
+    if bytes.is_empty() || bytes.len() > 8 {
+        return None;
+    }
+    let mut value = [0u8; 8];
+    value[8 - bytes.len()..].copy_from_slice(bytes);
+    usize::try_from(u64::from_be_bytes(value)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let tags = vec!["Images".to_string(), "Red\n6".to_string(), "Überweisungen".to_string(), "x".repeat(300)];
+        assert_eq!(decode(&encode(&tags)), Some(tags));
+        assert_eq!(decode(&encode(&[])), Some(Vec::new()));
+        assert_eq!(decode(b"not a plist"), None);
+    }
+
+    #[test]
+    fn test_decode_finder_tags() {
+        // The way Finder writes a file tagged "Work" (blue)
+        let finder: &[u8] = &[
+            0x62, 0x70, 0x6C, 0x69, 0x73, 0x74, 0x30, 0x30, 0xA1, 0x01, 0x56, 0x57, 0x6F, 0x72, 0x6B, 0x0A,
+            0x34, 0x08, 0x0A, 0, 0, 0, 0, 0, 0, 0x01, 0x01, 0, 0, 0, 0, 0, 0, 0, 0x02, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0, 0x11,
+        ];
+        assert_eq!(decode(finder), Some(vec!["Work\n4".to_string()]));
+    }
+}
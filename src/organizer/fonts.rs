@@ -0,0 +1,83 @@
+use std::fs;
+use std::path::Path;
+
+/// Folder used for web font formats that can't be read with `ttf-parser`
+pub const WEB_FONTS_FOLDER: &str = "Web (woff)";
+
+/// Folder used for faces whose contents are identical to one already planned
+pub const DUPLICATE_FONTS_FOLDER: &str = "Duplicates";
+
+/// Check whether a font is a web-only format (woff, woff2, eot)
+pub fn is_web_font(file_path: &Path) -> bool {
+    file_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| matches!(ext.to_lowercase().as_str(), "woff" | "woff2" | "eot"))
+        .unwrap_or(false)
+}
+
+/// Read the family name of a ttf/otf font, falling back to a guess from the file name
+pub fn font_family(file_path: &Path) -> String {
+    if let Some(family) = fs::read(file_path).ok().and_then(|data| family_from_data(&data)) {
+        return family;
+    }
+    family_from_file_name(file_path)
+}
+
+/// Guess the family of a font from its file name alone, without reading it
+pub fn family_from_file_name(file_path: &Path) -> String {
+    let stem = file_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    family_from_file_stem(&stem)
+}
+
+/// Read the family from the font's `name` table, preferring the typographic family
+fn family_from_data(data: &[u8]) -> Option<String> {
+    let face = ttf_parser::Face::parse(data, 0).ok()?;
+    let names = face.names();
+
+    let find_name = |name_id: u16| {
+        names
+            .into_iter()
+            .filter(|name| name.name_id == name_id && name.is_unicode())
+            .find_map(|name| name.to_string())
+            .filter(|name| !name.trim().is_empty())
+    };
+
+    find_name(ttf_parser::name_id::TYPOGRAPHIC_FAMILY)
+        .or_else(|| find_name(ttf_parser::name_id::FAMILY))
+        .map(|name| name.trim().to_string())
+}
+
+/// Guess a family name from names like `Roboto-BoldItalic`
+fn family_from_file_stem(stem: &str) -> String {
+    let family = stem.split(['-', '_']).next().unwrap_or(stem).trim();
+    if family.is_empty() {
+        "Unknown Family".to_string()
+    } else {
+        family.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_web_font_detection() {
+        assert!(is_web_font(&PathBuf::from("Inter.woff2")));
+        assert!(is_web_font(&PathBuf::from("Inter.WOFF")));
+        assert!(!is_web_font(&PathBuf::from("Inter.ttf")));
+    }
+
+    #[test]
+    fn test_family_fallback_from_file_name() {
+        assert_eq!(family_from_file_stem("Roboto-BoldItalic"), "Roboto");
+        assert_eq!(family_from_file_stem("Fira_Code"), "Fira");
+        assert_eq!(family_from_file_stem("Inter"), "Inter");
+        assert_eq!(family_from_data(b"not a font"), None);
+    }
+}
@@ -0,0 +1,142 @@
+//! Hard links in moves across filesystems. `rename()` keeps every link to a file intact, but
+//! a move to another filesystem is a copy, and two links to one file copied separately would
+//! become two independent files. So the first link of a file is copied, and the others moved
+//! with it are linked to that copy instead. When that can't be done (the filesystem has no
+//! hard links, or some of the links aren't part of the run) the file is copied as before, with
+//! a warning.
+//!
+//! Link counts come from the inode, so this only applies on Unix; elsewhere every link is
+//! copied.
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::sparse;
+
+/// The links copied to other filesystems during one run, by the file they were links to
+#[derive(Debug, Default)]
+pub struct HardLinks {
+    copies: HashMap<(u64, u64), Copied>,
+}
+
+#[derive(Debug)]
+struct Copied {
+    /// Where the first link went, which the others are linked to
+    first: PathBuf,
+    /// Links the file had before any was moved
+    links: u64,
+    /// Links moved so far
+    moved: u64,
+}
+
+impl HardLinks {
+    /// `rename()`, falling back to copying and deleting when the destination is on another
+    /// filesystem (merging from an external disk, undoing such a merge)
+    pub fn move_file(&mut self, source: &Path, destination: &Path) -> io::Result<()> {
+        match fs::rename(source, destination) {
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => self.copy_across(source, destination),
+            result => result,
+        }
+    }
+
+    /// Warn about files whose other links weren't moved, and are separate files now
+    pub fn warn_unpreserved(&self) {
+        for copied in self.copies.values().filter(|copied| copied.moved < copied.links) {
+            log::warn!(
+                "{} had {} hard links, but only {} moved to its filesystem; the others are now separate files",
+                copied.first.display(),
+                copied.links,
+                copied.moved
+            );
+        }
+    }
+
+    /// Copy `source` to `destination` on another filesystem and remove it, linking it to an
+    /// earlier copy of the same file if there is one
+    pub fn copy_across(&mut self, source: &Path, destination: &Path) -> io::Result<()> {
+        // Looked up whatever the link count is now: moving the earlier links lowered it
+        let inode = fs::metadata(source).ok().and_then(|metadata| inode(&metadata));
+        let linked = match inode.and_then(|(key, _)| self.copies.get(&key)) {
+            Some(copied) => match fs::hard_link(&copied.first, destination) {
+                Ok(()) => true,
+                Err(e) => {
+                    log::warn!(
+                        "Couldn't link {} to {}, copying it instead, so it's no longer the same file: {}",
+                        destination.display(),
+                        copied.first.display(),
+                        e
+                    );
+                    false
+                }
+            },
+            None => false,
+        };
+        if !linked {
+            sparse::copy(source, destination)?;
+        }
+        if let Err(e) = fs::remove_file(source) {
+            // Leave the file where it was rather than in two places
+            let _ = fs::remove_file(destination);
+            return Err(e);
+        }
+
+        if let Some((key, links)) = inode {
+            match self.copies.entry(key) {
+                Entry::Occupied(mut entry) if linked => entry.get_mut().moved += 1,
+                Entry::Occupied(_) => {}
+                Entry::Vacant(entry) if links > 1 => {
+                    entry.insert(Copied { first: destination.to_path_buf(), links, moved: 1 });
+                }
+                Entry::Vacant(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The device and inode of a file, and its link count
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> Option<((u64, u64), u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some(((metadata.dev(), metadata.ino()), metadata.nlink()))
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> Option<((u64, u64), u64)> {
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    #[test]
+    fn test_links_stay_linked() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.jpg"), b"jpg").unwrap();
+        fs::hard_link(root.join("a.jpg"), root.join("b.jpg")).unwrap();
+        fs::write(root.join("c.txt"), b"txt").unwrap();
+        fs::hard_link(root.join("c.txt"), root.join("d.txt")).unwrap();
+        fs::create_dir_all(root.join("Images")).unwrap();
+
+        // The move across filesystems, as it would happen on one
+        let mut links = HardLinks::default();
+        links.copy_across(&root.join("a.jpg"), &root.join("Images/a.jpg")).unwrap();
+        links.copy_across(&root.join("b.jpg"), &root.join("Images/b.jpg")).unwrap();
+        links.copy_across(&root.join("c.txt"), &root.join("Images/c.txt")).unwrap();
+
+        let ino = |name: &str| fs::metadata(root.join(name)).unwrap().ino();
+        assert_eq!(ino("Images/a.jpg"), ino("Images/b.jpg"));
+        assert_eq!(fs::metadata(root.join("Images/a.jpg")).unwrap().nlink(), 2);
+        assert!(!root.join("a.jpg").exists() && !root.join("b.jpg").exists());
+        // d.txt stayed behind, so the moved c.txt is a file of its own
+        assert_ne!(ino("Images/c.txt"), ino("d.txt"));
+        let unpreserved: Vec<_> = links.copies.values().filter(|copied| copied.moved < copied.links).collect();
+        assert_eq!(unpreserved.len(), 1);
+        assert_eq!(unpreserved[0].first, root.join("Images/c.txt"));
+    }
+}
@@ -0,0 +1,320 @@
+//! Content hashes, BLAKE3 unless SHA-256 is asked for (where only FIPS-approved algorithms
+//! will do) or XXH3 (where raw speed beats cryptographic strength). Big files are memory-mapped, and BLAKE3 hashes them on every core;
+//! [`quick_hash`] tells apart same-sized files by their ends alone.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::error::{IoContext, Result};
+
+/// Which checksum a hash is
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// Fastest, and parallel on big files
+    #[default]
+    Blake3,
+    /// FIPS-approved, for manifests that have to be checked with standard tools
+    Sha256,
+    /// Not cryptographic, but the fastest on a single core (128-bit XXH3)
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+}
+
+/// A hasher of either algorithm
+enum Hasher {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(Sha256),
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::default()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Xxh3 => Hasher::Xxh3(Box::default()),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+            Hasher::Sha256(hasher) => hasher.update(bytes),
+            Hasher::Xxh3(hasher) => hasher.update(bytes),
+        }
+    }
+
+    /// [`Hasher::update`] on every core where the algorithm allows it
+    fn update_parallel(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Blake3(hasher) => {
+                hasher.update_rayon(bytes);
+            }
+            Hasher::Sha256(hasher) => hasher.update(bytes),
+            Hasher::Xxh3(hasher) => hasher.update(bytes),
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Hasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            Hasher::Sha256(hasher) => hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect(),
+            Hasher::Xxh3(hasher) => format!("{:032x}", hasher.digest128()),
+        }
+    }
+}
+
+/// How often the hash cache saved hashing a file
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl CacheStats {
+    pub fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Files whose hash came from the cache
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Files that had to be read and hashed
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+/// Files at least this big are memory-mapped and hashed on all cores
+const MMAP_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// How much of each end of a file [`quick_hash`] reads
+pub const QUICK_HASH_BYTES: u64 = 64 * 1024;
+
+/// Compute a BLAKE3 content checksum for a file as a hex string
+pub fn hash_file(file_path: &Path) -> Result<String> {
+    hash_file_with(file_path, HashAlgorithm::Blake3)
+}
+
+/// Compute a content checksum for a file as a hex string
+pub fn hash_file_with(file_path: &Path, algorithm: HashAlgorithm) -> Result<String> {
+    let mut file = File::open(file_path)
+        .scan_context(file_path, format!("Failed to open {:?} for hashing", file_path))?;
+    let mut hasher = Hasher::new(algorithm);
+
+    let len = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+    if len >= MMAP_THRESHOLD {
+        // Some filesystems (FUSE, a few network shares) can't be mapped; those are read instead
+        if let Some(mapping) = Mapping::new(&file, len) {
+            hasher.update_parallel(mapping.bytes());
+            return Ok(hasher.finalize());
+        }
+    }
+
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .scan_context(file_path, format!("Failed to read {:?} for hashing", file_path))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// A checksum of the first and last [`QUICK_HASH_BYTES`] of a file. Files whose quick hashes
+/// differ differ, but equal quick hashes say nothing about the middle.
+pub fn quick_hash(file_path: &Path) -> Result<String> {
+    let context = || format!("Failed to read {:?} for hashing", file_path);
+    let mut file = File::open(file_path)
+        .scan_context(file_path, format!("Failed to open {:?} for hashing", file_path))?;
+    let len = file.metadata().scan_context(file_path, context())?.len();
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&len.to_le_bytes());
+
+    let mut buffer = Vec::new();
+    file.by_ref().take(QUICK_HASH_BYTES).read_to_end(&mut buffer).scan_context(file_path, context())?;
+    if len > QUICK_HASH_BYTES {
+        // The tail, without reading again what the head covered
+        file.seek(SeekFrom::Start(len.saturating_sub(QUICK_HASH_BYTES).max(QUICK_HASH_BYTES)))
+            .scan_context(file_path, context())?;
+        file.take(QUICK_HASH_BYTES).read_to_end(&mut buffer).scan_context(file_path, context())?;
+    }
+    hasher.update(&buffer);
+
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// A read-only view of a whole file.
+///
+/// Like any mapping, it assumes the file isn't truncated while it's being hashed; the hash
+/// of a file changing underneath is meaningless either way.
+#[cfg(unix)]
+struct Mapping {
+    address: *mut libc::c_void,
+    len: usize,
+}
+
+#[cfg(unix)]
+impl Mapping {
+    fn new(file: &File, len: u64) -> Option<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let len = usize::try_from(len).ok()?;
+        // SAFETY: a fresh private read-only mapping of an open file; nothing aliases it
+        let address = unsafe {
+            libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0)
+        };
+        (address != libc::MAP_FAILED).then_some(Mapping { address, len })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        // SAFETY: `address` points at `len` mapped bytes until `self` is dropped
+        unsafe { std::slice::from_raw_parts(self.address as *const u8, self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: `address` and `len` are exactly what mmap returned, and no slice outlives `self`
+        unsafe {
+            libc::munmap(self.address, self.len);
+        }
+    }
+}
+
+#[cfg(windows)]
+struct Mapping {
+    view: windows_sys::Win32::System::Memory::MEMORY_MAPPED_VIEW_ADDRESS,
+    len: usize,
+}
+
+#[cfg(windows)]
+impl Mapping {
+    fn new(file: &File, len: u64) -> Option<Self> {
+        use std::os::windows::io::AsRawHandle;
+        use windows_sys::Win32::Foundation::CloseHandle;
+        use windows_sys::Win32::System::Memory::{CreateFileMappingW, MapViewOfFile, FILE_MAP_READ, PAGE_READONLY};
+
+        let len = usize::try_from(len).ok()?;
+        // SAFETY: the handle belongs to the open `file`; the view keeps the mapping object
+        // alive after its handle is closed
+        let view = unsafe {
+            let mapping = CreateFileMappingW(file.as_raw_handle(), std::ptr::null(), PAGE_READONLY, 0, 0, std::ptr::null());
+            if mapping.is_null() {
+                return None;
+            }
+            let view = MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, len);
+            CloseHandle(mapping);
+            view
+        };
+        (!view.Value.is_null()).then_some(Mapping { view, len })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        // SAFETY: the view maps `len` bytes until `self` is dropped
+        unsafe { std::slice::from_raw_parts(self.view.Value as *const u8, self.len) }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: `view` came from MapViewOfFile, and no slice outlives `self`
+        unsafe {
+            windows_sys::Win32::System::Memory::UnmapViewOfFile(self.view);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+struct Mapping;
+
+#[cfg(not(any(unix, windows)))]
+impl Mapping {
+    fn new(_file: &File, _len: u64) -> Option<Self> {
+        None
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &[]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_big_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("video.mp4");
+        let contents: Vec<u8> = (0..MMAP_THRESHOLD + 12345).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &contents).unwrap();
+        assert_eq!(hash_file(&path).unwrap(), blake3::hash(&contents).to_hex().to_string());
+        let sha256: String = Sha256::digest(&contents).iter().map(|byte| format!("{:02x}", byte)).collect();
+        assert_eq!(hash_file_with(&path, HashAlgorithm::Sha256).unwrap(), sha256);
+        let xxh3 = format!("{:032x}", xxhash_rust::xxh3::xxh3_128(&contents));
+        assert_eq!(hash_file_with(&path, HashAlgorithm::Xxh3).unwrap(), xxh3);
+    }
+
+    #[test]
+    fn test_algorithms() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abc.txt");
+        std::fs::write(&path, "abc").unwrap();
+        assert_eq!(hash_file(&path).unwrap(), blake3::hash(b"abc").to_hex().to_string());
+        assert_eq!(
+            hash_file_with(&path, HashAlgorithm::Sha256).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(hash_file_with(&path, HashAlgorithm::Xxh3).unwrap(), format!("{:032x}", xxhash_rust::xxh3::xxh3_128(b"abc")));
+    }
+
+    #[test]
+    fn test_quick_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, contents: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            path
+        };
+        let original = vec![7u8; 3 * QUICK_HASH_BYTES as usize];
+        let mut middle = original.clone();
+        middle[original.len() / 2] = 0;
+        let mut end = original.clone();
+        *end.last_mut().unwrap() = 0;
+
+        let quick = |name: &str, contents: &[u8]| quick_hash(&write(name, contents)).unwrap();
+        assert_eq!(quick("a", &original), quick("b", &middle));
+        assert_ne!(quick("a", &original), quick("c", &end));
+        assert_ne!(quick("d", b"abc"), quick("e", b"abd"));
+        assert_ne!(quick("f", b""), quick("g", &original));
+    }
+}
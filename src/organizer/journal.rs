@@ -0,0 +1,292 @@
+//! Undo journals: what one run moved, and the JSON file format they're kept in (one file per
+//! run under `<directory>/.file-organizer/history/`, see [`store`](super::store)).
+//!
+//! Paths are stored relative to the organized directory, so a journal stays valid when the
+//! whole directory is moved or synced elsewhere.
+//!
+//! Every journal carries a format `version`. Older formats are upgraded in memory when they're
+//! read, so undo keeps working across upgrades, and `history migrate` rewrites them on disk.
+//! Changing the format means bumping [`JOURNAL_VERSION`] and adding a step to `upgrade`.
+//!
+//! | Version | Changes                                             |
+//! |---------|-----------------------------------------------------|
+//! | 1       | `id`, `created_at`, `mode`, `moves` (no `version`)  |
+//! | 2       | adds `version`                                      |
+//!
+//! Optional fields that older versions can ignore, such as `stats`, don't need a new version.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::error::{Context, OrganizerError, Result};
+use super::stats::RunStats;
+use super::FileOutcome;
+
+/// Folder inside an organized directory where the tool keeps its own data
+pub const METADATA_DIR: &str = ".file-organizer";
+const HISTORY_DIR: &str = "history";
+
+/// Journal format written by this version of the tool
+pub const JOURNAL_VERSION: u32 = 2;
+
+/// The moves made by one run, in the order they happened
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Journal {
+    pub version: u32,
+    /// `2024-06-01-0003`: the date of the run and its number on that day
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub mode: String,
+    pub moves: Vec<JournalEntry>,
+    /// Missing in journals written before statistics were recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<RunStats>,
+}
+
+/// One moved file, relative to the organized directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    #[serde(with = "super::os_path")]
+    pub source: PathBuf,
+    #[serde(with = "super::os_path")]
+    pub destination: PathBuf,
+}
+
+impl JournalEntry {
+    pub fn source_in(&self, target_dir: &Path) -> PathBuf {
+        target_dir.join(&self.source)
+    }
+
+    pub fn destination_in(&self, target_dir: &Path) -> PathBuf {
+        target_dir.join(&self.destination)
+    }
+}
+
+impl Journal {
+    /// Journal of the successful moves among `outcomes`, made by a run of `mode` (an
+    /// [`OrganizeMode`](super::OrganizeMode) name, or another command such as `Merge`)
+    pub fn from_outcomes(target_dir: &Path, mode: &str, outcomes: &[FileOutcome]) -> Self {
+        let relative = |path: &Path| path.strip_prefix(target_dir).unwrap_or(path).to_path_buf();
+        Self {
+            version: JOURNAL_VERSION,
+            id: String::new(),
+            created_at: Utc::now(),
+            mode: mode.to_string(),
+            moves: outcomes
+                .iter()
+                .filter(|outcome| outcome.succeeded())
+                .map(|outcome| JournalEntry {
+                    source: relative(&outcome.operation.source),
+                    destination: relative(&outcome.operation.destination),
+                })
+                .collect(),
+            stats: None,
+        }
+    }
+
+    /// The next free id on the journal's day, given the ids already taken: one past the
+    /// highest, so an id freed by undoing an older run is never handed out again
+    pub fn next_id<'a>(&self, taken: impl IntoIterator<Item = &'a str>) -> String {
+        let day = self.created_at.with_timezone(&Local).format("%Y-%m-%d").to_string();
+        let highest = taken
+            .into_iter()
+            .filter_map(|id| id.strip_prefix(&day)?.strip_prefix('-')?.parse::<u32>().ok())
+            .max()
+            .unwrap_or(0);
+        format!("{}-{:04}", day, highest + 1)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self).journal_context("Failed to serialize the journal")?;
+        fs::write(path, contents).journal_context(format!("Failed to write journal {}", path.display()))
+    }
+
+    /// Read a journal in any known format, upgraded to the current one
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::load_versioned(path).map(|(journal, _)| journal)
+    }
+
+    /// Like [`Journal::load`], also returning the format version found on disk
+    pub(super) fn load_versioned(path: &Path) -> Result<(Self, u32)> {
+        let contents = fs::read_to_string(path).journal_context(format!("Failed to read journal {}", path.display()))?;
+        let value: serde_json::Value =
+            serde_json::from_str(&contents).journal_context(format!("Invalid journal {}", path.display()))?;
+        upgrade(value).journal_context(format!("Invalid journal {}", path.display()))
+    }
+}
+
+/// A journal that is (or, in a dry run, would be) rewritten in the current format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    pub path: PathBuf,
+    pub from_version: u32,
+}
+
+/// Rewrite every journal for `target_dir` that uses an older format.
+///
+/// Nothing is written if any journal can't be read, so a failed migration never leaves the
+/// history half converted.
+pub fn migrate(target_dir: &Path, dry_run: bool) -> Result<Vec<Migration>> {
+    let mut outdated = Vec::new();
+    for path in journal_files(target_dir)? {
+        let (journal, from_version) = Journal::load_versioned(&path)?;
+        if from_version < JOURNAL_VERSION {
+            outdated.push((Migration { path, from_version }, journal));
+        }
+    }
+
+    if !dry_run {
+        for (migration, journal) in &outdated {
+            journal.write(&migration.path)?;
+        }
+    }
+    Ok(outdated.into_iter().map(|(migration, _)| migration).collect())
+}
+
+/// Bring a journal in any known format up to [`JOURNAL_VERSION`], one version at a time
+pub(super) fn upgrade(mut value: serde_json::Value) -> Result<(Journal, u32)> {
+    let found = match value.get("version") {
+        None => 1,
+        Some(version) => match version.as_u64().and_then(|version| u32::try_from(version).ok()) {
+            Some(version) if version >= 1 => version,
+            _ => return Err(OrganizerError::journal(format!("Unknown journal version {}", version))),
+        },
+    };
+    if found > JOURNAL_VERSION {
+        return Err(OrganizerError::journal(format!(
+            "Journal format {} is newer than this version of file-organizer supports ({}); please upgrade",
+            found,
+            JOURNAL_VERSION
+        )));
+    }
+
+    let Some(fields) = value.as_object_mut() else {
+        return Err(OrganizerError::journal("Expected a JSON object"));
+    };
+    if found < 2 {
+        fields.insert("version".to_string(), 2.into());
+    }
+
+    Ok((serde_json::from_value(value).journal_context("Invalid journal contents")?, found))
+}
+
+/// Whether `path` is one of the tool's own data folders (history, resume files, import
+/// ledgers) or inside one
+pub fn is_metadata_path(path: &Path) -> bool {
+    let inside = |path: &Path| path.components().any(|component| component.as_os_str() == METADATA_DIR);
+    inside(path) || path.canonicalize().is_ok_and(|path| inside(&path))
+}
+
+/// Refuse to organize, clean or deduplicate a metadata folder, which would lose the undo
+/// history
+pub(super) fn refuse_metadata_dir(dir: &Path) -> Result<()> {
+    if is_metadata_path(dir) {
+        return Err(OrganizerError::plan(
+            dir,
+            format!("{} holds the organizer's own history; it can't be organized, cleaned or deduplicated", dir.display()),
+        ));
+    }
+    Ok(())
+}
+
+pub fn history_dir(target_dir: &Path) -> PathBuf {
+    target_dir.join(METADATA_DIR).join(HISTORY_DIR)
+}
+
+/// Journal files for `target_dir`, oldest first (ids sort chronologically)
+pub(super) fn journal_files(target_dir: &Path) -> Result<Vec<PathBuf>> {
+    let dir = history_dir(target_dir);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+        .journal_context(format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{FileOperation, OperationType};
+
+    fn outcome(source: &Path, destination: &Path, error: Option<&str>) -> FileOutcome {
+        FileOutcome {
+            operation: FileOperation {
+                source: source.to_path_buf(),
+                destination: destination.to_path_buf(),
+                operation_type: OperationType::Move,
+                size: 0,
+            },
+            error: error.map(str::to_string),
+            skipped: false,
+            rolled_back: false,
+        }
+    }
+
+    #[test]
+    fn test_journal_from_outcomes() {
+        let root = Path::new("/downloads");
+        let outcomes = [
+            outcome(&root.join("a.jpg"), &root.join("Images/a.jpg"), None),
+            outcome(&root.join("b.jpg"), &root.join("Images/b.jpg"), Some("destination already exists")),
+        ];
+        let mut journal = Journal::from_outcomes(root, "Extension", &outcomes);
+        assert_eq!(
+            journal.moves,
+            [JournalEntry { source: PathBuf::from("a.jpg"), destination: PathBuf::from("Images/a.jpg") }]
+        );
+
+        journal.created_at = DateTime::parse_from_rfc3339("2024-06-01T12:00:00Z").unwrap().into();
+        let day = journal.created_at.with_timezone(&Local).format("%Y-%m-%d").to_string();
+        let earlier = format!("{}-0001", day);
+        assert_eq!(journal.next_id([]), earlier);
+        assert_eq!(journal.next_id(["2020-01-01-0001", earlier.as_str()]), format!("{}-0002", day));
+        // 0001 was undone, and 0002 is still there
+        assert_eq!(journal.next_id([format!("{}-0002", day).as_str()]), format!("{}-0003", day));
+    }
+
+    const VERSION_1: &str = r#"{
+  "id": "2024-06-01-0001",
+  "created_at": "2024-06-01T10:00:00Z",
+  "mode": "Extension",
+  "moves": [{ "source": "a.jpg", "destination": "Images/a.jpg" }]
+}"#;
+
+    #[test]
+    fn test_old_journals_are_upgraded() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(history_dir(root)).unwrap();
+        let old = history_dir(root).join("2024-06-01-0001.json");
+        fs::write(&old, VERSION_1).unwrap();
+
+        // Readable as-is
+        let journal = Journal::load(&old).unwrap();
+        assert_eq!(journal.version, JOURNAL_VERSION);
+        assert_eq!(journal.moves[0].destination, PathBuf::from("Images/a.jpg"));
+
+        let planned = migrate(root, true).unwrap();
+        assert_eq!(planned, [Migration { path: old.clone(), from_version: 1 }]);
+        assert_eq!(fs::read_to_string(&old).unwrap(), VERSION_1);
+
+        migrate(root, false).unwrap();
+        assert_eq!(Journal::load_versioned(&old).unwrap(), (journal, JOURNAL_VERSION));
+        assert!(migrate(root, false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_newer_journal_is_rejected() {
+        let newer = VERSION_1.replacen('{', "{ \"version\": 99,", 1);
+        let error = format!("{:#}", upgrade(serde_json::from_str(&newer).unwrap()).unwrap_err());
+        assert!(error.contains("newer than this version"), "{}", error);
+        assert!(upgrade(serde_json::json!({ "version": "two" })).is_err());
+    }
+}
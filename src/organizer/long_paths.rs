@@ -0,0 +1,63 @@
+//! Windows paths longer than `MAX_PATH` (260 characters), which deep trees of downloads and
+//! synced folders easily reach. The standard library adds the `\\?\` prefix that lifts the
+//! limit to its own file functions, but Win32 functions called directly (free space, copies
+//! that keep alternate data streams) need it spelled out; [`verbatim`] gives that form.
+//! Elsewhere paths have no such limit and are left as they are.
+
+use std::path::{Path, PathBuf};
+
+/// `path` as an absolute `\\?\` path on Windows, which every wide Win32 function takes at
+/// any length; unchanged on other platforms
+pub fn verbatim(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    {
+        // absolute() also resolves `.` and `..`, which `\\?\` paths don't
+        let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        match absolute.to_str() {
+            Some(text) => PathBuf::from(verbatim_str(text)),
+            None => absolute,
+        }
+    }
+    #[cfg(not(windows))]
+    path.to_path_buf()
+}
+
+/// The `\\?\` form of an absolute Windows path: `C:\a` -> `\\?\C:\a`, `\\server\share` ->
+/// `\\?\UNC\server\share`. Paths that already have a prefix, or aren't absolute, are kept.
+#[cfg_attr(not(any(windows, test)), allow(dead_code))]
+fn verbatim_str(path: &str) -> String {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return path.to_string();
+    }
+    // Verbatim paths are passed to the filesystem as is, so `/` has to become `\`
+    let path = path.replace('/', r"\");
+    if let Some(share) = path.strip_prefix(r"\\") {
+        return format!(r"\\?\UNC\{}", share);
+    }
+    let bytes = path.as_bytes();
+    if bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\' {
+        return format!(r"\\?\{}", path);
+    }
+    path
+}
+
+/// `path` as a NUL-terminated wide string for Win32, in its `\\?\` form
+#[cfg(windows)]
+pub fn wide(path: &Path) -> Vec<u16> {
+    use std::os::windows::ffi::OsStrExt;
+    verbatim(path).as_os_str().encode_wide().chain(std::iter::once(0)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbatim_str() {
+        assert_eq!(verbatim_str(r"C:\Users\me\Downloads\a.jpg"), r"\\?\C:\Users\me\Downloads\a.jpg");
+        assert_eq!(verbatim_str("D:/Photos/2024"), r"\\?\D:\Photos\2024");
+        assert_eq!(verbatim_str(r"\\nas\share\Sorted"), r"\\?\UNC\nas\share\Sorted");
+        assert_eq!(verbatim_str(r"\\?\C:\already"), r"\\?\C:\already");
+        assert_eq!(verbatim_str(r"relative\path"), r"relative\path");
+    }
+}
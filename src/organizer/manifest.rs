@@ -0,0 +1,362 @@
+//! Portable manifests of an organized library: every file with its size, content hash and
+//! category, so the library can be checked after it was copied to another machine or drive.
+//!
+//! Paths are relative to the library and always use `/`, so a manifest written on Windows
+//! checks out on Linux and the other way round. Manifests ending in `.csv` are CSV (one
+//! `path,size,hash,category` row per file, for spreadsheets); anything else is JSON.
+//!
+//! Hashes are BLAKE3 unless the manifest says otherwise: JSON manifests name their
+//! `algorithm`, and CSV ones head the hash column with it (`sha256` instead of `hash`).
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use super::error::{Context, IoContext, OrganizerError, Result};
+use super::hashing::HashAlgorithm;
+use super::journal::METADATA_DIR;
+use super::FileOrganizer;
+
+/// Manifest format written by this version of the tool
+pub const MANIFEST_VERSION: u32 = 1;
+
+const CSV_HEADER: [&str; 4] = ["path", "size", "hash", "category"];
+
+/// Position of the hash column, whose header names the algorithm unless it's BLAKE3
+const CSV_HASH_COLUMN: usize = 2;
+
+/// One file of the library
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Relative to the library, with `/` separators
+    pub path: String,
+    pub size: u64,
+    pub hash: String,
+    /// Category folder name, e.g. `Images`
+    pub category: String,
+}
+
+/// Every file of a library when the manifest was made, sorted by path
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// Missing in CSV manifests
+    #[serde(default)]
+    pub created: Option<DateTime<Utc>>,
+    /// What the hashes are; older manifests are all BLAKE3
+    #[serde(default)]
+    pub algorithm: HashAlgorithm,
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// How a library differs from its manifest
+#[derive(Debug, Default)]
+pub struct ManifestCheck {
+    /// Files that are where the manifest says, with the same content
+    pub matched: usize,
+    /// In the manifest, but gone from the library
+    pub missing: Vec<String>,
+    /// Same path, different content
+    pub changed: Vec<String>,
+    /// Same content under another path, as (manifest path, current path)
+    pub moved: Vec<(String, String)>,
+    /// In the library, but not in the manifest
+    pub added: Vec<String>,
+}
+
+impl ManifestCheck {
+    /// Whether every file in the manifest is intact. Added files don't count against it.
+    pub fn is_intact(&self) -> bool {
+        self.missing.is_empty() && self.changed.is_empty() && self.moved.is_empty()
+    }
+}
+
+impl Manifest {
+    /// Read a manifest written by [`Manifest::save`]
+    pub fn load(file: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(file).journal_context(format!("Failed to read {}", file.display()))?;
+        let manifest = if is_csv(file) {
+            from_csv(&contents).journal_context(format!("Invalid manifest {}", file.display()))?
+        } else {
+            serde_json::from_str::<Self>(&contents).journal_context(format!("Invalid manifest {}", file.display()))?
+        };
+        if manifest.version > MANIFEST_VERSION {
+            return Err(OrganizerError::journal(format!("{} was written by a newer version of file-organizer", file.display())));
+        }
+        Ok(manifest)
+    }
+
+    /// Write the manifest as CSV if `file` ends in `.csv`, as JSON otherwise
+    pub fn save(&self, file: &Path) -> Result<()> {
+        let contents = if is_csv(file) { to_csv(self) } else { serde_json::to_string_pretty(self).journal_context("Failed to serialize the manifest")? };
+        fs::write(file, contents).journal_context(format!("Failed to write {}", file.display()))
+    }
+
+    /// Total size of the files in the manifest
+    pub fn bytes(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+}
+
+impl FileOrganizer {
+    /// List every file below `root`, subdirectories included, leaving out the tool's own
+    /// metadata
+    pub fn manifest(&self, root: &Path) -> Result<Manifest> {
+        let mut entries = Vec::new();
+        for (relative, file) in library_files(root)? {
+            self.check_cancelled()?;
+            let size = fs::metadata(&file).scan_context(&file, format!("Failed to get metadata for {:?}", file))?.len();
+            entries.push(ManifestEntry {
+                path: relative,
+                size,
+                hash: self.hash_file(&file)?,
+                category: self.classifier.names().name(&self.classifier.classify(&file)).to_string(),
+            });
+        }
+        Ok(Manifest {
+            version: MANIFEST_VERSION,
+            created: Some(Utc::now()),
+            algorithm: self.options.hash_algorithm,
+            entries,
+        })
+    }
+
+    /// Compare the files below `root` with a manifest of them, hashing them the way the
+    /// manifest was
+    pub fn check_manifest(&self, manifest: &Manifest, root: &Path) -> Result<ManifestCheck> {
+        let mut current: BTreeMap<String, String> = BTreeMap::new();
+        for (relative, file) in library_files(root)? {
+            self.check_cancelled()?;
+            current.insert(relative, self.hash_file_as(&file, manifest.algorithm)?);
+        }
+
+        let mut check = ManifestCheck::default();
+        let mut gone = Vec::new();
+        for entry in &manifest.entries {
+            match current.remove(&entry.path) {
+                Some(hash) if hash == entry.hash => check.matched += 1,
+                Some(_) => check.changed.push(entry.path.clone()),
+                None => gone.push(entry),
+            }
+        }
+
+        // What's left in `current` wasn't in the manifest under its path; a missing file
+        // with the same content was moved there
+        let mut unclaimed: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (path, hash) in &current {
+            unclaimed.entry(hash.as_str()).or_default().push(path.as_str());
+        }
+        let mut moved_to = Vec::new();
+        for entry in gone {
+            match unclaimed.get_mut(entry.hash.as_str()).filter(|paths| !paths.is_empty()) {
+                Some(paths) => {
+                    let path = paths.remove(0);
+                    moved_to.push(path.to_string());
+                    check.moved.push((entry.path.clone(), path.to_string()));
+                }
+                None => check.missing.push(entry.path.clone()),
+            }
+        }
+        check.added = current.into_keys().filter(|path| !moved_to.contains(path)).collect();
+        Ok(check)
+    }
+}
+
+/// Every file below `root` as (portable relative path, path), sorted
+fn library_files(root: &Path) -> Result<Vec<(String, PathBuf)>> {
+    if !root.is_dir() {
+        return Err(OrganizerError::plan(root, format!("{} isn't a directory", root.display())));
+    }
+    let mut files = Vec::new();
+    for entry in WalkDir::new(root)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.file_name() != METADATA_DIR)
+    {
+        let entry = entry.map_err(|e| OrganizerError::walk(root, e))?;
+        if entry.file_type().is_file() {
+            let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            let portable: Vec<_> = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect();
+            files.push((portable.join("/"), entry.into_path()));
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+fn is_csv(file: &Path) -> bool {
+    file.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv"))
+}
+
+/// The CSV header for hashes of `algorithm`
+fn csv_header(algorithm: HashAlgorithm) -> [&'static str; 4] {
+    let mut header = CSV_HEADER;
+    if algorithm != HashAlgorithm::Blake3 {
+        header[CSV_HASH_COLUMN] = algorithm.name();
+    }
+    header
+}
+
+fn to_csv(manifest: &Manifest) -> String {
+    let mut csv = csv_header(manifest.algorithm).join(",");
+    csv.push('\n');
+    for entry in &manifest.entries {
+        let row = [csv_field(&entry.path), entry.size.to_string(), csv_field(&entry.hash), csv_field(&entry.category)];
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Quote a field if it holds a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn from_csv(contents: &str) -> Result<Manifest> {
+    let mut rows = parse_csv(contents).into_iter();
+    let header = rows.next().unwrap_or_default();
+    let Some(algorithm) = [HashAlgorithm::Blake3, HashAlgorithm::Sha256, HashAlgorithm::Xxh3]
+        .into_iter()
+        .find(|algorithm| header == csv_header(*algorithm))
+    else {
+        return Err(OrganizerError::journal(format!("the first line must be {}", CSV_HEADER.join(","))));
+    };
+    let mut entries = Vec::new();
+    for (line, row) in rows.enumerate() {
+        let [path, size, hash, category]: [String; 4] =
+            row.try_into().map_err(|row: Vec<String>| OrganizerError::journal(format!("row {} has {} fields, not 4", line + 1, row.len())))?;
+        let size = size.parse().journal_context(format!("row {} has an invalid size {:?}", line + 1, size))?;
+        entries.push(ManifestEntry { path, size, hash, category });
+    }
+    Ok(Manifest { version: MANIFEST_VERSION, created: None, algorithm, entries })
+}
+
+/// Split CSV into rows of fields, honoring quoted fields (which may span lines)
+fn parse_csv(contents: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let (mut row, mut field) = (Vec::new(), String::new());
+    let (mut quoted, mut chars) = (false, contents.chars().peekable());
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::config::Config;
+    use super::super::OrganizeOptions;
+
+    fn write(root: &Path, name: &str, contents: &str) {
+        let path = root.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_manifest_round_trip_and_check() {
+        let library = tempfile::tempdir().unwrap();
+        let root = library.path();
+        write(root, "Images/a.jpg", "aaa");
+        write(root, "Documents/b, \"final\".pdf", "bb");
+        write(root, "Documents/c.txt", "c");
+        write(root, ".file-organizer/history/x.json", "{}");
+        let organizer = FileOrganizer::with_config(OrganizeOptions::default(), &Config::default()).unwrap();
+
+        let manifest = organizer.manifest(root).unwrap();
+        let paths: Vec<_> = manifest.entries.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(paths, ["Documents/b, \"final\".pdf", "Documents/c.txt", "Images/a.jpg"]);
+        assert_eq!((manifest.entries[2].category.as_str(), manifest.bytes()), ("Images", 6));
+
+        let out = tempfile::tempdir().unwrap();
+        for name in ["manifest.json", "manifest.csv"] {
+            manifest.save(&out.path().join(name)).unwrap();
+            let loaded = Manifest::load(&out.path().join(name)).unwrap();
+            assert_eq!(loaded.entries, manifest.entries, "{}", name);
+        }
+        assert!(organizer.check_manifest(&manifest, root).unwrap().is_intact());
+
+        fs::rename(root.join("Images/a.jpg"), root.join("Images/renamed.jpg")).unwrap();
+        write(root, "Documents/c.txt", "changed");
+        fs::remove_file(root.join("Documents/b, \"final\".pdf")).unwrap();
+        write(root, "Videos/new.mp4", "new");
+        let check = organizer.check_manifest(&manifest, root).unwrap();
+        assert!(!check.is_intact());
+        assert_eq!(check.matched, 0);
+        assert_eq!(check.missing, ["Documents/b, \"final\".pdf"]);
+        assert_eq!(check.changed, ["Documents/c.txt"]);
+        assert_eq!(check.moved, [("Images/a.jpg".to_string(), "Images/renamed.jpg".to_string())]);
+        assert_eq!(check.added, ["Videos/new.mp4"]);
+    }
+
+    #[test]
+    fn test_sha256_manifests() {
+        let library = tempfile::tempdir().unwrap();
+        let root = library.path();
+        write(root, "Images/a.jpg", "abc");
+        let options = OrganizeOptions { hash_algorithm: HashAlgorithm::Sha256, ..OrganizeOptions::default() };
+        let manifest = FileOrganizer::with_config(options, &Config::default()).unwrap().manifest(root).unwrap();
+        assert!(manifest.entries[0].hash.starts_with("ba7816bf"));
+
+        let out = tempfile::tempdir().unwrap();
+        for name in ["manifest.json", "manifest.csv"] {
+            manifest.save(&out.path().join(name)).unwrap();
+            let loaded = Manifest::load(&out.path().join(name)).unwrap();
+            assert_eq!(loaded.algorithm, HashAlgorithm::Sha256, "{}", name);
+        }
+        let csv = fs::read_to_string(out.path().join("manifest.csv")).unwrap();
+        assert!(csv.starts_with("path,size,sha256,category\n"));
+
+        // Checked with SHA-256 whatever the organizer's own algorithm
+        let organizer = FileOrganizer::with_config(OrganizeOptions::default(), &Config::default()).unwrap();
+        assert!(organizer.check_manifest(&manifest, root).unwrap().is_intact());
+
+        let old = out.path().join("old.json");
+        fs::write(&old, r#"{"version": 1, "created": null, "entries": []}"#).unwrap();
+        assert_eq!(Manifest::load(&old).unwrap().algorithm, HashAlgorithm::Blake3);
+    }
+
+    #[test]
+    fn test_xxh3_manifests() {
+        let library = tempfile::tempdir().unwrap();
+        let root = library.path();
+        write(root, "Images/a.jpg", "abc");
+        let options = OrganizeOptions { hash_algorithm: HashAlgorithm::Xxh3, ..OrganizeOptions::default() };
+        let manifest = FileOrganizer::with_config(options, &Config::default()).unwrap().manifest(root).unwrap();
+        assert_eq!(manifest.entries[0].hash.len(), 32);
+
+        let out = tempfile::tempdir().unwrap();
+        manifest.save(&out.path().join("manifest.csv")).unwrap();
+        let loaded = Manifest::load(&out.path().join("manifest.csv")).unwrap();
+        assert_eq!(loaded.algorithm, HashAlgorithm::Xxh3);
+        let organizer = FileOrganizer::with_config(OrganizeOptions::default(), &Config::default()).unwrap();
+        assert!(organizer.check_manifest(&loaded, root).unwrap().is_intact());
+    }
+}
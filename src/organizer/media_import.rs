@@ -0,0 +1,250 @@
+//! Importing from SD cards and cameras.
+//!
+//! New files on the media are copied (never moved) into the destination, sorted by EXIF
+//! capture date. Every imported file's content hash is remembered in
+//! `<destination>/.file-organizer/imports.json`, so the next import only copies what's new,
+//! even after the originals were renamed or the card was reused in another camera.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use super::dates::{self, DateFallbacks, DateSource};
+use super::error::{Context, IoContext, OrganizerError, Result};
+use super::journal::METADATA_DIR;
+use super::{FileOperation, FileOrganizer, FileOutcome, OperationType, OrganizeMode};
+
+const LEDGER_FILE: &str = "imports.json";
+
+/// Import ledger format written by this version of the tool
+pub const LEDGER_VERSION: u32 = 1;
+
+/// Everything imported into a destination so far
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ImportLedger {
+    version: u32,
+    last_import: Option<DateTime<Utc>>,
+    /// Content hashes of every file copied in
+    hashes: BTreeSet<String>,
+}
+
+impl ImportLedger {
+    fn path(destination: &Path) -> PathBuf {
+        destination.join(METADATA_DIR).join(LEDGER_FILE)
+    }
+
+    fn load(destination: &Path) -> Result<Self> {
+        let path = Self::path(destination);
+        if !path.exists() {
+            return Ok(Self { version: LEDGER_VERSION, ..Self::default() });
+        }
+        let contents = fs::read_to_string(&path).journal_context(format!("Failed to read {}", path.display()))?;
+        let ledger: Self = serde_json::from_str(&contents).journal_context(format!("Invalid import ledger {}", path.display()))?;
+        if ledger.version > LEDGER_VERSION {
+            return Err(OrganizerError::journal(format!("{} was written by a newer version of file-organizer", path.display())));
+        }
+        Ok(ledger)
+    }
+
+    fn save(&self, destination: &Path) -> Result<()> {
+        let path = Self::path(destination);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).journal_context(format!("Failed to create {}", parent.display()))?;
+        }
+        let contents = serde_json::to_string_pretty(self).journal_context("Failed to serialize the import ledger")?;
+        fs::write(&path, contents).journal_context(format!("Failed to write {}", path.display()))
+    }
+}
+
+/// What [`FileOrganizer::import_media`] did
+#[derive(Debug)]
+pub struct ImportReport {
+    /// Copies of the new files, in the order they were made
+    pub plan: Vec<FileOperation>,
+    /// One entry per copy; empty for a dry run
+    pub outcomes: Vec<FileOutcome>,
+    /// Files left out because an earlier import already copied them (or an identical file
+    /// appears earlier on the media)
+    pub already_imported: usize,
+    /// When the destination last received an import, before this one
+    pub last_import: Option<DateTime<Utc>>,
+    pub dry_run: bool,
+}
+
+impl ImportReport {
+    pub fn copied(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| outcome.succeeded()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| outcome.error.is_some()).count()
+    }
+}
+
+impl FileOrganizer {
+    /// Copy the files on `media` that weren't imported before into date folders in
+    /// `destination`.
+    ///
+    /// Dates come from EXIF capture times, falling back to modification times; the folders
+    /// follow the date buckets or the config's folder template. Nothing on the media is
+    /// changed, and copies aren't part of the undo history.
+    pub fn import_media(&mut self, media: &Path, destination: &Path, dry_run: bool) -> Result<ImportReport> {
+        let dry_run = dry_run || self.options.analyze;
+        let mut ledger = ImportLedger::load(destination)?;
+        let last_import = ledger.last_import;
+        self.observer.on_scan_started(media);
+
+        let mut plan = Vec::new();
+        let mut hashes: HashMap<PathBuf, String> = HashMap::new();
+        let mut planned: HashSet<String> = HashSet::new();
+        let mut already_imported = 0;
+        let mut found = 0;
+        let mut fallbacks = DateFallbacks::default();
+        for entry in WalkDir::new(media).sort_by_file_name().into_iter().filter_entry(|e| e.depth() == 0 || !is_hidden(e.file_name())) {
+            self.check_cancelled()?;
+            let entry = entry.map_err(|e| OrganizerError::walk(media, e))?;
+            if !entry.file_type().is_file() || self.classifier.should_ignore(entry.path()) {
+                continue;
+            }
+            found += 1;
+            self.observer.on_scan_progress(found);
+
+            let source = entry.into_path();
+            let hash = self.hash_file(&source)?;
+            if ledger.hashes.contains(&hash) || !planned.insert(hash.clone()) {
+                already_imported += 1;
+                continue;
+            }
+            let metadata = fs::metadata(&source).scan_context(&source, format!("Failed to get metadata for {:?}", source))?;
+            let date = dates::file_date(&source, &metadata, DateSource::Exif, &mut fallbacks)
+                .ok_or_else(|| OrganizerError::plan(&source, format!("Failed to get a date for {:?}", source)))?;
+            let file_name = source.file_name().ok_or_else(|| OrganizerError::plan(&source, "Failed to get file name"))?;
+            let target = destination.join(self.date_folder("📅", &date)).join(file_name);
+            // Copied by hand before the ledger knew about it
+            if target.exists() && self.hash_file(&target).is_ok_and(|existing| existing == hash) {
+                already_imported += 1;
+                ledger.hashes.insert(hash);
+                continue;
+            }
+            hashes.insert(source.clone(), hash);
+            plan.push(FileOperation { source, destination: target, operation_type: OperationType::Copy, size: metadata.len() });
+        }
+        self.observer.on_scan_finished(found, plan.len());
+
+        let mut report = ImportReport { plan, outcomes: Vec::new(), already_imported, last_import, dry_run };
+        if !report.plan.is_empty() {
+            self.observer.on_plan_ready(&report.plan, &OrganizeMode::Date);
+        }
+        if dry_run {
+            return Ok(report);
+        }
+        if let Some(shortage) = self.space_shortage(destination, &report.plan) {
+            return Err(OrganizerError::plan(
+                destination,
+                format!("Not enough room in {}: {}; nothing was copied", destination.display(), shortage),
+            ));
+        }
+
+        report.outcomes = self.execute(&report.plan)?;
+        for outcome in report.outcomes.iter().filter(|outcome| outcome.succeeded()) {
+            if let Some(hash) = hashes.remove(&outcome.operation.source) {
+                ledger.hashes.insert(hash);
+            }
+        }
+        ledger.version = LEDGER_VERSION;
+        ledger.last_import = Some(Utc::now());
+        ledger.save(destination)?;
+        Ok(report)
+    }
+}
+
+/// Dot files and folders, including the ones cameras and desktops leave on cards
+/// (`.Trashes`, `.Spotlight-V100`)
+fn is_hidden(name: &std::ffi::OsStr) -> bool {
+    name.to_string_lossy().starts_with('.')
+}
+
+/// Unmount and eject the removable media that holds `media`
+pub fn eject(media: &Path) -> Result<()> {
+    platform_eject(media)
+}
+
+#[cfg(target_os = "linux")]
+fn platform_eject(media: &Path) -> Result<()> {
+    let mount = super::mounts::mount_for(media)
+        .ok_or_else(|| OrganizerError::plan(media, format!("Can't find the mount of {}", media.display())))?;
+    if mount.mount_point == Path::new("/") || !mount.device.starts_with("/dev/") {
+        return Err(OrganizerError::plan(media, format!("{} isn't on removable media", media.display())));
+    }
+    run("udisksctl", &["unmount", "--block-device", &mount.device])
+        .io_context(media, format!("Failed to unmount {}", media.display()))?;
+    // Partitions on card readers can't always be powered off; unmounted is safe to remove
+    if let Err(e) = run("udisksctl", &["power-off", "--block-device", &mount.device]) {
+        log::debug!("Couldn't power off {}: {:#}", mount.device, e);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn platform_eject(media: &Path) -> Result<()> {
+    run("diskutil", &["eject", &media.to_string_lossy()]).io_context(media, format!("Failed to eject {}", media.display()))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn platform_eject(media: &Path) -> Result<()> {
+    Err(OrganizerError::plan(
+        media,
+        format!("Ejecting isn't supported on this platform; eject {} from the file manager", media.display()),
+    ))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn run(program: &str, args: &[&str]) -> std::io::Result<()> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| std::io::Error::new(e.kind(), format!("Failed to run {}: {}", program, e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(std::io::Error::other(format!("{} {} failed: {}", program, args.join(" "), stderr.trim())));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::config::Config;
+    use super::super::OrganizeOptions;
+
+    #[test]
+    fn test_import_media() {
+        let card = tempfile::tempdir().unwrap();
+        let photos = tempfile::tempdir().unwrap();
+        fs::create_dir_all(card.path().join("DCIM/100CANON")).unwrap();
+        fs::create_dir_all(card.path().join(".Trashes")).unwrap();
+        fs::write(card.path().join("DCIM/100CANON/IMG_0001.JPG"), b"first").unwrap();
+        fs::write(card.path().join("DCIM/100CANON/IMG_0002.JPG"), b"second").unwrap();
+        fs::write(card.path().join(".Trashes/deleted.jpg"), b"deleted").unwrap();
+        let mut organizer = FileOrganizer::with_config(OrganizeOptions::default(), &Config::default()).unwrap();
+
+        let preview = organizer.import_media(card.path(), photos.path(), true).unwrap();
+        assert_eq!(preview.plan.len(), 2);
+        assert!(!photos.path().join(METADATA_DIR).exists());
+
+        let report = organizer.import_media(card.path(), photos.path(), false).unwrap();
+        assert_eq!((report.copied(), report.already_imported, report.last_import), (2, 0, None));
+        assert!(card.path().join("DCIM/100CANON/IMG_0001.JPG").exists());
+        assert!(report.outcomes.iter().all(|outcome| outcome.operation.destination.starts_with(photos.path())));
+
+        // Only the new shot is copied, even with an old one renamed on the card
+        fs::rename(card.path().join("DCIM/100CANON/IMG_0001.JPG"), card.path().join("DCIM/100CANON/renamed.JPG")).unwrap();
+        fs::write(card.path().join("DCIM/100CANON/IMG_0003.JPG"), b"third").unwrap();
+        let report = organizer.import_media(card.path(), photos.path(), false).unwrap();
+        assert_eq!((report.copied(), report.already_imported), (1, 2));
+        assert!(report.last_import.is_some());
+    }
+}
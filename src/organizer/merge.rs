@@ -0,0 +1,162 @@
+//! Folding one organized tree into another, e.g. the Sorted folder from an old laptop into
+//! the one on the new laptop.
+//!
+//! Every file keeps its place in the tree (`Images/2019/a.jpg` goes to
+//! `<destination>/Images/2019/a.jpg`). Where the destination already has a file by that
+//! name, identical content is left alone and different content is moved in as
+//! `a (1).jpg`. The moves are journaled in the destination, so `undo <destination>` puts
+//! them back.
+
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::conflicts;
+use super::error::{IoContext, OrganizerError, Result};
+use super::journal::{self, METADATA_DIR};
+use super::{FileOperation, FileOrganizer, FileOutcome, OperationType};
+
+/// Mode merges are recorded with in the history
+pub const MERGE_MODE: &str = "Merge";
+
+/// What [`FileOrganizer::merge`] did
+#[derive(Debug)]
+pub struct MergeReport {
+    /// Moves into the destination, with renamed destinations for differing files
+    pub plan: Vec<FileOperation>,
+    /// Files left in the source because the destination has identical copies, as
+    /// (source, copy in the destination)
+    pub identical: Vec<(PathBuf, PathBuf)>,
+    /// Planned moves that got a numbered name because a different file had theirs
+    pub renamed: usize,
+    /// One entry per move; empty for a dry run
+    pub outcomes: Vec<FileOutcome>,
+    pub dry_run: bool,
+    /// Journal id for `undo`, if anything was moved
+    pub operation_id: Option<String>,
+}
+
+impl MergeReport {
+    pub fn moved(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| outcome.succeeded()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| outcome.error.is_some()).count()
+    }
+}
+
+impl FileOrganizer {
+    /// Move every file of `source` to the same place in `destination`, skipping files the
+    /// destination already has and renaming differing ones
+    pub fn merge(&mut self, source: &Path, destination: &Path, dry_run: bool) -> Result<MergeReport> {
+        let dry_run = dry_run || self.options.analyze;
+        let canonical = |path: &Path| path.canonicalize().scan_context(path, format!("Can't find {}", path.display()));
+        let (source_dir, destination_dir) = (canonical(source)?, canonical(destination)?);
+        journal::refuse_metadata_dir(&source_dir)?;
+        journal::refuse_metadata_dir(&destination_dir)?;
+        if source_dir.starts_with(&destination_dir) || destination_dir.starts_with(&source_dir) {
+            return Err(OrganizerError::plan(
+                source,
+                format!("Can't merge {} and {}: one is inside the other", source.display(), destination.display()),
+            ));
+        }
+        self.observer.on_scan_started(source);
+
+        let mut report = MergeReport {
+            plan: Vec::new(),
+            identical: Vec::new(),
+            renamed: 0,
+            outcomes: Vec::new(),
+            dry_run,
+            operation_id: None,
+        };
+        let mut found = 0;
+        for entry in WalkDir::new(source)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || e.file_name() != METADATA_DIR)
+        {
+            self.check_cancelled()?;
+            let entry = entry.map_err(|e| OrganizerError::walk(source, e))?;
+            if !entry.file_type().is_file() || self.pins.contains(entry.path()) {
+                continue;
+            }
+            found += 1;
+            self.observer.on_scan_progress(found);
+
+            let file = entry.into_path();
+            let relative = file.strip_prefix(source).unwrap_or(&file);
+            let mut target = destination.join(relative);
+            if target.exists() {
+                if self.hash_file(&target)? == self.hash_file(&file)? {
+                    report.identical.push((file, target));
+                    continue;
+                }
+                target = conflicts::free_name(&target);
+                report.renamed += 1;
+            }
+            report.plan.push(FileOperation { source: file, destination: target, operation_type: OperationType::Move, size: 0 });
+        }
+        self.observer.on_scan_finished(found, report.plan.len());
+
+        if report.plan.is_empty() || dry_run {
+            return Ok(report);
+        }
+        if let Some(shortage) = self.space_shortage(destination, &report.plan) {
+            return Err(OrganizerError::plan(
+                destination,
+                format!("Not enough room in {}: {}; nothing was moved", destination.display(), shortage),
+            ));
+        }
+
+        report.outcomes = self.execute(&report.plan)?;
+        if report.moved() > 0 {
+            report.operation_id = self.journal(destination, MERGE_MODE, &report.outcomes);
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use super::super::config::Config;
+    use super::super::OrganizeOptions;
+
+    fn write(root: &Path, name: &str, contents: &str) {
+        let path = root.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_merge() {
+        let old = tempfile::tempdir().unwrap();
+        let new = tempfile::tempdir().unwrap();
+        write(old.path(), "Images/a.jpg", "same");
+        write(old.path(), "Images/b.jpg", "old b");
+        write(old.path(), "Documents/c.pdf", "c");
+        write(new.path(), "Images/a.jpg", "same");
+        write(new.path(), "Images/b.jpg", "new b");
+        let mut organizer = FileOrganizer::with_config(OrganizeOptions::default(), &Config::default()).unwrap();
+
+        let preview = organizer.merge(old.path(), new.path(), true).unwrap();
+        assert_eq!((preview.plan.len(), preview.identical.len(), preview.renamed), (2, 1, 1));
+        assert!(old.path().join("Documents/c.pdf").exists());
+
+        let report = organizer.merge(old.path(), new.path(), false).unwrap();
+        assert_eq!(report.moved(), 2);
+        assert_eq!(fs::read_to_string(new.path().join("Images/b (1).jpg")).unwrap(), "old b");
+        assert_eq!(fs::read_to_string(new.path().join("Images/b.jpg")).unwrap(), "new b");
+        assert!(new.path().join("Documents/c.pdf").exists());
+        assert!(old.path().join("Images/a.jpg").exists());
+
+        let undo = organizer.undo(new.path(), false).unwrap().unwrap();
+        assert_eq!(undo.restored(), 2);
+        assert_eq!(fs::read_to_string(old.path().join("Images/b.jpg")).unwrap(), "old b");
+        assert!(old.path().join("Documents/c.pdf").exists());
+
+        assert!(organizer.merge(new.path(), &new.path().join("Images"), true).is_err());
+    }
+}
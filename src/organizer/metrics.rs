@@ -0,0 +1,154 @@
+//! Prometheus metrics for `watch --metrics-listen` (the `metrics` feature), for watches running
+//! unattended on a NAS or home server. A plain HTTP endpoint serves `/metrics` in the text
+//! exposition format, with one series per watched directory:
+//!
+//! - `file_organizer_files_organized_total`: files moved into place
+//! - `file_organizer_bytes_moved_total`: their total size
+//! - `file_organizer_errors_total`: files that couldn't be moved, and rounds that failed
+//! - `file_organizer_queue_depth`: files written and waiting to be organized (while paused or
+//!   a sync client is at work)
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Counters of one watched directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirectoryMetrics {
+    pub files_organized: u64,
+    pub bytes_moved: u64,
+    pub errors: u64,
+    pub queue_depth: u64,
+}
+
+/// Metrics shared with the thread serving them
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    directories: Arc<Mutex<BTreeMap<PathBuf, DirectoryMetrics>>>,
+    address: Option<SocketAddr>,
+}
+
+const SERIES: [(&str, &str, &str); 4] = [
+    ("file_organizer_files_organized_total", "counter", "Files moved into place."),
+    ("file_organizer_bytes_moved_total", "counter", "Total size of the files moved into place."),
+    ("file_organizer_errors_total", "counter", "Files that couldn't be moved, and rounds that failed."),
+    ("file_organizer_queue_depth", "gauge", "Files written and waiting to be organized."),
+];
+
+impl Metrics {
+    /// Serve the metrics over HTTP at `address` (port 0 picks a free one)
+    pub fn serve(address: SocketAddr) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let metrics = Self { address: Some(listener.local_addr()?), ..Self::default() };
+        let served = metrics.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                if let Err(e) = served.answer(stream) {
+                    log::debug!("Metrics request failed: {}", e);
+                }
+            }
+        });
+        Ok(metrics)
+    }
+
+    /// Where the metrics are served
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.address
+    }
+
+    /// Change the counters of `directory`
+    pub fn update(&self, directory: &Path, change: impl FnOnce(&mut DirectoryMetrics)) {
+        let mut directories = self.directories.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        change(directories.entry(directory.to_path_buf()).or_default());
+    }
+
+    /// The metrics in the Prometheus text format
+    pub fn render(&self) -> String {
+        let directories = self.directories.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut text = String::new();
+        for (index, (name, kind, help)) in SERIES.iter().enumerate() {
+            let _ = writeln!(text, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+            for (directory, metrics) in directories.iter() {
+                let value = [metrics.files_organized, metrics.bytes_moved, metrics.errors, metrics.queue_depth][index];
+                let _ = writeln!(text, "{}{{directory=\"{}\"}} {}", name, label_value(directory), value);
+            }
+        }
+        text
+    }
+
+    fn answer(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // The headers don't matter, but have to be read before answering
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (status, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some("/metrics")) => ("200 OK", self.render()),
+            _ => ("404 Not Found", "Metrics are at /metrics\n".to_string()),
+        };
+        let mut stream = &stream;
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+}
+
+/// A path as a label value, with `\`, `"` and newlines escaped
+fn label_value(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', r"\\").replace('"', "\\\"").replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.update(Path::new("/srv/inbox"), |counters| {
+            counters.files_organized += 3;
+            counters.bytes_moved += 4096;
+        });
+        metrics.update(Path::new("/srv/\"odd\""), |counters| counters.queue_depth = 2);
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE file_organizer_files_organized_total counter\n"));
+        assert!(text.contains("file_organizer_files_organized_total{directory=\"/srv/inbox\"} 3\n"));
+        assert!(text.contains("file_organizer_bytes_moved_total{directory=\"/srv/inbox\"} 4096\n"));
+        assert!(text.contains("file_organizer_queue_depth{directory=\"/srv/\\\"odd\\\"\"} 2\n"));
+    }
+
+    #[test]
+    fn test_serve() {
+        let metrics = Metrics::serve("127.0.0.1:0".parse().unwrap()).unwrap();
+        metrics.update(Path::new("/srv/inbox"), |counters| counters.errors += 1);
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(metrics.local_addr().unwrap()).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: nas\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let response = get("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(&metrics.render()));
+        assert!(response.contains("file_organizer_errors_total{directory=\"/srv/inbox\"} 1\n"));
+        assert!(get("/").starts_with("HTTP/1.1 404"));
+    }
+}
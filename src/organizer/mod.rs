@@ -1,41 +1,332 @@
+#[cfg(feature = "async")]
+pub mod async_api;
+pub mod ambiguous;
+pub mod attributes;
+pub mod backend;
+pub mod budget;
+pub mod bursts;
+pub mod clean;
+pub mod conditions;
+pub mod config;
+pub mod conflicts;
+pub mod control;
+pub mod dates;
+pub mod dedupe;
+pub mod durable;
+pub mod ebooks;
+pub mod error;
+pub mod exif_time;
+pub mod extensionless;
+pub mod file_events;
 pub mod file_types;
+pub mod finder_tags;
+pub mod fonts;
+pub mod hardlinks;
+pub mod hashing;
+pub mod journal;
+pub mod long_paths;
+pub mod manifest;
+pub mod media_import;
+pub mod merge;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod mounts;
+pub mod multipart;
+pub mod names;
+pub mod observer;
+pub mod origin;
+pub mod os_path;
+pub mod pins;
+#[cfg(feature = "plugins")]
+pub mod plugins;
+pub mod priority;
+pub mod progress;
+pub mod resume;
+pub mod rules;
+pub mod sidecars;
+pub mod skips;
+pub mod space;
+pub mod sparse;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_store;
+pub mod stats;
+pub mod syncs;
+pub mod store;
+pub mod templates;
+pub mod timings;
+pub mod trash;
+pub mod twins;
+pub mod units;
+#[cfg(target_os = "linux")]
+pub mod uring;
 
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
-use anyhow::{Context, Result};
-use colored::Colorize;
+use std::time::Instant;
 use walkdir::WalkDir;
 
-use crate::OrganizeMode;
-use file_types::{FileTypeClassifier, FileSizeCategory};
+use attributes::FileAttributes;
+use backend::{Backend, Renamer};
+use config::{Config, NoExtension};
+use conflicts::{ConflictChoice, ConflictPolicy};
+use dates::{DateBucket, DateFallbacks, DateSource};
+use ebooks::BookMetadata;
+use hardlinks::HardLinks;
+use error::{IoContext, OrganizerError, Result};
+use journal::Journal;
+use names::NamePredicates;
+use resume::ResumeState;
+use pins::Pins;
+use stats::RunStats;
+use file_types::{CategoryNames, FileCategory, FileTypeClassifier, FileSizeCategory, SizeBuckets};
+use observer::{CategoryChoice, Notice, OrganizeObserver, SilentObserver};
+use rules::{RuleDecision, RuleSet};
+use sidecars::SidecarPairs;
+use skips::{ScanError, SkipReason, SkippedFile};
+use store::HistoryStore;
+use templates::FolderTemplate;
+
+/// How files are grouped into folders
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum OrganizeMode {
+    /// Organize by file extension (default)
+    Extension,
+    /// Organize by file size
+    Size,
+    /// Organize by creation date
+    Date,
+    /// Organize by modification date
+    Modified,
+    /// Organize by last access date, to separate stale files from ones in use
+    Accessed,
+    /// Organize downloads by the domain they were downloaded from
+    Origin,
+    /// Use custom rules from config file
+    Custom,
+}
 
 /// Represents a file operation to be performed
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct FileOperation {
+    #[serde(with = "os_path")]
     pub source: PathBuf,
+    #[serde(with = "os_path")]
     pub destination: PathBuf,
     pub operation_type: OperationType,
+    /// Size of the source as the scan saw it; 0 for plans that weren't made from a scan
+    #[serde(default)]
+    pub size: u64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OperationType {
     Move,
     Copy,
 }
 
+/// A file a `delete` rule deletes
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PlannedDeletion {
+    #[serde(with = "os_path")]
+    pub path: PathBuf,
+    /// Name of the rule
+    pub rule: String,
+}
+
+/// Files custom rules take out of the moves
+#[derive(Debug, Default)]
+struct RuledOut {
+    skipped: Vec<PathBuf>,
+    deletions: Vec<PlannedDeletion>,
+}
+
+/// Optional behaviors that refine how files are organized
+#[derive(Debug, Clone, Default)]
+pub struct OrganizeOptions {
+    /// Sort ebooks into `Books/{author}/{title}/` instead of a flat Books folder
+    pub books_by_author: bool,
+    /// Sort fonts into `Fonts/{family}/`, keeping web fonts and duplicate faces apart
+    pub fonts_by_family: bool,
+    /// Group photos taken within this many seconds of each other into `Bursts/<timestamp>/`
+    pub burst_window: Option<u64>,
+    /// Which timestamp Date mode uses
+    pub date_source: DateSource,
+    /// Folder granularity for the date modes, unless the config sets a folder template
+    pub date_buckets: DateBucket,
+    /// Keep each file's subdirectories beneath its category (`Images/vacation/img.jpg`)
+    /// instead of flattening recursive runs
+    pub preserve_structure: bool,
+    /// Organize every directory of a recursive run in place, making category folders inside
+    /// each subdirectory rather than at the top
+    pub per_directory: bool,
+    /// What happens when a destination (or, for undo, an original location) is taken
+    pub on_conflict: ConflictPolicy,
+    /// Ask the observer about each destination folder before moving anything
+    pub ask_per_category: bool,
+    /// Whether a failed move stops the run
+    pub on_error: ErrorPolicy,
+    /// Only organize files of these types (classified by extension, whatever the mode);
+    /// empty means every type
+    pub only_categories: Vec<FileCategory>,
+    /// Extensions (without the dot) never organized; exclusions win over the other filters
+    pub exclude_extensions: Vec<String>,
+    /// Categories never organized
+    pub exclude_categories: Vec<FileCategory>,
+    /// Only organize files whose names are too long or have unusual characters; empty means
+    /// every name
+    pub name_predicates: NamePredicates,
+    /// Save progress to a resume file every this many operations, so an interrupted run can
+    /// continue with [`FileOrganizer::resume`]
+    pub checkpoint_every: Option<usize>,
+    /// Refuse to run if the filesystem would be left with fewer free bytes than this (runs
+    /// that would run out of inodes are always refused)
+    pub min_free: Option<u64>,
+    /// Read-only analysis: every run is a dry run, the history is opened read-only and
+    /// nothing is ever written (no folders, journal or resume file), so it's safe on
+    /// read-only mounts
+    pub analyze: bool,
+    /// Also organize hidden files: dot files, and files with the hidden attribute on Windows
+    /// and macOS. System files (`Thumbs.db`, the Windows system attribute) are always left alone
+    pub include_hidden: bool,
+    /// Also organize cloud placeholders (OneDrive, Dropbox and iCloud files whose contents
+    /// aren't on disk), which downloads their contents; without it they're left alone
+    pub hydrate: bool,
+    /// Keep file reads to a minimum for network shares (SMB, NFS): files are classified and
+    /// placed by name and metadata alone. No content hashing (duplicate fonts aren't told
+    /// apart), no font, EPUB or EXIF metadata (so no bursts), and no look at the first bytes of
+    /// ambiguous extensions or files without one. File I/O is one file at a time either way
+    pub network_friendly: bool,
+    /// Checksum for duplicate detection and manifests
+    pub hash_algorithm: hashing::HashAlgorithm,
+    /// Fail when part of the directory can't be read (an unreadable subdirectory, say),
+    /// instead of organizing the rest and reporting it
+    pub strict_scan: bool,
+    /// Stay on the filesystem of the target directory in recursive runs, like `find -xdev`:
+    /// mount points below it (backup drives, network mounts) aren't descended into
+    pub one_file_system: bool,
+    /// Flush every moved file and its folders to disk before going on to the next, and save
+    /// progress for `--resume` after each one, so a power loss can't lose files or the undo
+    /// history. Much slower on spinning disks
+    pub durable: bool,
+    /// How files are renamed; [`Backend::Uring`] batches them through io_uring on Linux
+    pub backend: Backend,
+    /// Give every organized file a Finder tag named after its category (macOS)
+    pub finder_tags: bool,
+    /// Only carry out a plan with this [`OrganizePlan::id`], the one a dry run showed; a plan
+    /// that comes out differently (the files changed since) is refused before anything moves
+    pub plan_id: Option<String>,
+    /// Two-stage organizing: files modified within this long go to `Pending/` first, and on
+    /// to their category once they've been left unchanged that long
+    pub pending_for: Option<std::time::Duration>,
+    /// Delete what `delete` rules match for good, instead of moving it to the trash
+    pub permanent_delete: bool,
+}
+
+/// Folder at the top of the target directory that recently changed files wait in, with
+/// [`OrganizeOptions::pending_for`]
+pub const PENDING_DIR: &str = "Pending";
+
+/// What happens to the rest of a run when a file can't be moved
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorPolicy {
+    /// Move every file that can be moved and report the failures at the end
+    #[default]
+    KeepGoing,
+    /// Stop at the first failure, leaving the files moved so far where they are
+    FailFast,
+    /// Stop at the first failure and move the files moved so far back
+    Rollback,
+}
+
+type CancelCheck = Box<dyn Fn() -> bool + Send>;
+
 /// Main file organizer struct
 pub struct FileOrganizer {
     classifier: FileTypeClassifier,
-    operations: Vec<FileOperation>,
+    size_buckets: Option<SizeBuckets>,
+    folder_template: Option<FolderTemplate>,
+    rules: RuleSet,
+    sidecars: SidecarPairs,
+    pins: Pins,
+    options: OrganizeOptions,
+    history: Box<dyn HistoryStore>,
+    hash_cache: hashing::CacheStats,
+    observer: Box<dyn OrganizeObserver>,
+    cancelled: Option<CancelCheck>,
+    /// Files the scan is limited to, for [`FileOrganizer::organize_files`]
+    only_files: Option<Vec<PathBuf>>,
 }
 
 impl FileOrganizer {
     /// Create a new file organizer
     pub fn new() -> Self {
-        Self {
-            classifier: FileTypeClassifier::new(),
-            operations: Vec::new(),
+        Self::with_config(OrganizeOptions::default(), &Config::default())
+            .expect("default configuration is always valid")
+    }
+
+    /// Create a file organizer with the given options and user configuration
+    pub fn with_config(options: OrganizeOptions, config: &Config) -> Result<Self> {
+        let history = if options.analyze { store::open_read_only(&config.history)? } else { store::open(&config.history)? };
+        let names = CategoryNames::from_config(&config.categories)?;
+        let mut classifier = FileTypeClassifier::with_config(&config.categories).with_names(names.clone());
+        let mut rule_dates = DateSource::Exif;
+        if options.network_friendly {
+            classifier = classifier.without_content_checks();
+            rule_dates = rule_dates.without_exif();
+        }
+        Ok(Self {
+            classifier,
+            size_buckets: SizeBuckets::from_config(&config.size_buckets),
+            folder_template: config.dates.folder_template.as_deref().map(FolderTemplate::parse).transpose()?,
+            rules: RuleSet::new(&config.rules, &config.plugins)?
+                .with_default(&config.default_rule)
+                .with_category_names(names)
+                .with_date_source(rule_dates),
+            sidecars: SidecarPairs::from_config(&config.sidecars),
+            pins: Pins::from_config(&config.pins),
+            options,
+            history,
+            hash_cache: hashing::CacheStats::default(),
+            observer: Box::new(SilentObserver),
+            cancelled: None,
+            only_files: None,
+        })
+    }
+
+    /// How many of the files hashed so far came from the hash cache
+    pub fn hash_cache_stats(&self) -> &hashing::CacheStats {
+        &self.hash_cache
+    }
+
+    /// Forget every cached file hash, returning how many there were
+    pub fn clear_hash_cache(&self) -> Result<usize> {
+        self.history.clear_hash_cache()
+    }
+
+    /// Report scanning, planning and execution progress to an observer
+    pub fn with_observer(mut self, observer: impl OrganizeObserver + 'static) -> Self {
+        self.observer = Box::new(observer);
+        self
+    }
+
+    /// Fail in analysis mode, before anything on disk changes; `path` is what would have
+    fn check_writable(&self, path: &Path) -> Result<()> {
+        if self.options.analyze {
+            return Err(OrganizerError::plan(path, "Analysis mode never changes files"));
+        }
+        Ok(())
+    }
+
+    /// Fail with [`OrganizerError::Cancelled`] if the caller asked us to stop
+    fn check_cancelled(&self) -> Result<()> {
+        match &self.cancelled {
+            Some(cancelled) if cancelled() => Err(OrganizerError::Cancelled),
+            _ => Ok(()),
         }
     }
 
@@ -47,266 +338,1546 @@ impl FileOrganizer {
         recursive: bool,
         filters: Option<&Vec<String>>,
         dry_run: bool,
-    ) -> Result<OrganizationSummary> {
-        println!("🔍 Scanning directory: {}", target_dir.display().to_string().cyan());
+    ) -> Result<OrganizeReport> {
+        let dry_run = dry_run || self.options.analyze;
+        let mut plan = self.plan(target_dir, mode, recursive, filters)?;
+        if let Some(expected) = &self.options.plan_id {
+            let id = plan.id();
+            if !id.eq_ignore_ascii_case(expected) {
+                return Err(OrganizerError::plan(
+                    target_dir,
+                    format!("The plan changed since it was previewed (plan {} is now {}); nothing was moved", expected, id),
+                ));
+            }
+        }
+        if plan.operations.is_empty() && plan.deletions.is_empty() {
+            return Ok(OrganizeReport::from_plan(plan, Vec::new(), &[], dry_run, &self.classifier));
+        }
+
+        self.observer.on_plan_ready(&plan.operations, mode);
+        if self.options.ask_per_category && !dry_run {
+            self.review_categories(target_dir, &mut plan);
+            if plan.operations.is_empty() && plan.deletions.is_empty() {
+                return Ok(OrganizeReport::from_plan(plan, Vec::new(), &[], dry_run, &self.classifier));
+            }
+        }
+
+        if let Some(shortage) = self.space_shortage(target_dir, &plan.operations) {
+            if !dry_run {
+                return Err(OrganizerError::plan(
+                    target_dir,
+                    format!("Not enough room in {}: {}; nothing was moved", target_dir.display(), shortage),
+                ));
+            }
+            self.notify(&mut plan, Notice::LowSpace(shortage));
+        }
+
+        // Execute the operations
+        let started = Instant::now();
+        let (outcomes, created_dirs) = if dry_run {
+            (Vec::new(), Vec::new())
+        } else {
+            if self.checkpoint_every().is_some() && resume::resume_path(target_dir).exists() {
+                return Err(OrganizerError::plan(
+                    target_dir,
+                    format!(
+                        "An interrupted run in {} can be continued with --resume (or delete {})",
+                        target_dir.display(),
+                        resume::resume_path(target_dir).display()
+                    ),
+                ));
+            }
+            let state = ResumeState::new(target_dir, mode, &plan.operations);
+            if plan.operations.is_empty() {
+                (Vec::new(), Vec::new())
+            } else {
+                self.execute_resumable(target_dir, &plan.operations, &state)?
+            }
+        };
+        // A run that stopped at a failure (and maybe put everything back) deletes nothing either
+        let stopped = self.options.on_error != ErrorPolicy::KeepGoing && outcomes.iter().any(|outcome| outcome.error.is_some());
+        let deleted = if dry_run || stopped { Vec::new() } else { self.delete_all(&plan.deletions)? };
+
+        let mut report = OrganizeReport::from_plan(plan, outcomes, &created_dirs, dry_run, &self.classifier);
+        report.deleted = deleted;
+        if !dry_run {
+            report.summary.timings.execute_secs = started.elapsed().as_secs_f64();
+        }
+        self.record(target_dir, mode, &mut report, &[]);
+        self.observer.on_finished(&report.summary, dry_run);
+
+        Ok(report)
+    }
+
+    /// Organize only `files` of `target_dir`, as [`FileOrganizer::organize`] would if they
+    /// were all its scan found. `watch` uses this for the files written since its last round,
+    /// leaving alone files other programs still have open. Files that are gone, or outside
+    /// `target_dir` (below it only if `recursive`), are left out.
+    pub fn organize_files(
+        &mut self,
+        target_dir: &Path,
+        mode: &OrganizeMode,
+        recursive: bool,
+        files: &[PathBuf],
+    ) -> Result<OrganizeReport> {
+        self.only_files = Some(files.to_vec());
+        let report = self.organize(target_dir, mode, recursive, None, false);
+        self.only_files = None;
+        report
+    }
+
+    /// Continue the interrupted run in `target_dir` from its resume file.
+    ///
+    /// The remaining operations are checked first: files found at their destination already
+    /// count as moved, files that are gone are dropped. Returns `None` if no run was
+    /// interrupted.
+    pub fn resume(&mut self, target_dir: &Path) -> Result<Option<OrganizeReport>> {
+        let Some(mut state) = ResumeState::load(target_dir)? else {
+            return Ok(None);
+        };
+        let revalidation = state.revalidate(target_dir);
+        self.observer.on_notice(&Notice::Resumed {
+            moved: state.moved.len(),
+            remaining: state.remaining.len(),
+            vanished: revalidation.vanished,
+        });
+
+        let operations = state.operations(target_dir);
+        if let Some(shortage) = self.space_shortage(target_dir, &operations) {
+            return Err(OrganizerError::plan(
+                target_dir,
+                format!("Not enough room in {} to resume: {}", target_dir.display(), shortage),
+            ));
+        }
+        let earlier = state.moved_outcomes(target_dir);
+        if !operations.is_empty() {
+            self.observer.on_plan_ready(&operations, &state.mode);
+        }
+        let started = Instant::now();
+        let (outcomes, created_dirs) = self.execute_resumable(target_dir, &operations, &state)?;
+
+        let plan = OrganizePlan { operations, ..OrganizePlan::default() };
+        let mut report = OrganizeReport::from_plan(plan, outcomes, &created_dirs, false, &self.classifier);
+        report.summary.timings.execute_secs = started.elapsed().as_secs_f64();
+        self.record(target_dir, &state.mode, &mut report, &earlier);
+        self.observer.on_finished(&report.summary, false);
+        Ok(Some(report))
+    }
+
+    /// [`FileOrganizer::execute`], checkpointing to the resume file if the options ask for it.
+    ///
+    /// The file is removed once execution returns normally; after cancellation or an error
+    /// it stays so the run can be resumed.
+    fn execute_resumable(
+        &self,
+        target_dir: &Path,
+        operations: &[FileOperation],
+        state: &ResumeState,
+    ) -> Result<(Vec<FileOutcome>, Vec<PathBuf>)> {
+        self.check_writable(target_dir)?;
+        let Some(every) = self.checkpoint_every() else {
+            return self.execute_with(operations, |_| {});
+        };
+
+        let save = |state: &ResumeState| {
+            if let Err(e) = state.save(target_dir, self.options.durable) {
+                log::warn!("Couldn't save progress for --resume: {:#}", e);
+            }
+        };
+        save(state);
+        let executed = self.execute_with(operations, |outcomes| {
+            if outcomes.len().is_multiple_of(every.max(1)) {
+                save(&state.checkpoint(target_dir, outcomes));
+            }
+        })?;
+        ResumeState::remove(target_dir)?;
+        Ok(executed)
+    }
+
+    /// How often progress is saved for `--resume`: after every file for durable runs
+    fn checkpoint_every(&self) -> Option<usize> {
+        if self.options.durable {
+            return Some(1);
+        }
+        self.options.checkpoint_every
+    }
+
+    /// Journal the moves of a finished run (`earlier` holds those of interrupted sessions)
+    fn record(&self, target_dir: &Path, mode: &OrganizeMode, report: &mut OrganizeReport, earlier: &[FileOutcome]) {
+        if report.moved() + earlier.len() == 0 {
+            return;
+        }
+        let all: Vec<FileOutcome> = earlier.iter().chain(&report.outcomes).cloned().collect();
+        report.operation_id = self.journal(target_dir, &format!("{:?}", mode), &all);
+    }
+
+    /// Add the successful moves among `outcomes` to the history, returning the journal id.
+    /// Failing to do so doesn't fail the run, which already happened.
+    fn journal(&self, target_dir: &Path, mode: &str, outcomes: &[FileOutcome]) -> Option<String> {
+        let mut journal = Journal::from_outcomes(target_dir, mode, outcomes);
+        journal.stats = Some(RunStats::from_outcomes(target_dir, &self.classifier, outcomes));
+        let saved = self.history.insert(target_dir, &mut journal).and_then(|_| match self.options.durable {
+            true => self.history.sync(target_dir, &journal.id),
+            false => Ok(()),
+        });
+        match saved {
+            Ok(_) => Some(journal.id),
+            Err(e) => {
+                log::warn!("Files were moved, but undo won't be possible: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// Move the files of the most recent run in `target_dir` back where they came from.
+    ///
+    /// Returns `None` if there's nothing to undo. Files whose original location is taken
+    /// again are handled by the conflict policy; entries that couldn't be restored stay in
+    /// the journal so a later undo can retry them.
+    pub fn undo(&self, target_dir: &Path, dry_run: bool) -> Result<Option<UndoReport>> {
+        let Some(journal) = self.history.latest(target_dir)? else {
+            return Ok(None);
+        };
+        self.undo_journal(target_dir, journal, dry_run).map(Some)
+    }
+
+    /// Like [`FileOrganizer::undo`], for the run with history id `id` (as printed after each
+    /// run and listed by `history`) rather than the most recent one
+    pub fn undo_operation(&self, target_dir: &Path, id: &str, dry_run: bool) -> Result<UndoReport> {
+        let journal = self.history.journals(target_dir)?.into_iter().find(|journal| journal.id == id);
+        let Some(journal) = journal else {
+            return Err(OrganizerError::journal(format!("No operation {} in the history of {}", id, target_dir.display())));
+        };
+        self.undo_journal(target_dir, journal, dry_run)
+    }
+
+    fn undo_journal(&self, target_dir: &Path, mut journal: Journal, dry_run: bool) -> Result<UndoReport> {
+        let mut missing = Vec::new();
+        let mut operations = Vec::new();
+        for entry in journal.moves.iter().rev() {
+            let current = entry.destination_in(target_dir);
+            if current.exists() {
+                operations.push(FileOperation {
+                    source: current,
+                    destination: entry.source_in(target_dir),
+                    operation_type: OperationType::Move,
+                    size: 0,
+                });
+            } else {
+                missing.push(entry.clone());
+            }
+        }
+
+        let mut report = UndoReport {
+            operation_id: journal.id.clone(),
+            operations,
+            missing,
+            outcomes: Vec::new(),
+            dry_run,
+        };
+        if dry_run {
+            return Ok(report);
+        }
+
+        if !report.operations.is_empty() {
+            report.outcomes = self.execute(&report.operations)?;
+        }
+
+        // Keep what failed or was skipped for another try; restored and vanished files are done
+        let left: Vec<&FileOutcome> = report.outcomes.iter().filter(|outcome| !outcome.succeeded()).collect();
+        journal.moves.retain(|entry| {
+            left.iter().any(|outcome| outcome.operation.destination == entry.source_in(target_dir))
+        });
+        if journal.moves.is_empty() {
+            self.history.remove(target_dir, &journal.id)?;
+        } else {
+            self.history.save(target_dir, &journal)?;
+        }
+
+        Ok(report)
+    }
+
+    /// Scan the directory and work out where every file should go, without touching anything
+    pub fn plan(
+        &self,
+        target_dir: &Path,
+        mode: &OrganizeMode,
+        recursive: bool,
+        filters: Option<&Vec<String>>,
+    ) -> Result<OrganizePlan> {
+        self.check_cancelled()?;
+        journal::refuse_metadata_dir(target_dir)?;
+        self.check_filters(target_dir, filters.map(Vec::as_slice))?;
+        self.observer.on_scan_started(target_dir);
+        let started = Instant::now();
         
         // Collect all files to organize
-        let files_to_organize = self.collect_files(target_dir, recursive)?;
-        println!("📁 Found {} files to process", files_to_organize.len().to_string().yellow());
+        let mut scan = self.collect_files(target_dir, recursive)?;
+        let mut plan = OrganizePlan {
+            skipped: std::mem::take(&mut scan.skipped),
+            scan_errors: std::mem::take(&mut scan.errors),
+            ..OrganizePlan::default()
+        };
+        plan.timings.scan_secs = started.elapsed().as_secs_f64();
+        let files_to_organize = &scan.files;
         
         // Filter files if filters are provided
-        let filtered_files = if let Some(filter_list) = filters {
-            self.filter_files(&files_to_organize, filter_list)
-        } else {
-            files_to_organize
-        };
+        let mut filtered_files = Vec::new();
+        for file in files_to_organize {
+            match self.filter_reason(file, filters) {
+                Some(reason) => plan.skipped.push(SkippedFile::new(file, reason)),
+                None => filtered_files.push(file.clone()),
+            }
+        }
+        self.observer.on_scan_finished(files_to_organize.len(), filtered_files.len());
 
+        let placeholders = plan.skipped.iter().filter(|skipped| skipped.reason == SkipReason::Placeholder).count();
+        if placeholders > 0 {
+            self.notify(&mut plan, Notice::PlaceholdersSkipped(placeholders));
+        }
         if filtered_files.is_empty() {
-            println!("ℹ️  No files to organize after filtering");
-            return Ok(OrganizationSummary::new());
+            plan.timings.plan_secs = started.elapsed().as_secs_f64() - plan.timings.scan_secs;
+            self.observer.on_files_skipped(&plan.skipped);
+            return Ok(plan);
+        }
+
+        if matches!(mode, OrganizeMode::Custom) && self.rules.is_empty() {
+            self.notify(&mut plan, Notice::NoCustomRules);
         }
 
-        println!("🎯 Processing {} files after filtering", filtered_files.len().to_string().green());
+        if matches!(mode, OrganizeMode::Accessed) {
+            if let Some(option) = mounts::mount_for(target_dir).and_then(|mount| lazy_atime_option(&mount)) {
+                self.notify(&mut plan, Notice::LazyAccessTimes { mount_option: option.to_string() });
+            }
+        }
 
         // Plan the organization
-        let operations = self.plan_organization(&filtered_files, target_dir, mode)?;
-        
-        // Show preview
-        self.show_preview(&operations, mode);
-        
-        if dry_run {
-            println!("🔍 {} This was a dry run - no files were moved", "DRY RUN:".bold().yellow());
-            return Ok(OrganizationSummary::from_operations(&operations));
+        let mut date_fallbacks = DateFallbacks::default();
+        let mut ruled_out = RuledOut::default();
+        let mut operations =
+            self.plan_organization(&filtered_files, &scan.metadata, target_dir, mode, &mut date_fallbacks, &mut ruled_out)?;
+        if !ruled_out.skipped.is_empty() {
+            self.notify(&mut plan, Notice::SkippedByRules(ruled_out.skipped.len()));
+            plan.skipped.extend(ruled_out.skipped.into_iter().map(|file| SkippedFile::new(file, SkipReason::Rule)));
+        }
+        plan.deletions = ruled_out.deletions;
+        if date_fallbacks.total() > 0 {
+            let preferred = self.date_source().preferred();
+            self.notify(&mut plan, Notice::DateFallbacks { preferred, fallbacks: date_fallbacks });
         }
 
-        // Execute the operations
-        self.execute_operations(&operations)?;
-        
-        let summary = OrganizationSummary::from_operations(&operations);
-        self.show_completion_summary(&summary);
-        
-        Ok(summary)
+        // Bursts are told apart by EXIF capture times, which network-friendly runs don't read
+        if let Some(window_secs) = self.options.burst_window.filter(|_| !self.options.network_friendly) {
+            let bursts = self.group_bursts(&mut operations, window_secs);
+            if bursts > 0 {
+                self.notify(&mut plan, Notice::BurstsGrouped(bursts));
+            }
+        }
+
+        if let Some(pending_for) = self.options.pending_for {
+            let still_pending = stage(&mut operations, &scan.metadata, target_dir, pending_for);
+            plan.skipped.extend(still_pending.into_iter().map(|file| SkippedFile::new(file, SkipReason::Pending)));
+        }
+
+        // Keep subtitles, .nfo and .xmp files next to their primaries
+        self.sidecars.pair(&mut operations, files_to_organize, &scan.metadata);
+
+        // Split archives move as one unit; incomplete sets are left in place
+        let incomplete_archives = multipart::group_parts(&mut operations);
+        for group in &incomplete_archives {
+            plan.skipped.extend(group.present.iter().map(|part| SkippedFile::new(part, SkipReason::IncompleteArchive)));
+        }
+        if !incomplete_archives.is_empty() {
+            self.notify(&mut plan, Notice::IncompleteArchives(incomplete_archives));
+        }
+
+        // Folders that exist already under a name without (or with) the emoji are reused
+        let twin_folders = twins::merge(&mut operations);
+        if !twin_folders.is_empty() {
+            self.notify(&mut plan, Notice::TwinFolders(twin_folders));
+        }
+
+        // The same files give the same plan (and preview, and JSON) on every run
+        operations.sort_by(|a, b| {
+            (a.destination.parent(), a.destination.file_name(), &a.source)
+                .cmp(&(b.destination.parent(), b.destination.file_name(), &b.source))
+        });
+
+        let conflicts = conflicts::detect(&operations);
+        if !conflicts.is_empty() {
+            // Renamed here rather than as they move, so the preview shows the final names
+            if self.options.on_conflict == ConflictPolicy::Rename {
+                conflicts::rename_planned(&mut operations, &conflicts);
+            }
+            self.notify(&mut plan, Notice::Conflicts { conflicts, policy: self.options.on_conflict });
+        }
+
+        if !plan.deletions.is_empty() {
+            let deletions = plan.deletions.clone();
+            self.notify(&mut plan, Notice::Deletions { deletions, permanent: self.options.permanent_delete });
+        }
+
+        for op in &operations {
+            log::debug!("Planned {:?} -> {:?}", op.source, op.destination);
+        }
+        plan.operations = operations;
+        plan.timings.plan_secs = started.elapsed().as_secs_f64() - plan.timings.scan_secs;
+        self.observer.on_files_skipped(&plan.skipped);
+        Ok(plan)
+    }
+
+    /// The operations whose destination folder the observer approves, asking folder by folder
+    fn review_categories(&self, target_dir: &Path, plan: &mut OrganizePlan) {
+        let folder_of = |op: &FileOperation| {
+            stats::category_folder(target_dir, &op.source, &op.destination).unwrap_or_default()
+        };
+        let mut folders = std::collections::BTreeMap::new();
+        for op in &plan.operations {
+            *folders.entry(folder_of(op)).or_insert(0) += 1;
+        }
+
+        let mut approved = std::collections::HashSet::new();
+        let (mut answer_all, mut quit) = (false, false);
+        for (folder, files) in folders {
+            if answer_all {
+                approved.insert(folder);
+                continue;
+            }
+            match self.observer.on_category_review(&folder, files) {
+                CategoryChoice::Yes => {
+                    approved.insert(folder);
+                }
+                CategoryChoice::No => {}
+                CategoryChoice::All => {
+                    answer_all = true;
+                    approved.insert(folder);
+                }
+                CategoryChoice::Quit => {
+                    quit = true;
+                    break;
+                }
+            }
+        }
+        plan.operations.retain(|op| approved.contains(&folder_of(op)));
+
+        // Deletions get a question of their own, after the folders, which even `a` doesn't answer
+        let delete = !plan.deletions.is_empty() && !quit && self.observer.on_deletion_review(plan.deletions.len(), self.options.permanent_delete);
+        if !delete {
+            plan.deletions.clear();
+        }
+    }
+
+    /// What the filesystem of `target_dir` lacks for `operations`; nothing if its free space
+    /// can't be determined
+    fn space_shortage(&self, target_dir: &Path, operations: &[FileOperation]) -> Option<space::SpaceShortage> {
+        let free = space::free_space(target_dir)?;
+        free.shortage(target_dir, operations, self.options.min_free)
+    }
+
+    fn notify(&self, plan: &mut OrganizePlan, notice: Notice) {
+        self.observer.on_notice(&notice);
+        plan.notices.push(notice);
     }
 
-    /// Collect all files in the directory
-    fn collect_files(&self, target_dir: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
-        let mut files = Vec::new();
+    /// Collect all files in the directory, keeping the metadata the directory listing
+    /// already has so planning doesn't stat every file again
+    fn collect_files(&self, target_dir: &Path, recursive: bool) -> Result<Scan> {
+        let mut scan = Scan::default();
         
-        if recursive {
+        if let Some(files) = &self.only_files {
+            for file in files {
+                let Ok(relative) = file.strip_prefix(target_dir) else {
+                    continue;
+                };
+                let nested = relative.parent().is_some_and(|parent| !parent.as_os_str().is_empty());
+                if (nested && !recursive) || relative.starts_with(journal::METADATA_DIR) {
+                    continue;
+                }
+                self.scan_entry(&mut scan, file.clone(), None);
+            }
+        } else if recursive {
             for entry in WalkDir::new(target_dir)
+                .same_file_system(self.options.one_file_system)
                 .into_iter()
-                .filter_map(|e| e.ok())
+                .filter_entry(|e| e.depth() == 0 || e.file_name() != journal::METADATA_DIR)
             {
-                let path = entry.path();
-                if path.is_file() && !self.classifier.should_ignore(path) {
-                    files.push(path.to_path_buf());
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    // Not even the directory itself could be read
+                    Err(error) if error.depth() == 0 => return Err(OrganizerError::walk(target_dir, error)),
+                    Err(error) => {
+                        let path = error.path().unwrap_or(target_dir).to_path_buf();
+                        self.scan_failure(&mut scan, &path, walk_io_error(&error))?;
+                        continue;
+                    }
+                };
+                match entry.metadata() {
+                    Ok(metadata) => self.scan_entry(&mut scan, entry.into_path(), Some(metadata)),
+                    Err(error) => self.scan_failure(&mut scan, entry.path(), walk_io_error(&error))?,
                 }
             }
         } else {
             for entry in fs::read_dir(target_dir)
-                .context("Failed to read directory")?
-                .filter_map(|e| e.ok())
+                .scan_context(target_dir, format!("Failed to read directory {:?}", target_dir))?
             {
-                let path = entry.path();
-                if path.is_file() && !self.classifier.should_ignore(&path) {
-                    files.push(path);
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(error) => {
+                        self.scan_failure(&mut scan, target_dir, error)?;
+                        continue;
+                    }
+                };
+                match entry.metadata() {
+                    Ok(metadata) => self.scan_entry(&mut scan, entry.path(), Some(metadata)),
+                    Err(error) => self.scan_failure(&mut scan, &entry.path(), error)?,
+                }
+            }
+            // Files waiting in Pending/ are part of every run, to be filed away when it's time
+            if self.options.pending_for.is_some() {
+                let pending = target_dir.join(PENDING_DIR);
+                for entry in fs::read_dir(&pending).into_iter().flatten() {
+                    match entry.and_then(|entry| Ok((entry.path(), entry.metadata()?))) {
+                        Ok((path, metadata)) => self.scan_entry(&mut scan, path, Some(metadata)),
+                        Err(error) => self.scan_failure(&mut scan, &pending, error)?,
+                    }
                 }
             }
         }
-        
-        Ok(files)
+        // Directory listings come in whatever order the filesystem keeps
+        scan.files.sort();
+
+        Ok(scan)
     }
 
-    /// Filter files based on provided filters
-    fn filter_files(&self, files: &[PathBuf], filters: &[String]) -> Vec<PathBuf> {
-        files
-            .iter()
-            .filter(|file| {
-                if let Some(extension) = file.extension() {
-                    if let Some(ext_str) = extension.to_str() {
-                        return filters.contains(&ext_str.to_lowercase());
-                    }
+    /// Note a path the scan couldn't read, or with `strict_scan` fail the run on it
+    fn scan_failure(&self, scan: &mut Scan, path: &Path, error: std::io::Error) -> Result<()> {
+        if self.options.strict_scan {
+            return Err(OrganizerError::scan(path, format!("Failed to scan {:?}", path), error));
+        }
+        log::warn!("Can't read {:?}, leaving it out: {}", path, error);
+        scan.errors.push(ScanError::new(path, error.to_string()));
+        Ok(())
+    }
+
+    /// Add a directory entry to the scan if it's a file to organize. `metadata` is the
+    /// entry's own (free on Windows, one `lstat` elsewhere); only symlinks are statted again,
+    /// as moves follow them.
+    fn scan_entry(&self, scan: &mut Scan, path: PathBuf, metadata: Option<fs::Metadata>) {
+        let metadata = match metadata {
+            Some(metadata) if !metadata.file_type().is_symlink() => Some(metadata),
+            _ => fs::metadata(&path).ok(),
+        };
+        let Some(metadata) = metadata else {
+            return;
+        };
+        if !metadata.is_file() {
+            return;
+        }
+        match self.skip_reason(&path, &metadata) {
+            Some(reason) => {
+                log::trace!("Leaving {:?} alone: {:?}", path, reason);
+                scan.skipped.push(SkippedFile::new(path, reason));
+            }
+            None => {
+                scan.files.push(path.clone());
+                scan.metadata.insert(path, metadata);
+                self.observer.on_scan_progress(scan.files.len());
+            }
+        }
+    }
+
+    /// Why a scanned file stays out of this run: it's a system, hidden or cloud placeholder
+    /// file (unless those are included), or pinned. `None` for files the run may organize.
+    fn skip_reason(&self, path: &Path, metadata: &fs::Metadata) -> Option<SkipReason> {
+        let attributes = FileAttributes::from_metadata(path, metadata);
+        if self.classifier.is_system_file(path) || attributes.system {
+            return Some(SkipReason::System);
+        }
+        if self.pins.contains(path) {
+            return Some(SkipReason::Pinned);
+        }
+        let hidden = self.classifier.should_ignore(path) || attributes.hidden;
+        if hidden && !self.options.include_hidden {
+            return Some(SkipReason::Hidden);
+        }
+        if attributes.placeholder && !self.options.hydrate {
+            return Some(SkipReason::Placeholder);
+        }
+        None
+    }
+
+    /// Why a candidate file is left out by the filters of this run, if it is
+    fn filter_reason(&self, file: &Path, filters: Option<&Vec<String>>) -> Option<SkipReason> {
+        let selected = filters.is_none_or(|filters| file_extensions(file).any(|extension| filters.contains(&extension)))
+            && (self.options.only_categories.is_empty()
+                || self.options.only_categories.contains(&self.classifier.classify(file)))
+            && (self.options.name_predicates.is_empty() || self.options.name_predicates.matches(file));
+        if !selected {
+            Some(SkipReason::Filtered)
+        } else if self.is_excluded(file) {
+            Some(SkipReason::Excluded)
+        } else if self.classifier.no_extension() == NoExtension::Skip
+            && file_types::file_extension(file).is_none()
+            && !multipart::is_archive_part(file)
+        {
+            Some(SkipReason::NoExtension)
+        } else {
+            None
+        }
+    }
+
+    fn is_excluded(&self, file: &Path) -> bool {
+        file_extensions(file).any(|ext| self.options.exclude_extensions.iter().any(|excluded| normalize_extension(excluded) == ext))
+            || self.options.exclude_categories.contains(&self.classifier.classify(file))
+    }
+
+    /// Reject filter combinations that leave nothing to organize, which are almost certainly
+    /// mistakes (`--filter jpg --exclude-ext jpg`)
+    fn check_filters(&self, target_dir: &Path, extensions: Option<&[String]>) -> Result<()> {
+        let options = &self.options;
+        let ruled_out = |reason: String| Err(OrganizerError::plan(target_dir, reason));
+        if let Some(category) = options.only_categories.iter().find(|c| options.exclude_categories.contains(c)) {
+            return ruled_out(format!("{} is both selected with --only-categories and excluded", category.folder_name()));
+        }
+        if !options.only_categories.is_empty() && options.only_categories.iter().all(|c| options.exclude_categories.contains(c)) {
+            return ruled_out("--exclude-categories rules out every category selected with --only-categories".to_string());
+        }
+
+        let Some(extensions) = extensions.filter(|extensions| !extensions.is_empty()) else {
+            return Ok(());
+        };
+        // What's left of each --filter extension once the other filters apply
+        let mut survivors = 0;
+        for extension in extensions {
+            let extension = normalize_extension(extension);
+            let category = self.classifier.classify(Path::new(&format!("file.{}", extension)));
+            let contradiction = if options.exclude_extensions.iter().any(|excluded| normalize_extension(excluded) == extension) {
+                Some("--exclude-ext")
+            } else if options.exclude_categories.contains(&category) {
+                Some("--exclude-categories")
+            } else if !options.only_categories.is_empty() && !options.only_categories.contains(&category) {
+                Some("--only-categories")
+            } else {
+                None
+            };
+            match contradiction {
+                Some(flag) if extensions.len() == 1 => {
+                    return ruled_out(format!("--filter {} can't match anything: {} rules out {} files", extension, flag, extension));
                 }
-                false
-            })
-            .cloned()
-            .collect()
+                Some(flag) => log::warn!("--filter {} is ruled out by {}", extension, flag),
+                None => survivors += 1,
+            }
+        }
+        if survivors == 0 {
+            return ruled_out("The exclusions and category filters rule out every extension given to --filter".to_string());
+        }
+        Ok(())
     }
 
+    /// Filter files based on provided filters
     /// Plan the organization operations
     fn plan_organization(
         &self,
         files: &[PathBuf],
+        scanned_metadata: &HashMap<PathBuf, fs::Metadata>,
         target_dir: &Path,
         mode: &OrganizeMode,
+        date_fallbacks: &mut DateFallbacks,
+        ruled_out: &mut RuledOut,
     ) -> Result<Vec<FileOperation>> {
         let mut operations = Vec::new();
-        let mut folder_counts: HashMap<String, usize> = HashMap::new();
+        let mut folder_counts: HashMap<PathBuf, usize> = HashMap::new();
+        let mut seen_font_hashes: HashMap<String, PathBuf> = HashMap::new();
 
         for file_path in files {
+            self.check_cancelled()?;
+            // Scanned files come with their metadata; the stat is for callers that plan others
+            let metadata = match scanned_metadata.get(file_path) {
+                Some(metadata) => Cow::Borrowed(metadata),
+                None => Cow::Owned(fs::metadata(file_path).scan_context(file_path, format!("Failed to get metadata for {:?}", file_path))?),
+            };
+            // Set by custom rules that rename
+            let mut new_name = None;
             let destination_folder = match mode {
-                OrganizeMode::Extension => {
-                    let category = self.classifier.classify(file_path);
-                    format!("{} {}", category.emoji(), category.folder_name())
-                }
+                OrganizeMode::Extension => self.extension_folder(file_path, &metadata, &mut seen_font_hashes)?,
                 OrganizeMode::Size => {
-                    let metadata = fs::metadata(file_path)
-                        .context(format!("Failed to get metadata for {:?}", file_path))?;
-                    let size_category = FileSizeCategory::from_size(metadata.len());
-                    format!("{} {}", size_category.emoji(), size_category.folder_name())
+                    match &self.size_buckets {
+                        Some(buckets) => PathBuf::from(buckets.folder(metadata.len())),
+                        None => {
+                            let size_category = FileSizeCategory::from_size(metadata.len());
+                            PathBuf::from(format!("{} {}", size_category.emoji(), size_category.folder_name()))
+                        }
+                    }
                 }
                 OrganizeMode::Date => {
-                    let metadata = fs::metadata(file_path)
-                        .context(format!("Failed to get metadata for {:?}", file_path))?;
-                    let created = metadata.created()
-                        .or_else(|_| metadata.modified())
-                        .context("Failed to get file creation/modification time")?;
-                    
-                    use chrono::{DateTime, Utc};
-                    let datetime: DateTime<Utc> = created.into();
-                    format!("📅 {}", datetime.format("%Y-%m"))
+                    let date = dates::file_date(file_path, &metadata, self.date_source(), date_fallbacks)
+                        .ok_or_else(|| OrganizerError::plan(file_path, format!("Failed to get a date for {:?}", file_path)))?;
+                    self.date_folder("📅", &date)
                 }
                 OrganizeMode::Modified => {
-                    let metadata = fs::metadata(file_path)
-                        .context(format!("Failed to get metadata for {:?}", file_path))?;
                     let modified = metadata.modified()
-                        .context("Failed to get file modification time")?;
+                        .scan_context(file_path, "Failed to get file modification time")?;
                     
                     use chrono::{DateTime, Utc};
                     let datetime: DateTime<Utc> = modified.into();
-                    format!("🕒 {}", datetime.format("%Y-%m"))
+                    self.date_folder("🕒", &datetime.naive_utc())
+                }
+                OrganizeMode::Accessed => {
+                    let accessed = metadata.accessed()
+                        .scan_context(file_path, "Failed to get file access time")?;
+
+                    use chrono::{DateTime, Utc};
+                    let datetime: DateTime<Utc> = accessed.into();
+                    self.date_folder("👁️", &datetime.naive_utc())
                 }
-                OrganizeMode::Custom => {
-                    // TODO: Implement custom rules from config
-                    "📂 Custom".to_string()
+                OrganizeMode::Origin => {
+                    let domain = origin::origin_domain(file_path)
+                        .unwrap_or_else(|| "Unknown Origin".to_string());
+                    PathBuf::from(format!("🌐 {}", sanitize_folder_name(&domain)))
                 }
+                OrganizeMode::Custom => match self.rules.destination_with_metadata(file_path, &metadata)? {
+                    RuleDecision::Move(target) => {
+                        new_name = target.file_name;
+                        target.folder
+                    }
+                    RuleDecision::Skip { rule } => {
+                        log::debug!("Rule '{}' skips {:?}", rule, file_path);
+                        ruled_out.skipped.push(file_path.clone());
+                        continue;
+                    }
+                    RuleDecision::Delete { rule } => {
+                        log::debug!("Rule '{}' deletes {:?}", rule, file_path);
+                        ruled_out.deletions.push(PlannedDeletion { path: file_path.clone(), rule });
+                        continue;
+                    }
+                    RuleDecision::Unmatched { file_name } => {
+                        new_name = file_name;
+                        self.extension_folder(file_path, &metadata, &mut seen_font_hashes)?
+                    }
+                },
+            };
+
+            let base_dir = match file_path.parent() {
+                Some(parent) if self.options.per_directory => parent,
+                _ => target_dir,
             };
+            // Category folders made by an earlier per-directory run are walked again; their
+            // files are already where they belong
+            if self.options.per_directory
+                && base_dir != target_dir
+                && (base_dir.ends_with(&destination_folder) || self.same_category_folder(base_dir, &destination_folder))
+            {
+                continue;
+            }
 
             // Count files per folder for statistics
             *folder_counts.entry(destination_folder.clone()).or_insert(0) += 1;
 
-            let destination_dir = target_dir.join(&destination_folder);
-            let file_name = file_path.file_name()
-                .context("Failed to get file name")?;
-            let destination_path = destination_dir.join(file_name);
+            let mut destination_dir = base_dir.join(&destination_folder);
+            if self.options.preserve_structure {
+                // Pending/ isn't part of the structure
+                let relative_dir = file_path
+                    .parent()
+                    .and_then(|parent| parent.strip_prefix(target_dir).ok())
+                    .filter(|relative_dir| self.options.pending_for.is_none() || *relative_dir != Path::new(PENDING_DIR));
+                if let Some(relative_dir) = relative_dir {
+                    destination_dir.push(self.below_category_folder(relative_dir, &destination_folder));
+                }
+            }
+            let destination_path = match new_name {
+                Some(name) => destination_dir.join(name),
+                None => destination_dir.join(file_path.file_name().ok_or_else(|| OrganizerError::plan(file_path, "Failed to get file name"))?),
+            };
+            // Recursive runs see files an earlier run already put in place, possibly under an
+            // older name of their category folder
+            if destination_path == *file_path || self.in_renamed_category_folder(file_path, &destination_path, base_dir) {
+                continue;
+            }
 
             operations.push(FileOperation {
                 source: file_path.clone(),
                 destination: destination_path,
                 operation_type: OperationType::Move,
+                size: metadata.len(),
             });
         }
 
         Ok(operations)
     }
 
-    /// Show preview of planned operations
-    fn show_preview(&self, operations: &[FileOperation], mode: &OrganizeMode) {
-        println!("\n{}", "📋 Organization Preview:".bold().blue());
-        println!("Mode: {:?}", mode);
-        
-        // Group operations by destination folder
-        let mut folder_groups: HashMap<String, Vec<&FileOperation>> = HashMap::new();
-        
-        for op in operations {
-            if let Some(parent) = op.destination.parent() {
-                if let Some(folder_name) = parent.file_name() {
-                    if let Some(folder_str) = folder_name.to_str() {
-                        folder_groups.entry(folder_str.to_string())
-                            .or_insert_with(Vec::new)
-                            .push(op);
-                    }
-                }
-            }
-        }
-
-        for (folder_name, ops) in folder_groups {
-            println!("\n📁 {} ({} files)", folder_name.green(), ops.len().to_string().yellow());
-            
-            // Show first few files as examples
-            for op in ops.iter().take(3) {
-                if let Some(file_name) = op.source.file_name() {
-                    println!("   {} {}", "→".cyan(), file_name.to_string_lossy());
-                }
-            }
-            
-            if ops.len() > 3 {
-                println!("   {} and {} more files...", "...".dimmed(), (ops.len() - 3).to_string().dimmed());
-            }
+    /// Folder for the date modes: the configured template, or `<emoji> <bucket label>`
+    fn date_folder(&self, emoji: &str, date: &chrono::NaiveDateTime) -> PathBuf {
+        match &self.folder_template {
+            Some(template) => template.render(date),
+            None => PathBuf::from(format!("{} {}", emoji, self.options.date_buckets.label(date))),
         }
-        
-        println!("\n{} {} files will be organized", "Total:".bold(), operations.len().to_string().yellow());
     }
 
-    /// Execute the planned operations
-    fn execute_operations(&self, operations: &[FileOperation]) -> Result<()> {
-        println!("\n{}", "🚀 Executing file operations...".bold().green());
-        
-        // Create all necessary directories first
-        let mut dirs_created = 0;
-        for op in operations {
-            if let Some(parent) = op.destination.parent() {
-                if !parent.exists() {
-                    fs::create_dir_all(parent)
-                        .context(format!("Failed to create directory {:?}", parent))?;
-                    dirs_created += 1;
-                }
-            }
+    /// The part of `relative_dir` below the category folder an earlier run filed it in, so
+    /// running again doesn't nest categories in themselves; `relative_dir` as is when it isn't
+    /// in one
+    fn below_category_folder<'a>(&self, relative_dir: &'a Path, destination_folder: &Path) -> &'a Path {
+        if let Ok(below) = relative_dir.strip_prefix(destination_folder) {
+            return below;
         }
-        
-        if dirs_created > 0 {
-            println!("📁 Created {} directories", dirs_created.to_string().cyan());
+        let mut components = relative_dir.components();
+        match components.next() {
+            Some(first) if self.same_category_folder(Path::new(first.as_os_str()), destination_folder) => components.as_path(),
+            _ => relative_dir,
         }
+    }
 
-        // Move files
-        let mut moved_count = 0;
-        let mut failed_count = 0;
-        
+    /// Whether the last folder of `folder` and the first of `destination_folder` are folders
+    /// of the same category, under any of its names
+    fn same_category_folder(&self, folder: &Path, destination_folder: &Path) -> bool {
+        let names = self.classifier.names();
+        let category = |name: Option<&std::ffi::OsStr>| name.and_then(|name| names.category_of(&name.to_string_lossy()));
+        let existing = category(folder.file_name());
+        existing.is_some() && existing == category(destination_folder.iter().next())
+    }
+
+    /// Whether `file_path` is where `destination_path` would put it, except that its category
+    /// folder has another of the category's names (`🖼️ Images` when the names are Spanish)
+    fn in_renamed_category_folder(&self, file_path: &Path, destination_path: &Path, base_dir: &Path) -> bool {
+        let (Ok(current), Ok(planned)) = (file_path.strip_prefix(base_dir), destination_path.strip_prefix(base_dir)) else {
+            return false;
+        };
+        let (mut current, mut planned) = (current.iter(), planned.iter());
+        let (Some(existing), Some(wanted)) = (current.next(), planned.next()) else {
+            return false;
+        };
+        current.clone().next().is_some()
+            && self.same_category_folder(Path::new(existing), Path::new(wanted))
+            && current.eq(planned)
+    }
+
+    /// Where Date mode takes dates from; network-friendly runs don't read EXIF capture times
+    fn date_source(&self) -> DateSource {
+        match self.options.network_friendly {
+            true => self.options.date_source.without_exif(),
+            false => self.options.date_source,
+        }
+    }
+
+    /// Destination folder for extension mode, including the ebook/font sub-modes
+    fn extension_folder(
+        &self,
+        file_path: &Path,
+        metadata: &fs::Metadata,
+        seen_font_hashes: &mut HashMap<String, PathBuf>,
+    ) -> Result<PathBuf> {
+        let category = self.classifier.classify(file_path);
+        if category == FileCategory::Other
+            && self.classifier.no_extension() == NoExtension::Folder
+            && file_types::file_extension(file_path).is_none()
+        {
+            return Ok(PathBuf::from(extensionless::NO_EXTENSION_FOLDER));
+        }
+        let category_folder = PathBuf::from(self.classifier.names().folder(&category));
+
+        if category == FileCategory::Ebooks && self.options.books_by_author {
+            let book = match self.options.network_friendly {
+                true => BookMetadata::from_file_name(file_path),
+                false => BookMetadata::read(file_path),
+            };
+            Ok(category_folder
+                .join(sanitize_folder_name(&book.author))
+                .join(sanitize_folder_name(&book.title)))
+        } else if category == FileCategory::Fonts && self.options.fonts_by_family {
+            Ok(category_folder.join(self.font_subfolder(file_path, metadata, seen_font_hashes)?))
+        } else {
+            Ok(category_folder)
+        }
+    }
+
+    /// Extensions of the files among `operations` whose type isn't known, with how many files
+    /// have each, most common first
+    pub fn unknown_extensions(&self, operations: &[FileOperation]) -> Vec<(String, usize)> {
+        stats::by_count(&stats::other_extensions(&self.classifier, operations))
+            .into_iter()
+            .filter(|(extension, _)| !extension.is_empty())
+            .map(|(extension, stats)| (extension.to_string(), stats.files))
+            .collect()
+    }
+
+    /// Content hash of a file, reusing the history store's cache when it has one
+    fn hash_file(&self, file_path: &Path) -> Result<String> {
+        self.hash_file_as(file_path, self.options.hash_algorithm)
+    }
+
+    /// [`FileOrganizer::hash_file`] with another algorithm than the options'
+    fn hash_file_as(&self, file_path: &Path, algorithm: hashing::HashAlgorithm) -> Result<String> {
+        let metadata = fs::metadata(file_path).scan_context(file_path, format!("Failed to get metadata for {:?}", file_path))?;
+        self.hash_scanned_file(file_path, &metadata, algorithm)
+    }
+
+    /// [`FileOrganizer::hash_file_as`] for a file whose metadata was read already
+    fn hash_scanned_file(&self, file_path: &Path, metadata: &fs::Metadata, algorithm: hashing::HashAlgorithm) -> Result<String> {
+        let key = file_path.canonicalize().unwrap_or_else(|_| file_path.to_path_buf());
+        let Ok(modified) = metadata.modified() else {
+            return hashing::hash_file_with(file_path, algorithm);
+        };
+
+        // The cache keeps one hash per file; other algorithms than BLAKE3 are tagged with
+        // their name, so a hash of the wrong kind reads as a miss
+        let tag = match algorithm {
+            hashing::HashAlgorithm::Blake3 => String::new(),
+            other => format!("{}:", other.name()),
+        };
+        let cached = self.history.cached_hash(&key, metadata.len(), modified);
+        if let Some(hash) = cached.as_deref().and_then(|cached| cached.strip_prefix(&tag)).filter(|hash| !hash.contains(':')) {
+            self.hash_cache.hit();
+            return Ok(hash.to_string());
+        }
+        self.hash_cache.miss();
+        let hash = hashing::hash_file_with(file_path, algorithm)?;
+        self.history.remember_hash(&key, metadata.len(), modified, &format!("{}{}", tag, hash));
+        Ok(hash)
+    }
+
+    /// Pick the subfolder of Fonts for a font file, routing repeated faces to Duplicates
+    fn font_subfolder(
+        &self,
+        file_path: &Path,
+        metadata: &fs::Metadata,
+        seen_font_hashes: &mut HashMap<String, PathBuf>,
+    ) -> Result<PathBuf> {
+        // Reading every font would defeat the point of network-friendly runs
+        if !self.options.network_friendly {
+            let checksum = self.hash_scanned_file(file_path, metadata, self.options.hash_algorithm)?;
+            if let Some(original) = seen_font_hashes.get(&checksum) {
+                log::debug!("{:?} is identical to {:?}", file_path, original);
+                return Ok(PathBuf::from(fonts::DUPLICATE_FONTS_FOLDER));
+            }
+            seen_font_hashes.insert(checksum, file_path.to_path_buf());
+        }
+
+        if fonts::is_web_font(file_path) {
+            Ok(PathBuf::from(fonts::WEB_FONTS_FOLDER))
+        } else if self.options.network_friendly {
+            Ok(PathBuf::from(sanitize_folder_name(&fonts::family_from_file_name(file_path))))
+        } else {
+            Ok(PathBuf::from(sanitize_folder_name(&fonts::font_family(file_path))))
+        }
+    }
+
+    /// Group burst/bracketed photos using their EXIF capture times, returning the burst count
+    fn group_bursts(&self, operations: &mut [FileOperation], window_secs: u64) -> usize {
+        let shot_times: HashMap<usize, chrono::NaiveDateTime> = operations
+            .iter()
+            .enumerate()
+            .filter(|(_, op)| self.classifier.classify(&op.source) == FileCategory::Images)
+            .filter_map(|(index, op)| exif_time::exif_datetime(&op.source).map(|taken| (index, taken)))
+            .collect();
+
+        bursts::group_bursts(operations, &shot_times, window_secs)
+    }
+
+    /// Carry out planned operations, recording what happened to each file.
+    ///
+    /// Under [`ErrorPolicy::KeepGoing`] a file that fails to move doesn't stop the others;
+    /// otherwise execution stops at the first failure, leaving the rest of the plan out of
+    /// the outcomes. Cancellation or failing to create a destination directory always aborts.
+    pub fn execute(&self, operations: &[FileOperation]) -> Result<Vec<FileOutcome>> {
+        self.execute_with(operations, |_| {}).map(|(outcomes, _)| outcomes)
+    }
+
+    /// [`FileOrganizer::execute`], calling `progress` with the outcomes so far after each one,
+    /// and also returning the directories the run created (and kept).
+    ///
+    /// Directories made for files that never arrived (after a failure, a stop or
+    /// cancellation) are removed again, however execution ends.
+    fn execute_with(
+        &self,
+        operations: &[FileOperation],
+        progress: impl FnMut(&[FileOutcome]),
+    ) -> Result<(Vec<FileOutcome>, Vec<PathBuf>)> {
+        self.check_writable(operations.first().map_or(Path::new(""), |op| op.source.as_path()))?;
+        let mut new_dirs = Vec::new();
+        let result = self.move_all(operations, &mut new_dirs, progress);
+        for dir in new_dirs.iter().rev() {
+            // Only succeeds for directories that are still empty
+            if fs::remove_dir(dir).is_ok() {
+                log::debug!("Removed {:?}, which no file ended up in", dir);
+            }
+        }
+        new_dirs.retain(|dir| dir.is_dir());
+        result.map(|outcomes| (outcomes, new_dirs))
+    }
+
+    /// Create the destination directories, recording the new ones in `new_dirs` (parents
+    /// first), and move the files
+    fn move_all(
+        &self,
+        operations: &[FileOperation],
+        new_dirs: &mut Vec<PathBuf>,
+        mut progress: impl FnMut(&[FileOutcome]),
+    ) -> Result<Vec<FileOutcome>> {
+        self.observer.on_execution_started(operations.len());
+        
+        // Create all necessary directories first
         for op in operations {
-            match fs::rename(&op.source, &op.destination) {
-                Ok(_) => {
-                    moved_count += 1;
-                    if moved_count % 10 == 0 {
-                        println!("📦 Moved {} files...", moved_count.to_string().green());
+            if let Some(parent) = op.destination.parent() {
+                if !parent.exists() {
+                    let first_new = new_dirs.len();
+                    new_dirs.extend(parent.ancestors().take_while(|dir| !dir.exists()).map(Path::to_path_buf));
+                    new_dirs[first_new..].reverse();
+                    fs::create_dir_all(parent).map_err(|e| OrganizerError::execution(op, e))?;
+                }
+            }
+        }
+        
+        if !new_dirs.is_empty() {
+            self.observer.on_directories_created(new_dirs.len());
+        }
+
+        // Move files
+        let mut outcomes = Vec::with_capacity(operations.len());
+        let mut moved_count = 0;
+        let mut failed_count = 0;
+        let mut stopped = false;
+        let mut links = HardLinks::default();
+        let mut renamer = Renamer::new(self.options.backend);
+        let mut pending = operations.iter();
+
+        while !stopped {
+            // Settle where each file of the next batch goes before any of them moves
+            let mut batch: Vec<(FileOperation, Step)> = Vec::new();
+            let mut claimed: HashSet<PathBuf> = HashSet::new();
+            while claimed.len() < renamer.batch_size() {
+                let Some(op) = pending.next() else {
+                    break;
+                };
+                self.check_cancelled()?;
+                // rename() would silently replace an existing file, including one that an
+                // earlier file of the batch is about to become
+                let taken = |path: &Path| path.exists() || claimed.contains(path);
+                let mut op = op.clone();
+                let step = if taken(&op.destination) {
+                    match self.resolve_conflict(&op) {
+                        Some(ConflictChoice::Rename) => {
+                            op.destination = conflicts::free_name_where(&op.destination, taken);
+                            Step::Ready
+                        }
+                        Some(ConflictChoice::Skip) => Step::Skipped,
+                        None => Step::Refused,
+                    }
+                } else {
+                    Step::Ready
+                };
+                if step == Step::Ready {
+                    claimed.insert(op.destination.clone());
+                }
+                batch.push((op, step));
+                if step == Step::Refused && self.options.on_error != ErrorPolicy::KeepGoing {
+                    break;
+                }
+            }
+            if batch.is_empty() {
+                break;
+            }
+
+            let renames: Vec<(&Path, &Path)> = batch
+                .iter()
+                .filter(|(op, step)| *step == Step::Ready && op.operation_type == OperationType::Move)
+                .map(|(op, _)| (op.source.as_path(), op.destination.as_path()))
+                .collect();
+            let mut renamed = renamer.rename_all(&renames).into_iter();
+
+            for (op, step) in batch {
+                // Once the run stops, only the renames that went out with the failed one count
+                let result = match (step, &op.operation_type) {
+                    (Step::Ready, OperationType::Move) => match renamed.next().expect("a result for every rename") {
+                        Err(e) if e.kind() == io::ErrorKind::CrossesDevices && !stopped => {
+                            links.copy_across(&op.source, &op.destination)
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => continue,
+                        result => result,
+                    },
+                    _ if stopped => continue,
+                    (Step::Ready, OperationType::Copy) => fs::copy(&op.source, &op.destination).map(|_| ()),
+                    (Step::Skipped, _) => {
+                        progress_so_far(&mut progress, &outcomes);
+                        self.observer.on_file_skipped(&op);
+                        outcomes.push(FileOutcome { operation: op, error: None, skipped: true, rolled_back: false });
+                        continue;
+                    }
+                    (Step::Refused, _) => Err(io::Error::new(io::ErrorKind::AlreadyExists, "destination already exists")),
+                };
+                progress_so_far(&mut progress, &outcomes);
+
+                let error = match result {
+                    Ok(_) => {
+                        log::debug!("Moved {:?} to {:?}", op.source, op.destination);
+                        if self.options.durable {
+                            if let Err(e) = durable::sync_move(&op.source, &op.destination) {
+                                log::warn!("Moved {}, but couldn't flush it to disk: {}", op.destination.display(), e);
+                            }
+                        }
+                        if self.options.finder_tags {
+                            let category = self.classifier.classify(&op.destination);
+                            if let Err(e) = finder_tags::add_tag(&op.destination, self.classifier.names().name(&category)) {
+                                log::warn!("Couldn't tag {} in Finder: {}", op.destination.display(), e);
+                            }
+                        }
+                        moved_count += 1;
+                        self.observer.on_file_moved(&op);
+                        None
+                    }
+                    Err(e) => {
+                        failed_count += 1;
+                        self.observer.on_error(&op, &e);
+                        Some(e.to_string())
                     }
+                };
+                let failed = error.is_some();
+                outcomes.push(FileOutcome {
+                    operation: op,
+                    error,
+                    skipped: false,
+                    rolled_back: false,
+                });
+                if failed && self.options.on_error != ErrorPolicy::KeepGoing {
+                    stopped = true;
+                }
+            }
+        }
+
+        links.warn_unpreserved();
+        if stopped {
+            self.observer.on_execution_stopped(operations.len() - outcomes.len());
+            if self.options.on_error == ErrorPolicy::Rollback {
+                let rolled_back = self.roll_back(&mut outcomes);
+                moved_count -= rolled_back;
+            }
+        }
+        self.observer.on_execution_finished(moved_count, failed_count);
+
+        Ok(outcomes)
+    }
+
+    /// Put every moved file back, newest first; the directories the run created go with the
+    /// rest of [`FileOrganizer::execute_with`]'s cleanup. Returns how many files went back; the
+    /// others stay moved (and journaled), so `undo` can still restore them.
+    fn roll_back(&self, outcomes: &mut [FileOutcome]) -> usize {
+        let mut restored = 0;
+        let mut failed = 0;
+        let mut links = HardLinks::default();
+        for outcome in outcomes.iter_mut().rev().filter(|outcome| outcome.succeeded()) {
+            let op = &outcome.operation;
+            let result = match op.operation_type {
+                // The original can't have been replaced: nothing else moves files during a run
+                OperationType::Move => links.move_file(&op.destination, &op.source),
+                OperationType::Copy => fs::remove_file(&op.destination),
+            };
+            match result {
+                Ok(()) => {
+                    outcome.rolled_back = true;
+                    restored += 1;
                 }
                 Err(e) => {
-                    failed_count += 1;
-                    eprintln!("❌ Failed to move {:?}: {}", op.source.file_name(), e);
+                    failed += 1;
+                    log::warn!("Couldn't roll back {}: {}", op.destination.display(), e);
                 }
             }
         }
+        self.observer.on_rolled_back(restored, failed);
+        restored
+    }
 
-        println!("✅ Successfully moved {} files", moved_count.to_string().green());
-        if failed_count > 0 {
-            eprintln!("⚠️  {} files failed to move", failed_count.to_string().red());
+    /// How to handle an operation whose destination is taken; `None` refuses it
+    fn resolve_conflict(&self, operation: &FileOperation) -> Option<ConflictChoice> {
+        match self.options.on_conflict {
+            ConflictPolicy::Refuse => None,
+            ConflictPolicy::Skip => Some(ConflictChoice::Skip),
+            ConflictPolicy::Rename => Some(ConflictChoice::Rename),
+            ConflictPolicy::Ask => Some(self.observer.on_conflict(operation)),
         }
+    }
 
-        Ok(())
+    /// Delete what `delete` rules matched, after the moves; a file that can't be deleted
+    /// doesn't stop the others. Deletions aren't part of the undo history: trashed files are
+    /// restored from the trash.
+    fn delete_all(&self, deletions: &[PlannedDeletion]) -> Result<Vec<DeletionOutcome>> {
+        let mut outcomes = Vec::with_capacity(deletions.len());
+        for deletion in deletions {
+            self.check_cancelled()?;
+            let error = match trash::delete(&deletion.path, self.options.permanent_delete) {
+                Ok(()) => {
+                    log::info!("Rule '{}' deleted {:?}", deletion.rule, deletion.path);
+                    None
+                }
+                Err(e) => {
+                    log::warn!("Couldn't delete {}: {}", deletion.path.display(), e);
+                    Some(e.to_string())
+                }
+            };
+            outcomes.push(DeletionOutcome { deletion: deletion.clone(), error });
+        }
+        Ok(outcomes)
     }
+}
 
-    /// Show completion summary
-    fn show_completion_summary(&self, summary: &OrganizationSummary) {
-        println!("\n{}", "🎉 Organization Complete!".bold().green());
-        println!("📊 Summary:");
-        println!("   Files processed: {}", summary.total_files.to_string().cyan());
-        println!("   Folders created: {}", summary.folders_created.to_string().cyan());
-        
-        if !summary.categories.is_empty() {
-            println!("   Categories:");
-            for (category, count) in &summary.categories {
-                println!("     {} {}: {}", "📁".cyan(), category, count.to_string().yellow());
+/// What happens to a file of a batch, once its destination is settled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Ready,
+    /// Left where it is (`--on-conflict skip`)
+    Skipped,
+    /// Its destination is taken
+    Refused,
+}
+
+/// Report the outcomes so far, before adding the next
+fn progress_so_far(progress: &mut impl FnMut(&[FileOutcome]), outcomes: &[FileOutcome]) {
+    if !outcomes.is_empty() {
+        progress(outcomes);
+    }
+}
+
+/// Send files modified within `pending_for` to `Pending/` instead of their category. Returns
+/// the files already in `Pending/` that stay there, whose operations are dropped.
+fn stage(
+    operations: &mut Vec<FileOperation>,
+    metadata: &HashMap<PathBuf, fs::Metadata>,
+    target_dir: &Path,
+    pending_for: std::time::Duration,
+) -> Vec<PathBuf> {
+    let pending = target_dir.join(PENDING_DIR);
+    let cutoff = std::time::SystemTime::now().checked_sub(pending_for).unwrap_or(std::time::UNIX_EPOCH);
+    let mut still_pending = Vec::new();
+    operations.retain_mut(|op| {
+        let modified = match metadata.get(&op.source) {
+            Some(metadata) => metadata.modified(),
+            None => fs::metadata(&op.source).and_then(|metadata| metadata.modified()),
+        };
+        // Files whose modification time is unknown aren't held back
+        if !modified.is_ok_and(|modified| modified > cutoff) {
+            return true;
+        }
+        if op.source.parent() == Some(pending.as_path()) {
+            still_pending.push(op.source.clone());
+            return false;
+        }
+        let Some(file_name) = op.source.file_name() else {
+            return true;
+        };
+        op.destination = pending.join(file_name);
+        true
+    });
+    still_pending
+}
+
+/// Mount option that makes access times unreliable, if the mount uses one
+fn lazy_atime_option(mount: &mounts::MountInfo) -> Option<&'static str> {
+    // relatime (the Linux default) only updates atime once a day or when it's older than mtime
+    ["noatime", "relatime"]
+        .into_iter()
+        .find(|option| mount.has_option(option))
+}
+
+/// The extensions `--filter` and `--exclude-ext` match a file by: `gz` and `tar.gz` for
+/// `backup.tar.gz`
+fn file_extensions(file: &Path) -> impl Iterator<Item = String> {
+    let last = file.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    let full = file_types::file_extension(file).filter(|full| Some(full) != last.as_ref());
+    last.into_iter().chain(full)
+}
+
+/// `.JPG` -> `jpg`, the form extensions are compared in
+fn normalize_extension(extension: &str) -> String {
+    extension.trim().trim_start_matches('.').to_lowercase()
+}
+
+/// Turn untrusted text (e.g. ebook metadata) into a single safe path component
+pub fn sanitize_folder_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    let trimmed = cleaned.trim().trim_matches('.').trim();
+    if trimmed.is_empty() {
+        "Unknown".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+impl Default for FileOrganizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The I/O error behind a walk error, without walkdir's message, which repeats the path
+fn walk_io_error(error: &walkdir::Error) -> std::io::Error {
+    match error.io_error() {
+        Some(io) => std::io::Error::new(io.kind(), io.to_string()),
+        None => std::io::Error::other(error.to_string()),
+    }
+}
+
+/// Files found by [`FileOrganizer::collect_files`], with the metadata the scan read
+#[derive(Default)]
+struct Scan {
+    files: Vec<PathBuf>,
+    metadata: HashMap<PathBuf, fs::Metadata>,
+    /// Files found that the run leaves alone: hidden, pinned, cloud placeholders, ...
+    skipped: Vec<SkippedFile>,
+    /// Paths that couldn't be read
+    errors: Vec<ScanError>,
+}
+
+/// Where every selected file is going, produced by [`FileOrganizer::plan`]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct OrganizePlan {
+    pub operations: Vec<FileOperation>,
+    /// Warnings and remarks gathered while planning
+    pub notices: Vec<Notice>,
+    /// Files found that stay where they are, in the order they were left out
+    #[serde(default)]
+    pub skipped: Vec<SkippedFile>,
+    /// How long scanning and planning took
+    #[serde(default)]
+    pub timings: timings::PhaseTimings,
+    /// Parts of the directory that couldn't be read, so their files aren't in the plan
+    #[serde(default)]
+    pub scan_errors: Vec<ScanError>,
+    /// Files `delete` rules delete
+    #[serde(default)]
+    pub deletions: Vec<PlannedDeletion>,
+}
+
+impl OrganizePlan {
+    /// Short fingerprint of what carrying out the plan does: every move and what happens to
+    /// its conflicts, and every deletion and whether it's for good. The same files and options
+    /// give the same ID, so a real run given the ID of a dry run ([`OrganizeOptions::plan_id`])
+    /// does exactly what the dry run showed.
+    pub fn id(&self) -> String {
+        let conflicts: Vec<&Notice> = self.notices.iter().filter(|notice| matches!(notice, Notice::Conflicts { .. })).collect();
+        // Trashing and deleting for good are different plans
+        let permanent = self.notices.iter().any(|notice| matches!(notice, Notice::Deletions { permanent: true, .. }));
+        let json = serde_json::to_vec(&(&self.operations, conflicts, &self.deletions, permanent)).expect("plans always serialize");
+        blake3::hash(&json).to_hex()[..12].to_string()
+    }
+}
+
+/// What happened to one planned operation
+#[derive(Debug, Clone)]
+pub struct FileOutcome {
+    /// The operation as carried out; a renamed conflict has its new destination here
+    pub operation: FileOperation,
+    /// Why the operation failed, or `None` if the file was moved or skipped
+    pub error: Option<String>,
+    /// Left in place because the destination was taken and the conflict policy said skip
+    pub skipped: bool,
+    /// Moved, then put back because a later operation failed under [`ErrorPolicy::Rollback`]
+    pub rolled_back: bool,
+}
+
+impl FileOutcome {
+    /// The file was moved (and stayed moved)
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none() && !self.skipped && !self.rolled_back
+    }
+}
+
+/// Everything [`FileOrganizer::organize`] did: the plan, per-file outcomes and a summary
+#[derive(Debug)]
+pub struct OrganizeReport {
+    pub plan: Vec<FileOperation>,
+    pub notices: Vec<Notice>,
+    /// One entry per executed operation; empty for a dry run
+    pub outcomes: Vec<FileOutcome>,
+    /// Files left where they are and why, including conflicts the policy skipped
+    pub skipped_files: Vec<SkippedFile>,
+    pub summary: OrganizationSummary,
+    pub dry_run: bool,
+    /// Journal id for `undo`, if anything was moved
+    pub operation_id: Option<String>,
+    /// [`OrganizePlan::id`] of the plan previewed or carried out
+    pub plan_id: String,
+    /// Files `delete` rules delete
+    pub deletions: Vec<PlannedDeletion>,
+    /// What happened to each deletion; empty for a dry run
+    pub deleted: Vec<DeletionOutcome>,
+}
+
+/// What happened to one planned deletion
+#[derive(Debug, Clone)]
+pub struct DeletionOutcome {
+    pub deletion: PlannedDeletion,
+    /// Why the file couldn't be deleted, or `None` if it was
+    pub error: Option<String>,
+}
+
+impl OrganizeReport {
+    fn from_plan(
+        plan: OrganizePlan,
+        outcomes: Vec<FileOutcome>,
+        created_dirs: &[PathBuf],
+        dry_run: bool,
+        classifier: &FileTypeClassifier,
+    ) -> Self {
+        let plan_id = plan.id();
+        // A real run summarizes what actually moved, a dry run what would
+        let summarized: Vec<FileOperation> = if dry_run {
+            plan.operations.clone()
+        } else {
+            outcomes
+                .iter()
+                .filter(|outcome| outcome.succeeded())
+                .map(|outcome| outcome.operation.clone())
+                .collect()
+        };
+        let mut summary = OrganizationSummary::from_operations(&summarized, created_dirs);
+        summary.other_extensions = stats::by_count(&stats::other_extensions(classifier, &summarized))
+            .into_iter()
+            .map(|(extension, stats)| (extension.to_string(), stats))
+            .collect();
+
+        // Conflicts the policy skips: those a dry run would skip, or those a run did
+        let mut skipped_files = plan.skipped;
+        if dry_run {
+            for notice in &plan.notices {
+                if let Notice::Conflicts { conflicts, policy: ConflictPolicy::Skip } = notice {
+                    let blocking = conflicts.iter().filter(|conflict| conflict.kind.blocks());
+                    skipped_files.extend(blocking.map(|conflict| SkippedFile::new(&conflict.operation.source, SkipReason::Conflict)));
+                }
             }
+        } else {
+            let skipped = outcomes.iter().filter(|outcome| outcome.skipped);
+            skipped_files.extend(skipped.map(|outcome| SkippedFile::new(&outcome.operation.source, SkipReason::Conflict)));
         }
+        summary.skipped = skips::tally(skipped_files.iter().map(|skipped| skipped.reason));
+        summary.timings = timings::PhaseTimings {
+            files: summarized.len(),
+            // Wherever the file is now, like the sizes of unknown types
+            bytes: summarized
+                .iter()
+                .filter_map(|op| fs::metadata(&op.destination).or_else(|_| fs::metadata(&op.source)).ok())
+                .map(|metadata| metadata.len())
+                .sum(),
+            ..plan.timings
+        };
+        summary.scan_errors = plan.scan_errors;
+
+        Self {
+            summary,
+            plan: plan.operations,
+            notices: plan.notices,
+            outcomes,
+            skipped_files,
+            dry_run,
+            operation_id: None,
+            plan_id,
+            deletions: plan.deletions,
+            deleted: Vec::new(),
+        }
+    }
+
+    pub fn moved(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| outcome.succeeded()).count()
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| outcome.skipped).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| outcome.error.is_some()).count()
+    }
+
+    pub fn rolled_back(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| outcome.rolled_back).count()
+    }
+
+    /// Planned files execution never got to because an earlier failure stopped it
+    pub fn not_attempted(&self) -> usize {
+        if self.dry_run {
+            return 0;
+        }
+        self.plan.len() - self.outcomes.len()
+    }
+
+    /// Files delete rules didn't get to because a failure stopped the run first
+    pub fn deletions_not_attempted(&self) -> usize {
+        if self.dry_run {
+            return 0;
+        }
+        self.deletions.len() - self.deleted.len()
     }
 }
 
-/// Summary of organization operation
+/// What [`FileOrganizer::undo`] did, or would do in a dry run
 #[derive(Debug)]
+pub struct UndoReport {
+    /// Journal id of the run being undone
+    pub operation_id: String,
+    /// Moves back to the original locations, most recent first
+    pub operations: Vec<FileOperation>,
+    /// Journal entries whose file is no longer where the run put it
+    pub missing: Vec<journal::JournalEntry>,
+    /// One entry per operation; empty for a dry run
+    pub outcomes: Vec<FileOutcome>,
+    pub dry_run: bool,
+}
+
+impl UndoReport {
+    /// Operations whose original location is taken (what a real undo would have to resolve)
+    pub fn conflicts(&self) -> impl Iterator<Item = &FileOperation> {
+        self.operations.iter().filter(|op| op.destination.exists())
+    }
+
+    pub fn restored(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| outcome.succeeded()).count()
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| outcome.skipped).count()
+    }
+
+    /// Files that failed to move back or had vanished
+    pub fn unrestorable(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| outcome.error.is_some()).count() + self.missing.len()
+    }
+}
+
+/// Summary of organization operation
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct OrganizationSummary {
     pub total_files: usize,
+    /// Directories created, parents included: those a run made (and kept), or for a dry run
+    /// those it would make
     pub folders_created: usize,
-    pub categories: HashMap<String, usize>,
+    /// Destination folders that were there already
+    #[serde(default)]
+    pub folders_reused: usize,
+    /// Files per destination folder, by folder name
+    pub categories: BTreeMap<String, usize>,
+    /// Files of no known type by extension (`""` for none), most common first
+    #[serde(default)]
+    pub other_extensions: Vec<(String, stats::ExtensionStats)>,
+    /// Files left where they are, by why
+    #[serde(default)]
+    pub skipped: BTreeMap<SkipReason, usize>,
+    /// How long the run took, phase by phase
+    #[serde(default)]
+    pub timings: timings::PhaseTimings,
+    /// Paths the scan couldn't read
+    #[serde(default)]
+    pub scan_errors: Vec<ScanError>,
+}
+
+impl Default for OrganizationSummary {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl OrganizationSummary {
@@ -314,29 +1885,834 @@ impl OrganizationSummary {
         Self {
             total_files: 0,
             folders_created: 0,
-            categories: HashMap::new(),
+            folders_reused: 0,
+            categories: BTreeMap::new(),
+            other_extensions: Vec::new(),
+            skipped: BTreeMap::new(),
+            timings: timings::PhaseTimings::default(),
+            scan_errors: Vec::new(),
         }
     }
 
-    pub fn from_operations(operations: &[FileOperation]) -> Self {
-        let mut categories = HashMap::new();
-        let mut folders = std::collections::HashSet::new();
+    /// Summary of `operations`, carried out by a run that created `created_dirs`. With none
+    /// (a plan), the folders missing on disk are the ones it would create.
+    pub fn from_operations(operations: &[FileOperation], created_dirs: &[PathBuf]) -> Self {
+        let mut categories = BTreeMap::new();
+        let mut created = BTreeSet::new();
+        let mut reused = BTreeSet::new();
+        let is_new = |dir: &Path| created_dirs.iter().any(|created| created == dir) || !dir.exists();
 
         for op in operations {
             if let Some(parent) = op.destination.parent() {
                 if let Some(folder_name) = parent.file_name() {
-                    if let Some(folder_str) = folder_name.to_str() {
-                        *categories.entry(folder_str.to_string()).or_insert(0) += 1;
-                        folders.insert(folder_str.to_string());
-                    }
+                    *categories.entry(folder_name.to_string_lossy().into_owned()).or_insert(0) += 1;
+                }
+                if is_new(parent) {
+                    created.extend(parent.ancestors().take_while(|dir| !dir.as_os_str().is_empty() && is_new(dir)));
+                } else {
+                    reused.insert(parent);
                 }
             }
         }
 
         Self {
             total_files: operations.len(),
-            folders_created: folders.len(),
+            folders_created: created.len(),
+            folders_reused: reused.len(),
             categories,
+            other_extensions: Vec::new(),
+            skipped: BTreeMap::new(),
+            timings: timings::PhaseTimings::default(),
+            scan_errors: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(dir: &Path, name: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, name).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_plan_extension_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let photo = touch(dir.path(), "photo.jpg");
+        touch(dir.path(), "notes.txt");
+        touch(dir.path(), ".hidden");
+
+        let plan = FileOrganizer::new()
+            .plan(dir.path(), &OrganizeMode::Extension, false, None)
+            .unwrap();
+
+        assert_eq!(plan.operations.len(), 2);
+        let photo_op = plan.operations.iter().find(|op| op.source == photo).unwrap();
+        assert_eq!(photo_op.destination, dir.path().join("🖼️ Images").join("photo.jpg"));
+        assert!(plan.notices.is_empty());
+        // Planning never touches the filesystem
+        assert!(photo.exists());
+    }
+
+    #[test]
+    fn test_organize_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let photo = touch(dir.path(), "photo.jpg");
+        touch(dir.path(), "still-downloading.iso");
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let nested = touch(&dir.path().join("sub"), "nested.txt");
+
+        let mut organizer = FileOrganizer::new();
+        let files = [photo, nested, dir.path().join("gone.txt")];
+        let report = organizer.organize_files(dir.path(), &OrganizeMode::Extension, false, &files).unwrap();
+
+        assert_eq!(report.moved(), 1);
+        assert!(dir.path().join("🖼️ Images/photo.jpg").exists());
+        assert!(dir.path().join("still-downloading.iso").exists() && dir.path().join("sub/nested.txt").exists());
+        // The next run scans everything again
+        let report = organizer.organize(dir.path(), &OrganizeMode::Extension, false, None, true).unwrap();
+        assert_eq!(report.plan.len(), 1);
+    }
+
+    #[test]
+    fn test_folders_created_and_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "photo.jpg");
+        touch(dir.path(), "sunset.jpg");
+        touch(dir.path(), "notes.txt");
+        touch(dir.path(), "report.txt");
+        fs::create_dir(dir.path().join("🖼️ Images")).unwrap();
+
+        let mut organizer = FileOrganizer::new();
+        let preview = organizer.organize(dir.path(), &OrganizeMode::Extension, false, None, true).unwrap();
+        assert_eq!((preview.summary.folders_created, preview.summary.folders_reused), (1, 1));
+
+        let report = organizer.organize(dir.path(), &OrganizeMode::Extension, false, None, false).unwrap();
+        assert_eq!(report.moved(), 4);
+        assert_eq!((report.summary.folders_created, report.summary.folders_reused), (1, 1));
+        assert_eq!((preview.summary.timings.files, preview.summary.timings.execute_secs), (4, 0.0));
+        assert_eq!(report.summary.timings.files, 4);
+        assert!(report.summary.timings.execute_secs > 0.0);
+
+        // Nested folders count each directory made
+        touch(dir.path(), "old.txt");
+        let config = Config::parse("[dates]\nfolder_template = \"Paperwork/{year}\"\n").unwrap();
+        let mut organizer = FileOrganizer::with_config(OrganizeOptions::default(), &config).unwrap();
+        let report = organizer.organize(dir.path(), &OrganizeMode::Modified, false, None, false).unwrap();
+        assert_eq!((report.summary.folders_created, report.summary.folders_reused), (2, 0));
+    }
+
+    #[test]
+    fn test_skip_reasons() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "photo.jpg");
+        touch(dir.path(), "notes.txt");
+        touch(dir.path(), "setup.iso");
+        touch(dir.path(), ".bashrc");
+        touch(dir.path(), "Thumbs.db");
+        touch(dir.path(), "backup.part2.rar");
+        fs::create_dir(dir.path().join("🖼️ Images")).unwrap();
+        touch(&dir.path().join("🖼️ Images"), "photo.jpg");
+
+        let options = OrganizeOptions {
+            exclude_extensions: vec!["iso".to_string()],
+            on_conflict: ConflictPolicy::Skip,
+            ..OrganizeOptions::default()
+        };
+        let mut organizer = FileOrganizer::with_config(options, &Config::default()).unwrap();
+        let filters = vec!["jpg".to_string(), "iso".to_string(), "rar".to_string()];
+        let expected = BTreeMap::from([
+            (SkipReason::Hidden, 1),
+            (SkipReason::System, 1),
+            (SkipReason::Filtered, 1),
+            (SkipReason::Excluded, 1),
+            (SkipReason::IncompleteArchive, 1),
+            (SkipReason::Conflict, 1),
+        ]);
+
+        let preview = organizer.organize(dir.path(), &OrganizeMode::Extension, false, Some(&filters), true).unwrap();
+        assert_eq!(preview.summary.skipped, expected);
+        let report = organizer.organize(dir.path(), &OrganizeMode::Extension, false, Some(&filters), false).unwrap();
+        assert_eq!(report.summary.skipped, expected);
+        assert_eq!(report.moved(), 0);
+        assert!(report.skipped_files.contains(&SkippedFile::new(dir.path().join("notes.txt"), SkipReason::Filtered)));
+        assert!(report.skipped_files.contains(&SkippedFile::new(dir.path().join("photo.jpg"), SkipReason::Conflict)));
+    }
+
+    #[test]
+    fn test_plan_order() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["zebra.txt", "moon.jpg", "apple.txt", "comet.jpg", "berry.txt"] {
+            touch(dir.path(), name);
+        }
+
+        let plan = FileOrganizer::new().plan(dir.path(), &OrganizeMode::Extension, false, None).unwrap();
+        let destinations: Vec<&Path> = plan.operations.iter().map(|op| op.destination.as_path()).collect();
+        let mut sorted = destinations.clone();
+        sorted.sort();
+        assert_eq!(destinations, sorted);
+        let documents: Vec<&std::ffi::OsStr> = destinations
+            .iter()
+            .filter(|path| path.parent() == Some(&dir.path().join("📄 Documents")))
+            .filter_map(|path| path.file_name())
+            .collect();
+        assert_eq!(documents, ["apple.txt", "berry.txt", "zebra.txt"]);
+    }
+
+    #[test]
+    fn test_network_friendly_skips_hashing() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["Roboto-Bold.ttf", "Roboto-Bold (1).ttf"] {
+            fs::write(dir.path().join(name), b"same face").unwrap();
+        }
+        let folders = |network_friendly: bool| {
+            let options = OrganizeOptions { fonts_by_family: true, network_friendly, ..OrganizeOptions::default() };
+            let plan = FileOrganizer::with_config(options, &Config::default())
+                .unwrap()
+                .plan(dir.path(), &OrganizeMode::Extension, false, None)
+                .unwrap();
+            let mut folders: Vec<String> = plan
+                .operations
+                .iter()
+                .map(|op| op.destination.parent().unwrap().file_name().unwrap().to_string_lossy().to_string())
+                .collect();
+            folders.sort();
+            folders
+        };
+
+        assert_eq!(folders(false), [fonts::DUPLICATE_FONTS_FOLDER, "Roboto"]);
+        assert_eq!(folders(true), ["Roboto", "Roboto"]);
+    }
+
+    #[test]
+    fn test_network_friendly_reads_no_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("deploy"), b"#!/usr/bin/env bash\n").unwrap();
+        fs::write(dir.path().join("Makefile"), b"all:\n").unwrap();
+        let mut stream = vec![0u8; 2 * 188];
+        stream[0] = 0x47;
+        stream[188] = 0x47;
+        fs::write(dir.path().join("clip.ts"), stream).unwrap();
+        let config = Config::parse("[categories]\nno_extension = \"sniff\"\n").unwrap();
+        let folders = |network_friendly: bool| {
+            let options = OrganizeOptions { network_friendly, ..OrganizeOptions::default() };
+            let plan = FileOrganizer::with_config(options, &config)
+                .unwrap()
+                .plan(dir.path(), &OrganizeMode::Extension, false, None)
+                .unwrap();
+            let mut folders: Vec<(String, String)> = plan
+                .operations
+                .iter()
+                .map(|op| {
+                    let folder = op.destination.parent().unwrap().file_name().unwrap().to_string_lossy().to_string();
+                    (op.source.file_name().unwrap().to_string_lossy().to_string(), folder)
+                })
+                .collect();
+            folders.sort();
+            folders
+        };
+        let pair = |file: &str, folder: &str| (file.to_string(), folder.to_string());
+
+        assert_eq!(folders(false), [pair("Makefile", "💻 Code"), pair("clip.ts", "🎬 Videos"), pair("deploy", "💻 Code")]);
+        // Only names count: the extension's usual category, and build files by their name
+        assert_eq!(folders(true), [pair("Makefile", "💻 Code"), pair("clip.ts", "💻 Code"), pair("deploy", "📂 Other")]);
+    }
+
+    #[test]
+    fn test_durable_run() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "photo.jpg");
+        touch(dir.path(), "notes.txt");
+
+        let options = OrganizeOptions { durable: true, ..OrganizeOptions::default() };
+        let mut organizer = FileOrganizer::with_config(options, &Config::default()).unwrap();
+        assert_eq!(organizer.checkpoint_every(), Some(1));
+        let report = organizer.organize(dir.path(), &OrganizeMode::Extension, false, None, false).unwrap();
+
+        assert_eq!(report.moved(), 2);
+        assert!(report.operation_id.is_some());
+        assert!(!resume::resume_path(dir.path()).exists());
+        assert!(dir.path().join("🖼️ Images/photo.jpg").exists());
+    }
+
+    #[test]
+    fn test_plan_applies_filters_and_notices() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "photo.jpg");
+        touch(dir.path(), "notes.txt");
+
+        let filters = vec!["txt".to_string()];
+        let plan = FileOrganizer::new()
+            .plan(dir.path(), &OrganizeMode::Custom, false, Some(&filters))
+            .unwrap();
+
+        assert_eq!(plan.operations.len(), 1);
+        assert_eq!(plan.operations[0].destination, dir.path().join("📄 Documents").join("notes.txt"));
+        assert!(matches!(plan.notices.as_slice(), [Notice::NoCustomRules]));
+    }
+
+    #[test]
+    fn test_execute_records_outcomes() {
+        let dir = tempfile::tempdir().unwrap();
+        let photo = touch(dir.path(), "photo.jpg");
+        let operations = vec![
+            FileOperation {
+                source: photo.clone(),
+                destination: dir.path().join("Images").join("photo.jpg"),
+                operation_type: OperationType::Move,
+                size: 0,
+            },
+            FileOperation {
+                source: dir.path().join("missing.jpg"),
+                destination: dir.path().join("Images").join("missing.jpg"),
+                operation_type: OperationType::Move,
+                size: 0,
+            },
+        ];
+
+        let outcomes = FileOrganizer::new().execute(&operations).unwrap();
+
+        assert!(outcomes[0].succeeded());
+        assert!(!outcomes[1].succeeded());
+        assert!(!photo.exists());
+        assert!(dir.path().join("Images").join("photo.jpg").exists());
+    }
+
+    #[test]
+    fn test_organize_dry_run_reports_without_moving() {
+        let dir = tempfile::tempdir().unwrap();
+        let photo = touch(dir.path(), "photo.jpg");
+
+        let report = FileOrganizer::new()
+            .organize(dir.path(), &OrganizeMode::Extension, false, None, true)
+            .unwrap();
+
+        assert!(report.dry_run);
+        assert_eq!(report.plan.len(), 1);
+        assert!(report.outcomes.is_empty());
+        assert_eq!(report.summary.total_files, 1);
+        assert!(photo.exists());
+    }
+
+    #[test]
+    fn test_recent_files_wait_in_pending() {
+        let dir = tempfile::tempdir().unwrap();
+        let age = |path: &Path, days: u64| {
+            let modified = std::time::SystemTime::now() - std::time::Duration::from_secs(days * 86400);
+            fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+        };
+        touch(dir.path(), "draft.txt");
+        age(&touch(dir.path(), "report.txt"), 10);
+        let pending = dir.path().join(PENDING_DIR);
+        fs::create_dir(&pending).unwrap();
+        touch(&pending, "notes.txt");
+        age(&touch(&pending, "invoice.txt"), 8);
+
+        let options = OrganizeOptions { pending_for: Some(std::time::Duration::from_secs(7 * 86400)), ..OrganizeOptions::default() };
+        let report = FileOrganizer::with_config(options, &Config::default())
+            .unwrap()
+            .organize(dir.path(), &OrganizeMode::Extension, false, None, false)
+            .unwrap();
+
+        assert_eq!(report.moved(), 3);
+        let documents = dir.path().join("📄 Documents");
+        assert!(pending.join("draft.txt").exists() && pending.join("notes.txt").exists());
+        assert!(documents.join("report.txt").exists() && documents.join("invoice.txt").exists());
+        assert_eq!(report.skipped_files, [SkippedFile::new(pending.join("notes.txt"), SkipReason::Pending)]);
+    }
+
+    #[test]
+    fn test_real_run_follows_the_previewed_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        let photo = touch(dir.path(), "photo.jpg");
+        let options = OrganizeOptions { on_conflict: ConflictPolicy::Rename, ..OrganizeOptions::default() };
+        let preview = FileOrganizer::with_config(options.clone(), &Config::default())
+            .unwrap()
+            .plan(dir.path(), &OrganizeMode::Extension, false, None)
+            .unwrap();
+        let images = preview.operations[0].destination.parent().unwrap().to_path_buf();
+        fs::create_dir(&images).unwrap();
+        touch(&images, "photo.jpg");
+
+        // The conflict is renamed in the plan itself, not only once the files move
+        let dry_run = FileOrganizer::with_config(options.clone(), &Config::default())
+            .unwrap()
+            .organize(dir.path(), &OrganizeMode::Extension, false, None, true)
+            .unwrap();
+        assert_eq!(dry_run.plan[0].destination, images.join("photo (1).jpg"));
+        assert_ne!(dry_run.plan_id, preview.id());
+
+        let guarded = OrganizeOptions { plan_id: Some(dry_run.plan_id.clone()), ..options };
+        let notes = touch(dir.path(), "notes.txt");
+        let result = FileOrganizer::with_config(guarded.clone(), &Config::default())
+            .unwrap()
+            .organize(dir.path(), &OrganizeMode::Extension, false, None, false);
+        assert!(matches!(result, Err(OrganizerError::Plan { .. })));
+        assert!(photo.exists() && notes.exists());
+
+        fs::remove_file(&notes).unwrap();
+        let report = FileOrganizer::with_config(guarded, &Config::default())
+            .unwrap()
+            .organize(dir.path(), &OrganizeMode::Extension, false, None, false)
+            .unwrap();
+        assert_eq!(report.plan, dry_run.plan);
+        assert_eq!(report.outcomes[0].operation, dry_run.plan[0]);
+        assert!(images.join("photo (1).jpg").exists());
+    }
+
+    #[test]
+    fn test_delete_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let build = touch(dir.path(), "build.tmp");
+        touch(dir.path(), "photo.jpg");
+        let config = Config::parse(
+            "[execution]\nallow_delete = true\n[[rules]]\nname = \"temp files\"\npattern = \"*.tmp\"\naction = \"delete\"\n",
+        )
+        .unwrap();
+        let options = OrganizeOptions { permanent_delete: true, ..OrganizeOptions::default() };
+
+        let plan = FileOrganizer::with_config(options.clone(), &config).unwrap().plan(dir.path(), &OrganizeMode::Custom, false, None).unwrap();
+        assert_eq!(plan.deletions, [PlannedDeletion { path: build.clone(), rule: "temp files".to_string() }]);
+        assert!(plan.operations.iter().all(|op| op.source != build));
+        let trashing = FileOrganizer::with_config(OrganizeOptions::default(), &config).unwrap().plan(dir.path(), &OrganizeMode::Custom, false, None).unwrap();
+        assert_ne!(trashing.id(), plan.id());
+
+        let report = FileOrganizer::with_config(options, &config).unwrap().organize(dir.path(), &OrganizeMode::Custom, false, None, false).unwrap();
+        assert_eq!(report.deleted.len(), 1);
+        assert_eq!(report.deleted[0].error, None);
+        assert!(!build.exists() && !dir.path().join("photo.jpg").exists());
+
+        // A run stopped by a failure doesn't go on to delete
+        let images = dir.path().join("🖼️ Images");
+        let build = touch(dir.path(), "build.tmp");
+        touch(dir.path(), "photo.jpg");
+        for on_error in [ErrorPolicy::FailFast, ErrorPolicy::Rollback] {
+            let options = OrganizeOptions { permanent_delete: true, on_error, ..OrganizeOptions::default() };
+            let report = FileOrganizer::with_config(options, &config).unwrap().organize(dir.path(), &OrganizeMode::Custom, false, None, false).unwrap();
+            assert_eq!((report.failed(), report.deletions_not_attempted()), (1, 1));
+            assert!(build.exists() && images.join("photo.jpg").exists());
+        }
+    }
+
+    #[test]
+    fn test_undo_asks_about_taken_locations() {
+        struct RenameEverything;
+        impl OrganizeObserver for RenameEverything {
+            fn on_conflict(&self, _operation: &FileOperation) -> ConflictChoice {
+                ConflictChoice::Rename
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let photo = touch(dir.path(), "photo.jpg");
+        touch(dir.path(), "notes.txt");
+        let options = OrganizeOptions { on_conflict: ConflictPolicy::Ask, ..OrganizeOptions::default() };
+        let mut organizer = FileOrganizer::with_config(options, &Config::default())
+            .unwrap()
+            .with_observer(RenameEverything);
+
+        let report = organizer.organize(dir.path(), &OrganizeMode::Extension, false, None, false).unwrap();
+        assert!(report.operation_id.is_some());
+        fs::write(&photo, b"a different photo").unwrap();
+        let notes = report.plan.iter().find(|op| op.source.ends_with("notes.txt")).unwrap();
+        fs::remove_file(&notes.destination).unwrap();
+
+        let undo = organizer.undo(dir.path(), false).unwrap().unwrap();
+        assert_eq!(undo.restored(), 1);
+        assert_eq!(undo.missing.len(), 1);
+        assert_eq!(undo.unrestorable(), 1);
+        assert_eq!(fs::read(&photo).unwrap(), b"a different photo");
+        assert!(dir.path().join("photo (1).jpg").exists());
+        // Nothing left that a retry could fix
+        assert!(organizer.undo(dir.path(), false).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_files_without_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("deploy"), b"#!/usr/bin/env bash\n").unwrap();
+        fs::write(dir.path().join("LICENSE"), b"MIT License\n").unwrap();
+        fs::write(dir.path().join("photo.jpg"), b"jpg").unwrap();
+        let plan = |no_extension: &str| {
+            let config = Config::parse(&format!("[categories]\nno_extension = \"{}\"\n", no_extension)).unwrap();
+            let organizer = FileOrganizer::with_config(OrganizeOptions::default(), &config).unwrap();
+            let plan = organizer.plan(dir.path(), &OrganizeMode::Extension, false, None).unwrap();
+            let mut folders: Vec<(String, String)> = plan
+                .operations
+                .iter()
+                .map(|op| {
+                    let folder = op.destination.parent().unwrap().file_name().unwrap().to_string_lossy().to_string();
+                    (op.source.file_name().unwrap().to_string_lossy().to_string(), folder)
+                })
+                .collect();
+            folders.sort();
+            (folders, plan.skipped)
+        };
+        let pair = |file: &str, folder: &str| (file.to_string(), folder.to_string());
+
+        let (folders, _) = plan("other");
+        assert_eq!(folders, [pair("LICENSE", "📂 Other"), pair("deploy", "💻 Code"), pair("photo.jpg", "🖼️ Images")]);
+        let (folders, _) = plan("sniff");
+        assert_eq!(folders[0], pair("LICENSE", "📄 Documents"));
+        let (folders, _) = plan("folder");
+        assert_eq!(folders[..2], [pair("LICENSE", extensionless::NO_EXTENSION_FOLDER), pair("deploy", "💻 Code")]);
+        let (folders, skipped) = plan("skip");
+        assert_eq!(folders, [pair("photo.jpg", "🖼️ Images")]);
+        assert!(skipped.iter().all(|file| file.reason == SkipReason::NoExtension) && skipped.len() == 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_names() {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = tempfile::tempdir().unwrap();
+        let name = |bytes: &[u8]| std::ffi::OsStr::from_bytes(bytes).to_os_string();
+        let photo = dir.path().join(name(b"caf\xe9.jpg"));
+        fs::write(&photo, b"jpg").unwrap();
+        fs::write(dir.path().join(name(b".\xe9t\xe9")), b"dot file").unwrap();
+
+        let mut organizer = FileOrganizer::new();
+        let report = organizer.organize(dir.path(), &OrganizeMode::Extension, false, None, false).unwrap();
+        assert_eq!(report.plan.len(), 1);
+        assert_eq!(report.plan[0].destination, dir.path().join("🖼️ Images").join(name(b"caf\xe9.jpg")));
+        assert_eq!(report.summary.skipped[&SkipReason::Hidden], 1);
+        // The journal keeps the exact name, so undo finds the file
+        assert!(report.operation_id.is_some());
+        organizer.undo(dir.path(), false).unwrap().unwrap();
+        assert!(photo.exists());
+    }
+
+    #[test]
+    fn test_scan_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "photo.jpg");
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+
+        let mut scan = Scan::default();
+        FileOrganizer::new().scan_failure(&mut scan, &dir.path().join("locked"), denied).unwrap();
+        assert_eq!(scan.errors, [ScanError::new(dir.path().join("locked"), "permission denied")]);
+        let strict = FileOrganizer::with_config(OrganizeOptions { strict_scan: true, ..OrganizeOptions::default() }, &Config::default()).unwrap();
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert!(matches!(strict.scan_failure(&mut scan, dir.path(), denied), Err(OrganizerError::Scan { .. })));
+
+        // An unreadable subdirectory is reported rather than dropped (root reads it anyway)
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let locked = dir.path().join("locked");
+            fs::create_dir(&locked).unwrap();
+            touch(&locked, "notes.txt");
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+            if fs::read_dir(&locked).is_err() {
+                let plan = FileOrganizer::new().plan(dir.path(), &OrganizeMode::Extension, true, None).unwrap();
+                assert_eq!(plan.operations.len(), 1);
+                assert_eq!(plan.scan_errors.len(), 1);
+                assert_eq!(plan.scan_errors[0].path, locked);
+                assert!(strict.plan(dir.path(), &OrganizeMode::Extension, true, None).is_err());
+            }
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_undo_operation() {
+        let dir = tempfile::tempdir().unwrap();
+        let photo = touch(dir.path(), "photo.jpg");
+        let mut organizer = FileOrganizer::new();
+        let first = organizer.organize(dir.path(), &OrganizeMode::Extension, false, None, false).unwrap();
+        let notes = touch(dir.path(), "notes.txt");
+        let second = organizer.organize(dir.path(), &OrganizeMode::Extension, false, None, false).unwrap();
+
+        // The earlier run, leaving the later one alone
+        let id = first.operation_id.unwrap();
+        assert_eq!(organizer.undo_operation(dir.path(), &id, false).unwrap().restored(), 1);
+        assert!(photo.exists() && !notes.exists());
+        assert!(matches!(organizer.undo_operation(dir.path(), &id, false), Err(OrganizerError::Journal { .. })));
+        assert_eq!(organizer.undo(dir.path(), false).unwrap().unwrap().operation_id, second.operation_id.unwrap());
+        assert!(notes.exists());
+    }
+
+    #[test]
+    fn test_exclusions_and_contradictions() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "photo.jpg");
+        touch(dir.path(), "main.rs");
+        touch(dir.path(), "backup.zip");
+        touch(dir.path(), "notes.TXT");
+        let organizer = |options: OrganizeOptions| FileOrganizer::with_config(options, &Config::default()).unwrap();
+        let planned = |organizer: &FileOrganizer, filters: Option<&Vec<String>>| {
+            let plan = organizer.plan(dir.path(), &OrganizeMode::Extension, false, filters);
+            plan.map(|plan| {
+                let mut names: Vec<String> =
+                    plan.operations.iter().map(|op| op.source.file_name().unwrap().to_string_lossy().to_string()).collect();
+                names.sort();
+                names
+            })
+        };
+
+        let everything_but_code = organizer(OrganizeOptions {
+            exclude_extensions: vec![".txt".to_string()],
+            exclude_categories: vec![FileCategory::Code, FileCategory::Archives],
+            ..OrganizeOptions::default()
+        });
+        assert_eq!(planned(&everything_but_code, None).unwrap(), ["photo.jpg"]);
+
+        let strings = |items: &[&str]| items.iter().map(|item| item.to_string()).collect::<Vec<_>>();
+        // Exclusions win, and a filter they rule out completely is an error
+        assert_eq!(planned(&everything_but_code, Some(&strings(&["jpg", "rs"]))).unwrap(), ["photo.jpg"]);
+        let error = planned(&everything_but_code, Some(&strings(&["rs"]))).unwrap_err().to_string();
+        assert!(error.contains("--exclude-categories"), "{}", error);
+
+        let contradictory = organizer(OrganizeOptions {
+            only_categories: vec![FileCategory::Images],
+            exclude_categories: vec![FileCategory::Images],
+            ..OrganizeOptions::default()
+        });
+        assert!(matches!(planned(&contradictory, None), Err(OrganizerError::Plan { path, .. }) if path == dir.path()));
+
+        // Compressed tarballs match by their whole extension as well as the last part
+        touch(dir.path(), "logs.tar.gz");
+        let everything = organizer(OrganizeOptions::default());
+        assert_eq!(planned(&everything, Some(&strings(&["tar.gz"]))).unwrap(), ["logs.tar.gz"]);
+        assert_eq!(planned(&everything, Some(&strings(&["gz"]))).unwrap(), ["logs.tar.gz"]);
+        let no_tarballs = organizer(OrganizeOptions { exclude_extensions: vec!["tar.gz".to_string()], ..OrganizeOptions::default() });
+        assert!(!planned(&no_tarballs, None).unwrap().contains(&"logs.tar.gz".to_string()));
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let dir = tempfile::tempdir().unwrap();
+        let organizer = FileOrganizer::new();
+        let missing = dir.path().join("missing");
+        let error = organizer.plan(&missing, &OrganizeMode::Extension, false, None).unwrap_err();
+        assert!(matches!(&error, OrganizerError::Scan { path, source, .. } if *path == missing && source.kind() == io::ErrorKind::NotFound));
+
+        // A file where a destination folder should go
+        touch(dir.path(), "photo.jpg");
+        touch(dir.path(), "Images");
+        let op = FileOperation {
+            source: dir.path().join("photo.jpg"),
+            destination: dir.path().join("Images/2024/photo.jpg"),
+            operation_type: OperationType::Move,
+            size: 0,
+        };
+        match organizer.execute(std::slice::from_ref(&op)).unwrap_err() {
+            OrganizerError::Execution { op: failed, .. } => assert_eq!(failed, op),
+            other => panic!("expected an execution error, got {:?}", other),
+        }
+
+        let config = Config::parse("[[rules]]\nname = \"nowhere\"\n");
+        assert!(matches!(config, Err(OrganizerError::Config { .. })));
+    }
+
+    #[test]
+    fn test_analyze_writes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "photo.jpg");
+        touch(dir.path(), "notes.txt");
+        let options = OrganizeOptions { analyze: true, checkpoint_every: Some(1), ..OrganizeOptions::default() };
+        let mut organizer = FileOrganizer::with_config(options, &Config::default()).unwrap();
+
+        let report = organizer.organize(dir.path(), &OrganizeMode::Extension, false, None, false).unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.plan.len(), 2);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+        assert!(organizer.execute(&report.plan).is_err());
+        assert!(organizer.resume(dir.path()).unwrap().is_none());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_plan_serialization() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "photo.jpg");
+        touch(dir.path(), "notes.txt");
+        fs::create_dir(dir.path().join("🖼️ Images")).unwrap();
+        touch(&dir.path().join("🖼️ Images"), "photo.jpg");
+
+        let plan = FileOrganizer::new().plan(dir.path(), &OrganizeMode::Extension, false, None).unwrap();
+        assert!(plan.notices.iter().any(|notice| matches!(notice, Notice::Conflicts { .. })));
+        let json = serde_json::to_value(&plan).unwrap();
+        assert_eq!(json["operations"][0]["operation_type"], "move");
+        assert_eq!(json["notices"][0]["conflicts"]["policy"], "refuse");
+
+        let read: OrganizePlan = serde_json::from_value(json).unwrap();
+        assert_eq!(read.operations, plan.operations);
+        assert_eq!(format!("{:?}", read.notices), format!("{:?}", plan.notices));
+
+        let summary = OrganizationSummary::from_operations(&plan.operations, &[]);
+        let read: OrganizationSummary = serde_json::from_str(&serde_json::to_string(&summary).unwrap()).unwrap();
+        assert_eq!(read.categories, summary.categories);
+    }
+
+    #[test]
+    fn test_error_policies() {
+        let run = |on_error: ErrorPolicy| {
+            let dir = tempfile::tempdir().unwrap();
+            let root = dir.path().to_path_buf();
+            fs::create_dir(root.join("Images")).unwrap();
+            touch(&root.join("Images"), "taken.jpg");
+            let op = |source: &str, destination: &str| FileOperation {
+                source: touch(&root, source),
+                destination: root.join(destination),
+                operation_type: OperationType::Move,
+                size: 0,
+            };
+            let operations = [
+                op("a.txt", "New/a.txt"),
+                op("taken.jpg", "Images/taken.jpg"),
+                op("c.txt", "New/c.txt"),
+                op("d.txt", "Later/Deep/d.txt"),
+            ];
+
+            let options = OrganizeOptions { on_error, ..OrganizeOptions::default() };
+            let outcomes = FileOrganizer::with_config(options, &Config::default()).unwrap().execute(&operations).unwrap();
+            (dir, outcomes)
+        };
+
+        let (_dir, outcomes) = run(ErrorPolicy::KeepGoing);
+        assert_eq!(outcomes.iter().map(FileOutcome::succeeded).collect::<Vec<_>>(), [true, false, true, true]);
+
+        let (dir, outcomes) = run(ErrorPolicy::FailFast);
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes[0].succeeded());
+        assert!(dir.path().join("New/a.txt").exists());
+        assert!(dir.path().join("c.txt").exists());
+        // Folders made for files that were never attempted don't stay behind
+        assert!(!dir.path().join("Later").exists());
+
+        let (dir, outcomes) = run(ErrorPolicy::Rollback);
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes[0].rolled_back && !outcomes[0].succeeded());
+        assert!(dir.path().join("a.txt").exists());
+        // The directory the run made is gone again, the one that was there stays
+        assert!(!dir.path().join("New").exists());
+        assert!(dir.path().join("Images/taken.jpg").exists());
+    }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+        use std::collections::{BTreeMap, HashSet};
+        use std::path::Component;
+
+        /// Relative file paths like `a/b/report.pdf`; directories and names come from small
+        /// alphabets so that collisions (same name in different folders) are common
+        fn file_tree() -> impl Strategy<Value = Vec<String>> {
+            let dir = prop::sample::select(vec!["a", "b", "Images", "deep"]);
+            let stem = prop::sample::select(vec!["photo", "notes", "song", "main", "data"]);
+            let ext = prop::sample::select(vec![".jpg", ".txt", ".pdf", ".rs", ".mp3", ".zip", ".xyz", ""]);
+            let file = (prop::collection::vec(dir, 0..3), stem, ext)
+                .prop_map(|(dirs, stem, ext)| {
+                    let mut path = dirs.join("/");
+                    if !path.is_empty() {
+                        path.push('/');
+                    }
+                    format!("{}{}{}", path, stem, ext)
+                });
+            prop::collection::vec(file, 1..20)
+        }
+
+        fn mode() -> impl Strategy<Value = OrganizeMode> {
+            prop::sample::select(vec![
+                OrganizeMode::Extension,
+                OrganizeMode::Size,
+                OrganizeMode::Modified,
+                OrganizeMode::Accessed,
+                OrganizeMode::Origin,
+                OrganizeMode::Custom,
+            ])
+        }
+
+        /// Create the tree; each file contains its own relative path. Paths that clash with
+        /// an existing file or directory are skipped.
+        fn create_tree(root: &Path, files: &[String]) -> BTreeMap<PathBuf, String> {
+            let mut created = BTreeMap::new();
+            for file in files {
+                let path = root.join(file);
+                if fs::create_dir_all(path.parent().unwrap()).is_ok() && !path.exists() && fs::write(&path, file).is_ok() {
+                    created.insert(path, file.clone());
+                }
+            }
+            created
+        }
+
+        /// The files under `root` with their contents, leaving out the run's history
+        fn snapshot(root: &Path) -> BTreeMap<PathBuf, String> {
+            WalkDir::new(root)
+                .into_iter()
+                .filter_entry(|entry| entry.file_name() != journal::METADATA_DIR)
+                .map(|entry| entry.unwrap())
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| (entry.path().to_path_buf(), fs::read_to_string(entry.path()).unwrap()))
+                .collect()
+        }
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(64))]
+
+            #[test]
+            fn plan_invariants_hold(files in file_tree(), mode in mode(), layout in 0..3u8) {
+                let dir = tempfile::tempdir().unwrap();
+                let root = dir.path();
+                let original = create_tree(root, &files);
+                let options = OrganizeOptions {
+                    preserve_structure: layout == 1,
+                    per_directory: layout == 2,
+                    ..OrganizeOptions::default()
+                };
+                let mut organizer = FileOrganizer::with_config(options, &Config::default()).unwrap();
+
+                let plan = organizer.plan(root, &mode, true, None).unwrap();
+
+                // Every source appears exactly once (per-directory runs skip files that are
+                // already in their category folder)
+                let sources: Vec<&PathBuf> = plan.operations.iter().map(|op| &op.source).collect();
+                let unique_sources: HashSet<&PathBuf> = sources.iter().copied().collect();
+                prop_assert_eq!(sources.len(), unique_sources.len());
+                prop_assert!(unique_sources.iter().all(|source| original.contains_key(*source)));
+                if layout != 2 {
+                    // ...or already in `Images`, the twin of `🖼️ Images`
+                    for source in original.keys().filter(|source| !unique_sources.contains(source)) {
+                        prop_assert!(source.starts_with(root.join("Images")), "{:?} isn't planned", source);
+                    }
+                }
+
+                // Destinations never escape the target root
+                for op in &plan.operations {
+                    let relative = op.destination.strip_prefix(root);
+                    prop_assert!(relative.is_ok(), "{:?} escapes {:?}", op.destination, root);
+                    prop_assert!(relative.unwrap().components().all(|c| matches!(c, Component::Normal(_))));
+                }
+
+                // Operations may share a destination (same name from different folders); the
+                // executor's policy is to move the first and leave the rest in place
+                let report = organizer.organize(root, &mode, true, None, false).unwrap();
+                prop_assert_eq!(&report.plan, &plan.operations);
+                let mut moved_to: HashSet<&PathBuf> = HashSet::new();
+                for outcome in &report.outcomes {
+                    if outcome.succeeded() {
+                        prop_assert!(moved_to.insert(&outcome.operation.destination));
+                    } else {
+                        prop_assert!(outcome.operation.source.exists());
+                    }
+                }
+
+                // Nothing was lost or overwritten
+                let mut contents: Vec<String> = snapshot(root).into_values().collect();
+                contents.sort();
+                let mut expected: Vec<String> = original.values().cloned().collect();
+                expected.sort();
+                prop_assert_eq!(contents, expected);
+
+                // Undoing the journaled run restores the original tree
+                let undone = organizer.undo(root, false).unwrap();
+                prop_assert_eq!(undone.is_some(), report.moved() > 0);
+                prop_assert_eq!(snapshot(root), original);
+            }
         }
     }
 }
@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+
+/// A mounted filesystem, as listed in `/proc/self/mountinfo`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    pub mount_point: PathBuf,
+    /// Per-mount options such as `rw`, `noatime`, `relatime`
+    pub options: Vec<String>,
+    pub filesystem: String,
+    /// What's mounted: a device such as `/dev/sdb1`, or a name like `tmpfs` or `drive:`
+    pub device: String,
+}
+
+impl MountInfo {
+    pub fn has_option(&self, option: &str) -> bool {
+        self.options.iter().any(|o| o == option)
+    }
+}
+
+/// The mount that contains `path`; `None` where mount tables aren't available (non-Linux)
+pub fn mount_for(path: &Path) -> Option<MountInfo> {
+    let path = path.canonicalize().ok()?;
+    let table = read_mount_table()?;
+    find_mount(&parse_mountinfo(&table), &path).cloned()
+}
+
+#[cfg(target_os = "linux")]
+fn read_mount_table() -> Option<String> {
+    std::fs::read_to_string("/proc/self/mountinfo").ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_mount_table() -> Option<String> {
+    None
+}
+
+/// Parse the mountinfo format described in proc(5)
+fn parse_mountinfo(table: &str) -> Vec<MountInfo> {
+    table
+        .lines()
+        .filter_map(|line| {
+            let (mount_fields, fs_fields) = line.split_once(" - ")?;
+            let mount_fields: Vec<&str> = mount_fields.split_whitespace().collect();
+            let mut fs_fields = fs_fields.split_whitespace();
+            Some(MountInfo {
+                mount_point: PathBuf::from(unescape(mount_fields.get(4)?)),
+                options: mount_fields.get(5)?.split(',').map(str::to_string).collect(),
+                filesystem: fs_fields.next()?.to_string(),
+                device: unescape(fs_fields.next()?),
+            })
+        })
+        .collect()
+}
+
+/// The deepest mount point containing `path`; later entries win (they're mounted on top)
+fn find_mount<'a>(mounts: &'a [MountInfo], path: &Path) -> Option<&'a MountInfo> {
+    mounts
+        .iter()
+        .enumerate()
+        .filter(|(_, mount)| path.starts_with(&mount.mount_point))
+        .max_by_key(|(index, mount)| (mount.mount_point.components().count(), *index))
+        .map(|(_, mount)| mount)
+}
+
+/// Mount points escape spaces, tabs, newlines and backslashes as `\ooo` octal
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() && bytes[i + 1..i + 4].iter().all(|b| (b'0'..=b'7').contains(b)) {
+            let octal = std::str::from_utf8(&bytes[i + 1..i + 4]).unwrap();
+            out.push(u8::from_str_radix(octal, 8).unwrap_or(b'?'));
+            i += 4;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTINFO: &str = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw,errors=remount-ro
+30 22 8:2 / /home rw,noatime shared:2 - ext4 /dev/sda2 rw
+31 30 0:40 / /home/me/My\\040Drive rw,nosuid - fuse.rclone drive: rw
+";
+
+    #[test]
+    fn test_find_mount() {
+        let mounts = parse_mountinfo(MOUNTINFO);
+        assert_eq!(mounts.len(), 3);
+        assert_eq!(mounts[2].mount_point, PathBuf::from("/home/me/My Drive"));
+        assert_eq!(mounts[2].filesystem, "fuse.rclone");
+        assert_eq!(mounts[1].device, "/dev/sda2");
+
+        let home = find_mount(&mounts, Path::new("/home/me/Downloads")).unwrap();
+        assert!(home.has_option("noatime"));
+        let root = find_mount(&mounts, Path::new("/tmp")).unwrap();
+        assert!(root.has_option("relatime"));
+        let drive = find_mount(&mounts, Path::new("/home/me/My Drive/file.txt")).unwrap();
+        assert_eq!(drive.filesystem, "fuse.rclone");
+    }
+}
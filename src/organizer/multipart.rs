@@ -0,0 +1,243 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use super::FileOperation;
+
+/// Naming scheme used by a split archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum PartScheme {
+    /// `name.part1.rar`, `name.part2.rar`, ...
+    RarParts,
+    /// `name.rar`, `name.r00`, `name.r01`, ...
+    RarVolumes,
+    /// `name.z01`, `name.z02`, ..., `name.zip`
+    ZipVolumes,
+    /// `name.7z.001`, `name.zip.002`, ...
+    Numbered,
+}
+
+/// One file's position within a split archive
+#[derive(Debug, Clone, PartialEq)]
+struct ArchivePart {
+    scheme: PartScheme,
+    base: String,
+    /// Position in the set; `.rar`/`.zip` heads use 0
+    index: u32,
+}
+
+/// A split archive that is missing some of its parts
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct IncompleteGroup {
+    pub name: String,
+    pub present: Vec<PathBuf>,
+    pub missing: Vec<String>,
+}
+
+/// Check whether a file name looks like one volume of a split archive
+pub fn is_archive_part(file_path: &Path) -> bool {
+    file_path
+        .file_name()
+        .and_then(|name| parse_part(&name.to_string_lossy().to_lowercase()))
+        .map(|part| part.index > 0 || part.scheme == PartScheme::RarParts)
+        .unwrap_or(false)
+}
+
+/// Make every split archive move as one unit.
+///
+/// Complete sets are sent to the destination of their first part. Sets with missing parts
+/// are dropped from the plan entirely and returned so they can be reported.
+pub fn group_parts(operations: &mut Vec<FileOperation>) -> Vec<IncompleteGroup> {
+    let mut groups: HashMap<(PathBuf, PartScheme, String), Vec<(u32, usize)>> = HashMap::new();
+
+    for (op_index, op) in operations.iter().enumerate() {
+        let part = op
+            .source
+            .file_name()
+            .and_then(|name| parse_part(&name.to_string_lossy().to_lowercase()));
+        let dir = op.source.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        if let Some(part) = part {
+            groups
+                .entry((dir, part.scheme, part.base))
+                .or_default()
+                .push((part.index, op_index));
+        }
+    }
+
+    let mut incomplete = Vec::new();
+    let mut dropped = BTreeSet::new();
+
+    for ((_, scheme, base), mut members) in groups {
+        members.sort();
+
+        // A lone `name.rar` or `name.zip` is just a regular archive
+        if members.len() == 1 && members[0].0 == 0 {
+            continue;
+        }
+
+        let indexes: BTreeSet<u32> = members.iter().map(|(index, _)| *index).collect();
+        let missing = missing_parts(scheme, &indexes);
+
+        if missing.is_empty() {
+            let lead_dir = operations[members[0].1]
+                .destination
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            for (_, op_index) in &members {
+                let op = &mut operations[*op_index];
+                if let Some(file_name) = op.source.file_name() {
+                    op.destination = lead_dir.join(file_name);
+                }
+            }
+        } else {
+            incomplete.push(IncompleteGroup {
+                name: base,
+                present: members
+                    .iter()
+                    .map(|(_, op_index)| operations[*op_index].source.clone())
+                    .collect(),
+                missing,
+            });
+            dropped.extend(members.iter().map(|(_, op_index)| *op_index));
+        }
+    }
+
+    incomplete.sort_by(|a, b| a.present.cmp(&b.present));
+
+    for op_index in dropped.into_iter().rev() {
+        operations.remove(op_index);
+    }
+
+    incomplete.sort_by(|a, b| a.name.cmp(&b.name));
+    incomplete
+}
+
+/// Describe the parts a set needs but doesn't have
+fn missing_parts(scheme: PartScheme, indexes: &BTreeSet<u32>) -> Vec<String> {
+    let max = indexes.iter().next_back().copied().unwrap_or(0);
+    let first = match scheme {
+        PartScheme::RarParts | PartScheme::Numbered => 1,
+        PartScheme::RarVolumes | PartScheme::ZipVolumes => 0,
+    };
+
+    (first..=max)
+        .filter(|index| !indexes.contains(index))
+        .map(|index| match (scheme, index) {
+            (PartScheme::RarParts, n) => format!(".part{}.rar", n),
+            (PartScheme::RarVolumes, 0) => ".rar".to_string(),
+            (PartScheme::RarVolumes, n) => format!(".r{:02}", n - 1),
+            (PartScheme::ZipVolumes, 0) => ".zip".to_string(),
+            (PartScheme::ZipVolumes, n) => format!(".z{:02}", n),
+            (PartScheme::Numbered, n) => format!(".{:03}", n),
+        })
+        .collect()
+}
+
+/// Parse a lowercase file name into its split-archive position, if any
+fn parse_part(name: &str) -> Option<ArchivePart> {
+    let (stem, ext) = name.rsplit_once('.')?;
+
+    if ext == "rar" {
+        if let Some((base, part)) = stem.rsplit_once(".part") {
+            if let Some(index) = parse_digits(part) {
+                return Some(ArchivePart { scheme: PartScheme::RarParts, base: base.to_string(), index });
+            }
+        }
+        return Some(ArchivePart { scheme: PartScheme::RarVolumes, base: stem.to_string(), index: 0 });
+    }
+
+    if ext == "zip" {
+        return Some(ArchivePart { scheme: PartScheme::ZipVolumes, base: stem.to_string(), index: 0 });
+    }
+
+    if ext.len() == 3 {
+        if let (Some(prefix), Some(index)) = (ext.chars().next(), parse_digits(&ext[1..])) {
+            match prefix {
+                'r' => {
+                    return Some(ArchivePart { scheme: PartScheme::RarVolumes, base: stem.to_string(), index: index + 1 })
+                }
+                'z' if index > 0 => {
+                    return Some(ArchivePart { scheme: PartScheme::ZipVolumes, base: stem.to_string(), index })
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(index) = parse_digits(ext).filter(|index| *index > 0) {
+            let inner_ext = stem.rsplit_once('.').map(|(_, inner)| inner)?;
+            if matches!(inner_ext, "7z" | "zip" | "rar" | "tar" | "gz" | "bz2" | "xz") {
+                return Some(ArchivePart { scheme: PartScheme::Numbered, base: stem.to_string(), index });
+            }
+        }
+    }
+
+    None
+}
+
+fn parse_digits(text: &str) -> Option<u32> {
+    if !text.is_empty() && text.chars().all(|c| c.is_ascii_digit()) {
+        text.parse().ok()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::OperationType;
+
+    fn op(source: &str, destination: &str) -> FileOperation {
+        FileOperation {
+            source: PathBuf::from(source),
+            destination: PathBuf::from(destination),
+            operation_type: OperationType::Move,
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn test_part_detection() {
+        assert!(is_archive_part(Path::new("movie.part2.rar")));
+        assert!(is_archive_part(Path::new("movie.r00")));
+        assert!(is_archive_part(Path::new("backup.7z.001")));
+        assert!(is_archive_part(Path::new("backup.z01")));
+        assert!(!is_archive_part(Path::new("backup.zip")));
+        assert!(!is_archive_part(Path::new("notes.txt")));
+        assert!(!is_archive_part(Path::new("track.001")));
+    }
+
+    #[test]
+    fn test_complete_set_moves_together() {
+        let mut operations = vec![
+            op("dl/backup.7z.001", "dl/Archives/backup.7z.001"),
+            op("dl/backup.7z.002", "dl/Other/backup.7z.002"),
+            op("dl/single.zip", "dl/Archives/single.zip"),
+        ];
+
+        let incomplete = group_parts(&mut operations);
+
+        assert!(incomplete.is_empty());
+        assert_eq!(operations.len(), 3);
+        assert_eq!(operations[1].destination, PathBuf::from("dl/Archives/backup.7z.002"));
+    }
+
+    #[test]
+    fn test_incomplete_set_is_dropped() {
+        let mut operations = vec![
+            op("dl/movie.part1.rar", "dl/Archives/movie.part1.rar"),
+            op("dl/movie.part3.rar", "dl/Archives/movie.part3.rar"),
+            op("dl/game.rar", "dl/Archives/game.rar"),
+            op("dl/game.r01", "dl/Archives/game.r01"),
+        ];
+
+        let incomplete = group_parts(&mut operations);
+
+        assert!(operations.is_empty());
+        assert_eq!(incomplete.len(), 2);
+        assert_eq!(incomplete[0].name, "game");
+        assert_eq!(incomplete[0].missing, vec![".r00".to_string()]);
+        assert_eq!(incomplete[1].missing, vec![".part2.rar".to_string()]);
+    }
+}
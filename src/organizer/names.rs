@@ -0,0 +1,114 @@
+//! File names that tend to break sync tools and other platforms: very long names and names
+//! with non-ASCII or control characters.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::error::{OrganizerError, Result};
+use super::journal;
+
+/// Which name problems to look for; a name matches if it has any of them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NamePredicates {
+    /// Names with more characters than this
+    pub longer_than: Option<usize>,
+    /// Names with non-ASCII or control characters
+    pub unusual_characters: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameIssue {
+    TooLong { length: usize },
+    NonAscii,
+    ControlCharacters,
+}
+
+impl fmt::Display for NameIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NameIssue::TooLong { length } => write!(f, "{} characters long", length),
+            NameIssue::NonAscii => write!(f, "non-ASCII characters"),
+            NameIssue::ControlCharacters => write!(f, "control characters"),
+        }
+    }
+}
+
+impl NamePredicates {
+    pub fn is_empty(&self) -> bool {
+        self.longer_than.is_none() && !self.unusual_characters
+    }
+
+    /// Every problem `name` has among the ones looked for
+    pub fn issues(&self, name: &str) -> Vec<NameIssue> {
+        let mut issues = Vec::new();
+        let length = name.chars().count();
+        if self.longer_than.is_some_and(|limit| length > limit) {
+            issues.push(NameIssue::TooLong { length });
+        }
+        if self.unusual_characters {
+            if name.chars().any(char::is_control) {
+                issues.push(NameIssue::ControlCharacters);
+            }
+            if !name.is_ascii() {
+                issues.push(NameIssue::NonAscii);
+            }
+        }
+        issues
+    }
+
+    /// The file's name has one of the problems (names that aren't valid Unicode count as
+    /// non-ASCII, through the replacement character)
+    pub fn matches(&self, file_path: &Path) -> bool {
+        file_path
+            .file_name()
+            .is_some_and(|name| !self.issues(&name.to_string_lossy()).is_empty())
+    }
+}
+
+/// Files below `dir` whose names have any of the problems, sorted by path
+pub fn find(dir: &Path, predicates: &NamePredicates) -> Result<Vec<(PathBuf, Vec<NameIssue>)>> {
+    let mut found = Vec::new();
+    for entry in WalkDir::new(dir)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != journal::METADATA_DIR)
+    {
+        let entry = entry.map_err(|e| OrganizerError::walk(dir, e))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let issues = predicates.issues(&entry.file_name().to_string_lossy());
+        if !issues.is_empty() {
+            found.push((entry.into_path(), issues));
+        }
+    }
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_name_issues() {
+        let predicates = NamePredicates { longer_than: Some(10), unusual_characters: true };
+        assert!(predicates.issues("report.pdf").is_empty());
+        assert_eq!(predicates.issues("quarterly-report.pdf"), [NameIssue::TooLong { length: 20 }]);
+        assert_eq!(predicates.issues("café.txt"), [NameIssue::NonAscii]);
+        assert_eq!(predicates.issues("a\tb.txt"), [NameIssue::ControlCharacters]);
+        // Length is counted in characters, not bytes
+        assert!(predicates.issues("日本語.txt").iter().all(|issue| *issue == NameIssue::NonAscii));
+        assert!(NamePredicates::default().issues("a\tb.txt").is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("sub/.file-organizer")).unwrap();
+        fs::write(dir.path().join("sub/naïve.txt"), b"").unwrap();
+        fs::write(dir.path().join("plain.txt"), b"").unwrap();
+        let found = find(dir.path(), &predicates).unwrap();
+        assert_eq!(found, [(dir.path().join("sub/naïve.txt"), vec![NameIssue::NonAscii])]);
+        assert!(predicates.matches(&found[0].0));
+    }
+}
@@ -0,0 +1,194 @@
+use std::io;
+use std::path::Path;
+
+use super::conflicts::{ConflictChoice, ConflictPolicy, PlannedConflict};
+use super::dates::{DateFallbacks, DateKind};
+use super::multipart::IncompleteGroup;
+use super::skips::SkippedFile;
+use super::space::SpaceShortage;
+use super::twins::TwinFolder;
+use super::{FileOperation, OrganizationSummary, OrganizeMode, PlannedDeletion};
+
+/// Something worth telling the user about that isn't tied to a single file operation
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Notice {
+    /// This many cloud placeholders were left alone, as moving them would download them
+    PlaceholdersSkipped(usize),
+    /// Custom mode was requested but the config has no rules
+    NoCustomRules,
+    /// This many files matched a `skip` rule and stay where they are
+    SkippedByRules(usize),
+    /// This many photo bursts were grouped into `Bursts/` folders
+    BurstsGrouped(usize),
+    /// Split archives left in place because parts are missing
+    IncompleteArchives(Vec<IncompleteGroup>),
+    /// Accessed mode on a filesystem mounted with `noatime`/`relatime`
+    LazyAccessTimes { mount_option: String },
+    /// Some files lacked the preferred timestamp (e.g. no creation time on ext4) in Date mode
+    DateFallbacks {
+        preferred: DateKind,
+        fallbacks: DateFallbacks,
+    },
+    /// Planned moves whose destination is taken, shared or clashes in case; `policy` decides
+    /// what happens to the blocking ones
+    Conflicts {
+        conflicts: Vec<PlannedConflict>,
+        policy: ConflictPolicy,
+    },
+    /// Planned folders that exist under an equivalent name (`Images` for `🖼️ Images`); their
+    /// files go to the existing folder
+    TwinFolders(Vec<TwinFolder>),
+    /// Files `delete` rules delete: to the trash, or for good if `permanent`
+    Deletions {
+        deletions: Vec<PlannedDeletion>,
+        permanent: bool,
+    },
+    /// The filesystem lacks room for the plan; a real run would refuse to start
+    LowSpace(SpaceShortage),
+    /// An interrupted run continues: `moved` files were moved before, `remaining` are left and
+    /// `vanished` planned files no longer exist
+    Resumed {
+        moved: usize,
+        remaining: usize,
+        vanished: usize,
+    },
+}
+
+/// Answer to "Move 42 files to Images?" when reviewing a plan folder by folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CategoryChoice {
+    /// Move this folder's files
+    Yes,
+    /// Leave this folder's files where they are
+    No,
+    /// Move this folder's files and every remaining folder's without asking
+    All,
+    /// Leave this folder's files and every remaining folder's; earlier answers still apply
+    Quit,
+}
+
+/// Receives progress from [`FileOrganizer`](super::FileOrganizer) as it scans, plans and
+/// moves files.
+///
+/// Every method has an empty default, so observers only implement what they show. The CLI's
+/// console output is one observer; GUIs and services can plug in their own.
+pub trait OrganizeObserver: Send {
+    /// Scanning of the target directory started
+    fn on_scan_started(&self, _directory: &Path) {}
+
+    /// Another candidate file was found while scanning
+    fn on_scan_progress(&self, _files_found: usize) {}
+
+    /// Scanning finished; `files_selected` is what's left after filtering
+    fn on_scan_finished(&self, _files_found: usize, _files_selected: usize) {}
+
+    fn on_notice(&self, _notice: &Notice) {}
+
+    /// Files the scan found that this run leaves where they are (hidden, filtered out, ...),
+    /// once planning is done; empty when nothing was left out
+    fn on_files_skipped(&self, _skipped: &[SkippedFile]) {}
+
+    /// The plan is final; in a dry run nothing happens after this
+    fn on_plan_ready(&self, _plan: &[FileOperation], _mode: &OrganizeMode) {}
+
+    /// With [`OrganizeOptions::ask_per_category`](super::OrganizeOptions::ask_per_category),
+    /// decide whether the `files` planned for `folder` move. Observers that can't ask anyone
+    /// leave them.
+    fn on_category_review(&self, _folder: &str, _files: usize) -> CategoryChoice {
+        CategoryChoice::No
+    }
+
+    /// With [`OrganizeOptions::ask_per_category`](super::OrganizeOptions::ask_per_category),
+    /// after the folders: decide whether the `files` delete rules matched are deleted (for
+    /// good if `permanent`, to the trash otherwise). Observers that can't ask anyone keep them.
+    fn on_deletion_review(&self, _files: usize, _permanent: bool) -> bool {
+        false
+    }
+
+    /// Execution of the plan is about to start
+    fn on_execution_started(&self, _operations: usize) {}
+
+    fn on_directories_created(&self, _count: usize) {}
+
+    fn on_file_moved(&self, _operation: &FileOperation) {}
+
+    /// Decide a conflict under [`ConflictPolicy::Ask`](super::conflicts::ConflictPolicy::Ask):
+    /// `operation.destination` already exists. Observers that can't ask anyone skip.
+    fn on_conflict(&self, _operation: &FileOperation) -> ConflictChoice {
+        ConflictChoice::Skip
+    }
+
+    /// A file was left in place because its destination was taken
+    fn on_file_skipped(&self, _operation: &FileOperation) {}
+
+    /// An operation failed; execution continues with the next one
+    fn on_error(&self, _operation: &FileOperation, _error: &io::Error) {}
+
+    /// A failure stopped execution with `not_attempted` operations left
+    fn on_execution_stopped(&self, _not_attempted: usize) {}
+
+    /// After a stop under [`ErrorPolicy::Rollback`](super::ErrorPolicy::Rollback), this many
+    /// moved files went back (and this many couldn't)
+    fn on_rolled_back(&self, _restored: usize, _failed: usize) {}
+
+    fn on_execution_finished(&self, _moved: usize, _failed: usize) {}
+
+    /// The organization finished (or, for a dry run, was planned)
+    fn on_finished(&self, _summary: &OrganizationSummary, _dry_run: bool) {}
+}
+
+/// Observer that ignores everything (the default)
+pub struct SilentObserver;
+
+impl OrganizeObserver for SilentObserver {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::FileOrganizer;
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl OrganizeObserver for Recorder {
+        fn on_scan_finished(&self, files_found: usize, files_selected: usize) {
+            self.0.lock().unwrap().push(format!("scan {} {}", files_found, files_selected));
+        }
+
+        fn on_plan_ready(&self, plan: &[FileOperation], _mode: &OrganizeMode) {
+            self.0.lock().unwrap().push(format!("plan {}", plan.len()));
+        }
+
+        fn on_file_moved(&self, operation: &FileOperation) {
+            let name = operation.source.file_name().unwrap().to_string_lossy().to_string();
+            self.0.lock().unwrap().push(format!("moved {}", name));
+        }
+
+        fn on_finished(&self, summary: &OrganizationSummary, dry_run: bool) {
+            self.0.lock().unwrap().push(format!("finished {} {}", summary.total_files, dry_run));
+        }
+    }
+
+    #[test]
+    fn test_observer_sees_each_stage() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("photo.jpg"), b"jpg").unwrap();
+        fs::write(dir.path().join("notes.txt"), b"txt").unwrap();
+
+        let recorder = Recorder::default();
+        let filters = vec!["jpg".to_string()];
+        FileOrganizer::new()
+            .with_observer(recorder.clone())
+            .organize(dir.path(), &OrganizeMode::Extension, false, Some(&filters), false)
+            .unwrap();
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            ["scan 2 1", "plan 1", "moved photo.jpg", "finished 1 false"]
+        );
+        assert!(dir.path().join("notes.txt").exists());
+    }
+}
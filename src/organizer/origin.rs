@@ -0,0 +1,154 @@
+use std::path::Path;
+
+/// Find the URL a file was downloaded from, using the metadata browsers attach to downloads:
+///
+/// - Linux: the `user.xdg.origin.url` extended attribute
+/// - macOS: the `com.apple.metadata:kMDItemWhereFroms` extended attribute written alongside
+///   `com.apple.quarantine` (the quarantine attribute itself only names the downloading app)
+/// - Windows: the `Zone.Identifier` alternate data stream (`HostUrl=`/`ReferrerUrl=`)
+pub fn download_url(file_path: &Path) -> Option<String> {
+    platform_download_url(file_path).filter(|url| is_web_url(url))
+}
+
+/// Domain a file was downloaded from, e.g. `github.com`
+pub fn origin_domain(file_path: &Path) -> Option<String> {
+    download_url(file_path).and_then(|url| host_from_url(&url))
+}
+
+#[cfg(target_os = "linux")]
+fn platform_download_url(file_path: &Path) -> Option<String> {
+    ["user.xdg.origin.url", "user.xdg.referrer.url"]
+        .into_iter()
+        .filter_map(|name| xattr::get(file_path, name).ok().flatten())
+        .map(|value| String::from_utf8_lossy(&value).trim().to_string())
+        .find(|url| !url.is_empty())
+}
+
+#[cfg(target_os = "macos")]
+fn platform_download_url(file_path: &Path) -> Option<String> {
+    let where_froms = xattr::get(file_path, "com.apple.metadata:kMDItemWhereFroms").ok()??;
+    urls_from_plist(&where_froms).into_iter().next()
+}
+
+#[cfg(windows)]
+fn platform_download_url(file_path: &Path) -> Option<String> {
+    let mut stream = file_path.as_os_str().to_os_string();
+    stream.push(":Zone.Identifier");
+    let contents = std::fs::read_to_string(stream).ok()?;
+    parse_zone_identifier(&contents)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn platform_download_url(_file_path: &Path) -> Option<String> {
+    None
+}
+
+/// Read `HostUrl` (or `ReferrerUrl`) from a `Zone.Identifier` stream
+#[cfg_attr(not(any(windows, test)), allow(dead_code))]
+fn parse_zone_identifier(contents: &str) -> Option<String> {
+    let value_of = |key: &str| {
+        contents
+            .lines()
+            .filter_map(|line| line.trim().split_once('='))
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.trim().to_string())
+            .filter(|value| is_web_url(value))
+    };
+    value_of("HostUrl").or_else(|| value_of("ReferrerUrl"))
+}
+
+/// Pull URL strings out of a plist (binary or XML) without a full plist parser
+#[cfg_attr(not(any(target_os = "macos", test)), allow(dead_code))]
+fn urls_from_plist(data: &[u8]) -> Vec<String> {
+    let mut urls = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let rest = &data[i..];
+        if rest.starts_with(b"http://") || rest.starts_with(b"https://") {
+            let len = bplist_string_len(data, i)
+                .unwrap_or_else(|| {
+                    // XML plist: the string runs until the closing tag
+                    rest.iter()
+                        .position(|&b| !b.is_ascii_graphic() || b == b'<')
+                        .unwrap_or(rest.len())
+                })
+                .min(rest.len());
+            urls.push(String::from_utf8_lossy(&rest[..len]).to_string());
+            i += len;
+        } else {
+            i += 1;
+        }
+    }
+
+    urls
+}
+
+/// Length of a binary plist ASCII string starting at `start`, read from its object marker
+#[cfg_attr(not(any(target_os = "macos", test)), allow(dead_code))]
+fn bplist_string_len(data: &[u8], start: usize) -> Option<usize> {
+    let before = &data[..start];
+    match before {
+        [.., 0x5F, 0x10, len] => Some(*len as usize),
+        [.., 0x5F, 0x11, hi, lo] => Some(u16::from_be_bytes([*hi, *lo]) as usize),
+        [.., marker] if (0x50..=0x5E).contains(marker) => Some((*marker & 0x0F) as usize),
+        _ => None,
+    }
+}
+
+fn is_web_url(url: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// Extract the host of a URL, dropping credentials, port and a leading `www.`
+pub fn host_from_url(url: &str) -> Option<String> {
+    let after_scheme = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let authority = after_scheme.split(['/', '?', '#']).next()?;
+    let host_port = authority.rsplit_once('@').map(|(_, host)| host).unwrap_or(authority);
+    let host = if host_port.starts_with('[') {
+        host_port.split(']').next().map(|h| h.trim_start_matches('['))?
+    } else {
+        host_port.split(':').next()?
+    };
+
+    let host = host.to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_from_url() {
+        assert_eq!(host_from_url("https://github.com/user/repo/archive.zip"), Some("github.com".to_string()));
+        assert_eq!(host_from_url("https://www.Example.com:8443/x?y"), Some("example.com".to_string()));
+        assert_eq!(host_from_url("http://user:pw@drive.google.com/file"), Some("drive.google.com".to_string()));
+        assert_eq!(host_from_url("https:///nothing"), None);
+    }
+
+    #[test]
+    fn test_parse_zone_identifier() {
+        let stream = "[ZoneTransfer]\r\nZoneId=3\r\nReferrerUrl=https://github.com/\r\nHostUrl=https://objects.githubusercontent.com/file.zip\r\n";
+        assert_eq!(
+            parse_zone_identifier(stream),
+            Some("https://objects.githubusercontent.com/file.zip".to_string())
+        );
+        assert_eq!(parse_zone_identifier("[ZoneTransfer]\nZoneId=3\nHostUrl=about:internet\n"), None);
+    }
+
+    #[test]
+    fn test_urls_from_plist() {
+        let bplist = b"bplist00\xa2\x01\x02_\x10\x1chttps://example.com/file.zip_\x10\x14https://example.com/\x08";
+        assert_eq!(
+            urls_from_plist(bplist),
+            vec!["https://example.com/file.zip".to_string(), "https://example.com/".to_string()]
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! Paths in JSON (journals, resume files, plans and progress events) that survive file names
+//! that aren't valid UTF-8, as legacy-encoded names on Linux often are.
+//!
+//! A path that is valid UTF-8 is written as a plain string, as it always was. Any other is
+//! written as `{"bytes": [...]}` with its raw bytes (`{"wide": [...]}` with its UTF-16 units
+//! on Windows), so it reads back as the same file rather than a mangled one. Use it with
+//! `#[serde(with = "os_path")]`.
+
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Repr {
+    Text(String),
+    #[cfg(unix)]
+    Bytes { bytes: Vec<u8> },
+    #[cfg(windows)]
+    Wide { wide: Vec<u16> },
+}
+
+pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    if let Some(text) = path.to_str() {
+        return serializer.serialize_str(text);
+    }
+    #[cfg(unix)]
+    let repr = {
+        use std::os::unix::ffi::OsStrExt;
+        Repr::Bytes { bytes: path.as_os_str().as_bytes().to_vec() }
+    };
+    #[cfg(windows)]
+    let repr = {
+        use std::os::windows::ffi::OsStrExt;
+        Repr::Wide { wide: path.as_os_str().encode_wide().collect() }
+    };
+    #[cfg(not(any(unix, windows)))]
+    let repr = Repr::Text(path.to_string_lossy().into_owned());
+    repr.serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    Ok(match Repr::deserialize(deserializer)? {
+        Repr::Text(text) => PathBuf::from(text),
+        #[cfg(unix)]
+        Repr::Bytes { bytes } => {
+            use std::os::unix::ffi::OsStringExt;
+            PathBuf::from(std::ffi::OsString::from_vec(bytes))
+        }
+        #[cfg(windows)]
+        Repr::Wide { wide } => {
+            use std::os::windows::ffi::OsStringExt;
+            PathBuf::from(std::ffi::OsString::from_wide(&wide))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Entry {
+        #[serde(with = "super")]
+        path: PathBuf,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let entry = Entry { path: PathBuf::from("Images/café.jpg") };
+        let json = serde_json::to_string(&entry).unwrap();
+        assert_eq!(json, r#"{"path":"Images/café.jpg"}"#);
+        assert_eq!(serde_json::from_str::<Entry>(&json).unwrap(), entry);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStringExt;
+            let latin1 = Entry { path: PathBuf::from(std::ffi::OsString::from_vec(b"caf\xe9.jpg".to_vec())) };
+            let json = serde_json::to_string(&latin1).unwrap();
+            assert_eq!(json, r#"{"path":{"bytes":[99,97,102,233,46,106,112,103]}}"#);
+            assert_eq!(serde_json::from_str::<Entry>(&json).unwrap(), latin1);
+        }
+    }
+}
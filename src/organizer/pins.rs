@@ -0,0 +1,156 @@
+//! Pinned files: files that stay where they are, whatever mode later runs use.
+//!
+//! `file-organizer pin` marks a file with an extended attribute (an alternate data stream on
+//! Windows), so the mark follows the file when it's renamed. Files on filesystems without
+//! extended attributes can be pinned by name in the config instead:
+//!
+//! ```toml
+//! [pins]
+//! patterns = ["Setup*.exe", "keep-*"]
+//! ```
+
+use std::io;
+use std::path::Path;
+
+use super::config::PinConfig;
+use super::error::{IoContext, OrganizerError, Result};
+use super::rules::wildcard_match;
+
+#[cfg(unix)]
+const PIN_ATTRIBUTE: &str = "user.file-organizer.pinned";
+#[cfg(windows)]
+const PIN_STREAM: &str = ":file-organizer.pinned";
+
+/// Everything that can pin a file: its own mark, or a name pattern from the config
+#[derive(Debug, Clone, Default)]
+pub struct Pins {
+    /// Lowercased, as names are matched case-insensitively
+    patterns: Vec<String>,
+}
+
+impl Pins {
+    pub fn from_config(config: &PinConfig) -> Self {
+        Self {
+            patterns: config.patterns.iter().map(|pattern| pattern.to_lowercase()).collect(),
+        }
+    }
+
+    pub fn contains(&self, file_path: &Path) -> bool {
+        let name = file_path.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+        self.patterns.iter().any(|pattern| wildcard_match(pattern, &name)) || is_pinned(file_path)
+    }
+}
+
+/// Mark a file so no run moves it
+pub fn pin(file_path: &Path) -> Result<()> {
+    if !file_path.is_file() {
+        return Err(OrganizerError::plan(file_path, format!("{} is not a file", file_path.display())));
+    }
+    set_mark(file_path).map_err(|e| unsupported_hint(file_path, e))
+}
+
+/// Remove the mark; `false` if the file wasn't pinned
+pub fn unpin(file_path: &Path) -> Result<bool> {
+    if !is_pinned(file_path) {
+        return Ok(false);
+    }
+    remove_mark(file_path).io_context(file_path, format!("Failed to unpin {}", file_path.display()))?;
+    Ok(true)
+}
+
+/// The file carries a pin mark (config patterns aren't checked)
+pub fn is_pinned(file_path: &Path) -> bool {
+    has_mark(file_path)
+}
+
+fn unsupported_hint(file_path: &Path, error: io::Error) -> OrganizerError {
+    let reason = format!(
+        "Failed to pin {} (if its filesystem has no extended attributes, add it to [pins] patterns in the config)",
+        file_path.display()
+    );
+    OrganizerError::io(file_path, reason, error)
+}
+
+#[cfg(unix)]
+fn set_mark(file_path: &Path) -> io::Result<()> {
+    xattr::set(file_path, PIN_ATTRIBUTE, b"1")
+}
+
+#[cfg(unix)]
+fn remove_mark(file_path: &Path) -> io::Result<()> {
+    xattr::remove(file_path, PIN_ATTRIBUTE)
+}
+
+#[cfg(unix)]
+fn has_mark(file_path: &Path) -> bool {
+    matches!(xattr::get(file_path, PIN_ATTRIBUTE), Ok(Some(_)))
+}
+
+#[cfg(windows)]
+fn stream(file_path: &Path) -> std::ffi::OsString {
+    let mut stream = file_path.as_os_str().to_os_string();
+    stream.push(PIN_STREAM);
+    stream
+}
+
+#[cfg(windows)]
+fn set_mark(file_path: &Path) -> io::Result<()> {
+    std::fs::write(stream(file_path), b"1")
+}
+
+#[cfg(windows)]
+fn remove_mark(file_path: &Path) -> io::Result<()> {
+    std::fs::remove_file(stream(file_path))
+}
+
+#[cfg(windows)]
+fn has_mark(file_path: &Path) -> bool {
+    Path::new(&stream(file_path)).exists()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn set_mark(_file_path: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "pin marks aren't supported on this platform"))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn remove_mark(_file_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn has_mark(_file_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_pin_patterns() {
+        let pins = Pins::from_config(&PinConfig { patterns: vec!["Setup*.exe".to_string()] });
+        assert!(pins.contains(Path::new("/downloads/setup-1.2.exe")));
+        assert!(!pins.contains(Path::new("/downloads/report.pdf")));
+    }
+
+    #[test]
+    fn test_pin_and_unpin() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("installer.dmg");
+        fs::write(&file, b"dmg").unwrap();
+        if let Err(e) = pin(&file) {
+            // Some test machines keep temp files on filesystems without user attributes
+            eprintln!("skipping: {:#}", e);
+            return;
+        }
+
+        assert!(is_pinned(&file));
+        assert!(Pins::default().contains(&file));
+        assert!(unpin(&file).unwrap());
+        assert!(!is_pinned(&file));
+        assert!(!unpin(&file).unwrap());
+        assert!(pin(dir.path()).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+//! WASM classifier plugins (requires the `plugins` feature).
+//!
+//! A plugin is a core WebAssembly module with no imports, so it can't touch the filesystem,
+//! network or clock. It must export:
+//!
+//! - `memory`
+//! - `alloc(len: i32) -> i32`: reserve `len` bytes and return a pointer to them
+//! - `classify(name_ptr: i32, name_len: i32, data_ptr: i32, data_len: i32, size: i64) -> i64`
+//!
+//! `classify` receives the file name, the first `max_bytes` of the file and its full size.
+//! It returns `(ptr << 32) | len` pointing at a UTF-8 answer in its memory, or 0 for
+//! "no opinion". The answer is either a destination folder (`Work/Reports`) or a built-in
+//! category (`category:Images`).
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use wasmtime::{Engine, Instance, Module, Store, StoreLimits, StoreLimitsBuilder, Trap};
+
+use super::config::PluginConfig;
+use super::error::{Context, IoContext, OrganizerError, Result};
+
+/// How often the engine's epoch advances; timeouts are rounded up to this granularity
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// Memory a single plugin invocation may use
+const MAX_PLUGIN_MEMORY: usize = 64 * 1024 * 1024;
+
+struct Plugin {
+    module: Module,
+    timeout_ticks: u64,
+    max_bytes: usize,
+}
+
+/// Compiled plugins plus the ticker thread that enforces their timeouts
+pub struct PluginHost {
+    engine: Engine,
+    plugins: HashMap<String, Plugin>,
+    stop_ticker: Arc<AtomicBool>,
+}
+
+impl PluginHost {
+    /// Compile every plugin registered in the config
+    pub fn load(config: &PluginConfig) -> Result<Self> {
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.epoch_interruption(true);
+        let engine = Engine::new(&engine_config).config_context("Failed to set up the plugin engine")?;
+
+        let directory = config.directory();
+        let mut plugins = HashMap::new();
+        for plugin in &config.classifiers {
+            let path = resolve_plugin_path(directory.as_deref(), &plugin.file);
+            let module = Module::from_file(&engine, &path)
+                .config_context(format!("Failed to load plugin '{}' from {}", plugin.name, path.display()))?;
+            let ticks = plugin.timeout_ms.div_ceil(EPOCH_TICK.as_millis() as u64).max(1);
+
+            plugins.insert(
+                plugin.name.clone(),
+                Plugin {
+                    module,
+                    timeout_ticks: ticks,
+                    max_bytes: plugin.max_bytes,
+                },
+            );
+        }
+
+        let stop_ticker = Arc::new(AtomicBool::new(false));
+        if !plugins.is_empty() {
+            let engine = engine.clone();
+            let stop = stop_ticker.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(EPOCH_TICK);
+                    engine.increment_epoch();
+                }
+            });
+        }
+
+        Ok(Self {
+            engine,
+            plugins,
+            stop_ticker,
+        })
+    }
+
+    /// Ask a plugin to classify a file; `None` means it had no opinion or timed out
+    pub fn classify(&self, name: &str, file_path: &Path) -> Result<Option<String>> {
+        let plugin = self
+            .plugins
+            .get(name)
+            .ok_or_else(|| OrganizerError::config(format!("Unknown plugin '{}'", name)))?;
+
+        let file_name = file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let size = file_path.metadata().map(|m| m.len()).unwrap_or(0);
+        let mut head = Vec::with_capacity(plugin.max_bytes);
+        File::open(file_path)
+            .and_then(|file| file.take(plugin.max_bytes as u64).read_to_end(&mut head))
+            .scan_context(file_path, format!("Failed to read {:?} for plugin '{}'", file_path, name))?;
+
+        match self.invoke(plugin, file_name.as_bytes(), &head, size) {
+            Ok(answer) => Ok(answer),
+            Err(e) if matches!(e.downcast_ref::<Trap>(), Some(Trap::Interrupt)) => {
+                log::warn!("Plugin '{}' timed out on {:?}", name, file_path);
+                Ok(None)
+            }
+            Err(e) => Err(OrganizerError::plan(file_path, format!("Plugin '{}' failed on {:?}: {:#}", name, file_path, e))),
+        }
+    }
+
+    /// Run `classify` in a fresh, memory-limited store
+    fn invoke(&self, plugin: &Plugin, name: &[u8], head: &[u8], size: u64) -> wasmtime::Result<Option<String>> {
+        let limits = StoreLimitsBuilder::new().memory_size(MAX_PLUGIN_MEMORY).instances(1).build();
+        let mut store: Store<StoreLimits> = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_epoch_deadline(plugin.timeout_ticks);
+
+        let instance = Instance::new(&mut store, &plugin.module, &[])?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| wasmtime::format_err!("plugin does not export memory"))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+        let classify = instance.get_typed_func::<(i32, i32, i32, i32, i64), i64>(&mut store, "classify")?;
+
+        let name_ptr = alloc.call(&mut store, name.len() as i32)?;
+        memory.write(&mut store, name_ptr as usize, name)?;
+        let data_ptr = alloc.call(&mut store, head.len() as i32)?;
+        memory.write(&mut store, data_ptr as usize, head)?;
+
+        let packed = classify.call(
+            &mut store,
+            (name_ptr, name.len() as i32, data_ptr, head.len() as i32, size as i64),
+        )? as u64;
+        let (ptr, len) = ((packed >> 32) as usize, (packed & 0xFFFF_FFFF) as usize);
+        if len == 0 {
+            return Ok(None);
+        }
+
+        let mut answer = vec![0u8; len];
+        memory.read(&store, ptr, &mut answer)?;
+        let answer = String::from_utf8(answer).map_err(|_| wasmtime::format_err!("plugin returned invalid UTF-8"))?;
+        let answer = answer.trim();
+        Ok(if answer.is_empty() { None } else { Some(answer.to_string()) })
+    }
+}
+
+impl Drop for PluginHost {
+    fn drop(&mut self) {
+        self.stop_ticker.store(true, Ordering::Relaxed);
+    }
+}
+
+fn resolve_plugin_path(directory: Option<&Path>, file: &Path) -> PathBuf {
+    match directory {
+        Some(directory) if file.is_relative() => directory.join(file),
+        _ => file.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::config::PluginClassifierConfig;
+
+    /// Answers "Scans" for files starting with `%PDF`, otherwise has no opinion
+    const PDF_PLUGIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (global $next (mut i32) (i32.const 1024))
+          (data (i32.const 16) "Scans")
+          (func (export "alloc") (param $len i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $next))
+            (global.set $next (i32.add (global.get $next) (local.get $len)))
+            (local.get $ptr))
+          (func (export "classify") (param i32 i32 i32 i32 i64) (result i64)
+            (if (result i64) (i32.eq (i32.load (local.get 2)) (i32.const 0x46445025))
+              (then (i64.or (i64.shl (i64.const 16) (i64.const 32)) (i64.const 5)))
+              (else (i64.const 0)))))
+    "#;
+
+    const SPIN_PLUGIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "classify") (param i32 i32 i32 i32 i64) (result i64)
+            (loop $forever (br $forever))
+            (i64.const 0)))
+    "#;
+
+    fn host_with(dir: &Path, plugins: &[(&str, &str)]) -> PluginHost {
+        let mut config = PluginConfig::default();
+        for (name, wat) in plugins {
+            let file = dir.join(format!("{}.wat", name));
+            std::fs::write(&file, wat).unwrap();
+            config.classifiers.push(PluginClassifierConfig {
+                name: name.to_string(),
+                file,
+                timeout_ms: 50,
+                max_bytes: 16,
+            });
+        }
+        PluginHost::load(&config).unwrap()
+    }
+
+    #[test]
+    fn test_plugin_classifies_and_times_out() {
+        let dir = std::env::temp_dir().join(format!("file-organizer-plugins-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let host = host_with(&dir, &[("pdf", PDF_PLUGIN), ("spin", SPIN_PLUGIN)]);
+
+        let pdf = dir.join("scan.bin");
+        std::fs::write(&pdf, b"%PDF-1.7 ...").unwrap();
+        let text = dir.join("notes.bin");
+        std::fs::write(&text, b"hello").unwrap();
+
+        assert_eq!(host.classify("pdf", &pdf).unwrap(), Some("Scans".to_string()));
+        assert_eq!(host.classify("pdf", &text).unwrap(), None);
+        assert_eq!(host.classify("spin", &text).unwrap(), None);
+        assert!(host.classify("missing", &text).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
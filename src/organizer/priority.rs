@@ -0,0 +1,54 @@
+//! Background priority (`--background`), so a scheduled organize doesn't make the machine
+//! sluggish while someone is using it. The process gets the lowest CPU priority and idle I/O
+//! priority where the platform has one: `nice` 19 and the idle `ionice` class on Linux, the
+//! background QoS (low CPU, throttled disk and network) on macOS, background processing mode
+//! on Windows.
+
+use std::io;
+
+/// Lower the CPU and I/O priority of this process. Call it before any threads are started:
+/// on Linux priorities are per thread, and only threads started afterwards inherit them.
+pub fn lower() -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+        const IOPRIO_CLASS_IDLE: libc::c_long = 3;
+        const IOPRIO_CLASS_SHIFT: libc::c_long = 13;
+        nice()?;
+        // SAFETY: ioprio_set only changes the calling thread's I/O priority
+        let result = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT) };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        // SAFETY: only changes this process's scheduling
+        if unsafe { libc::setpriority(libc::PRIO_DARWIN_PROCESS, 0, libc::PRIO_DARWIN_BG) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Threading::{GetCurrentProcess, SetPriorityClass, PROCESS_MODE_BACKGROUND_BEGIN};
+        // SAFETY: the pseudo handle of this process needs no closing
+        if unsafe { SetPriorityClass(GetCurrentProcess(), PROCESS_MODE_BACKGROUND_BEGIN) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    // Other Unixes have no I/O priority, but `nice` is everywhere
+    #[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+    nice()
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn nice() -> io::Result<()> {
+    // SAFETY: only changes this process's scheduling
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 19) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
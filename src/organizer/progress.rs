@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+/// Progress reported while an organization runs
+#[derive(Debug, Clone)]
+pub enum ProgressEvent {
+    /// Scanning of the target directory started
+    ScanStarted { directory: PathBuf },
+    /// Scanning finished and this many candidate files were found
+    ScanFinished { files_found: usize },
+    /// The plan is final and this many operations will be executed
+    PlanReady { operations: usize },
+    /// A file was moved (or copied) to its destination
+    FileMoved { source: PathBuf, destination: PathBuf },
+    /// A file could not be moved
+    FileFailed { source: PathBuf, error: String },
+}
@@ -0,0 +1,210 @@
+//! Resume files for long runs.
+//!
+//! While a run executes, its progress is checkpointed to
+//! `<directory>/.file-organizer/resume.json` every few operations. The file is removed when the
+//! run ends; if it's still there, the run was interrupted (killed, crashed, rebooted) and
+//! `organize --resume` carries on with the operations that are left.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::durable;
+use super::error::{Context, OrganizerError, Result};
+use super::journal::{JournalEntry, METADATA_DIR};
+use super::{FileOperation, FileOutcome, OperationType, OrganizeMode};
+
+const RESUME_FILE: &str = "resume.json";
+
+/// Resume file format written by this version of the tool
+pub const RESUME_VERSION: u32 = 1;
+
+/// Where an interrupted run stood at its last checkpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResumeState {
+    pub version: u32,
+    pub mode: OrganizeMode,
+    pub started_at: DateTime<Utc>,
+    /// Moves already made, by this session and any earlier ones
+    pub moved: Vec<JournalEntry>,
+    /// Operations not carried out yet, in plan order
+    pub remaining: Vec<JournalEntry>,
+}
+
+/// What checking the remaining operations against the disk found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Revalidation {
+    /// Already at their destination (moved after the last checkpoint)
+    pub already_moved: usize,
+    /// Neither at the source nor at the destination any more
+    pub vanished: usize,
+}
+
+pub fn resume_path(target_dir: &Path) -> PathBuf {
+    target_dir.join(METADATA_DIR).join(RESUME_FILE)
+}
+
+impl ResumeState {
+    /// A fresh state for a run about to execute `operations`
+    pub fn new(target_dir: &Path, mode: &OrganizeMode, operations: &[FileOperation]) -> Self {
+        Self {
+            version: RESUME_VERSION,
+            mode: mode.clone(),
+            started_at: Utc::now(),
+            moved: Vec::new(),
+            remaining: operations.iter().map(|op| entry(target_dir, op)).collect(),
+        }
+    }
+
+    /// The interrupted run in `target_dir`, if there is one
+    pub fn load(target_dir: &Path) -> Result<Option<Self>> {
+        let path = resume_path(target_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path).journal_context(format!("Failed to read {}", path.display()))?;
+        let state: Self = serde_json::from_str(&contents).journal_context(format!("Invalid resume file {}", path.display()))?;
+        if state.version > RESUME_VERSION {
+            return Err(OrganizerError::journal(format!("{} was written by a newer version of file-organizer", path.display())));
+        }
+        Ok(Some(state))
+    }
+
+    /// Write the state, replacing the previous checkpoint in one step so a crash mid-write
+    /// can't leave a truncated file behind. `durable` also flushes it to disk.
+    pub fn save(&self, target_dir: &Path, durable: bool) -> Result<()> {
+        let path = resume_path(target_dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).journal_context(format!("Failed to create {}", parent.display()))?;
+        }
+        let partial = path.with_extension("json.partial");
+        let contents = serde_json::to_string_pretty(self).journal_context("Failed to serialize the resume file")?;
+        fs::write(&partial, contents).journal_context(format!("Failed to write {}", partial.display()))?;
+        if durable {
+            durable::sync_file(&partial).journal_context(format!("Failed to flush {}", partial.display()))?;
+        }
+        fs::rename(&partial, &path).journal_context(format!("Failed to write {}", path.display()))?;
+        if durable {
+            if let Some(parent) = path.parent() {
+                durable::sync_dir(parent).journal_context(format!("Failed to flush {}", parent.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove(target_dir: &Path) -> Result<()> {
+        let path = resume_path(target_dir);
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).journal_context(format!("Failed to remove {}", path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The state once the first remaining operations have run, one per outcome
+    pub fn checkpoint(&self, target_dir: &Path, outcomes: &[FileOutcome]) -> Self {
+        let mut next = self.clone();
+        next.moved.extend(
+            outcomes
+                .iter()
+                .filter(|outcome| outcome.succeeded())
+                .map(|outcome| entry(target_dir, &outcome.operation)),
+        );
+        next.remaining.drain(..outcomes.len().min(next.remaining.len()));
+        next
+    }
+
+    /// Check the remaining operations against the disk: files already at their destination
+    /// count as moved, files that are gone altogether are dropped
+    pub fn revalidate(&mut self, target_dir: &Path) -> Revalidation {
+        let mut result = Revalidation::default();
+        let mut still_remaining = Vec::new();
+        for entry in self.remaining.drain(..) {
+            if entry.source_in(target_dir).exists() {
+                still_remaining.push(entry);
+            } else if entry.destination_in(target_dir).exists() {
+                result.already_moved += 1;
+                self.moved.push(entry);
+            } else {
+                result.vanished += 1;
+            }
+        }
+        self.remaining = still_remaining;
+        result
+    }
+
+    pub fn operations(&self, target_dir: &Path) -> Vec<FileOperation> {
+        self.remaining.iter().map(|entry| operation(target_dir, entry)).collect()
+    }
+
+    /// Moves of earlier sessions as outcomes, for the journal and statistics of the whole run
+    pub fn moved_outcomes(&self, target_dir: &Path) -> Vec<FileOutcome> {
+        self.moved
+            .iter()
+            .map(|entry| FileOutcome {
+                operation: operation(target_dir, entry),
+                error: None,
+                skipped: false,
+                rolled_back: false,
+            })
+            .collect()
+    }
+}
+
+fn entry(target_dir: &Path, op: &FileOperation) -> JournalEntry {
+    let relative = |path: &Path| path.strip_prefix(target_dir).unwrap_or(path).to_path_buf();
+    JournalEntry {
+        source: relative(&op.source),
+        destination: relative(&op.destination),
+    }
+}
+
+fn operation(target_dir: &Path, entry: &JournalEntry) -> FileOperation {
+    let source = entry.source_in(target_dir);
+    // Nothing is scanned on resume; the files left are looked at once here
+    let size = fs::metadata(&source).map_or(0, |metadata| metadata.len());
+    FileOperation { source, destination: entry.destination_in(target_dir), operation_type: OperationType::Move, size }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let op = |source: &str, destination: &str| FileOperation {
+            source: root.join(source),
+            destination: root.join(destination),
+            operation_type: OperationType::Move,
+            size: 0,
+        };
+        let operations = [op("a.jpg", "Images/a.jpg"), op("b.jpg", "Images/b.jpg"), op("c.jpg", "Images/c.jpg"), op("d.jpg", "Images/d.jpg")];
+        let state = ResumeState::new(root, &OrganizeMode::Extension, &operations);
+        assert!(ResumeState::load(root).unwrap().is_none());
+
+        // a was moved and checkpointed; b was moved just before the crash; c vanished
+        let done = [FileOutcome { operation: operations[0].clone(), error: None, skipped: false, rolled_back: false }];
+        let mut state = state.checkpoint(root, &done);
+        state.save(root, false).unwrap();
+        fs::create_dir(root.join("Images")).unwrap();
+        fs::write(root.join("Images/b.jpg"), b"b").unwrap();
+        fs::write(root.join("d.jpg"), b"d").unwrap();
+
+        let mut loaded = ResumeState::load(root).unwrap().unwrap();
+        assert_eq!(loaded, state);
+        let revalidation = loaded.revalidate(root);
+        assert_eq!(revalidation, Revalidation { already_moved: 1, vanished: 1 });
+        assert_eq!(loaded.operations(root), [FileOperation { size: 1, ..operations[3].clone() }]);
+        assert_eq!(loaded.moved_outcomes(root).len(), 2);
+
+        ResumeState::remove(root).unwrap();
+        ResumeState::remove(root).unwrap();
+        state.version = RESUME_VERSION + 1;
+        state.save(root, false).unwrap();
+        assert!(ResumeState::load(root).is_err());
+    }
+}
@@ -0,0 +1,751 @@
+use std::cell::OnceCell;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use chrono::NaiveDateTime;
+use regex::{Captures, Regex};
+
+use super::conditions::{Condition, FileFacts, Test};
+use super::config::{DefaultAction, DefaultRuleConfig, PluginConfig, RuleAction, RuleConfig};
+use super::dates::{self, DateFallbacks, DateSource};
+use super::error::{Context, OrganizerError, Result};
+use super::file_types::CategoryNames;
+use super::sanitize_folder_name;
+
+/// Where a rule sends a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleTarget {
+    /// Relative to the target directory
+    pub folder: PathBuf,
+    /// New file name, for rules that rename
+    pub file_name: Option<String>,
+}
+
+/// What the rules decided for a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleDecision {
+    Move(RuleTarget),
+    /// A `skip` rule matched, or no rule placed the file and the default rule skips; the
+    /// file stays where it is
+    Skip { rule: String },
+    /// No rule placed the file, and the default rule leaves it to extension mode. Rules that
+    /// `continue` may still have renamed it.
+    Unmatched { file_name: Option<String> },
+    /// A `delete` rule matched
+    Delete { rule: String },
+}
+
+/// Custom organization rules from the config, evaluated in order
+pub struct RuleSet {
+    rules: Vec<RuleConfig>,
+    /// Each rule's predicates combined into one condition
+    conditions: Vec<Condition>,
+    /// Each rule's compiled `regex`
+    regexes: Vec<Option<Regex>>,
+    default: DefaultRuleConfig,
+    /// For `category:<name>` answers from commands and plugins
+    names: CategoryNames,
+    /// Where the date placeholders of templates come from
+    date_source: DateSource,
+    #[cfg(feature = "plugins")]
+    plugins: super::plugins::PluginHost,
+}
+
+impl RuleSet {
+    #[cfg(feature = "plugins")]
+    pub fn new(rules: &[RuleConfig], plugins: &PluginConfig) -> Result<Self> {
+        Ok(Self {
+            rules: rules.to_vec(),
+            conditions: rules.iter().map(rule_condition).collect(),
+            regexes: rules.iter().map(compile_regex).collect::<Result<_>>()?,
+            default: DefaultRuleConfig::default(),
+            names: CategoryNames::default(),
+            date_source: DateSource::Exif,
+            plugins: super::plugins::PluginHost::load(plugins)?,
+        })
+    }
+
+    #[cfg(not(feature = "plugins"))]
+    pub fn new(rules: &[RuleConfig], _plugins: &PluginConfig) -> Result<Self> {
+        Ok(Self {
+            rules: rules.to_vec(),
+            conditions: rules.iter().map(rule_condition).collect(),
+            regexes: rules.iter().map(compile_regex).collect::<Result<_>>()?,
+            default: DefaultRuleConfig::default(),
+            names: CategoryNames::default(),
+            date_source: DateSource::Exif,
+        })
+    }
+
+    /// Use `default` for files no rule places, instead of extension mode
+    pub fn with_default(mut self, default: &DefaultRuleConfig) -> Self {
+        self.default = default.clone();
+        self
+    }
+
+    /// Folder names for the categories commands and plugins answer with
+    pub fn with_category_names(mut self, names: CategoryNames) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// Fill date placeholders from `source` rather than EXIF capture times first
+    pub fn with_date_source(mut self, source: DateSource) -> Self {
+        self.date_source = source;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// What the rules do with a file: skip it, send it to a folder relative to the target
+    /// directory (with a new name if a rule renames), or leave it to extension mode.
+    ///
+    /// Rules are tried in order and the first match decides, except that a rule with
+    /// `continue` only contributes its folder and name and lets later rules add to them.
+    pub fn destination(&self, file_path: &Path) -> Result<RuleDecision> {
+        self.decide(FileFacts::new(file_path))
+    }
+
+    /// [`RuleSet::destination`] for a file whose metadata was read already
+    pub fn destination_with_metadata(&self, file_path: &Path, metadata: &fs::Metadata) -> Result<RuleDecision> {
+        self.decide(FileFacts::with_metadata(file_path, metadata.clone()))
+    }
+
+    fn decide(&self, facts: FileFacts) -> Result<RuleDecision> {
+        let file_path = facts.path();
+        let file_name = file_path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let (mut folder, mut new_name) = (None, None);
+        for ((rule, condition), regex) in self.rules.iter().zip(&self.conditions).zip(&self.regexes) {
+            if !condition.matches(&facts) {
+                continue;
+            }
+            let captures = match regex {
+                Some(regex) => match regex.captures(&file_name) {
+                    Some(captures) => Some(captures),
+                    None => continue,
+                },
+                None => None,
+            };
+            match rule.action {
+                RuleAction::Skip => return Ok(RuleDecision::Skip { rule: rule.name.clone() }),
+                RuleAction::Delete => return Ok(RuleDecision::Delete { rule: rule.name.clone() }),
+                RuleAction::Move => {}
+            }
+            let values = TemplateValues { file_path, facts: &facts, captures, date_source: self.date_source, date: OnceCell::new() };
+
+            let rule_folder = if let Some(command) = &rule.classify_command {
+                match run_classify_command(command, file_path)? {
+                    Some(answer) => Some(answer_to_destination(&answer, &self.names, file_path)?),
+                    // The command declined to classify the file; try the next rule
+                    None => continue,
+                }
+            } else if let Some(plugin) = &rule.plugin {
+                match self.run_plugin(plugin, file_path)? {
+                    Some(answer) => Some(answer_to_destination(&answer, &self.names, file_path)?),
+                    None => continue,
+                }
+            } else if let Some(destination) = &rule.destination {
+                Some(relative_destination(&values.fill(destination)?))
+            } else {
+                None
+            };
+
+            if rule_folder.is_some() {
+                folder = rule_folder;
+            }
+            if let Some(rename) = &rule.rename {
+                new_name = Some(sanitize_folder_name(&values.fill(rename)?));
+            }
+            if !rule.fall_through {
+                break;
+            }
+        }
+
+        Ok(match (folder, self.default.action) {
+            (Some(folder), _) => RuleDecision::Move(RuleTarget { folder, file_name: new_name }),
+            (None, DefaultAction::Extension) => RuleDecision::Unmatched { file_name: new_name },
+            (None, DefaultAction::Skip) => RuleDecision::Skip { rule: "default_rule".to_string() },
+            (None, DefaultAction::Quarantine) => RuleDecision::Move(RuleTarget {
+                folder: relative_destination(&self.default.destination),
+                file_name: new_name,
+            }),
+        })
+    }
+
+    #[cfg(feature = "plugins")]
+    fn run_plugin(&self, plugin: &str, file_path: &Path) -> Result<Option<String>> {
+        self.plugins.classify(plugin, file_path)
+    }
+
+    #[cfg(not(feature = "plugins"))]
+    fn run_plugin(&self, plugin: &str, _file_path: &Path) -> Result<Option<String>> {
+        Err(OrganizerError::config(format!("Plugin '{}' can't run: this build doesn't include the `plugins` feature", plugin)))
+    }
+}
+
+/// Every predicate a rule sets, as one condition; a rule without predicates matches
+/// everything
+fn rule_condition(rule: &RuleConfig) -> Condition {
+    let mut conditions = Vec::new();
+    if let Some(extensions) = &rule.extensions {
+        conditions.push(Condition::Test(Test::Extension(extensions.iter().map(|ext| ext.to_lowercase()).collect())));
+    }
+    if let Some(pattern) = &rule.pattern {
+        conditions.push(Condition::Test(Test::Name(pattern.to_lowercase())));
+    }
+    if let Some(origin) = &rule.origin {
+        conditions.push(Condition::Test(Test::Origin(origin.clone())));
+    }
+    if let Some(when) = &rule.when {
+        conditions.push(when.clone());
+    }
+    Condition::All(conditions)
+}
+
+fn compile_regex(rule: &RuleConfig) -> Result<Option<Regex>> {
+    rule.regex
+        .as_deref()
+        .map(Regex::new)
+        .transpose()
+        .config_context(format!("Rule '{}' has an invalid regex", rule.name))
+}
+
+/// A piece of a destination or rename template
+#[derive(Debug, PartialEq, Eq)]
+enum TemplatePiece {
+    Literal(String),
+    /// `{name}`: a regex group (by name or number) or one of [`PLACEHOLDERS`]
+    Placeholder(String),
+}
+
+/// Placeholders every rule template can use, besides the regex's groups. Dates are the EXIF
+/// capture time, falling back to the modification time.
+const PLACEHOLDERS: [&str; 10] = ["name", "ext", "yyyy", "yy", "mm", "dd", "hh", "min", "ss", "hhmmss"];
+
+/// Split a template into literal text and `{...}` placeholders; `{{` and `}}` are literal
+/// braces
+fn template_pieces(template: &str) -> Result<Vec<TemplatePiece>> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                let closed = loop {
+                    match chars.next() {
+                        Some('}') => break true,
+                        Some(c) => name.push(c),
+                        None => break false,
+                    }
+                };
+                if !closed || name.is_empty() || name.contains('{') {
+                    return Err(OrganizerError::config(format!("'{}' has an unclosed or empty {{...}}; write {{{{ for a literal brace", template)));
+                }
+                if !literal.is_empty() {
+                    pieces.push(TemplatePiece::Literal(std::mem::take(&mut literal)));
+                }
+                pieces.push(TemplatePiece::Placeholder(name));
+            }
+            '}' => return Err(OrganizerError::config(format!("'{}' has a stray '}}'; write }}}} for a literal brace", template))),
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        pieces.push(TemplatePiece::Literal(literal));
+    }
+    Ok(pieces)
+}
+
+/// What a matching rule's templates are filled in from
+struct TemplateValues<'a> {
+    file_path: &'a Path,
+    facts: &'a FileFacts<'a>,
+    captures: Option<Captures<'a>>,
+    date_source: DateSource,
+    /// Read the first time a date placeholder needs it
+    date: OnceCell<Option<NaiveDateTime>>,
+}
+
+impl TemplateValues<'_> {
+    /// Fill in a template. Each value becomes a single folder or file name, so a `/` in the
+    /// file name can't add levels.
+    fn fill(&self, template: &str) -> Result<String> {
+        let mut filled = String::new();
+        for piece in template_pieces(template)? {
+            match piece {
+                TemplatePiece::Literal(text) => filled.push_str(&text),
+                TemplatePiece::Placeholder(name) => filled.push_str(&sanitize_folder_name(&self.value(&name)?)),
+            }
+        }
+        Ok(filled)
+    }
+
+    fn value(&self, name: &str) -> Result<String> {
+        if let Some(captures) = &self.captures {
+            let group = match name.parse::<usize>() {
+                Ok(index) => captures.get(index),
+                Err(_) => captures.name(name),
+            };
+            if let Some(group) = group {
+                return Ok(group.as_str().to_string());
+            }
+        }
+        let part = |part: Option<&std::ffi::OsStr>| part.map(|part| part.to_string_lossy().to_string()).unwrap_or_default();
+        let format = match name {
+            "name" => return Ok(part(self.file_path.file_stem())),
+            "ext" => return Ok(part(self.file_path.extension())),
+            "yyyy" => "%Y",
+            "yy" => "%y",
+            "mm" => "%m",
+            "dd" => "%d",
+            "hh" => "%H",
+            "min" => "%M",
+            "ss" => "%S",
+            "hhmmss" => "%H%M%S",
+            // A regex group that didn't take part in the match
+            _ => return Ok(String::new()),
+        };
+        let date = self.date.get_or_init(|| {
+            let metadata = self.facts.metadata()?;
+            dates::file_date(self.file_path, metadata, self.date_source, &mut DateFallbacks::default())
+        });
+        match date {
+            Some(date) => Ok(date.format(format).to_string()),
+            None => Err(OrganizerError::plan(self.file_path, format!("{:?} has no date for {{{}}}", self.file_path, name))),
+        }
+    }
+}
+
+/// A problem with the rules that doesn't stop them from loading
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleWarning {
+    pub rule: String,
+    pub message: String,
+}
+
+/// Find rules that can never apply: ones whose predicates contradict each other, and ones
+/// shadowed by an earlier rule that takes every file they match.
+///
+/// Only rules with a fixed destination, no regex and no `continue` shadow later ones, since
+/// a classify_command or plugin may pass a file on to the next rule.
+pub fn lint(rules: &[RuleConfig]) -> Vec<RuleWarning> {
+    let conditions: Vec<Condition> = rules.iter().map(rule_condition).collect();
+    let mut warnings = Vec::new();
+    for (i, (rule, condition)) in rules.iter().zip(&conditions).enumerate() {
+        let warning = |message: String| RuleWarning { rule: rule.name.clone(), message };
+        if condition.never_matches() {
+            warnings.push(warning("its predicates contradict each other, so it never matches".to_string()));
+            continue;
+        }
+        let shadowing = rules[..i].iter().zip(&conditions[..i]).find(|(earlier, earlier_condition)| {
+            let takes_all = earlier.classify_command.is_none()
+                && earlier.plugin.is_none()
+                && earlier.regex.is_none()
+                && !earlier.fall_through;
+            takes_all && condition.implies(earlier_condition)
+        });
+        if let Some((earlier, _)) = shadowing {
+            warnings.push(warning(format!("is unreachable: rule '{}' comes first and takes every file it matches", earlier.name)));
+        }
+    }
+    warnings
+}
+
+/// Run an external classifier with the file path as its only argument.
+///
+/// The first line of stdout is the destination folder. Empty output or a non-zero exit
+/// status means "no opinion".
+fn run_classify_command(command: &str, file_path: &Path) -> Result<Option<String>> {
+    let output = Command::new(command)
+        .arg(file_path)
+        .output()
+        .map_err(|e| OrganizerError::plan(file_path, format!("Failed to run classify_command {}: {}", command, e)))?;
+
+    if !output.status.success() {
+        log::debug!("{} exited with {} for {:?}", command, output.status, file_path);
+        return Ok(None);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let answer = stdout.lines().next().unwrap_or("").trim();
+    if answer.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(answer.to_string()))
+}
+
+/// Interpret a command/plugin answer: `category:<name>` picks a category folder (by its
+/// built-in or configured name), anything else is a destination folder
+fn answer_to_destination(answer: &str, names: &CategoryNames, file_path: &Path) -> Result<PathBuf> {
+    match answer.strip_prefix("category:") {
+        Some(name) => names
+            .by_name(name)
+            .map(|category| PathBuf::from(names.folder(&category)))
+            .ok_or_else(|| {
+                OrganizerError::plan(file_path, format!("Unknown category '{}' returned by classifier", name.trim()))
+            }),
+        None => Ok(relative_destination(answer)),
+    }
+}
+
+/// Turn a user/command supplied folder like `Work/Invoices` into a safe relative path
+fn relative_destination(destination: &str) -> PathBuf {
+    let mut path = PathBuf::new();
+    for component in Path::new(destination).components() {
+        if let Component::Normal(part) = component {
+            path.push(sanitize_folder_name(&part.to_string_lossy()));
+        }
+    }
+    if path.as_os_str().is_empty() {
+        path.push("Unknown");
+    }
+    path
+}
+
+/// Validate a rule when the config is loaded
+pub fn validate_rule(rule: &RuleConfig, plugins: &PluginConfig) -> Result<()> {
+    if rule.action == RuleAction::Skip {
+        if rule.destination.is_some() || rule.rename.is_some() || rule.classify_command.is_some() || rule.plugin.is_some() {
+            return Err(OrganizerError::config(format!("Rule '{}' skips files, so it can't have a destination, rename, classify_command or plugin", rule.name)));
+        }
+        if rule.fall_through {
+            return Err(OrganizerError::config(format!("Rule '{}' skips files, so later rules never see them; remove `continue`", rule.name)));
+        }
+        return compile_regex(rule).map(|_| ());
+    }
+    if rule.action == RuleAction::Delete {
+        if rule.destination.is_some() || rule.rename.is_some() || rule.classify_command.is_some() || rule.plugin.is_some() {
+            return Err(OrganizerError::config(format!("Rule '{}' deletes files, so it can't have a destination, rename, classify_command or plugin", rule.name)));
+        }
+        if rule.fall_through {
+            return Err(OrganizerError::config(format!("Rule '{}' deletes files, so later rules never see them; remove `continue`", rule.name)));
+        }
+        // A rule without predicates would delete everything
+        if rule.extensions.is_none() && rule.pattern.is_none() && rule.regex.is_none() && rule.origin.is_none() && rule.when.is_none() {
+            return Err(OrganizerError::config(format!(
+                "Rule '{}' deletes files, so it needs extensions, a pattern, a regex, an origin or a `when` condition",
+                rule.name
+            )));
+        }
+        return compile_regex(rule).map(|_| ());
+    }
+    let places = rule.destination.is_some() || rule.classify_command.is_some() || rule.plugin.is_some();
+    let only_renames = rule.fall_through && rule.rename.is_some();
+    if !places && !only_renames {
+        return Err(OrganizerError::config(format!("Rule '{}' needs a destination, a classify_command or a plugin (or `continue` and a rename)", rule.name)));
+    }
+
+    let regex = compile_regex(rule)?;
+    let templates = [("destination", &rule.destination), ("rename", &rule.rename)];
+    for (field, template) in templates.into_iter().filter_map(|(field, template)| Some((field, template.as_deref()?))) {
+        for piece in template_pieces(template).config_context(format!("Rule '{}' has an invalid {}", rule.name, field))? {
+            let TemplatePiece::Placeholder(name) = piece else { continue };
+            let is_group = regex.as_ref().is_some_and(|regex| match name.parse::<usize>() {
+                Ok(index) => index < regex.captures_len(),
+                Err(_) => regex.capture_names().flatten().any(|group| group == name),
+            });
+            if !is_group && !PLACEHOLDERS.contains(&name.as_str()) {
+                return Err(OrganizerError::config(format!(
+                    "Rule '{}' uses {{{}}} in its {}, which is neither a regex group nor one of {{{}}}",
+                    rule.name,
+                    name,
+                    field,
+                    PLACEHOLDERS.join("}, {")
+                )));
+            }
+        }
+    }
+
+    if let Some(plugin) = &rule.plugin {
+        if cfg!(not(feature = "plugins")) {
+            return Err(OrganizerError::config(format!(
+                "Rule '{}' uses plugin '{}', but this build doesn't include the `plugins` feature",
+                rule.name,
+                plugin
+            )));
+        }
+        if !plugins.classifiers.iter().any(|registered| registered.name == *plugin) {
+            return Err(OrganizerError::config(format!("Rule '{}' uses plugin '{}', which isn't registered in [[plugins.classifiers]]", rule.name, plugin)));
+        }
+    }
+    Ok(())
+}
+
+/// Case-sensitive glob matching supporting `*` and `?`
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str) -> RuleConfig {
+        RuleConfig {
+            name: name.to_string(),
+            ..RuleConfig::default()
+        }
+    }
+
+    fn folder(decision: RuleDecision) -> Option<PathBuf> {
+        match decision {
+            RuleDecision::Move(target) => Some(target.folder),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("invoice*.pdf", "invoice-2024.pdf"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("img_????.jpg", "img_0042.jpg"));
+        assert!(!wildcard_match("img_????.jpg", "img_42.jpg"));
+        assert!(!wildcard_match("*.pdf", "report.pdf.txt"));
+        // A `*` in the file name is just a character
+        assert!(wildcard_match("*", "*x"));
+        assert!(wildcard_match("a*", "a*b"));
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let rules = [
+            RuleConfig {
+                extensions: Some(vec!["pdf".to_string()]),
+                pattern: Some("invoice*".to_string()),
+                destination: Some("Finance/../Invoices".to_string()),
+                ..rule("invoices")
+            },
+            RuleConfig {
+                extensions: Some(vec!["PDF".to_string()]),
+                destination: Some("PDFs".to_string()),
+                ..rule("pdfs")
+            },
+        ];
+        let rules = RuleSet::new(&rules, &PluginConfig::default()).unwrap();
+
+        assert_eq!(
+            folder(rules.destination(Path::new("Invoice-42.pdf")).unwrap()),
+            Some(PathBuf::from("Finance/Invoices"))
+        );
+        assert_eq!(folder(rules.destination(Path::new("paper.pdf")).unwrap()), Some(PathBuf::from("PDFs")));
+        assert_eq!(folder(rules.destination(Path::new("paper.txt")).unwrap()), None);
+    }
+
+    #[test]
+    fn test_skip_rules() {
+        let rules = [
+            RuleConfig { pattern: Some("keep-*".to_string()), action: RuleAction::Skip, ..rule("keep") },
+            RuleConfig { extensions: Some(vec!["iso".to_string()]), destination: Some("Images".to_string()), ..rule("isos") },
+        ];
+        rules.iter().for_each(|rule| validate_rule(rule, &PluginConfig::default()).unwrap());
+        let rules = RuleSet::new(&rules, &PluginConfig::default()).unwrap();
+        assert_eq!(rules.destination(Path::new("keep-me.iso")).unwrap(), RuleDecision::Skip { rule: "keep".to_string() });
+        assert_eq!(folder(rules.destination(Path::new("ubuntu.iso")).unwrap()), Some(PathBuf::from("Images")));
+
+        let skip_with_destination =
+            RuleConfig { action: RuleAction::Skip, destination: Some("Somewhere".to_string()), ..rule("confused") };
+        assert!(validate_rule(&skip_with_destination, &PluginConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_delete_rules() {
+        let temp = RuleConfig { pattern: Some("*.tmp".to_string()), action: RuleAction::Delete, ..rule("temp") };
+        validate_rule(&temp, &PluginConfig::default()).unwrap();
+        let rules = RuleSet::new(&[temp], &PluginConfig::default()).unwrap();
+        assert_eq!(rules.destination(Path::new("build.tmp")).unwrap(), RuleDecision::Delete { rule: "temp".to_string() });
+        assert_eq!(rules.destination(Path::new("build.log")).unwrap(), RuleDecision::Unmatched { file_name: None });
+
+        // Deleting everything is never what was meant
+        let everything = RuleConfig { action: RuleAction::Delete, ..rule("everything") };
+        assert!(validate_rule(&everything, &PluginConfig::default()).is_err());
+        let delete_with_destination =
+            RuleConfig { destination: Some("Somewhere".to_string()), pattern: Some("*".to_string()), action: RuleAction::Delete, ..rule("confused") };
+        assert!(validate_rule(&delete_with_destination, &PluginConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_continue_and_default_rule() {
+        let rules = [
+            RuleConfig {
+                pattern: Some("scan*".to_string()),
+                rename: Some("Scanned {name}.{ext}".to_string()),
+                fall_through: true,
+                ..rule("name scans")
+            },
+            RuleConfig { extensions: Some(vec!["pdf".to_string()]), destination: Some("Papers".to_string()), ..rule("pdfs") },
+        ];
+        rules.iter().for_each(|rule| validate_rule(rule, &PluginConfig::default()).unwrap());
+        let renamed = |name: &str| Some(name.to_string());
+
+        let extension = RuleSet::new(&rules, &PluginConfig::default()).unwrap();
+        assert_eq!(
+            extension.destination(Path::new("scan1.pdf")).unwrap(),
+            RuleDecision::Move(RuleTarget { folder: PathBuf::from("Papers"), file_name: renamed("Scanned scan1.pdf") })
+        );
+        assert_eq!(extension.destination(Path::new("scan2.jpg")).unwrap(), RuleDecision::Unmatched { file_name: renamed("Scanned scan2.jpg") });
+        assert_eq!(extension.destination(Path::new("photo.jpg")).unwrap(), RuleDecision::Unmatched { file_name: None });
+
+        let quarantine = DefaultRuleConfig { action: DefaultAction::Quarantine, destination: "To sort".to_string() };
+        let quarantine = RuleSet::new(&rules, &PluginConfig::default()).unwrap().with_default(&quarantine);
+        assert_eq!(folder(quarantine.destination(Path::new("photo.jpg")).unwrap()), Some(PathBuf::from("To sort")));
+        let skip = DefaultRuleConfig { action: DefaultAction::Skip, ..DefaultRuleConfig::default() };
+        let skip = RuleSet::new(&rules, &PluginConfig::default()).unwrap().with_default(&skip);
+        assert!(matches!(skip.destination(Path::new("photo.jpg")).unwrap(), RuleDecision::Skip { .. }));
+
+        // A rule that only renames needs `continue`, and continuing rules shadow nothing
+        let rename_only = RuleConfig { rename: Some("{name}".to_string()), ..rule("rename only") };
+        assert!(validate_rule(&rename_only, &PluginConfig::default()).is_err());
+        assert!(lint(&[RuleConfig { destination: Some("All".to_string()), fall_through: true, ..rule("all") }, rules[1].clone()]).is_empty());
+    }
+
+    #[test]
+    fn test_lint() {
+        let when = |condition: &str| Some(condition.parse().unwrap());
+        let rules = [
+            RuleConfig {
+                when: when("ext in [jpg, png] AND size > 5MB"),
+                destination: Some("Big pictures".to_string()),
+                ..rule("big-pictures")
+            },
+            RuleConfig {
+                extensions: Some(vec!["JPG".to_string()]),
+                when: when("size > 10MB AND age < 30d"),
+                destination: Some("Recent".to_string()),
+                ..rule("shadowed")
+            },
+            RuleConfig {
+                when: when("size > 5MB AND size < 1MB"),
+                destination: Some("Nowhere".to_string()),
+                ..rule("contradiction")
+            },
+            RuleConfig {
+                when: when("ext = jpg"),
+                destination: Some("Pictures".to_string()),
+                ..rule("pictures")
+            },
+        ];
+        let warnings = lint(&rules);
+        let names: Vec<&str> = warnings.iter().map(|warning| warning.rule.as_str()).collect();
+        assert_eq!(names, ["shadowed", "contradiction"]);
+        assert!(warnings[0].message.contains("rule 'big-pictures'"));
+
+        let rules = RuleSet::new(&rules, &PluginConfig::default()).unwrap();
+        assert_eq!(folder(rules.destination(Path::new("missing.jpg")).unwrap()), Some(PathBuf::from("Pictures")));
+    }
+
+    #[test]
+    fn test_regex_captures_in_destination() {
+        let rules = [RuleConfig {
+            regex: Some(r"^Invoice-(?P<year>\d{4})-(?P<client>\w+)".to_string()),
+            destination: Some("Invoices/{client}/{year}/{{{1}}}".to_string()),
+            ..rule("invoices")
+        }];
+        rules.iter().for_each(|rule| validate_rule(rule, &PluginConfig::default()).unwrap());
+        let rules = RuleSet::new(&rules, &PluginConfig::default()).unwrap();
+
+        assert_eq!(
+            rules.destination(Path::new("Invoice-2024-acme.pdf")).unwrap(),
+            RuleDecision::Move(RuleTarget { folder: PathBuf::from("Invoices/acme/2024/{2024}"), file_name: None })
+        );
+        assert_eq!(rules.destination(Path::new("Invoice-24-acme.pdf")).unwrap(), RuleDecision::Unmatched { file_name: None });
+
+        let invalid = |regex: &str, destination: &str| {
+            let rule = RuleConfig {
+                regex: Some(regex.to_string()),
+                destination: Some(destination.to_string()),
+                ..rule("bad")
+            };
+            validate_rule(&rule, &PluginConfig::default()).unwrap_err().to_string()
+        };
+        assert!(invalid(r"(?P<year>\d{4})", "Invoices/{client}").contains("neither a regex group"));
+        assert!(invalid(r"(\d{4})", "Invoices/{2}").contains("neither a regex group"));
+        assert!(invalid(r"(\d{4}", "Invoices").contains("invalid regex"));
+        assert!(invalid(r"(\d{4})", "Invoices/{1").contains("invalid destination"));
+    }
+
+    #[test]
+    fn test_rename_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let photo = dir.path().join("IMG_0042.JPG");
+        std::fs::write(&photo, b"no exif").unwrap();
+        let modified = chrono::NaiveDate::from_ymd_opt(2023, 7, 14).unwrap().and_hms_opt(9, 5, 30).unwrap();
+        let modified = std::time::SystemTime::from(modified.and_utc());
+        std::fs::File::options().write(true).open(&photo).unwrap().set_modified(modified).unwrap();
+
+        let rules = [RuleConfig {
+            extensions: Some(vec!["jpg".to_string()]),
+            destination: Some("Photos/{yyyy}/{mm}".to_string()),
+            rename: Some("{yyyy}{mm}{dd}_{hhmmss}_{name}.{ext}".to_string()),
+            ..rule("photos")
+        }];
+        rules.iter().for_each(|rule| validate_rule(rule, &PluginConfig::default()).unwrap());
+        let rules = RuleSet::new(&rules, &PluginConfig::default()).unwrap();
+        assert_eq!(
+            rules.destination(&photo).unwrap(),
+            RuleDecision::Move(RuleTarget {
+                folder: PathBuf::from("Photos/2023/07"),
+                file_name: Some("20230714_090530_IMG_0042.JPG".to_string()),
+            })
+        );
+
+        let bad = RuleConfig { destination: Some("Photos".to_string()), rename: Some("{date}.{ext}".to_string()), ..rule("bad") };
+        let error = validate_rule(&bad, &PluginConfig::default()).unwrap_err().to_string();
+        assert!(error.contains("uses {date} in its rename"), "{}", error);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_classify_command() {
+        let rules = [
+            RuleConfig {
+                classify_command: Some("false".to_string()),
+                ..rule("declines")
+            },
+            RuleConfig {
+                classify_command: Some("echo".to_string()),
+                ..rule("echo")
+            },
+        ];
+        let rules = RuleSet::new(&rules, &PluginConfig::default()).unwrap();
+
+        // `echo <path>` prints the path back, which becomes the destination
+        assert_eq!(
+            folder(rules.destination(Path::new("Sorted/by-script.bin")).unwrap()),
+            Some(PathBuf::from("Sorted/by-script.bin"))
+        );
+        assert!(validate_rule(&rule("empty"), &PluginConfig::default()).is_err());
+    }
+}
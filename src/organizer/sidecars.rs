@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::config::SidecarConfig;
+use super::{FileOperation, OperationType};
+
+/// Extension pairs deciding which sidecar files travel with which primaries
+pub struct SidecarPairs {
+    /// Primary extension -> sidecar extensions (all lowercase)
+    pairs: HashMap<String, Vec<String>>,
+}
+
+impl SidecarPairs {
+    /// Build the pairing table from the config, or an empty one when disabled
+    pub fn from_config(config: &SidecarConfig) -> Self {
+        let pairs = if config.enabled {
+            config
+                .pairs
+                .iter()
+                .map(|(primary, sidecars)| {
+                    (
+                        primary.to_lowercase(),
+                        sidecars.iter().map(|ext| ext.to_lowercase()).collect(),
+                    )
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        Self { pairs }
+    }
+
+    /// Move sidecar files next to their primaries.
+    ///
+    /// Sidecars that are already planned get their destination rewritten; sidecars found in
+    /// `candidates` (e.g. excluded by a filter) are added to the plan, with their size from the
+    /// scan's `metadata`.
+    pub fn pair(&self, operations: &mut Vec<FileOperation>, candidates: &[PathBuf], metadata: &HashMap<PathBuf, fs::Metadata>) {
+        if self.pairs.is_empty() {
+            return;
+        }
+
+        // (directory, stem) -> (primary extension, destination folder)
+        let mut primaries: HashMap<(PathBuf, String), (String, PathBuf)> = HashMap::new();
+        for op in operations.iter() {
+            if let (Some(ext), Some(key), Some(dest_dir)) =
+                (lowercase_ext(&op.source), stem_key(&op.source), op.destination.parent())
+            {
+                if self.pairs.contains_key(&ext) {
+                    primaries.insert(key, (ext, dest_dir.to_path_buf()));
+                }
+            }
+        }
+
+        if primaries.is_empty() {
+            return;
+        }
+
+        let planned: HashMap<PathBuf, usize> = operations
+            .iter()
+            .enumerate()
+            .map(|(index, op)| (op.source.clone(), index))
+            .collect();
+
+        for candidate in candidates {
+            let Some(dest_dir) = self.primary_destination(candidate, &primaries) else {
+                continue;
+            };
+            let Some(file_name) = candidate.file_name() else {
+                continue;
+            };
+            let destination = dest_dir.join(file_name);
+
+            match planned.get(candidate) {
+                Some(&index) => operations[index].destination = destination,
+                None => operations.push(FileOperation {
+                    source: candidate.clone(),
+                    destination,
+                    operation_type: OperationType::Move,
+                    size: metadata.get(candidate).map_or(0, |metadata| metadata.len()),
+                }),
+            }
+        }
+    }
+
+    /// Find the destination folder of the primary a sidecar belongs to.
+    ///
+    /// `movie.en.srt` matches `movie.mkv` as well as `movie.en.mkv`.
+    fn primary_destination<'a>(
+        &self,
+        candidate: &Path,
+        primaries: &'a HashMap<(PathBuf, String), (String, PathBuf)>,
+    ) -> Option<&'a PathBuf> {
+        let ext = lowercase_ext(candidate)?;
+        let (dir, mut stem) = stem_key(candidate)?;
+
+        loop {
+            if let Some((primary_ext, dest_dir)) = primaries.get(&(dir.clone(), stem.clone())) {
+                if *primary_ext != ext && self.pairs[primary_ext].contains(&ext) {
+                    return Some(dest_dir);
+                }
+            }
+
+            match stem.rfind('.') {
+                Some(index) => stem.truncate(index),
+                None => return None,
+            }
+        }
+    }
+}
+
+fn lowercase_ext(path: &Path) -> Option<String> {
+    path.extension().map(|ext| ext.to_string_lossy().to_lowercase())
+}
+
+fn stem_key(path: &Path) -> Option<(PathBuf, String)> {
+    let dir = path.parent()?.to_path_buf();
+    let stem = path.file_stem()?.to_string_lossy().to_string();
+    Some((dir, stem))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(source: &str, destination: &str) -> FileOperation {
+        FileOperation {
+            source: PathBuf::from(source),
+            destination: PathBuf::from(destination),
+            operation_type: OperationType::Move,
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn test_sidecars_follow_primary() {
+        let pairs = SidecarPairs::from_config(&SidecarConfig::default());
+        let mut operations = vec![
+            op("dl/movie.mkv", "dl/Videos/movie.mkv"),
+            op("dl/movie.en.srt", "dl/Other/movie.en.srt"),
+        ];
+        let candidates = vec![
+            PathBuf::from("dl/movie.mkv"),
+            PathBuf::from("dl/movie.en.srt"),
+            PathBuf::from("dl/movie.nfo"),
+            PathBuf::from("dl/other.nfo"),
+        ];
+
+        pairs.pair(&mut operations, &candidates, &HashMap::new());
+
+        assert_eq!(operations.len(), 3);
+        assert_eq!(operations[1].destination, PathBuf::from("dl/Videos/movie.en.srt"));
+        assert_eq!(operations[2].source, PathBuf::from("dl/movie.nfo"));
+        assert_eq!(operations[2].destination, PathBuf::from("dl/Videos/movie.nfo"));
+    }
+
+    #[test]
+    fn test_disabled_pairing_leaves_plan_alone() {
+        let pairs = SidecarPairs::from_config(&SidecarConfig {
+            enabled: false,
+            ..SidecarConfig::default()
+        });
+        let mut operations = vec![op("dl/photo.raw", "dl/Images/photo.raw")];
+
+        pairs.pair(&mut operations, &[PathBuf::from("dl/photo.xmp")], &HashMap::new());
+
+        assert_eq!(operations.len(), 1);
+    }
+}
@@ -0,0 +1,87 @@
+//! Files a run found but left where they are, and why, so they don't just vanish from the
+//! output: `skipped: 14 hidden, 3 pinned, 2 conflicts`. Parts of the directory that couldn't
+//! be read at all are reported the same way.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+
+/// Why a file wasn't organized, in the order the summary lists them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// A dot file, or hidden by its attributes, without `include_hidden`
+    Hidden,
+    /// A file the system keeps (`.DS_Store`, `Thumbs.db`, the Windows system attribute)
+    System,
+    /// Pinned to where it is
+    Pinned,
+    /// A cloud placeholder whose contents would be downloaded, without `hydrate`
+    Placeholder,
+    /// Not selected by `--filter`, `--only-categories` or the name predicates
+    Filtered,
+    /// Ruled out by `--exclude-ext` or `--exclude-categories`
+    Excluded,
+    /// Without an extension, with `no_extension = "skip"`
+    NoExtension,
+    /// Matched a `skip` rule
+    Rule,
+    /// Part of a split archive with parts missing
+    IncompleteArchive,
+    /// Its destination was taken and the conflict policy said skip
+    Conflict,
+    /// Waiting in `Pending/` until it has been left unchanged for the staging period
+    Pending,
+}
+
+/// One file that stays put
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedFile {
+    #[serde(with = "super::os_path")]
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+impl SkippedFile {
+    pub fn new(path: impl Into<PathBuf>, reason: SkipReason) -> Self {
+        Self { path: path.into(), reason }
+    }
+}
+
+/// A file or directory the scan couldn't read, so whatever it holds wasn't organized
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanError {
+    #[serde(with = "super::os_path")]
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl ScanError {
+    pub fn new(path: impl Into<PathBuf>, reason: impl Into<String>) -> Self {
+        Self { path: path.into(), reason: reason.into() }
+    }
+}
+
+/// How many files were skipped for each reason
+pub fn tally(reasons: impl IntoIterator<Item = SkipReason>) -> BTreeMap<SkipReason, usize> {
+    let mut counts = BTreeMap::new();
+    for reason in reasons {
+        *counts.entry(reason).or_insert(0) += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tally() {
+        let counts = tally([SkipReason::Conflict, SkipReason::Hidden, SkipReason::Hidden]);
+        assert_eq!(counts.into_iter().collect::<Vec<_>>(), [(SkipReason::Hidden, 2), (SkipReason::Conflict, 1)]);
+        assert_eq!(
+            serde_json::to_string(&tally([SkipReason::IncompleteArchive])).unwrap(),
+            r#"{"incomplete_archive":1}"#
+        );
+    }
+}
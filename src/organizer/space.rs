@@ -0,0 +1,167 @@
+//! Free space and inode checks, so a run on a nearly full filesystem stops before it moves
+//! anything rather than halfway through, when a new folder can't be created.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::units::ByteSize;
+use super::{FileOperation, OperationType};
+
+/// Room a new directory is assumed to take; most filesystems allocate one block
+const DIRECTORY_BYTES: u64 = 4096;
+
+/// Inodes the journal may need: `.file-organizer/`, `history/` and the journal itself
+const JOURNAL_INODES: u64 = 3;
+
+/// What's left on the filesystem holding a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreeSpace {
+    /// Bytes available to this user
+    pub bytes: u64,
+    /// Inodes available to this user; `None` where the filesystem doesn't have a fixed
+    /// number (btrfs, NTFS)
+    pub inodes: Option<u64>,
+}
+
+/// Why a run wouldn't fit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpaceShortage {
+    /// Fewer free inodes than the run's new directories need
+    Inodes { free: u64, needed: u64 },
+    /// The run would leave less free space than the minimum (or none at all)
+    Bytes { free: u64, needed: u64 },
+}
+
+impl fmt::Display for SpaceShortage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpaceShortage::Inodes { free, needed } => {
+                write!(f, "{} free inodes, but the run needs {} for new folders", free, needed)
+            }
+            SpaceShortage::Bytes { free, needed } => {
+                write!(f, "{} free, but the run needs {} to stay above the minimum", ByteSize(*free), ByteSize(*needed))
+            }
+        }
+    }
+}
+
+impl FreeSpace {
+    /// The first shortage running `operations` would run into, if any. Moves within a
+    /// filesystem take no room themselves; copies, new directories and the journal do.
+    pub fn shortage(&self, target_dir: &Path, operations: &[FileOperation], min_free: Option<u64>) -> Option<SpaceShortage> {
+        let directories = new_directories(target_dir, operations) as u64;
+        if let Some(free) = self.inodes {
+            let needed = directories + JOURNAL_INODES;
+            if free < needed {
+                return Some(SpaceShortage::Inodes { free, needed });
+            }
+        }
+        let copies: u64 = operations
+            .iter()
+            .filter(|op| op.operation_type == OperationType::Copy)
+            .filter_map(|op| fs::metadata(&op.source).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        let needed = min_free.unwrap_or(0) + directories * DIRECTORY_BYTES + copies;
+        (self.bytes < needed).then_some(SpaceShortage::Bytes { free: self.bytes, needed })
+    }
+}
+
+/// Free space on the filesystem holding `path`; `None` if it can't be determined
+pub fn free_space(path: &Path) -> Option<FreeSpace> {
+    platform_free_space(path)
+}
+
+/// Directories the moves would create inside `target_dir`
+fn new_directories(target_dir: &Path, operations: &[FileOperation]) -> usize {
+    let mut directories: HashSet<PathBuf> = HashSet::new();
+    for operation in operations {
+        let mut directory = operation.destination.parent();
+        while let Some(dir) = directory.filter(|dir| dir.starts_with(target_dir) && *dir != target_dir) {
+            if dir.exists() || !directories.insert(dir.to_path_buf()) {
+                break;
+            }
+            directory = dir.parent();
+        }
+    }
+    directories.len()
+}
+
+#[cfg(unix)]
+fn platform_free_space(path: &Path) -> Option<FreeSpace> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statvfs only writes into `buffer`, and `c_path` is a valid NUL-terminated
+    // string that outlives the call
+    let (result, buffer) = unsafe {
+        let mut buffer: libc::statvfs = std::mem::zeroed();
+        let result = libc::statvfs(c_path.as_ptr(), &mut buffer);
+        (result, buffer)
+    };
+    if result != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)] // the field types differ between platforms
+    let (blocks, block_size, files, free_files) =
+        (buffer.f_bavail as u64, buffer.f_frsize as u64, buffer.f_files as u64, buffer.f_favail as u64);
+    Some(FreeSpace {
+        bytes: blocks.saturating_mul(block_size),
+        // Filesystems that allocate inodes on demand report a total of zero
+        inodes: (files > 0).then_some(free_files),
+    })
+}
+
+#[cfg(windows)]
+fn platform_free_space(path: &Path) -> Option<FreeSpace> {
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide = super::long_paths::wide(path);
+    let mut available = 0u64;
+    // SAFETY: `wide` is NUL-terminated and outlives the call, which only writes `available`
+    let result = unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, std::ptr::null_mut(), std::ptr::null_mut()) };
+    (result != 0).then_some(FreeSpace { bytes: available, inodes: None })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn platform_free_space(_path: &Path) -> Option<FreeSpace> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_space_shortage() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("Images")).unwrap();
+        let op = |destination: &str| FileOperation {
+            source: root.join("file"),
+            destination: root.join(destination),
+            operation_type: OperationType::Move,
+            size: 0,
+        };
+        // Images exists; Documents, Documents/2024 and Videos are new
+        let operations = [op("Images/a.jpg"), op("Documents/2024/b.pdf"), op("Documents/c.pdf"), op("Videos/d.mp4")];
+        assert_eq!(new_directories(root, &operations), 3);
+
+        let roomy = FreeSpace { bytes: 1 << 30, inodes: Some(1000) };
+        assert_eq!(roomy.shortage(root, &operations, None), None);
+        assert_eq!(
+            roomy.shortage(root, &operations, Some(1 << 30)),
+            Some(SpaceShortage::Bytes { free: 1 << 30, needed: (1 << 30) + 3 * DIRECTORY_BYTES })
+        );
+        let few_inodes = FreeSpace { bytes: 1 << 30, inodes: Some(5) };
+        assert_eq!(few_inodes.shortage(root, &operations, None), Some(SpaceShortage::Inodes { free: 5, needed: 6 }));
+        let no_fixed_inodes = FreeSpace { bytes: 1 << 30, inodes: None };
+        assert_eq!(no_fixed_inodes.shortage(root, &operations, None), None);
+
+        assert!(free_space(root).is_some_and(|free| free.bytes > 0));
+    }
+}
@@ -0,0 +1,247 @@
+//! Copying files to another filesystem, for moves that can't be renames.
+//!
+//! Sparse files (VM disk images, some databases) keep their holes, so a 100 GB qcow2 holding
+//! 3 GB of data takes 3 GB on the other drive too: on Linux the data regions are found with
+//! `SEEK_DATA`/`SEEK_HOLE` and copied with `copy_file_range()`, and the holes are left as
+//! holes. On Windows `CopyFileExW` copies every alternate data stream along with the file, so
+//! downloads keep their mark of the web (`Zone.Identifier`), and takes paths of any length; a
+//! stream the destination can't hold (FAT drives have none) is warned about. On macOS
+//! `copyfile()` copies everything Finder keeps beside the data too: tags, color labels,
+//! resource forks, ACLs. Everything else goes through `fs::copy()`.
+
+use std::io;
+use std::path::Path;
+
+/// Copy `source` to `destination` like `fs::copy()` (permissions included), keeping holes on
+/// Linux, alternate data streams on Windows and Finder metadata on macOS
+pub fn copy(source: &Path, destination: &Path) -> io::Result<u64> {
+    #[cfg(target_os = "linux")]
+    if let Some(copied) = linux::copy_sparse(source, destination)? {
+        return Ok(copied);
+    }
+    #[cfg(windows)]
+    return windows::copy_with_streams(source, destination);
+    #[cfg(target_os = "macos")]
+    return macos::copy_all(source, destination);
+    #[cfg(not(any(windows, target_os = "macos")))]
+    std::fs::copy(source, destination)
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    /// `copyfile(COPYFILE_ALL)`: the data, and every extended attribute (Finder tags and
+    /// labels, resource forks), ACL and mode with it
+    pub(super) fn copy_all(source: &Path, destination: &Path) -> io::Result<u64> {
+        let c_path = |path: &Path| {
+            CString::new(path.as_os_str().as_bytes()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))
+        };
+        let (from, to) = (c_path(source)?, c_path(destination)?);
+        // SAFETY: both paths are NUL-terminated and outlive the call; no state object
+        let result = unsafe {
+            libc::copyfile(from.as_ptr(), to.as_ptr(), std::ptr::null_mut(), libc::COPYFILE_METADATA | libc::COPYFILE_DATA)
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(std::fs::metadata(destination)?.len())
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::OsString;
+    use std::fs;
+    use std::io;
+    use std::os::windows::ffi::OsStringExt;
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
+    use windows_sys::Win32::Storage::FileSystem::{
+        CopyFileExW, FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard, WIN32_FIND_STREAM_DATA,
+    };
+
+    use super::super::long_paths;
+
+    pub(super) fn copy_with_streams(source: &Path, destination: &Path) -> io::Result<u64> {
+        let (from, to) = (long_paths::wide(source), long_paths::wide(destination));
+        // SAFETY: both paths are NUL-terminated and outlive the call; no progress callback
+        let copied = unsafe { CopyFileExW(from.as_ptr(), to.as_ptr(), None, std::ptr::null(), std::ptr::null_mut(), 0) };
+        if copied == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let kept = streams(destination);
+        for stream in streams(source).into_iter().filter(|stream| !kept.contains(stream)) {
+            log::warn!(
+                "{} lost its {} stream in the copy (the destination drive can't hold it)",
+                destination.display(),
+                stream.to_string_lossy()
+            );
+        }
+        Ok(fs::metadata(destination)?.len())
+    }
+
+    /// Names of the alternate data streams of a file, such as `:Zone.Identifier:$DATA`
+    fn streams(path: &Path) -> Vec<OsString> {
+        let wide = long_paths::wide(path);
+        let mut data = WIN32_FIND_STREAM_DATA::default();
+        let mut names = Vec::new();
+        // SAFETY: `data` is the structure FindStreamInfoStandard fills in; the handle is
+        // closed below
+        let handle = unsafe { FindFirstStreamW(wide.as_ptr(), FindStreamInfoStandard, (&mut data as *mut WIN32_FIND_STREAM_DATA).cast(), 0) };
+        if handle == INVALID_HANDLE_VALUE {
+            return names;
+        }
+        loop {
+            let len = data.cStreamName.iter().position(|&unit| unit == 0).unwrap_or(data.cStreamName.len());
+            let name = OsString::from_wide(&data.cStreamName[..len]);
+            // The file's own contents
+            if name != "::$DATA" {
+                names.push(name);
+            }
+            // SAFETY: as above
+            if unsafe { FindNextStreamW(handle, (&mut data as *mut WIN32_FIND_STREAM_DATA).cast()) } == 0 {
+                break;
+            }
+        }
+        // SAFETY: the handle came from FindFirstStreamW and isn't used again
+        unsafe { FindClose(handle) };
+        names
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::fs::{self, File};
+    use std::io;
+    use std::os::unix::fs::{FileExt, MetadataExt};
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    /// Copy the data regions of a sparse file; `None` if it isn't sparse or its filesystem
+    /// can't say where the holes are
+    pub(super) fn copy_sparse(source: &Path, destination: &Path) -> io::Result<Option<u64>> {
+        let input = File::open(source)?;
+        let metadata = input.metadata()?;
+        let len = metadata.len();
+        // Fewer blocks than the size needs means there are holes
+        if !metadata.is_file() || metadata.blocks() * 512 >= len {
+            return Ok(None);
+        }
+        let first = match seek(&input, 0, libc::SEEK_DATA) {
+            Ok(first) => first,
+            Err(e) if e.raw_os_error() == Some(libc::ENXIO) => len,
+            Err(_) => return Ok(None),
+        };
+
+        let output = File::create(destination)?;
+        let mut offset = first;
+        while offset < len {
+            let end = seek(&input, offset, libc::SEEK_HOLE)?.min(len);
+            copy_range(&input, &output, offset, end)?;
+            offset = match seek(&input, end, libc::SEEK_DATA) {
+                Ok(next) => next,
+                // Nothing but a hole after this
+                Err(e) if e.raw_os_error() == Some(libc::ENXIO) => len,
+                Err(e) => return Err(e),
+            };
+        }
+        // Ends in a hole, which nothing was written to
+        output.set_len(len)?;
+        fs::set_permissions(destination, metadata.permissions())?;
+        Ok(Some(len))
+    }
+
+    fn seek(file: &File, offset: u64, whence: libc::c_int) -> io::Result<u64> {
+        // SAFETY: lseek only moves the offset of a descriptor the file owns
+        let result = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(result as u64)
+    }
+
+    /// Copy bytes `start..end` to the same place in `output`, by `copy_file_range()` where the
+    /// kernel can do it between these filesystems
+    fn copy_range(input: &File, output: &File, start: u64, end: u64) -> io::Result<()> {
+        let mut offset = start;
+        while offset < end {
+            let mut in_offset = offset as libc::loff_t;
+            let mut out_offset = offset as libc::loff_t;
+            // SAFETY: both descriptors are open, and the offsets are ours to update
+            let copied = unsafe {
+                libc::copy_file_range(
+                    input.as_raw_fd(),
+                    &mut in_offset,
+                    output.as_raw_fd(),
+                    &mut out_offset,
+                    (end - offset) as usize,
+                    0,
+                )
+            };
+            match copied {
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                copied if copied > 0 => offset += copied as u64,
+                _ => {
+                    let error = io::Error::last_os_error();
+                    match error.raw_os_error() {
+                        Some(libc::EXDEV | libc::ENOSYS | libc::EOPNOTSUPP | libc::EINVAL) => {
+                            return read_write(input, output, offset, end)
+                        }
+                        _ => return Err(error),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_write(input: &File, output: &File, start: u64, end: u64) -> io::Result<()> {
+        let mut buffer = vec![0; 1 << 20];
+        let mut offset = start;
+        while offset < end {
+            let want = buffer.len().min((end - offset) as usize);
+            let read = input.read_at(&mut buffer[..want], offset)?;
+            if read == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            output.write_all_at(&buffer[..read], offset)?;
+            offset += read as u64;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_copy_keeps_holes() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("disk.qcow2");
+        let file = fs::File::create(&source).unwrap();
+        file.set_len(64 << 20).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::FileExt::write_all_at(&file, b"data", 8 << 20).unwrap();
+        drop(file);
+
+        let destination = dir.path().join("copy.qcow2");
+        assert_eq!(copy(&source, &destination).unwrap(), 64 << 20);
+        assert_eq!(fs::read(&destination).unwrap(), fs::read(&source).unwrap());
+
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let allocated = |path: &Path| fs::metadata(path).unwrap().blocks() * 512;
+            // Only meaningful where the temporary directory keeps holes to begin with
+            if allocated(&source) < 1 << 20 {
+                assert!(allocated(&destination) < 1 << 20);
+            }
+        }
+    }
+}
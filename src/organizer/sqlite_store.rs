@@ -0,0 +1,397 @@
+//! A single SQLite database holding the history of every organized directory, plus a cache of
+//! file hashes.
+//!
+//! There's no cache of scans. Whether a cached entry still holds takes a stat of the file, and
+//! that stat is all a scan does; what costs more than a stat (hashing) is cached already.
+//!
+//! Moved files are stored as BLOBs of their raw OS bytes (WTF-8 on Windows), so file names
+//! that aren't valid UTF-8 can be undone like any other, as with `os_path` in the JSON store.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+
+use super::error::{Context, OrganizerError, Result};
+use super::journal::{Journal, JournalEntry, JOURNAL_VERSION};
+use super::store::HistoryStore;
+
+/// Schema migrations, applied in order; `PRAGMA user_version` records how many have run
+const MIGRATIONS: &[&str] = &["
+    CREATE TABLE operations (
+        directory  TEXT NOT NULL,
+        id         TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        mode       TEXT NOT NULL,
+        PRIMARY KEY (directory, id)
+    );
+    CREATE INDEX operations_id ON operations (id);
+    CREATE TABLE moves (
+        directory    TEXT NOT NULL,
+        operation_id TEXT NOT NULL,
+        position     INTEGER NOT NULL,
+        source       TEXT NOT NULL,
+        destination  TEXT NOT NULL,
+        PRIMARY KEY (directory, operation_id, position),
+        FOREIGN KEY (directory, operation_id) REFERENCES operations (directory, id) ON DELETE CASCADE
+    );
+    CREATE INDEX moves_source ON moves (directory, source);
+    CREATE INDEX moves_destination ON moves (directory, destination);
+    CREATE TABLE hash_cache (
+        path        TEXT PRIMARY KEY,
+        size        INTEGER NOT NULL,
+        modified_ns INTEGER NOT NULL,
+        hash        TEXT NOT NULL
+    );
+", "
+    ALTER TABLE operations ADD COLUMN stats TEXT;
+", "
+    CREATE TABLE moves_bytes (
+        directory    TEXT NOT NULL,
+        operation_id TEXT NOT NULL,
+        position     INTEGER NOT NULL,
+        source       BLOB NOT NULL,
+        destination  BLOB NOT NULL,
+        PRIMARY KEY (directory, operation_id, position),
+        FOREIGN KEY (directory, operation_id) REFERENCES operations (directory, id) ON DELETE CASCADE
+    );
+    INSERT INTO moves_bytes
+        SELECT directory, operation_id, position, CAST(source AS BLOB), CAST(destination AS BLOB) FROM moves;
+    DROP TABLE moves;
+    ALTER TABLE moves_bytes RENAME TO moves;
+    CREATE INDEX moves_source ON moves (directory, source);
+    CREATE INDEX moves_destination ON moves (directory, destination);
+"];
+
+/// Queries fail with the history database, whatever the statement
+impl From<rusqlite::Error> for OrganizerError {
+    fn from(error: rusqlite::Error) -> Self {
+        OrganizerError::Journal { reason: "History database error".to_string(), source: Some(error.into()) }
+    }
+}
+
+pub struct SqliteStore {
+    // rusqlite connections aren't Sync; the lock also keeps multi-statement writes together
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).journal_context(format!("Failed to create {}", parent.display()))?;
+        }
+        let connection = Connection::open(path).journal_context(format!("Failed to open {}", path.display()))?;
+        Self::with_connection(connection).journal_context(format!("Failed to set up {}", path.display()))
+    }
+
+    /// Open the database without writing anything: no directory, file or migration is
+    /// created, and every change fails. A database that doesn't exist yet reads as empty.
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Self::with_connection(Connection::open_in_memory()?);
+        }
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .journal_context(format!("Failed to open {}", path.display()))?;
+        let applied: usize = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if applied > MIGRATIONS.len() {
+            return Err(OrganizerError::journal("The history database was created by a newer version of file-organizer"));
+        }
+        if applied < MIGRATIONS.len() {
+            return Err(OrganizerError::journal(format!("The history database {} is from an older version; `file-organizer history` upgrades it", path.display())));
+        }
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn with_connection(mut connection: Connection) -> Result<Self> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        // Let a second process (say, a daemon and a manual run) wait instead of failing
+        connection.busy_timeout(std::time::Duration::from_secs(5))?;
+
+        let applied: usize = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if applied > MIGRATIONS.len() {
+            return Err(OrganizerError::journal("The history database was created by a newer version of file-organizer"));
+        }
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+            let transaction = connection.transaction()?;
+            transaction.execute_batch(migration)?;
+            transaction.pragma_update(None, "user_version", index + 1)?;
+            transaction.commit()?;
+        }
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Directories are keyed by their canonical path, so `.` and `~/Downloads` share a history
+fn directory_key(target_dir: &Path) -> Result<String> {
+    let canonical = target_dir.canonicalize().unwrap_or_else(|_| target_dir.to_path_buf());
+    text(&canonical)
+}
+
+fn text(path: &Path) -> Result<String> {
+    path.to_str()
+        .map(str::to_string)
+        .ok_or_else(|| {
+            OrganizerError::journal(format!("{} isn't valid UTF-8, so it can't be stored in the history database", path.display()))
+        })
+}
+
+/// A path's raw bytes, as the `moves` table stores them
+fn path_bytes(path: &Path) -> &[u8] {
+    path.as_os_str().as_encoded_bytes()
+}
+
+fn path_from_bytes(bytes: Vec<u8>) -> Result<PathBuf> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        Ok(PathBuf::from(std::ffi::OsString::from_vec(bytes)))
+    }
+    #[cfg(windows)]
+    {
+        from_wtf8(&bytes)
+            .map(PathBuf::from)
+            .ok_or_else(|| OrganizerError::journal("A path in the history database isn't valid for this system"))
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        Ok(PathBuf::from(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+}
+
+/// Decode WTF-8: UTF-8 that may also hold lone surrogates, as Windows file names can
+#[cfg(windows)]
+fn from_wtf8(mut bytes: &[u8]) -> Option<std::ffi::OsString> {
+    use std::os::windows::ffi::OsStringExt;
+    let mut wide = Vec::new();
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => {
+                wide.extend(text.encode_utf16());
+                return Some(std::ffi::OsString::from_wide(&wide));
+            }
+            Err(e) => {
+                let (valid, rest) = bytes.split_at(e.valid_up_to());
+                wide.extend(std::str::from_utf8(valid).ok()?.encode_utf16());
+                // A surrogate (U+D800 to U+DFFF) is encoded as ED A0..BF 80..BF
+                let [0xED, high @ 0xA0..=0xBF, low @ 0x80..=0xBF, ..] = *rest else {
+                    return None;
+                };
+                wide.push(0xD000 | ((high as u16 & 0x3F) << 6) | (low as u16 & 0x3F));
+                bytes = &rest[3..];
+            }
+        }
+    }
+}
+
+fn nanos(time: SystemTime) -> Option<i64> {
+    let since_epoch = time.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+    i64::try_from(since_epoch.as_nanos()).ok()
+}
+
+impl HistoryStore for SqliteStore {
+    fn journals(&self, target_dir: &Path) -> Result<Vec<Journal>> {
+        let directory = directory_key(target_dir)?;
+        let connection = self.connection();
+
+        let mut operations = connection.prepare(
+            "SELECT id, created_at, mode, stats FROM operations WHERE directory = ?1 ORDER BY id",
+        )?;
+        let mut moves = connection.prepare(
+            "SELECT source, destination FROM moves WHERE directory = ?1 AND operation_id = ?2 ORDER BY position",
+        )?;
+
+        let rows = operations.query_map(params![directory], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })?;
+        let mut journals = Vec::new();
+        for row in rows {
+            let (id, created_at, mode, stats) = row?;
+            let moves = moves
+                .query_map(params![directory, id], |row| {
+                    Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Vec<u8>>(1)?))
+                })?
+                .map(|row| {
+                    let (source, destination) = row?;
+                    Ok(JournalEntry { source: path_from_bytes(source)?, destination: path_from_bytes(destination)? })
+                })
+                .collect::<Result<_>>()?;
+            let stats = stats
+                .map(|stats| serde_json::from_str(&stats))
+                .transpose()
+                .journal_context(format!("Invalid statistics for operation {}", id))?;
+            journals.push(Journal {
+                version: JOURNAL_VERSION,
+                created_at: DateTime::parse_from_rfc3339(&created_at)
+                    .journal_context(format!("Invalid timestamp for operation {}", id))?
+                    .with_timezone(&Utc),
+                id,
+                mode,
+                moves,
+                stats,
+            });
+        }
+        Ok(journals)
+    }
+
+    fn save(&self, target_dir: &Path, journal: &Journal) -> Result<()> {
+        let directory = directory_key(target_dir)?;
+        let stats = journal.stats.as_ref().map(serde_json::to_string).transpose().journal_context("Failed to serialize the statistics")?;
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+
+        transaction.execute(
+            "DELETE FROM operations WHERE directory = ?1 AND id = ?2",
+            params![directory, journal.id],
+        )?;
+        transaction.execute(
+            "INSERT INTO operations (directory, id, created_at, mode, stats) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![directory, journal.id, journal.created_at.to_rfc3339(), journal.mode, stats],
+        )?;
+        {
+            let mut insert = transaction.prepare(
+                "INSERT INTO moves (directory, operation_id, position, source, destination)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for (position, entry) in journal.moves.iter().enumerate() {
+                insert.execute(params![
+                    directory,
+                    journal.id,
+                    position as i64,
+                    path_bytes(&entry.source),
+                    path_bytes(&entry.destination)
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn remove(&self, target_dir: &Path, id: &str) -> Result<()> {
+        let directory = directory_key(target_dir)?;
+        self.connection()
+            .execute("DELETE FROM operations WHERE directory = ?1 AND id = ?2", params![directory, id])?;
+        Ok(())
+    }
+
+    fn directories(&self) -> Result<Vec<PathBuf>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare("SELECT DISTINCT directory FROM operations ORDER BY directory")?;
+        let directories = statement
+            .query_map([], |row| row.get::<_, String>(0).map(PathBuf::from))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(directories)
+    }
+
+    fn cached_hash(&self, path: &Path, size: u64, modified: SystemTime) -> Option<String> {
+        let path = text(path).ok()?;
+        self.connection()
+            .query_row(
+                "SELECT hash FROM hash_cache WHERE path = ?1 AND size = ?2 AND modified_ns = ?3",
+                params![path, size as i64, nanos(modified)?],
+                |row| row.get(0),
+            )
+            .optional()
+            .ok()
+            .flatten()
+    }
+
+    fn remember_hash(&self, path: &Path, size: u64, modified: SystemTime, hash: &str) {
+        let (Ok(path), Some(modified_ns)) = (text(path), nanos(modified)) else {
+            return;
+        };
+        let result = self.connection().execute(
+            "INSERT OR REPLACE INTO hash_cache (path, size, modified_ns, hash) VALUES (?1, ?2, ?3, ?4)",
+            params![path, size as i64, modified_ns, hash],
+        );
+        if let Err(e) = result {
+            log::debug!("Couldn't cache the hash of {}: {}", path, e);
+        }
+    }
+
+    fn clear_hash_cache(&self) -> Result<usize> {
+        Ok(self.connection().execute("DELETE FROM hash_cache", [])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sqlite_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open(&dir.path().join("data/history.sqlite3")).unwrap();
+        super::super::store::tests::check_store(&store, dir.path());
+
+        // Reopening doesn't rerun migrations
+        drop(store);
+        let store = SqliteStore::open(&dir.path().join("data/history.sqlite3")).unwrap();
+        assert_eq!(store.journals(dir.path()).unwrap().len(), 2);
+
+        let read_only = SqliteStore::open_read_only(&dir.path().join("data/history.sqlite3")).unwrap();
+        let journal = read_only.latest(dir.path()).unwrap().unwrap();
+        assert!(read_only.remove(dir.path(), &journal.id).is_err());
+        let missing = dir.path().join("elsewhere/history.sqlite3");
+        assert!(SqliteStore::open_read_only(&missing).unwrap().journals(dir.path()).unwrap().is_empty());
+        assert!(!missing.parent().unwrap().exists());
+    }
+
+    #[test]
+    fn test_moves_become_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.sqlite3");
+        let old = Connection::open(&path).unwrap();
+        for migration in &MIGRATIONS[..2] {
+            old.execute_batch(migration).unwrap();
+        }
+        old.pragma_update(None, "user_version", 2).unwrap();
+        let directory = directory_key(dir.path()).unwrap();
+        old.execute("INSERT INTO operations (directory, id, created_at, mode) VALUES (?1, 'op', '2024-05-01T10:00:00+00:00', 'Extension')", params![directory]).unwrap();
+        old.execute("INSERT INTO moves VALUES (?1, 'op', 0, 'café.jpg', 'Images/café.jpg')", params![directory]).unwrap();
+        drop(old);
+
+        let store = SqliteStore::open(&path).unwrap();
+        let moves = &store.journals(dir.path()).unwrap()[0].moves;
+        assert_eq!(moves[0], JournalEntry { source: PathBuf::from("café.jpg"), destination: PathBuf::from("Images/café.jpg") });
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_from_wtf8() {
+        use std::os::windows::ffi::OsStringExt;
+        let lone_surrogate = std::ffi::OsString::from_wide(&[0x61, 0xD800, 0x62]);
+        assert_eq!(from_wtf8(lone_surrogate.as_encoded_bytes()), Some(lone_surrogate));
+        assert_eq!(from_wtf8(b"\xff"), None);
+    }
+
+    #[test]
+    fn test_hash_cache() {
+        let store = SqliteStore::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let path = Path::new("/fonts/Inter.ttf");
+        let modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+
+        assert_eq!(store.cached_hash(path, 10, modified), None);
+        store.remember_hash(path, 10, modified, "abc");
+        assert_eq!(store.cached_hash(path, 10, modified).as_deref(), Some("abc"));
+        // Any change to the file invalidates the entry
+        assert_eq!(store.cached_hash(path, 11, modified), None);
+        assert_eq!(store.cached_hash(path, 10, modified + std::time::Duration::from_secs(1)), None);
+        assert_eq!(store.clear_hash_cache().unwrap(), 1);
+        assert_eq!(store.cached_hash(path, 10, modified), None);
+    }
+}
@@ -0,0 +1,172 @@
+//! Per-run statistics kept in the history, so `stats --trend` can show how a directory
+//! evolves from one run to the next.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path};
+use serde::{Deserialize, Serialize};
+
+use super::file_types::{self, FileCategory, FileTypeClassifier};
+use super::{FileOperation, FileOutcome};
+
+/// What one run moved
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunStats {
+    pub files: usize,
+    pub total_bytes: u64,
+    /// Files of no known type (the ones that end up in "Other" when organizing by extension)
+    pub other_files: usize,
+    /// Files per top-level destination folder
+    pub categories: BTreeMap<String, usize>,
+    /// The files of no known type by extension (`""` for none), missing in older journals
+    #[serde(default)]
+    pub other_extensions: BTreeMap<String, ExtensionStats>,
+}
+
+/// How many files of one extension there were, and their size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtensionStats {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl RunStats {
+    /// Statistics of the successful moves among `outcomes`, made inside `target_dir`
+    pub fn from_outcomes(target_dir: &Path, classifier: &FileTypeClassifier, outcomes: &[FileOutcome]) -> Self {
+        let mut stats = Self::default();
+        for outcome in outcomes.iter().filter(|outcome| outcome.succeeded()) {
+            let operation = &outcome.operation;
+            stats.files += 1;
+            // Already moved, so the size is read at the destination
+            stats.total_bytes += fs::metadata(&operation.destination).map(|m| m.len()).unwrap_or(0);
+            if let Some(folder) = category_folder(target_dir, &operation.source, &operation.destination) {
+                *stats.categories.entry(folder).or_insert(0) += 1;
+            }
+        }
+        let moved: Vec<&FileOperation> =
+            outcomes.iter().filter(|outcome| outcome.succeeded()).map(|outcome| &outcome.operation).collect();
+        stats.other_extensions = other_extensions(classifier, moved);
+        stats.other_files = stats.other_extensions.values().map(|extension| extension.files).sum();
+        stats
+    }
+
+    /// [`RunStats::other_extensions`] with the most common first, then the biggest
+    pub fn other_by_extension(&self) -> Vec<(&str, ExtensionStats)> {
+        by_count(&self.other_extensions)
+    }
+
+    /// The `n` folders that received the most files, most first
+    pub fn top_categories(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut categories: Vec<(&str, usize)> =
+            self.categories.iter().map(|(name, count)| (name.as_str(), *count)).collect();
+        categories.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        categories.truncate(n);
+        categories
+    }
+}
+
+/// The operations whose files are of no known type, by extension (`""` for none). Sizes are
+/// read wherever the file is now, before or after the move.
+pub fn other_extensions<'a>(
+    classifier: &FileTypeClassifier,
+    operations: impl IntoIterator<Item = &'a FileOperation>,
+) -> BTreeMap<String, ExtensionStats> {
+    let mut extensions: BTreeMap<String, ExtensionStats> = BTreeMap::new();
+    for operation in operations {
+        if classifier.classify(&operation.source) != FileCategory::Other {
+            continue;
+        }
+        let extension = extensions.entry(file_types::file_extension(&operation.source).unwrap_or_default()).or_default();
+        extension.files += 1;
+        extension.bytes += fs::metadata(&operation.destination)
+            .or_else(|_| fs::metadata(&operation.source))
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+    }
+    extensions
+}
+
+/// Extensions with the most files first, then the biggest, then by name
+pub fn by_count(extensions: &BTreeMap<String, ExtensionStats>) -> Vec<(&str, ExtensionStats)> {
+    let mut sorted: Vec<(&str, ExtensionStats)> =
+        extensions.iter().map(|(extension, stats)| (extension.as_str(), *stats)).collect();
+    sorted.sort_by(|a, b| b.1.files.cmp(&a.1.files).then(b.1.bytes.cmp(&a.1.bytes)).then(a.0.cmp(b.0)));
+    sorted
+}
+
+/// The folder a move put its file into, relative to the directory the file came from
+/// (`--per-directory` runs make category folders inside each subdirectory)
+pub(super) fn category_folder(target_dir: &Path, source: &Path, destination: &Path) -> Option<String> {
+    let base = source.parent().filter(|parent| destination.starts_with(parent)).unwrap_or(target_dir);
+    let relative = destination.strip_prefix(base).ok()?;
+    if relative.components().count() < 2 {
+        return None;
+    }
+    match relative.components().next()? {
+        Component::Normal(folder) => Some(folder.to_string_lossy().to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{FileOperation, OperationType};
+
+    #[test]
+    fn test_run_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let mut outcomes = Vec::new();
+        for (source, destination, contents) in [
+            ("a.jpg", "Images/a.jpg", "12345"),
+            ("b.png", "Images/b.png", "12"),
+            ("project/setup.zip", "project/Archives/setup.zip", "1"),
+            ("blob.xyz", "Other/blob.xyz", ""),
+        ] {
+            let destination = root.join(destination);
+            fs::create_dir_all(destination.parent().unwrap()).unwrap();
+            fs::write(&destination, contents).unwrap();
+            outcomes.push(FileOutcome {
+                operation: FileOperation {
+                    source: root.join(source),
+                    destination,
+                    operation_type: OperationType::Move,
+                    size: 0,
+                },
+                error: None,
+                skipped: false,
+                rolled_back: false,
+            });
+        }
+        outcomes[1].skipped = true;
+
+        let stats = RunStats::from_outcomes(root, &FileTypeClassifier::new(), &outcomes);
+        assert_eq!(stats.files, 3);
+        assert_eq!(stats.total_bytes, 6);
+        assert_eq!(stats.other_files, 1);
+        assert_eq!(stats.top_categories(2), [("Archives", 1), ("Images", 1)]);
+        assert_eq!(stats.categories.len(), 3);
+    }
+
+    #[test]
+    fn test_other_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let mut operations = Vec::new();
+        for (name, contents) in [("photo.jpg", "1"), ("a.xyz", "12"), ("b.xyz", "34"), ("README", "1234"), ("c.abc", "1")] {
+            fs::write(root.join(name), contents).unwrap();
+            operations.push(FileOperation {
+                source: root.join(name),
+                destination: root.join("Other").join(name),
+                operation_type: OperationType::Move,
+                size: 0,
+            });
+        }
+
+        // Not moved yet, so the sizes come from the sources
+        let extensions = other_extensions(&FileTypeClassifier::new(), &operations);
+        let extension = |files, bytes| ExtensionStats { files, bytes };
+        assert_eq!(by_count(&extensions), [("xyz", extension(2, 4)), ("", extension(1, 4)), ("abc", extension(1, 1))]);
+    }
+}
@@ -0,0 +1,525 @@
+//! Where undo history and caches live.
+//!
+//! The default [`JsonStore`] keeps one journal file per run inside each organized directory,
+//! plus an index of those directories and a cache of file hashes in the user's data directory.
+//! With the `sqlite` feature, `[history] store = "sqlite"` switches to a single database for
+//! every directory, which stays fast with thousands of runs.
+//!
+//! Cached hashes are keyed by the file's path, size and modification time, so a file that
+//! changed in any way is hashed again.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::config::{HistoryBackend, HistoryConfig};
+use super::durable;
+use super::error::{Context, OrganizerError, Result};
+use super::journal::{self, Journal};
+
+/// Storage for journals, keyed by the directory they belong to
+pub trait HistoryStore: Send {
+    /// Every journal for `target_dir`, oldest first
+    fn journals(&self, target_dir: &Path) -> Result<Vec<Journal>>;
+
+    /// Store a journal, replacing any with the same id
+    fn save(&self, target_dir: &Path, journal: &Journal) -> Result<()>;
+
+    fn remove(&self, target_dir: &Path, id: &str) -> Result<()>;
+
+    /// Make sure a saved journal survives a power loss, for durable runs
+    fn sync(&self, _target_dir: &Path, _id: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Every directory with history, as it was recorded (canonical paths)
+    fn directories(&self) -> Result<Vec<PathBuf>>;
+
+    fn latest(&self, target_dir: &Path) -> Result<Option<Journal>> {
+        Ok(self.journals(target_dir)?.pop())
+    }
+
+    /// Give a new journal the next free id and store it, never replacing another journal
+    fn insert(&self, target_dir: &Path, journal: &mut Journal) -> Result<()> {
+        let existing = self.journals(target_dir)?;
+        journal.id = journal.next_id(existing.iter().map(|journal| journal.id.as_str()));
+        if existing.iter().any(|other| other.id == journal.id) {
+            return Err(OrganizerError::journal(format!("Operation {} is already in the history", journal.id)));
+        }
+        self.save(target_dir, journal)
+    }
+
+    /// Hash of a file from an earlier run, if the file hasn't changed since
+    fn cached_hash(&self, _path: &Path, _size: u64, _modified: SystemTime) -> Option<String> {
+        None
+    }
+
+    fn remember_hash(&self, _path: &Path, _size: u64, _modified: SystemTime, _hash: &str) {}
+
+    /// Forget every cached hash, returning how many there were
+    fn clear_hash_cache(&self) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+/// Journals as JSON files in `<directory>/.file-organizer/history/`
+pub struct JsonStore {
+    /// File listing every directory with journals; `None` keeps no index
+    index: Option<PathBuf>,
+    /// Refuse every change, for analysis runs
+    read_only: bool,
+    /// File the hash cache is kept in; `None` keeps no cache
+    hash_cache: Option<PathBuf>,
+    /// The hash cache, read when it's first needed and written back when the store is dropped
+    hashes: Mutex<Option<HashCache>>,
+}
+
+/// Hashes of files as they were when hashed, by path
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HashCache {
+    version: u32,
+    hashes: HashMap<String, CachedHash>,
+    /// Changed since it was read
+    #[serde(skip)]
+    dirty: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedHash {
+    size: u64,
+    modified_ns: u128,
+    hash: String,
+}
+
+/// The index of directories kept next to the SQLite database's default location
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DirectoryIndex {
+    version: u32,
+    directories: Vec<PathBuf>,
+}
+
+impl JsonStore {
+    pub fn new(index: Option<PathBuf>) -> Self {
+        Self { index, read_only: false, hash_cache: None, hashes: Mutex::new(None) }
+    }
+
+    /// A store that reads journals and the index but never writes
+    pub fn read_only(index: Option<PathBuf>) -> Self {
+        Self { index, read_only: true, hash_cache: None, hashes: Mutex::new(None) }
+    }
+
+    /// Cache file hashes in `hash_cache` (read-only stores use the cache but don't update it)
+    pub fn with_hash_cache(mut self, hash_cache: Option<PathBuf>) -> Self {
+        self.hash_cache = hash_cache;
+        self
+    }
+
+    /// Run `f` on the hash cache, reading it first if needed; `None` without a cache file
+    fn with_hashes<T>(&self, f: impl FnOnce(&mut HashCache) -> T) -> Option<T> {
+        let path = self.hash_cache.as_ref()?;
+        let mut hashes = self.hashes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let hashes = hashes.get_or_insert_with(|| {
+            // A cache that can't be read is only a slower run
+            let cache = fs::read_to_string(path).ok().and_then(|contents| serde_json::from_str(&contents).ok());
+            cache.unwrap_or(HashCache { version: 1, ..HashCache::default() })
+        });
+        Some(f(hashes))
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(OrganizerError::journal("The history is read-only in analysis mode"));
+        }
+        Ok(())
+    }
+
+    fn path(target_dir: &Path, id: &str) -> PathBuf {
+        journal::history_dir(target_dir).join(format!("{}.json", id))
+    }
+
+    fn read_index(index: &Path) -> Result<DirectoryIndex> {
+        if !index.exists() {
+            return Ok(DirectoryIndex { version: 1, directories: Vec::new() });
+        }
+        let contents = fs::read_to_string(index).journal_context(format!("Failed to read {}", index.display()))?;
+        serde_json::from_str(&contents).journal_context(format!("Invalid directory index {}", index.display()))
+    }
+
+    fn register(&self, target_dir: &Path) -> Result<()> {
+        let Some(index) = &self.index else {
+            return Ok(());
+        };
+        let directory = target_dir.canonicalize().unwrap_or_else(|_| target_dir.to_path_buf());
+        let mut contents = Self::read_index(index)?;
+        if contents.directories.contains(&directory) {
+            return Ok(());
+        }
+
+        contents.directories.push(directory);
+        if let Some(parent) = index.parent() {
+            fs::create_dir_all(parent).journal_context(format!("Failed to create {}", parent.display()))?;
+        }
+        let contents = serde_json::to_string_pretty(&contents).journal_context("Failed to serialize the directory index")?;
+        fs::write(index, contents).journal_context(format!("Failed to write {}", index.display()))
+    }
+}
+
+impl HistoryStore for JsonStore {
+    fn journals(&self, target_dir: &Path) -> Result<Vec<Journal>> {
+        journal::journal_files(target_dir)?
+            .iter()
+            .map(|path| Journal::load(path))
+            .collect()
+    }
+
+    fn save(&self, target_dir: &Path, journal: &Journal) -> Result<()> {
+        self.check_writable()?;
+        let dir = journal::history_dir(target_dir);
+        fs::create_dir_all(&dir).journal_context(format!("Failed to create {}", dir.display()))?;
+        journal.write(&Self::path(target_dir, &journal.id))?;
+        // Losing the index only hides the directory from `history --global`
+        if let Err(e) = self.register(target_dir) {
+            log::warn!("Couldn't add {} to the history index: {:#}", target_dir.display(), e);
+        }
+        Ok(())
+    }
+
+    fn sync(&self, target_dir: &Path, id: &str) -> Result<()> {
+        let path = Self::path(target_dir, id);
+        durable::sync_file(&path).journal_context(format!("Failed to flush journal {}", path.display()))?;
+        let dir = journal::history_dir(target_dir);
+        durable::sync_dir(&dir).journal_context(format!("Failed to flush {}", dir.display()))
+    }
+
+    fn remove(&self, target_dir: &Path, id: &str) -> Result<()> {
+        self.check_writable()?;
+        let path = Self::path(target_dir, id);
+        fs::remove_file(&path).journal_context(format!("Failed to remove journal {}", path.display()))
+    }
+
+    fn directories(&self) -> Result<Vec<PathBuf>> {
+        match &self.index {
+            Some(index) => Ok(Self::read_index(index)?.directories),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn cached_hash(&self, path: &Path, size: u64, modified: SystemTime) -> Option<String> {
+        let modified_ns = modified.duration_since(SystemTime::UNIX_EPOCH).ok()?.as_nanos();
+        self.with_hashes(|cache| {
+            let cached = cache.hashes.get(path.to_str()?)?;
+            (cached.size == size && cached.modified_ns == modified_ns).then(|| cached.hash.clone())
+        })
+        .flatten()
+    }
+
+    fn remember_hash(&self, path: &Path, size: u64, modified: SystemTime, hash: &str) {
+        let (Some(path), Ok(since_epoch)) = (path.to_str(), modified.duration_since(SystemTime::UNIX_EPOCH)) else {
+            return;
+        };
+        self.with_hashes(|cache| {
+            let cached = CachedHash { size, modified_ns: since_epoch.as_nanos(), hash: hash.to_string() };
+            cache.hashes.insert(path.to_string(), cached);
+            cache.dirty = true;
+        });
+    }
+
+    fn clear_hash_cache(&self) -> Result<usize> {
+        self.check_writable()?;
+        let Some(path) = &self.hash_cache else {
+            return Ok(0);
+        };
+        let cleared = self.with_hashes(|cache| std::mem::take(cache).hashes.len()).unwrap_or(0);
+        if path.exists() {
+            fs::remove_file(path).journal_context(format!("Failed to remove {}", path.display()))?;
+        }
+        Ok(cleared)
+    }
+}
+
+impl Drop for JsonStore {
+    fn drop(&mut self) {
+        let (Some(path), Some(cache)) = (&self.hash_cache, self.hashes.get_mut().ok().and_then(Option::as_mut)) else {
+            return;
+        };
+        if !cache.dirty || self.read_only {
+            return;
+        }
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(path, serde_json::to_string(cache).unwrap_or_default()));
+        if let Err(e) = written {
+            log::debug!("Couldn't save the hash cache to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Open the store the configuration asks for
+pub fn open(config: &HistoryConfig) -> Result<Box<dyn HistoryStore>> {
+    match config.store {
+        // Unit tests must not touch the user's data directory
+        HistoryBackend::Json if cfg!(test) => Ok(Box::new(JsonStore::new(None))),
+        HistoryBackend::Json => Ok(Box::new(JsonStore::new(index_path()).with_hash_cache(hash_cache_path()))),
+        #[cfg(feature = "sqlite")]
+        HistoryBackend::Sqlite => {
+            let path = match &config.database {
+                Some(path) => path.clone(),
+                None => default_database_path()
+                    .ok_or_else(|| OrganizerError::journal("Can't find a data directory for the history database"))?,
+            };
+            Ok(Box::new(super::sqlite_store::SqliteStore::open(&path)?))
+        }
+        #[cfg(not(feature = "sqlite"))]
+        HistoryBackend::Sqlite => Err(OrganizerError::journal("The SQLite history store needs a build with the `sqlite` feature")),
+    }
+}
+
+/// Open the configured store for reading only: nothing is created or changed on disk, and
+/// saving or removing journals fails
+pub fn open_read_only(config: &HistoryConfig) -> Result<Box<dyn HistoryStore>> {
+    match config.store {
+        HistoryBackend::Json if cfg!(test) => Ok(Box::new(JsonStore::read_only(None))),
+        HistoryBackend::Json => Ok(Box::new(JsonStore::read_only(index_path()).with_hash_cache(hash_cache_path()))),
+        #[cfg(feature = "sqlite")]
+        HistoryBackend::Sqlite => {
+            let path = match &config.database {
+                Some(path) => path.clone(),
+                None => default_database_path()
+                    .ok_or_else(|| OrganizerError::journal("Can't find a data directory for the history database"))?,
+            };
+            Ok(Box::new(super::sqlite_store::SqliteStore::open_read_only(&path)?))
+        }
+        #[cfg(not(feature = "sqlite"))]
+        HistoryBackend::Sqlite => Err(OrganizerError::journal("The SQLite history store needs a build with the `sqlite` feature")),
+    }
+}
+
+fn index_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("file-organizer").join("directories.json"))
+}
+
+fn hash_cache_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("file-organizer").join("hashes.json"))
+}
+
+/// `~/.local/share/file-organizer/history.sqlite3` (`$XDG_DATA_HOME` and `%LOCALAPPDATA%`
+/// are respected)
+pub fn default_database_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("file-organizer").join("history.sqlite3"))
+}
+
+pub(crate) fn data_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    if cfg!(windows) {
+        if let Some(dir) = std::env::var_os("LOCALAPPDATA") {
+            return Some(PathBuf::from(dir));
+        }
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+}
+
+/// Which runs `history --global` shows
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    /// Only directories inside this one
+    pub within: Option<PathBuf>,
+    pub since: Option<DateTime<Utc>>,
+    /// Only runs before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Organize mode as recorded in journals (`Extension`, `Date`, ...), compared ignoring case
+    pub mode: Option<String>,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, journal: &Journal) -> bool {
+        self.since.is_none_or(|since| journal.created_at >= since)
+            && self.until.is_none_or(|until| journal.created_at < until)
+            && self.mode.as_ref().is_none_or(|mode| journal.mode.eq_ignore_ascii_case(mode))
+    }
+}
+
+/// Runs in every directory the store knows about, newest first.
+///
+/// Directories that were deleted or whose history can't be read are left out.
+pub fn global_history(store: &dyn HistoryStore, filter: &HistoryFilter) -> Result<Vec<(PathBuf, Journal)>> {
+    let within = filter.within.as_ref().map(|dir| dir.canonicalize().unwrap_or_else(|_| dir.clone()));
+    let mut runs = Vec::new();
+    for directory in store.directories()? {
+        if within.as_ref().is_some_and(|within| !directory.starts_with(within)) {
+            continue;
+        }
+        let journals = match store.journals(&directory) {
+            Ok(journals) => journals,
+            Err(e) => {
+                log::debug!("Skipping history of {}: {:#}", directory.display(), e);
+                continue;
+            }
+        };
+        runs.extend(
+            journals
+                .into_iter()
+                .filter(|journal| filter.matches(journal))
+                .map(|journal| (directory.clone(), journal)),
+        );
+    }
+    runs.sort_by_key(|(_, journal)| std::cmp::Reverse(journal.created_at));
+    Ok(runs)
+}
+
+/// Version of the export file layout (the journals inside carry their own versions)
+const EXPORT_VERSION: u32 = 1;
+
+/// Every journal of a directory, in a file that any store can import
+#[derive(Debug, Serialize, Deserialize)]
+struct Export {
+    version: u32,
+    journals: Vec<serde_json::Value>,
+}
+
+/// Write every journal for `target_dir` to `file` as JSON, returning how many there were
+pub fn export(store: &dyn HistoryStore, target_dir: &Path, file: &Path) -> Result<usize> {
+    let journals = store.journals(target_dir)?;
+    let export = Export {
+        version: EXPORT_VERSION,
+        journals: journals
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<_, _>>()
+            .journal_context("Failed to serialize the history")?,
+    };
+    let contents = serde_json::to_string_pretty(&export).journal_context("Failed to serialize the history")?;
+    fs::write(file, contents)
+        .journal_context(format!("Failed to write {}", file.display()))?;
+    Ok(journals.len())
+}
+
+/// Add the journals in an export file to `target_dir`'s history, returning how many were new.
+///
+/// Journals whose id is already in the store are left alone, so importing twice is harmless.
+pub fn import(store: &dyn HistoryStore, target_dir: &Path, file: &Path) -> Result<usize> {
+    let contents = fs::read_to_string(file).journal_context(format!("Failed to read {}", file.display()))?;
+    let export: Export = serde_json::from_str(&contents).journal_context(format!("Invalid history export {}", file.display()))?;
+    if export.version > EXPORT_VERSION {
+        return Err(OrganizerError::journal(format!("{} was exported by a newer version of file-organizer", file.display())));
+    }
+
+    let mut journals = Vec::with_capacity(export.journals.len());
+    for value in export.journals {
+        let (journal, _) = journal::upgrade(value).journal_context(format!("Invalid history export {}", file.display()))?;
+        journals.push(journal);
+    }
+
+    let existing: Vec<String> = store.journals(target_dir)?.into_iter().map(|journal| journal.id).collect();
+    let mut imported = 0;
+    for journal in journals.iter().filter(|journal| !existing.contains(&journal.id)) {
+        store.save(target_dir, journal)?;
+        imported += 1;
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use chrono::{Duration, Utc};
+    use super::super::journal::JournalEntry;
+    use super::super::stats::RunStats;
+
+    fn journal(mode: &str) -> Journal {
+        Journal {
+            version: journal::JOURNAL_VERSION,
+            id: String::new(),
+            created_at: Utc::now(),
+            mode: mode.to_string(),
+            moves: vec![JournalEntry { source: PathBuf::from("a.jpg"), destination: PathBuf::from("Images/a.jpg") }],
+            stats: Some(RunStats {
+                files: 1,
+                total_bytes: 3,
+                other_files: 0,
+                categories: [("Images".to_string(), 1)].into(),
+                other_extensions: Default::default(),
+            }),
+        }
+    }
+
+    /// The behavior every store has to provide
+    pub(in super::super) fn check_store(store: &dyn HistoryStore, root: &Path) {
+        assert!(store.latest(root).unwrap().is_none());
+
+        let mut first = journal("Extension");
+        store.insert(root, &mut first).unwrap();
+        let mut second = journal("Size");
+        store.insert(root, &mut second).unwrap();
+        assert!(first.id.ends_with("-0001"), "{}", first.id);
+        assert!(second.id.ends_with("-0002"), "{}", second.id);
+        assert_eq!(store.latest(root).unwrap().as_ref(), Some(&second));
+
+        second.moves.clear();
+        // Names that aren't valid UTF-8 come back as the same files
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStringExt;
+            let latin1 = PathBuf::from(std::ffi::OsString::from_vec(b"caf\xe9.jpg".to_vec()));
+            second.moves.push(JournalEntry { destination: Path::new("Images").join(&latin1), source: latin1 });
+        }
+        store.save(root, &second).unwrap();
+        assert_eq!(store.journals(root).unwrap(), [first.clone(), second.clone()]);
+
+        // Export, clear, import
+        let export_file = root.join("export.json");
+        assert_eq!(export(store, root, &export_file).unwrap(), 2);
+        store.remove(root, &second.id).unwrap();
+        assert_eq!(store.journals(root).unwrap(), [first.clone()]);
+        assert_eq!(import(store, root, &export_file).unwrap(), 1);
+        assert_eq!(import(store, root, &export_file).unwrap(), 0);
+        assert_eq!(store.journals(root).unwrap(), [first, second]);
+    }
+
+    #[test]
+    fn test_json_store() {
+        let dir = tempfile::tempdir().unwrap();
+        check_store(&JsonStore::new(None), dir.path());
+        assert!(journal::history_dir(dir.path()).is_dir());
+
+        let read_only = JsonStore::read_only(None);
+        assert_eq!(read_only.journals(dir.path()).unwrap().len(), 2);
+        assert!(read_only.insert(dir.path(), &mut journal("Extension")).is_err());
+        assert_eq!(read_only.journals(dir.path()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_global_history() {
+        let home = tempfile::tempdir().unwrap();
+        let store = JsonStore::new(Some(home.path().join("data/directories.json")));
+        let downloads = home.path().join("Downloads");
+        let photos = home.path().join("Pictures/Import");
+        fs::create_dir_all(&downloads).unwrap();
+        fs::create_dir_all(&photos).unwrap();
+
+        let mut old = journal("Extension");
+        old.created_at = Utc::now() - Duration::days(10);
+        store.insert(&downloads, &mut old).unwrap();
+        let mut recent = journal("Date");
+        store.insert(&photos, &mut recent).unwrap();
+        store.insert(&photos, &mut journal("Date")).unwrap();
+        assert_eq!(store.directories().unwrap().len(), 2);
+
+        let all = global_history(&store, &HistoryFilter::default()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all.last().unwrap().1, old);
+
+        let filter = |filter: HistoryFilter| global_history(&store, &filter).unwrap().len();
+        assert_eq!(filter(HistoryFilter { mode: Some("date".to_string()), ..HistoryFilter::default() }), 2);
+        assert_eq!(filter(HistoryFilter { since: Some(Utc::now() - Duration::days(1)), ..HistoryFilter::default() }), 2);
+        assert_eq!(filter(HistoryFilter { until: Some(Utc::now() - Duration::days(1)), ..HistoryFilter::default() }), 1);
+        assert_eq!(filter(HistoryFilter { within: Some(home.path().join("Pictures")), ..HistoryFilter::default() }), 2);
+    }
+}
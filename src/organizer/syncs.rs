@@ -0,0 +1,109 @@
+//! Sync clients writing into a directory. Syncthing downloads into `.syncthing.NAME.tmp`
+//! (`~syncthing~NAME.tmp` on Windows) and renames it when complete, Resilio Sync into
+//! `NAME.!sync`, and Dropbox stages downloads in `.dropbox.cache` at the root of the Dropbox
+//! folder. Moving files while a sync engine is at work makes it re-download them or sprout
+//! conflict copies, so its temporary files are never organized and `watch` waits until they
+//! have been quiet for a while.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use walkdir::WalkDir;
+
+/// Folder Dropbox stages downloads and deletions in, at the root of the Dropbox folder
+const DROPBOX_CACHE: &str = ".dropbox.cache";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncClient {
+    Syncthing,
+    Dropbox,
+    Resilio,
+}
+
+impl fmt::Display for SyncClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SyncClient::Syncthing => "Syncthing",
+            SyncClient::Dropbox => "Dropbox",
+            SyncClient::Resilio => "Resilio Sync",
+        })
+    }
+}
+
+/// The latest sign of a sync client writing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncActivity {
+    pub client: SyncClient,
+    /// The temporary file (or Dropbox's cache folder) that changed
+    pub path: PathBuf,
+    pub modified: SystemTime,
+}
+
+/// The sync client a temporary file belongs to, if its name is one they download into
+pub fn temp_file_client(name: &str) -> Option<SyncClient> {
+    if (name.starts_with(".syncthing.") || name.starts_with("~syncthing~")) && name.ends_with(".tmp") {
+        Some(SyncClient::Syncthing)
+    } else if name.ends_with(".!sync") {
+        Some(SyncClient::Resilio)
+    } else {
+        None
+    }
+}
+
+/// The most recent sync activity in `dir` (and its subdirectories if `recursive`) since
+/// `since`: a sync client's temporary file changed, or Dropbox's cache folder did. Leftover
+/// temporary files of a sync that crashed long ago don't count.
+pub fn activity_since(dir: &Path, recursive: bool, since: SystemTime) -> Option<SyncActivity> {
+    let walker = WalkDir::new(dir).min_depth(1).max_depth(if recursive { usize::MAX } else { 1 });
+    let temp_files = walker.into_iter().filter_map(Result::ok).filter_map(|entry| {
+        let client = temp_file_client(&entry.file_name().to_string_lossy())?;
+        let modified = entry.metadata().ok()?.modified().ok()?;
+        Some(SyncActivity { client, path: entry.into_path(), modified })
+    });
+    // The watched directory may be anywhere inside the Dropbox folder
+    let dropbox = dir.ancestors().map(|ancestor| ancestor.join(DROPBOX_CACHE)).find(|cache| cache.is_dir()).and_then(|cache| {
+        let modified = cache.metadata().ok()?.modified().ok()?;
+        Some(SyncActivity { client: SyncClient::Dropbox, path: cache, modified })
+    });
+
+    temp_files.chain(dropbox).filter(|activity| activity.modified >= since).max_by_key(|activity| activity.modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Duration;
+
+    #[test]
+    fn test_temp_file_client() {
+        assert_eq!(temp_file_client(".syncthing.report.pdf.tmp"), Some(SyncClient::Syncthing));
+        assert_eq!(temp_file_client("~syncthing~report.pdf.tmp"), Some(SyncClient::Syncthing));
+        assert_eq!(temp_file_client("video.mp4.!sync"), Some(SyncClient::Resilio));
+        assert_eq!(temp_file_client("report.pdf"), None);
+        assert_eq!(temp_file_client("scratch.tmp"), None);
+    }
+
+    #[test]
+    fn test_activity_since() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("photo.jpg"), b"jpg").unwrap();
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        assert_eq!(activity_since(dir.path(), false, an_hour_ago), None);
+
+        let temp = dir.path().join(".syncthing.movie.mkv.tmp");
+        fs::write(&temp, b"partial").unwrap();
+        let activity = activity_since(dir.path(), false, an_hour_ago).unwrap();
+        assert_eq!((activity.client, activity.path), (SyncClient::Syncthing, temp.clone()));
+
+        // Left over from a sync long gone
+        fs::File::options().write(true).open(&temp).unwrap().set_modified(an_hour_ago - Duration::from_secs(60)).unwrap();
+        assert_eq!(activity_since(dir.path(), false, an_hour_ago), None);
+
+        fs::create_dir(dir.path().join(DROPBOX_CACHE)).unwrap();
+        fs::create_dir(dir.path().join("Camera Uploads")).unwrap();
+        let activity = activity_since(&dir.path().join("Camera Uploads"), false, an_hour_ago).unwrap();
+        assert_eq!(activity.client, SyncClient::Dropbox);
+    }
+}
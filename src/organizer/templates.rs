@@ -0,0 +1,197 @@
+//! Folder templates such as `{year}/{quarter}` that turn a file's date into a destination.
+//!
+//! Placeholders:
+//!
+//! | Placeholder   | Example | Meaning                                  |
+//! |---------------|---------|------------------------------------------|
+//! | `{year}`      | `2024`  | calendar year                            |
+//! | `{quarter}`   | `Q2`    | calendar quarter                         |
+//! | `{month}`     | `06`    | month number                             |
+//! | `{day}`       | `01`    | day of the month                         |
+//! | `{week}`      | `W23`   | ISO week number                          |
+//! | `{week_year}` | `2024`  | ISO week-numbering year (use with week)  |
+//!
+//! `/` starts a subfolder and `{{` / `}}` are literal braces.
+
+use std::fmt;
+use std::path::PathBuf;
+use chrono::{Datelike, NaiveDateTime};
+
+use super::error::{OrganizerError, Result};
+use super::sanitize_folder_name;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    Year,
+    Quarter,
+    Month,
+    Day,
+    Week,
+    WeekYear,
+}
+
+impl Placeholder {
+    const ALL: [(&'static str, Placeholder); 6] = [
+        ("year", Placeholder::Year),
+        ("quarter", Placeholder::Quarter),
+        ("month", Placeholder::Month),
+        ("day", Placeholder::Day),
+        ("week", Placeholder::Week),
+        ("week_year", Placeholder::WeekYear),
+    ];
+
+    fn render(self, date: &NaiveDateTime) -> String {
+        match self {
+            Placeholder::Year => format!("{:04}", date.year()),
+            Placeholder::Quarter => format!("Q{}", date.month0() / 3 + 1),
+            Placeholder::Month => format!("{:02}", date.month()),
+            Placeholder::Day => format!("{:02}", date.day()),
+            Placeholder::Week => format!("W{:02}", date.iso_week().week()),
+            Placeholder::WeekYear => format!("{:04}", date.iso_week().year()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+    Separator,
+}
+
+/// A parsed folder template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FolderTemplate {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl FolderTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(OrganizerError::config(format!("Unclosed '{{' in folder template '{}'", template))),
+                        }
+                    }
+                    let Some(&(_, placeholder)) = Placeholder::ALL.iter().find(|(known, _)| *known == name.trim()) else {
+                        let known: Vec<&str> = Placeholder::ALL.iter().map(|(known, _)| *known).collect();
+                        return Err(OrganizerError::config(format!(
+                            "Unknown placeholder '{{{}}}' in folder template '{}' (expected one of: {})",
+                            name,
+                            template,
+                            known.join(", ")
+                        )));
+                    };
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Placeholder(placeholder));
+                }
+                '}' => return Err(OrganizerError::config(format!("Unmatched '}}' in folder template '{}' (write '}}}}' for a literal brace)", template))),
+                '/' | '\\' => {
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Separator);
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        if !segments.iter().any(|segment| matches!(segment, Segment::Literal(_) | Segment::Placeholder(_))) {
+            return Err(OrganizerError::config(format!("Folder template '{}' is empty", template)));
+        }
+
+        Ok(Self {
+            source: template.to_string(),
+            segments,
+        })
+    }
+
+    /// Relative folder for a date; every component is sanitized, and empty, `.` and `..`
+    /// components are dropped
+    pub fn render(&self, date: &NaiveDateTime) -> PathBuf {
+        let mut path = PathBuf::new();
+        let mut component = String::new();
+        let push = |component: &mut String, path: &mut PathBuf| {
+            if !component.trim().trim_matches('.').trim().is_empty() {
+                path.push(sanitize_folder_name(component));
+            }
+            component.clear();
+        };
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => component.push_str(text),
+                Segment::Placeholder(placeholder) => component.push_str(&placeholder.render(date)),
+                Segment::Separator => push(&mut component, &mut path),
+            }
+        }
+        push(&mut component, &mut path);
+        if path.as_os_str().is_empty() {
+            path.push("Unknown");
+        }
+        path
+    }
+}
+
+impl fmt::Display for FolderTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(9, 30, 0).unwrap()
+    }
+
+    fn render(template: &str, date: NaiveDateTime) -> PathBuf {
+        FolderTemplate::parse(template).unwrap().render(&date)
+    }
+
+    #[test]
+    fn test_render_template() {
+        assert_eq!(render("{year}/{quarter}", date(2024, 6, 1)), PathBuf::from("2024/Q2"));
+        assert_eq!(render("{year}-{month}-{day}", date(2024, 6, 1)), PathBuf::from("2024-06-01"));
+        assert_eq!(render("📅 {week_year}-{week}", date(2024, 6, 5)), PathBuf::from("📅 2024-W23"));
+        // 2024-12-30 belongs to ISO week 1 of 2025
+        assert_eq!(render("{week_year}-{week}", date(2024, 12, 30)), PathBuf::from("2025-W01"));
+        assert_eq!(render("Taxes {{{year}}}//../x:y", date(2023, 1, 1)), PathBuf::from("Taxes {2023}/x_y"));
+    }
+
+    #[test]
+    fn test_template_errors() {
+        let error = |template: &str| FolderTemplate::parse(template).unwrap_err().to_string();
+        assert!(error("{year").contains("Unclosed"));
+        assert!(error("{decade}").contains("Unknown placeholder '{decade}'"));
+        assert!(error("year}").contains("Unmatched"));
+        assert!(error("//").contains("empty"));
+        assert_eq!(render("../..", date(2023, 1, 1)), PathBuf::from("Unknown"));
+    }
+}
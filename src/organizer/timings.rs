@@ -0,0 +1,58 @@
+//! How long each phase of a run took, and how fast files went through, so slow scans of
+//! network shares can be told apart from slow moves.
+
+use serde::{Deserialize, Serialize};
+
+/// Seconds per phase, and what the run got through
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PhaseTimings {
+    /// Listing the directory and reading file attributes
+    pub scan_secs: f64,
+    /// Working out destinations: classification, rules, hashing fonts, conflict checks
+    pub plan_secs: f64,
+    /// Creating folders and moving files; 0 for a dry run
+    pub execute_secs: f64,
+    /// Files moved, or planned in a dry run
+    pub files: usize,
+    /// Their total size
+    pub bytes: u64,
+}
+
+impl PhaseTimings {
+    pub fn total_secs(&self) -> f64 {
+        self.scan_secs + self.plan_secs + self.execute_secs
+    }
+
+    /// Files per second through execution, or through scanning and planning in a dry run;
+    /// `None` when that took no measurable time
+    pub fn files_per_sec(&self) -> Option<f64> {
+        self.rate(self.files as f64)
+    }
+
+    /// Bytes per second, measured like [`PhaseTimings::files_per_sec`]
+    pub fn bytes_per_sec(&self) -> Option<f64> {
+        self.rate(self.bytes as f64)
+    }
+
+    fn rate(&self, amount: f64) -> Option<f64> {
+        let secs = if self.execute_secs > 0.0 { self.execute_secs } else { self.scan_secs + self.plan_secs };
+        (secs > 0.0).then(|| amount / secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rates() {
+        let run = PhaseTimings { scan_secs: 1.0, plan_secs: 1.0, execute_secs: 4.0, files: 100, bytes: 8_000 };
+        assert_eq!(run.total_secs(), 6.0);
+        assert_eq!(run.files_per_sec(), Some(25.0));
+        assert_eq!(run.bytes_per_sec(), Some(2_000.0));
+
+        let dry_run = PhaseTimings { execute_secs: 0.0, ..run };
+        assert_eq!(dry_run.files_per_sec(), Some(50.0));
+        assert_eq!(PhaseTimings::default().files_per_sec(), None);
+    }
+}
@@ -0,0 +1,213 @@
+//! Deleting files for `delete` rules. Unless a deletion is permanent, the file goes to the
+//! trash, where it can be restored from: the freedesktop.org trash on Linux and other unixes
+//! (`~/.local/share/Trash`, with a `.trashinfo` so file managers can put it back), `~/.Trash`
+//! on macOS and the Recycle Bin on Windows. Files on another filesystem than the home trash
+//! go to the trash at the top of their own (`.Trash-$UID`, `.Trashes/$UID`), so nothing is
+//! copied.
+
+use std::io;
+use std::path::Path;
+
+/// Delete `path`: for good if `permanent`, to the trash otherwise
+pub fn delete(path: &Path, permanent: bool) -> io::Result<()> {
+    if permanent {
+        return std::fs::remove_file(path);
+    }
+    #[cfg(target_os = "macos")]
+    return macos::trash(path);
+    #[cfg(windows)]
+    return windows::trash(path);
+    #[cfg(all(unix, not(target_os = "macos")))]
+    freedesktop::trash(path)
+}
+
+/// The topmost directory above `path` on the same filesystem, where per-drive trash lives
+#[cfg(unix)]
+fn top_dir(path: &Path) -> io::Result<std::path::PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+    let device = std::fs::symlink_metadata(path)?.dev();
+    let mut top = path.parent().unwrap_or(path);
+    while let Some(parent) = top.parent() {
+        if std::fs::metadata(parent)?.dev() != device {
+            break;
+        }
+        top = parent;
+    }
+    Ok(top.to_path_buf())
+}
+
+/// Make a directory only this user can look into, as trash directories must be
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    std::fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod freedesktop {
+    use std::ffi::OsString;
+    use std::fs;
+    use std::io::{self, Write};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    use super::super::{conflicts, store};
+
+    pub(super) fn trash(path: &Path) -> io::Result<()> {
+        let path = std::path::absolute(path)?;
+        let home = store::data_dir()
+            .map(|dir| dir.join("Trash"))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory for the trash"))?;
+        match move_into(&home, &path, None) {
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                let top = super::top_dir(&path)?;
+                // SAFETY: getuid() can't fail
+                let uid = unsafe { libc::getuid() };
+                move_into(&top.join(format!(".Trash-{}", uid)), &path, Some(&top))
+            }
+            result => result,
+        }
+    }
+
+    /// Move `path` into the `files` directory of `trash` and describe it in `info`. The trash at
+    /// the top of a drive records paths relative to `top`.
+    fn move_into(trash: &Path, path: &Path, top: Option<&Path>) -> io::Result<()> {
+        let (files, info) = (trash.join("files"), trash.join("info"));
+        super::create_private_dir(&files)?;
+        super::create_private_dir(&info)?;
+        let name = path.file_name().ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+        let info_file = |file: &Path| {
+            let mut name = OsString::from(file.file_name().unwrap_or_default());
+            name.push(".trashinfo");
+            info.join(name)
+        };
+        let recorded = top.and_then(|top| path.strip_prefix(top).ok()).unwrap_or(path);
+
+        loop {
+            let taken = |file: &Path| file.exists() || info_file(file).exists();
+            let mut destination = files.join(name);
+            if taken(&destination) {
+                destination = conflicts::free_name_where(&destination, taken);
+            }
+            // Creating the .trashinfo first claims the name, as the specification asks
+            let mut description = match fs::File::options().write(true).create_new(true).open(info_file(&destination)) {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            };
+            let written = write!(
+                description,
+                "[Trash Info]\nPath={}\nDeletionDate={}\n",
+                percent_encode(recorded),
+                chrono::Local::now().format("%Y-%m-%dT%H:%M:%S")
+            );
+            let moved = written.and_then(|_| fs::rename(path, &destination));
+            if moved.is_err() {
+                let _ = fs::remove_file(info_file(&destination));
+            }
+            return moved;
+        }
+    }
+
+    /// A path as the `Path=` key wants it: bytes other than unreserved characters and `/`
+    /// percent-encoded
+    fn percent_encode(path: &Path) -> String {
+        let mut encoded = String::new();
+        for &byte in path.as_os_str().as_bytes() {
+            if byte.is_ascii_alphanumeric() || b"-_.~/".contains(&byte) {
+                encoded.push(byte as char);
+            } else {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        }
+        encoded
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_move_into() {
+            let dir = tempfile::tempdir().unwrap();
+            let trash = dir.path().join("Trash");
+            for _ in 0..2 {
+                let file = dir.path().join("old report.tmp");
+                fs::write(&file, b"tmp").unwrap();
+                move_into(&trash, &file, None).unwrap();
+                assert!(!file.exists());
+            }
+
+            assert!(trash.join("files/old report.tmp").exists());
+            assert!(trash.join("files/old report (1).tmp").exists());
+            let description = fs::read_to_string(trash.join("info/old report (1).tmp.trashinfo")).unwrap();
+            let expected = format!("Path={}/old%20report.tmp\n", percent_encode(dir.path()));
+            assert!(description.starts_with("[Trash Info]\n") && description.contains(&expected));
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+
+    use super::super::conflicts;
+
+    pub(super) fn trash(path: &Path) -> io::Result<()> {
+        let path = std::path::absolute(path)?;
+        let home = std::env::var_os("HOME")
+            .map(|home| Path::new(&home).join(".Trash"))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory for the trash"))?;
+        match move_into(&home, &path) {
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                // SAFETY: getuid() can't fail
+                let uid = unsafe { libc::getuid() };
+                let trash = super::top_dir(&path)?.join(".Trashes").join(uid.to_string());
+                super::create_private_dir(&trash)?;
+                move_into(&trash, &path)
+            }
+            result => result,
+        }
+    }
+
+    fn move_into(trash: &Path, path: &Path) -> io::Result<()> {
+        let name = path.file_name().ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+        let mut destination = trash.join(name);
+        if destination.exists() {
+            destination = conflicts::free_name(&destination);
+        }
+        fs::rename(path, destination)
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use windows_sys::Win32::UI::Shell::{
+        SHFileOperationW, FOF_ALLOWUNDO, FOF_NOCONFIRMATION, FOF_NOERRORUI, FOF_SILENT, FO_DELETE, SHFILEOPSTRUCTW,
+    };
+
+    /// `SHFileOperationW(FO_DELETE)` with undo allowed, which is what sends a file to the
+    /// Recycle Bin
+    pub(super) fn trash(path: &Path) -> io::Result<()> {
+        let path = std::path::absolute(path)?;
+        // A list of paths, ended by an empty one
+        let from: Vec<u16> = path.as_os_str().encode_wide().chain([0, 0]).collect();
+        let mut operation = SHFILEOPSTRUCTW {
+            wFunc: FO_DELETE,
+            pFrom: from.as_ptr(),
+            fFlags: (FOF_ALLOWUNDO | FOF_NOCONFIRMATION | FOF_NOERRORUI | FOF_SILENT) as u16,
+            ..SHFILEOPSTRUCTW::default()
+        };
+        // SAFETY: `from` is double-NUL-terminated and outlives the call; nothing else is set
+        let result = unsafe { SHFileOperationW(&mut operation) };
+        if result != 0 || operation.fAnyOperationsAborted != 0 {
+            return Err(io::Error::other(format!("the shell couldn't move it to the Recycle Bin (error {:#x})", result)));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::FileOperation;
+
+/// A planned folder that already exists under an equivalent name, e.g. `🖼️ Images` on disk as
+/// `Images` (made by an older version, or by hand)
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TwinFolder {
+    pub planned: PathBuf,
+    pub existing: PathBuf,
+    /// Planned files sent to `existing` instead
+    pub files: usize,
+}
+
+/// A folder name without its emoji prefix: `Images` for `🖼️ Images`
+fn bare_name(name: &str) -> &str {
+    match name.split_once(' ') {
+        Some((prefix, rest)) if !prefix.is_ascii() && !prefix.chars().any(char::is_alphanumeric) => rest,
+        _ => name,
+    }
+}
+
+/// An existing sibling of the missing directory `dir` that differs from it only by the emoji
+fn find_twin(dir: &Path) -> Option<PathBuf> {
+    let name = dir.file_name()?.to_str()?;
+    let bare = bare_name(name);
+    let mut twins: Vec<PathBuf> = fs::read_dir(dir.parent()?)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .filter(|entry| entry.file_name().to_str().is_some_and(|other| other != name && bare_name(other) == bare))
+        .map(|entry| entry.path())
+        .collect();
+    twins.sort();
+    twins.into_iter().next()
+}
+
+/// The topmost directory on the way to `destination` that doesn't exist yet
+fn first_missing_dir(destination: &Path) -> Option<PathBuf> {
+    let mut missing = None;
+    for dir in destination.parent()?.ancestors() {
+        if dir.as_os_str().is_empty() || dir.is_dir() {
+            break;
+        }
+        missing = Some(dir.to_path_buf());
+    }
+    missing
+}
+
+/// Send files into existing twins of the folders they'd otherwise create, so a run never makes
+/// `🖼️ Images` next to `Images`. The conflict policy then handles names taken in the twin.
+///
+/// Files already in a twin of their folder are dropped from the plan, as they're in place.
+pub fn merge(operations: &mut Vec<FileOperation>) -> Vec<TwinFolder> {
+    let mut twins: HashMap<PathBuf, Option<PathBuf>> = HashMap::new();
+    let mut merged: Vec<TwinFolder> = Vec::new();
+
+    for op in operations.iter_mut() {
+        while let Some(missing) = first_missing_dir(&op.destination) {
+            let Some(existing) = twins.entry(missing.clone()).or_insert_with(|| find_twin(&missing)).clone() else {
+                break;
+            };
+            let rest = op.destination.strip_prefix(&missing).unwrap_or(&op.destination).to_path_buf();
+            op.destination = existing.join(rest);
+            match merged.iter_mut().find(|twin| twin.planned == missing) {
+                Some(twin) => twin.files += 1,
+                None => merged.push(TwinFolder { planned: missing, existing, files: 1 }),
+            }
+        }
+    }
+
+    operations.retain(|op| op.destination != op.source);
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::OperationType;
+
+    #[test]
+    fn test_bare_name() {
+        assert_eq!(bare_name("🖼️ Images"), "Images");
+        assert_eq!(bare_name("📅 2024 March"), "2024 March");
+        assert_eq!(bare_name("Images"), "Images");
+        assert_eq!(bare_name("My Images"), "My Images");
+        assert_eq!(bare_name("- notes"), "- notes");
+    }
+
+    #[test]
+    fn test_merge_into_twins() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("Images")).unwrap();
+        fs::create_dir_all(root.join("📄 Documents")).unwrap();
+        let op = |source: &str, destination: &str| FileOperation {
+            source: root.join(source),
+            destination: root.join(destination),
+            operation_type: OperationType::Move,
+            size: 0,
+        };
+
+        let mut operations = vec![
+            op("a.jpg", "🖼️ Images/a.jpg"),
+            op("b.jpg", "🖼️ Images/2024/b.jpg"),
+            op("Images/c.jpg", "🖼️ Images/c.jpg"),
+            op("d.txt", "📄 Documents/d.txt"),
+            op("e.mp3", "🎵 Audio/e.mp3"),
+        ];
+        let twins = merge(&mut operations);
+
+        assert_eq!(
+            twins,
+            [TwinFolder { planned: root.join("🖼️ Images"), existing: root.join("Images"), files: 3 }]
+        );
+        let destinations: Vec<PathBuf> = operations.iter().map(|op| op.destination.clone()).collect();
+        assert_eq!(
+            destinations,
+            [
+                root.join("Images/a.jpg"),
+                root.join("Images/2024/b.jpg"),
+                root.join("📄 Documents/d.txt"),
+                root.join("🎵 Audio/e.mp3"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! Human-friendly sizes (`1.5GB`, `300K`) and durations (`2h30m`, `7d`) for flags and config.
+//!
+//! Sizes use binary multiples (1KB = 1024 bytes), matching the size-mode folders. Both types
+//! implement `FromStr`, so they work directly as clap arguments, and `Display`, which
+//! formats them back into a form that parses again.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use serde::{Deserialize, Deserializer};
+
+const SIZE_UNITS: [(&str, u64); 5] = [
+    ("TB", 1 << 40),
+    ("GB", 1 << 30),
+    ("MB", 1 << 20),
+    ("KB", 1 << 10),
+    ("B", 1),
+];
+
+const DURATION_UNITS: [(char, u64); 5] = [
+    ('w', 7 * 24 * 60 * 60),
+    ('d', 24 * 60 * 60),
+    ('h', 60 * 60),
+    ('m', 60),
+    ('s', 1),
+];
+
+/// Error for a size or duration that couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseUnitError {
+    kind: &'static str,
+    input: String,
+    reason: String,
+}
+
+impl ParseUnitError {
+    fn new(kind: &'static str, input: &str, reason: impl Into<String>) -> Self {
+        Self {
+            kind,
+            input: input.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseUnitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {} '{}': {}", self.kind, self.input, self.reason)
+    }
+}
+
+impl std::error::Error for ParseUnitError {}
+
+/// A size in bytes, written like `512`, `300K`, `1.5GB` or `2 MiB`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+    type Err = ParseUnitError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = |reason: &str| ParseUnitError::new("size", input, reason);
+
+        let trimmed = input.trim();
+        let number_len = trimmed
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(trimmed.len());
+        let (number, unit) = trimmed.split_at(number_len);
+        if number.is_empty() {
+            return Err(error("expected a number followed by an optional unit, like 1.5GB"));
+        }
+
+        let unit = unit.trim();
+        let normalized = unit.to_ascii_uppercase();
+        let normalized = normalized.strip_suffix("IB").map(|prefix| format!("{}B", prefix)).unwrap_or(normalized);
+        let multiplier: u64 = match normalized.as_str() {
+            "" | "B" => 1,
+            "K" | "KB" => 1 << 10,
+            "M" | "MB" => 1 << 20,
+            "G" | "GB" => 1 << 30,
+            "T" | "TB" => 1 << 40,
+            _ => {
+                let reason = format!("unknown unit '{}' (expected B, KB, MB, GB or TB)", unit);
+                return Err(error(&reason));
+            }
+        };
+
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        if (whole.is_empty() && fraction.is_empty()) || fraction.contains('.') {
+            return Err(error("malformed number"));
+        }
+        if fraction.len() > 9 {
+            return Err(error("too many decimal places"));
+        }
+        if multiplier == 1 && !fraction.trim_end_matches('0').is_empty() {
+            return Err(error("a size in bytes can't have a fraction"));
+        }
+
+        let whole: u128 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| error("number is too large"))? };
+        let fraction_value: u128 = if fraction.is_empty() { 0 } else { fraction.parse().unwrap_or(0) };
+        let scale = 10u128.pow(fraction.len() as u32);
+        let bytes = whole
+            .checked_mul(multiplier as u128)
+            .and_then(|bytes| bytes.checked_add(fraction_value * multiplier as u128 / scale))
+            .filter(|&bytes| bytes <= u64::MAX as u128)
+            .ok_or_else(|| error("number is too large"))?;
+
+        Ok(ByteSize(bytes as u64))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    /// The largest unit the size reaches, with up to two decimals: `512B`, `300KB`, `1.5GB`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (unit, multiplier) = SIZE_UNITS
+            .iter()
+            .find(|(_, multiplier)| self.0 >= *multiplier)
+            .copied()
+            .unwrap_or(("B", 1));
+        if multiplier == 1 {
+            return write!(f, "{}B", self.0);
+        }
+
+        // Round down, so the formatted size never exceeds the real one (or u64::MAX)
+        let hundredths = self.0 as u128 * 100 / multiplier as u128;
+        let decimals = format!("{:02}", hundredths % 100);
+        let decimals = decimals.trim_end_matches('0');
+        if decimals.is_empty() {
+            write!(f, "{}{}", hundredths / 100, unit)
+        } else {
+            write!(f, "{}.{}{}", hundredths / 100, decimals, unit)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    /// Accepts a byte count (`1048576`) or a string (`"1MB"`)
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Bytes(u64),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Bytes(bytes) => Ok(ByteSize(bytes)),
+            Raw::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// A duration written like `90` (seconds), `45s`, `2h30m`, `7d` or `1w`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HumanDuration(pub Duration);
+
+impl HumanDuration {
+    pub fn as_secs(&self) -> u64 {
+        self.0.as_secs()
+    }
+}
+
+impl FromStr for HumanDuration {
+    type Err = ParseUnitError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = |reason: &str| ParseUnitError::new("duration", input, reason);
+
+        let trimmed = input.trim();
+        if trimmed.is_empty() {
+            return Err(error("expected a duration like 90s, 2h30m or 7d"));
+        }
+        // A bare number is a count of seconds
+        if trimmed.bytes().all(|b| b.is_ascii_digit()) {
+            let secs = trimmed.parse().map_err(|_| error("number is too large"))?;
+            return Ok(HumanDuration(Duration::from_secs(secs)));
+        }
+
+        let mut total: u64 = 0;
+        let mut rest = trimmed;
+        while !rest.is_empty() {
+            let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            let (number, after) = rest.split_at(digits);
+            if number.is_empty() {
+                let reason = format!("expected a number before '{}'", after);
+                return Err(error(&reason));
+            }
+
+            let mut chars = after.chars();
+            let Some(unit) = chars.next() else {
+                let reason = format!("missing unit after '{}' (expected w, d, h, m or s)", number);
+                return Err(error(&reason));
+            };
+            let Some(&(_, multiplier)) = DURATION_UNITS.iter().find(|(name, _)| *name == unit.to_ascii_lowercase()) else {
+                let reason = format!("unknown unit '{}' (expected w, d, h, m or s)", unit);
+                return Err(error(&reason));
+            };
+
+            total = number
+                .parse::<u64>()
+                .ok()
+                .and_then(|value| value.checked_mul(multiplier))
+                .and_then(|secs| total.checked_add(secs))
+                .ok_or_else(|| error("duration is too long"))?;
+            rest = chars.as_str();
+        }
+
+        Ok(HumanDuration(Duration::from_secs(total)))
+    }
+}
+
+impl fmt::Display for HumanDuration {
+    /// Whole seconds split into days, hours, minutes and seconds: `7d`, `2h30m`, `0s`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut remaining = self.0.as_secs();
+        if remaining == 0 {
+            return write!(f, "0s");
+        }
+
+        // Weeks are accepted as input but read more naturally as days
+        for (unit, secs) in DURATION_UNITS.iter().skip(1) {
+            if remaining >= *secs {
+                write!(f, "{}{}", remaining / secs, unit)?;
+                remaining %= secs;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(input: &str) -> u64 {
+        input.parse::<ByteSize>().unwrap().0
+    }
+
+    fn secs(input: &str) -> u64 {
+        input.parse::<HumanDuration>().unwrap().as_secs()
+    }
+
+    #[test]
+    fn test_parse_sizes() {
+        assert_eq!(size("512"), 512);
+        assert_eq!(size("300K"), 300 * 1024);
+        assert_eq!(size("1.5GB"), 3 * (1 << 29));
+        assert_eq!(size(" 2 MiB "), 2 * (1 << 20));
+        assert_eq!(size(".5kb"), 512);
+        assert_eq!(size("1.0B"), 1);
+    }
+
+    #[test]
+    fn test_size_errors() {
+        let message = |input: &str| input.parse::<ByteSize>().unwrap_err().to_string();
+        assert_eq!(message("1.5XB"), "invalid size '1.5XB': unknown unit 'XB' (expected B, KB, MB, GB or TB)");
+        assert_eq!(message("GB"), "invalid size 'GB': expected a number followed by an optional unit, like 1.5GB");
+        assert_eq!(message("1.5B"), "invalid size '1.5B': a size in bytes can't have a fraction");
+        assert!(message("1.2.3MB").contains("malformed number"));
+        assert!(message("99999999999TB").contains("too large"));
+        assert!("-1K".parse::<ByteSize>().is_err());
+    }
+
+    #[test]
+    fn test_format_sizes_round_trip() {
+        for input in ["0B", "512B", "300KB", "1.5GB", "1.25MB", "2TB"] {
+            let parsed: ByteSize = input.parse().unwrap();
+            assert_eq!(parsed.to_string(), input);
+        }
+        assert_eq!(ByteSize(1023).to_string(), "1023B");
+        assert_eq!(ByteSize(1_000_000).to_string(), "976.56KB");
+    }
+
+    #[test]
+    fn test_parse_durations() {
+        assert_eq!(secs("90"), 90);
+        assert_eq!(secs("45s"), 45);
+        assert_eq!(secs("2h30m"), 9000);
+        assert_eq!(secs("7d"), 7 * 86_400);
+        assert_eq!(secs("1w1d"), 8 * 86_400);
+        assert_eq!(secs("1H"), 3600);
+    }
+
+    #[test]
+    fn test_duration_errors() {
+        let message = |input: &str| input.parse::<HumanDuration>().unwrap_err().to_string();
+        assert_eq!(message("2h30"), "invalid duration '2h30': missing unit after '30' (expected w, d, h, m or s)");
+        assert_eq!(message("5y"), "invalid duration '5y': unknown unit 'y' (expected w, d, h, m or s)");
+        assert_eq!(message("h"), "invalid duration 'h': expected a number before 'h'");
+        assert!(message("").contains("expected a duration"));
+        assert!(message("99999999999999999w").contains("too long"));
+    }
+
+    #[test]
+    fn test_format_durations_round_trip() {
+        for input in ["0s", "45s", "2h30m", "7d", "1d2h3m4s"] {
+            let parsed: HumanDuration = input.parse().unwrap();
+            assert_eq!(parsed.to_string(), input);
+        }
+        assert_eq!(HumanDuration(Duration::from_secs(90)).to_string(), "1m30s");
+    }
+}
@@ -0,0 +1,25 @@
+//! Where the CLI's ordinary output goes through, so `--quiet` silences it in one place. Errors
+//! and warnings don't go through here: they're printed or returned as they always are, and
+//! questions that need an answer are still asked.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// `say!("Moved {} files", count)`: `println!`, unless the run is quiet
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Print only errors from now on
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
@@ -0,0 +1,79 @@
+//! Output too tall for the terminal goes through `$PAGER` (`less` by default), the way `git
+//! log` does. Anything not going to a terminal, such as a pipe or a test, is printed as is.
+
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+/// Print `text`, through the pager if stdout is a terminal it doesn't fit on; nothing under
+/// `--quiet`
+pub fn show(text: &str) {
+    if crate::output::is_quiet() {
+        return;
+    }
+    let paged = io::stdout().is_terminal()
+        && terminal_height().is_some_and(|height| needs_pager(text, height))
+        && page(text).is_ok();
+    if !paged {
+        print!("{}", text);
+    }
+}
+
+/// Whether `text` scrolls off a terminal `height` lines tall, keeping a line for the prompt
+fn needs_pager(text: &str, height: usize) -> bool {
+    text.lines().count() >= height
+}
+
+/// Run the pager on `text` and wait for the user to quit it. `PAGER=` (empty) or `cat` turns
+/// paging off; failing to start it prints the text instead.
+fn page(text: &str) -> io::Result<()> {
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    let mut words = pager.split_whitespace();
+    let Some(program) = words.next().filter(|program| *program != "cat") else {
+        return Err(io::ErrorKind::NotFound.into());
+    };
+
+    let mut command = Command::new(program);
+    command.args(words).stdin(Stdio::piped());
+    // Keep colors, and quit straight away if it fits after all
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    let mut child = command.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // Quitting before the end closes the pipe; that's not a failure
+        match stdin.write_all(text.as_bytes()) {
+            Err(error) if error.kind() != io::ErrorKind::BrokenPipe => return Err(error),
+            _ => {}
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Rows of the terminal: `$LINES` if set, otherwise what the terminal reports
+fn terminal_height() -> Option<usize> {
+    if let Some(lines) = env::var("LINES").ok().and_then(|lines| lines.parse().ok()) {
+        return Some(lines);
+    }
+    #[cfg(unix)]
+    {
+        // SAFETY: TIOCGWINSZ only fills in the winsize it's given
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_row > 0 {
+            return Some(size.ws_row as usize);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_pager() {
+        assert!(!needs_pager("one\ntwo\n", 24));
+        assert!(needs_pager(&"line\n".repeat(24), 24));
+    }
+}
@@ -0,0 +1,137 @@
+use std::io::{self, Write};
+use std::path::Path;
+use serde_json::{json, Value};
+
+use file_organizer::organizer::conflicts::ConflictChoice;
+use file_organizer::organizer::observer::{CategoryChoice, Notice, OrganizeObserver};
+use file_organizer::organizer::skips::{SkipReason, SkippedFile};
+use file_organizer::organizer::{FileOperation, OrganizationSummary, OrganizeMode};
+
+/// How `organize` reports progress
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProgressFormat {
+    /// Human-readable output only
+    #[default]
+    Pretty,
+    /// Also write one JSON event per line to stderr
+    Ndjson,
+}
+
+/// Writes each stage as a JSON line to stderr (`{"event":"file_moved",...}`) and passes
+/// everything on to `inner`, so prompts and the pretty output still work
+pub struct NdjsonObserver<O> {
+    inner: O,
+}
+
+impl<O: OrganizeObserver> NdjsonObserver<O> {
+    pub fn new(inner: O) -> Self {
+        Self { inner }
+    }
+}
+
+/// Write one event; a closed stderr isn't worth failing the run for
+fn emit(event: &str, mut fields: Value) {
+    fields["event"] = json!(event);
+    let _ = writeln!(io::stderr().lock(), "{}", fields);
+}
+
+/// The error that ended the run, as an `error` event without a file
+pub fn emit_run_error(message: &str) {
+    emit("error", json!({ "message": message }));
+}
+
+/// `source`, `destination` and `operation_type`, as the library serializes an operation
+fn operation_fields(operation: &FileOperation) -> Value {
+    serde_json::to_value(operation).unwrap_or_default()
+}
+
+impl<O: OrganizeObserver> OrganizeObserver for NdjsonObserver<O> {
+    fn on_scan_started(&self, directory: &Path) {
+        emit("scan_started", json!({ "directory": directory.to_string_lossy() }));
+        self.inner.on_scan_started(directory);
+    }
+
+    fn on_scan_progress(&self, files_found: usize) {
+        self.inner.on_scan_progress(files_found);
+    }
+
+    fn on_scan_finished(&self, files_found: usize, files_selected: usize) {
+        self.inner.on_scan_finished(files_found, files_selected);
+    }
+
+    fn on_notice(&self, notice: &Notice) {
+        self.inner.on_notice(notice);
+    }
+
+    fn on_files_skipped(&self, skipped: &[SkippedFile]) {
+        for file in skipped {
+            emit("file_skipped", json!({ "source": file.path.to_string_lossy(), "reason": file.reason }));
+        }
+        self.inner.on_files_skipped(skipped);
+    }
+
+    fn on_plan_ready(&self, plan: &[FileOperation], mode: &OrganizeMode) {
+        for operation in plan {
+            emit("file_planned", operation_fields(operation));
+        }
+        self.inner.on_plan_ready(plan, mode);
+    }
+
+    fn on_category_review(&self, folder: &str, files: usize) -> CategoryChoice {
+        self.inner.on_category_review(folder, files)
+    }
+
+    fn on_deletion_review(&self, files: usize, permanent: bool) -> bool {
+        self.inner.on_deletion_review(files, permanent)
+    }
+
+    fn on_execution_started(&self, operations: usize) {
+        self.inner.on_execution_started(operations);
+    }
+
+    fn on_directories_created(&self, count: usize) {
+        self.inner.on_directories_created(count);
+    }
+
+    fn on_file_moved(&self, operation: &FileOperation) {
+        emit("file_moved", operation_fields(operation));
+        self.inner.on_file_moved(operation);
+    }
+
+    fn on_conflict(&self, operation: &FileOperation) -> ConflictChoice {
+        self.inner.on_conflict(operation)
+    }
+
+    fn on_file_skipped(&self, operation: &FileOperation) {
+        let mut fields = operation_fields(operation);
+        fields["reason"] = json!(SkipReason::Conflict);
+        emit("file_skipped", fields);
+        self.inner.on_file_skipped(operation);
+    }
+
+    fn on_error(&self, operation: &FileOperation, error: &io::Error) {
+        let mut fields = operation_fields(operation);
+        fields["message"] = json!(error.to_string());
+        emit("error", fields);
+        self.inner.on_error(operation, error);
+    }
+
+    fn on_execution_stopped(&self, not_attempted: usize) {
+        self.inner.on_execution_stopped(not_attempted);
+    }
+
+    fn on_rolled_back(&self, restored: usize, failed: usize) {
+        self.inner.on_rolled_back(restored, failed);
+    }
+
+    fn on_execution_finished(&self, moved: usize, failed: usize) {
+        self.inner.on_execution_finished(moved, failed);
+    }
+
+    fn on_finished(&self, summary: &OrganizationSummary, dry_run: bool) {
+        let mut fields = serde_json::to_value(summary).unwrap_or_default();
+        fields["dry_run"] = json!(dry_run);
+        emit("summary", fields);
+        self.inner.on_finished(summary, dry_run);
+    }
+}
@@ -0,0 +1,162 @@
+//! `self-update`: replace the running binary with the latest GitHub release.
+//!
+//! Releases carry one bare binary per platform, named like `file-organizer-linux-x86_64`
+//! (`.exe` on Windows), and a `SHA256SUMS` file in `sha256sum` format. Downloads go through
+//! `curl`, which every supported platform ships; a binary whose checksum doesn't match is
+//! never installed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+const CHECKSUMS: &str = "SHA256SUMS";
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// The latest release, and whether it's newer than this binary
+pub struct Update {
+    release: Release,
+    pub current: String,
+    pub latest: String,
+}
+
+impl Update {
+    pub fn is_newer(&self) -> bool {
+        parse_version(&self.latest) > parse_version(&self.current)
+    }
+}
+
+/// Ask GitHub for the latest release
+pub fn check() -> Result<Update> {
+    let repository = env!("CARGO_PKG_REPOSITORY").trim_end_matches('/');
+    let slug = repository
+        .strip_prefix("https://github.com/")
+        .ok_or_else(|| anyhow!("{} isn't a GitHub repository", repository))?;
+    let body = download(&format!("https://api.github.com/repos/{}/releases/latest", slug))?;
+    let release: Release = serde_json::from_slice(&body).context("Unexpected answer from the GitHub releases API")?;
+    Ok(Update {
+        current: env!("CARGO_PKG_VERSION").to_string(),
+        latest: release.tag_name.trim_start_matches('v').to_string(),
+        release,
+    })
+}
+
+/// Download this platform's binary of `update`, verify it and put it in place of the running
+/// executable. Returns the path that was replaced.
+pub fn install(update: &Update) -> Result<PathBuf> {
+    let name = asset_name();
+    let asset = |name: &str| {
+        update.release.assets.iter().find(|asset| asset.name == name).ok_or_else(|| {
+            anyhow!("Release {} has no {} (this platform may not have prebuilt binaries)", update.release.tag_name, name)
+        })
+    };
+    let binary = download(&asset(&name)?.browser_download_url)?;
+    let checksums = String::from_utf8(download(&asset(CHECKSUMS)?.browser_download_url)?)
+        .context(format!("{} isn't text", CHECKSUMS))?;
+
+    let expected = expected_checksum(&checksums, &name).ok_or_else(|| anyhow!("{} doesn't list {}", CHECKSUMS, name))?;
+    let actual = hex(&Sha256::digest(&binary));
+    if !actual.eq_ignore_ascii_case(&expected) {
+        bail!("Checksum mismatch for {}: expected {}, downloaded {}; nothing was installed", name, expected, actual);
+    }
+
+    let current = std::env::current_exe().context("Can't find the running executable")?;
+    replace(&current, &binary)?;
+    Ok(current)
+}
+
+/// Release asset for this platform
+fn asset_name() -> String {
+    format!("file-organizer-{}-{}{}", std::env::consts::OS, std::env::consts::ARCH, std::env::consts::EXE_SUFFIX)
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--header", "Accept: application/vnd.github+json"])
+        .args(["--user-agent", concat!("file-organizer/", env!("CARGO_PKG_VERSION"))])
+        .arg(url)
+        .output()
+        .context("self-update needs curl, which wasn't found")?;
+    if !output.status.success() {
+        bail!("Downloading {} failed: {}", url, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(output.stdout)
+}
+
+/// Look up a file in `sha256sum` output (`<hex>  <name>`, or `<hex> *<name>`)
+fn expected_checksum(checksums: &str, name: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (hash, file) = line.trim().split_once(char::is_whitespace)?;
+        (file.trim_start().trim_start_matches('*') == name).then(|| hash.to_string())
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// `1.2.3` (or `v1.2.3-rc1`) as numbers for comparing; missing parts count as 0
+fn parse_version(version: &str) -> Vec<u64> {
+    let release = version.trim().trim_start_matches('v').split(['-', '+']).next().unwrap_or_default();
+    let mut parts: Vec<u64> = release.split('.').map(|part| part.parse().unwrap_or(0)).collect();
+    parts.resize(3.max(parts.len()), 0);
+    parts
+}
+
+/// Write the new binary next to `current` and rename it over `current`, so a failure halfway
+/// leaves the old binary working
+fn replace(current: &Path, binary: &[u8]) -> Result<()> {
+    let staged = current.with_extension("new");
+    fs::write(&staged, binary).context(format!("Can't write {} (is the directory writable?)", staged.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    }
+    // Windows can't overwrite a running executable, but it can rename it out of the way
+    #[cfg(windows)]
+    {
+        let old = current.with_extension("old");
+        let _ = fs::remove_file(&old);
+        fs::rename(current, &old).context(format!("Can't move {} aside", current.display()))?;
+        if let Err(e) = fs::rename(&staged, current) {
+            let _ = fs::rename(&old, current);
+            return Err(e).context(format!("Can't replace {}", current.display()));
+        }
+        Ok(())
+    }
+    #[cfg(not(windows))]
+    fs::rename(&staged, current).context(format!("Can't replace {}", current.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_and_checksums() {
+        assert!(parse_version("v0.10.0") > parse_version("0.9.3"));
+        assert_eq!(parse_version("1.2"), parse_version("1.2.0"));
+        assert_eq!(parse_version("v1.2.3-rc1"), [1, 2, 3]);
+
+        let sums = "abc123  file-organizer-linux-x86_64\ndef456 *file-organizer-windows-x86_64.exe\n";
+        assert_eq!(expected_checksum(sums, "file-organizer-linux-x86_64").as_deref(), Some("abc123"));
+        assert_eq!(expected_checksum(sums, "file-organizer-windows-x86_64.exe").as_deref(), Some("def456"));
+        assert_eq!(expected_checksum(sums, "file-organizer-macos-aarch64"), None);
+        assert_eq!(hex(&Sha256::digest(b"abc"))[..8], *"ba7816bf");
+    }
+}
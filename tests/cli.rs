@@ -0,0 +1,1720 @@
+//! End-to-end tests that run the `file-organizer` binary against synthetic directory trees.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use predicates::prelude::*;
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+/// A scratch directory to organize, plus an isolated config home
+struct Sandbox {
+    root: TempDir,
+    config_home: TempDir,
+}
+
+impl Sandbox {
+    fn new() -> Self {
+        Self {
+            root: tempfile::tempdir().unwrap(),
+            config_home: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    fn path(&self) -> &Path {
+        self.root.path()
+    }
+
+    /// Create a file (and its parent directories) with the given contents
+    fn file(&self, relative: &str, contents: &[u8]) -> PathBuf {
+        let path = self.path().join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Write a config file that every command run from this sandbox will pick up
+    fn config(&self, toml: &str) {
+        let dir = self.config_home.path().join("file-organizer");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("config.toml"), toml).unwrap();
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        self.command_in(self.path(), args)
+    }
+
+    /// Like [`Sandbox::command`], run against `dir` instead of the root
+    fn command_in(&self, dir: &Path, args: &[&str]) -> Command {
+        let mut command = self.command_without_path(args);
+        command.arg(dir);
+        command
+    }
+
+    /// The command with the sandbox's config and nothing appended, for commands that don't
+    /// take a directory
+    fn command_without_path(&self, args: &[&str]) -> Command {
+        let mut command = cargo_bin_cmd!("file-organizer");
+        command
+            .envs(self.environment())
+            .env_remove("LANGUAGE")
+            .env_remove("LC_ALL")
+            .env_remove("LC_MESSAGES")
+            .env_remove("LANG")
+            .args(args);
+        command
+    }
+
+    /// Start the binary in the background, set up like [`Sandbox::command_without_path`],
+    /// for commands that run until they're stopped
+    fn spawn(&self, args: &[&str]) -> std::process::Child {
+        std::process::Command::new(env!("CARGO_BIN_EXE_file-organizer"))
+            .envs(self.environment())
+            .env_remove("LANGUAGE")
+            .env_remove("LC_ALL")
+            .env_remove("LC_MESSAGES")
+            .env_remove("LANG")
+            .args(args)
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .unwrap()
+    }
+
+    /// Config, data and runtime directories in the config home, and no colors
+    fn environment(&self) -> Vec<(&'static str, &std::ffi::OsStr)> {
+        let home = self.config_home.path().as_os_str();
+        vec![
+            ("XDG_CONFIG_HOME", home),
+            ("XDG_DATA_HOME", home),
+            ("XDG_RUNTIME_DIR", home),
+            ("HOME", home),
+            ("NO_COLOR", "1".as_ref()),
+        ]
+    }
+
+    fn organize(&self, args: &[&str]) -> Command {
+        let mut full_args = vec!["organize"];
+        full_args.extend_from_slice(args);
+        self.command(&full_args)
+    }
+
+    /// Every file below the root as a sorted list of `/`-separated relative paths, leaving
+    /// out the tool's own `.file-organizer` folder
+    fn files(&self) -> Vec<String> {
+        self.entries(|entry| entry.file_type().is_file())
+    }
+
+    /// Every directory below the root, like [`Sandbox::files`]
+    fn dirs(&self) -> Vec<String> {
+        self.entries(|entry| entry.file_type().is_dir())
+    }
+
+    /// Undo journals kept for the root
+    fn journals(&self) -> usize {
+        let history = self.path().join(".file-organizer/history");
+        fs::read_dir(history).map(|entries| entries.count()).unwrap_or(0)
+    }
+
+    fn entries(&self, wanted: impl Fn(&walkdir::DirEntry) -> bool) -> Vec<String> {
+        let mut entries: Vec<String> = WalkDir::new(self.path())
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != ".file-organizer")
+            .map(|entry| entry.unwrap())
+            .filter(|entry| wanted(entry))
+            .map(|entry| {
+                let relative = entry.path().strip_prefix(self.path()).unwrap();
+                relative.components()
+                    .map(|part| part.as_os_str().to_string_lossy().to_string())
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+            .collect();
+        entries.sort();
+        entries
+    }
+}
+
+#[test]
+fn test_extension_mode_layout() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.file("report.pdf", b"pdf");
+    sandbox.file("song.mp3", b"mp3");
+    sandbox.file("main.rs", b"fn main() {}");
+    sandbox.file(".hidden", b"secret");
+
+    sandbox.organize(&[]).assert().success();
+
+    let mut expected = [".hidden", "💻 Code/main.rs", "📄 Documents/report.pdf", "🎵 Audio/song.mp3", "🖼️ Images/photo.jpg"];
+    expected.sort();
+    assert_eq!(sandbox.files(), expected);
+}
+
+#[test]
+fn test_size_mode_layout() {
+    let sandbox = Sandbox::new();
+    sandbox.file("tiny.txt", b"x");
+    let big = sandbox.file("big.bin", b"");
+    fs::File::options().write(true).open(&big).unwrap().set_len(2 * 1024 * 1024).unwrap();
+
+    sandbox.organize(&["--mode", "size"]).assert().success();
+
+    assert_eq!(sandbox.files(), ["📄 Small (1-10MB)/big.bin", "🔍 Tiny (< 1MB)/tiny.txt"]);
+}
+
+#[test]
+fn test_size_mode_with_configured_buckets() {
+    let sandbox = Sandbox::new();
+    sandbox.config(
+        r#"
+        [[size_buckets]]
+        name = "Clips"
+        max = "1MB"
+        [[size_buckets]]
+        max = "1GB"
+        "#,
+    );
+    sandbox.file("tiny.txt", b"x");
+    let big = sandbox.file("big.bin", b"");
+    fs::File::options().write(true).open(&big).unwrap().set_len(2 * 1024 * 1024).unwrap();
+
+    sandbox.organize(&["--mode", "size"]).assert().success();
+
+    assert_eq!(sandbox.files(), ["1MB-1GB/big.bin", "Clips/tiny.txt"]);
+}
+
+#[test]
+fn test_modified_mode_layout() {
+    let sandbox = Sandbox::new();
+    let old = sandbox.file("old.txt", b"old");
+    // 2020-05-15 12:00:00 UTC
+    let may_2020 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_589_544_000);
+    fs::File::options().write(true).open(&old).unwrap().set_modified(may_2020).unwrap();
+
+    sandbox.organize(&["--mode", "modified"]).assert().success();
+
+    assert_eq!(sandbox.files(), ["🕒 2020-05/old.txt"]);
+}
+
+#[test]
+fn test_quarter_and_week_buckets() {
+    // 2020-05-15 12:00:00 UTC, in Q2 and ISO week 20
+    let may_2020 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_589_544_000);
+    for (buckets, folder) in [("quarter", "🕒 2020-Q2"), ("week", "🕒 2020-W20")] {
+        let sandbox = Sandbox::new();
+        let report = sandbox.file("report.pdf", b"pdf");
+        fs::File::options().write(true).open(&report).unwrap().set_modified(may_2020).unwrap();
+
+        sandbox.organize(&["--mode", "modified", "--date-buckets", buckets]).assert().success();
+
+        assert_eq!(sandbox.files(), [format!("{}/report.pdf", folder)]);
+    }
+}
+
+#[test]
+fn test_configured_folder_template() {
+    let sandbox = Sandbox::new();
+    sandbox.config("[dates]\nfolder_template = \"Paperwork/{year}/{quarter}\"\n");
+    let invoice = sandbox.file("invoice.pdf", b"pdf");
+    let may_2020 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_589_544_000);
+    fs::File::options().write(true).open(&invoice).unwrap().set_modified(may_2020).unwrap();
+
+    sandbox.organize(&["--mode", "modified"]).assert().success();
+
+    assert_eq!(sandbox.files(), ["Paperwork/2020/Q2/invoice.pdf"]);
+}
+
+#[test]
+fn test_accessed_mode_layout() {
+    let sandbox = Sandbox::new();
+    let stale = sandbox.file("stale.zip", b"zip");
+    // 2020-05-15 12:00:00 UTC
+    let may_2020 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_589_544_000);
+    let times = fs::FileTimes::new().set_accessed(may_2020);
+    fs::File::options().write(true).open(&stale).unwrap().set_times(times).unwrap();
+
+    sandbox.organize(&["--mode", "accessed"]).assert().success();
+
+    assert_eq!(sandbox.files(), ["👁️ 2020-05/stale.zip"]);
+}
+
+#[test]
+fn test_date_mode_layout() {
+    let sandbox = Sandbox::new();
+    sandbox.file("a.txt", b"a");
+    sandbox.file("b.txt", b"b");
+
+    sandbox.organize(&["--mode", "date"]).assert().success();
+
+    // Creation times are whatever the filesystem recorded, so only check the shape
+    let dirs = sandbox.dirs();
+    assert_eq!(dirs.len(), 1);
+    assert!(dirs[0].starts_with("📅 "), "unexpected folder {:?}", dirs[0]);
+    assert_eq!(sandbox.files(), [format!("{}/a.txt", dirs[0]), format!("{}/b.txt", dirs[0])]);
+}
+
+#[test]
+fn test_origin_mode_without_metadata() {
+    let sandbox = Sandbox::new();
+    sandbox.file("setup.bin", b"bin");
+
+    sandbox.organize(&["--mode", "origin"]).assert().success();
+
+    assert_eq!(sandbox.files(), ["🌐 Unknown Origin/setup.bin"]);
+}
+
+#[test]
+fn test_custom_mode_layout() {
+    let sandbox = Sandbox::new();
+    sandbox.config(
+        r#"
+        [[rules]]
+        name = "invoices"
+        pattern = "invoice*"
+        destination = "Finance/Invoices"
+        "#,
+    );
+    sandbox.file("invoice-42.pdf", b"pdf");
+    sandbox.file("photo.jpg", b"jpg");
+
+    sandbox.organize(&["--mode", "custom"]).assert().success();
+
+    assert_eq!(sandbox.files(), ["Finance/Invoices/invoice-42.pdf", "🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_delete_rules() {
+    let sandbox = Sandbox::new();
+    let rule = r#"
+        [[rules]]
+        name = "temp files"
+        pattern = "*.tmp"
+        action = "delete"
+        "#;
+    sandbox.config(rule);
+    sandbox.file("build.tmp", b"tmp");
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.organize(&["--mode", "custom"]).assert().failure().stderr(predicate::str::contains("Invalid config file"));
+
+    sandbox.config(&format!("[execution]\nallow_delete = true\n{}", rule));
+    sandbox
+        .organize(&["--mode", "custom", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Moved to the trash by rules (1):").and(predicate::str::contains("build.tmp (rule 'temp files')")));
+    assert_eq!(sandbox.files(), ["build.tmp", "photo.jpg"]);
+
+    sandbox.organize(&["--mode", "custom"]).assert().success().stdout(predicate::str::contains("Moved 1 files to the trash"));
+    assert_eq!(sandbox.files(), ["🖼️ Images/photo.jpg"]);
+    let trash = sandbox.config_home.path().join("Trash");
+    if cfg!(all(unix, not(target_os = "macos"))) {
+        assert!(trash.join("files/build.tmp").exists() && trash.join("info/build.tmp.trashinfo").exists());
+    }
+
+    sandbox.file("cache.tmp", b"tmp");
+    sandbox.organize(&["--mode", "custom", "--permanent"]).assert().success().stdout(predicate::str::contains("Deleted 1 files for good"));
+    assert_eq!(sandbox.files(), ["🖼️ Images/photo.jpg"]);
+    assert!(!trash.join("files/cache.tmp").exists());
+
+    // Reviewing per category asks about the deletions last, and quitting keeps them
+    sandbox.file("a.tmp", b"tmp");
+    sandbox.file("notes.txt", b"notes");
+    sandbox.organize(&["--mode", "custom", "--ask-per-category"]).write_stdin("q\n").assert().success();
+    assert_eq!(sandbox.files(), ["a.tmp", "notes.txt", "🖼️ Images/photo.jpg"]);
+    sandbox
+        .organize(&["--mode", "custom", "--ask-per-category"])
+        .write_stdin("y\ny\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Move 1 files to the trash? [y/n]"));
+    assert_eq!(sandbox.files(), ["📄 Documents/notes.txt", "🖼️ Images/photo.jpg"]);
+    if cfg!(all(unix, not(target_os = "macos"))) {
+        assert!(trash.join("files/a.tmp").exists());
+    }
+}
+
+#[test]
+fn test_recursive_with_filter() {
+    let sandbox = Sandbox::new();
+    sandbox.file("nested/deeper/photo.jpg", b"jpg");
+    sandbox.file("nested/notes.txt", b"txt");
+
+    sandbox.organize(&["--recursive", "--filter", "jpg"]).assert().success();
+
+    assert_eq!(sandbox.files(), ["nested/notes.txt", "🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_recursive_one_file_system() {
+    let sandbox = Sandbox::new();
+    sandbox.file("nested/deeper/photo.jpg", b"jpg");
+
+    // Everything in the sandbox is on one filesystem, so nothing is left out
+    sandbox.organize(&["--recursive", "--one-file-system"]).assert().success();
+
+    assert_eq!(sandbox.files(), ["🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_watch_waits_for_sync() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.file(".syncthing.movie.mkv.tmp", b"partial");
+
+    // Watching never ends by itself
+    sandbox
+        .command(&["watch", "--interval", "1s", "--quiet-period", "1m"])
+        .timeout(Duration::from_secs(1))
+        .assert()
+        .interrupted()
+        .stdout(predicate::str::contains("Syncthing is syncing"));
+    assert_eq!(sandbox.files(), [".syncthing.movie.mkv.tmp", "photo.jpg"]);
+
+    fs::remove_file(sandbox.path().join(".syncthing.movie.mkv.tmp")).unwrap();
+    sandbox.command(&["watch", "--interval", "1s"]).timeout(Duration::from_secs(1)).assert().interrupted();
+    assert_eq!(sandbox.files(), ["🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_daemon_controls_watch() {
+    let sandbox = Sandbox::new();
+    let mut watch = sandbox.spawn(&["watch", &sandbox.path().display().to_string()]);
+    let listening = (0..100).any(|_| {
+        std::thread::sleep(Duration::from_millis(100));
+        sandbox.command_without_path(&["daemon", "status"]).output().unwrap().status.success()
+    });
+    assert!(listening, "the watch never answered");
+
+    sandbox.command(&["daemon", "pause"]).assert().success().stdout(predicate::str::contains("paused"));
+    sandbox.file("notes.txt", b"txt");
+    std::thread::sleep(Duration::from_millis(1500));
+    assert_eq!(sandbox.files(), ["notes.txt"]);
+
+    sandbox.command(&["daemon", "flush"]).assert().success().stdout(predicate::str::contains("Moved 1 files"));
+    assert_eq!(sandbox.files(), ["📄 Documents/notes.txt"]);
+    sandbox.command_without_path(&["daemon", "resume", "/"]).assert().failure();
+
+    watch.kill().unwrap();
+    watch.wait().unwrap();
+    sandbox.command_without_path(&["daemon", "status"]).assert().failure().stderr(predicate::str::contains("No watch is running"));
+}
+
+#[test]
+fn test_background_priority() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+
+    sandbox.command(&["--background", "organize"]).assert().success();
+
+    assert_eq!(sandbox.files(), ["🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_finder_tags_elsewhere() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+
+    let organize = sandbox.organize(&["--finder-tags"]).assert().success();
+    if !cfg!(target_os = "macos") {
+        organize.stdout(predicate::str::contains("Finder tags only exist on macOS"));
+    }
+
+    assert_eq!(sandbox.files(), ["🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_recursive_preserve_structure() {
+    let sandbox = Sandbox::new();
+    sandbox.file("vacation/2019/img.jpg", b"jpg");
+    sandbox.file("vacation/notes.txt", b"txt");
+    sandbox.file("top.jpg", b"jpg");
+
+    sandbox.organize(&["--recursive", "--preserve-structure"]).assert().success();
+
+    let organized = ["📄 Documents/vacation/notes.txt", "🖼️ Images/top.jpg", "🖼️ Images/vacation/2019/img.jpg"];
+    assert_eq!(sandbox.files(), organized);
+    sandbox.organize(&["--preserve-structure"]).assert().failure();
+
+    // Running again leaves everything where it is
+    sandbox.organize(&["--recursive", "--preserve-structure"]).assert().success();
+    assert_eq!(sandbox.files(), organized);
+}
+
+#[test]
+fn test_recursive_per_directory() {
+    let sandbox = Sandbox::new();
+    sandbox.file("project-a/setup.zip", b"zip");
+    sandbox.file("project-a/docs/readme.txt", b"txt");
+    sandbox.file("top.jpg", b"jpg");
+
+    sandbox.organize(&["--recursive", "--per-directory"]).assert().success();
+    let organized = sandbox.files();
+    assert_eq!(
+        organized,
+        [
+            "project-a/docs/📄 Documents/readme.txt",
+            "project-a/📦 Archives/setup.zip",
+            "🖼️ Images/top.jpg",
+        ]
+    );
+
+    // A second run leaves the category folders alone
+    sandbox.organize(&["--recursive", "--per-directory"]).assert().success();
+    assert_eq!(sandbox.files(), organized);
+    sandbox.organize(&["--recursive", "--per-directory", "--preserve-structure"]).assert().failure();
+}
+
+#[test]
+fn test_dry_run_changes_nothing() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.file("nested/notes.txt", b"txt");
+    let before = sandbox.files();
+
+    for mode in ["extension", "size", "date", "modified", "accessed", "origin", "custom"] {
+        sandbox.organize(&["--dry-run", "--recursive", "--mode", mode]).assert().success();
+    }
+
+    assert_eq!(sandbox.files(), before);
+    assert_eq!(sandbox.dirs(), ["nested"]);
+}
+
+#[test]
+fn test_plan_id_from_dry_run() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.file("🖼️ Images/photo.jpg", b"older jpg");
+
+    let output = sandbox.organize(&["--dry-run", "--on-conflict", "rename"]).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("photo (1).jpg"));
+    let plan_id = stdout.split("--plan-id ").nth(1).unwrap().split('`').next().unwrap().to_string();
+
+    sandbox.file("notes.txt", b"txt");
+    sandbox
+        .organize(&["--on-conflict", "rename", "--plan-id", &plan_id])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("The plan changed since it was previewed"));
+    assert_eq!(sandbox.files(), ["notes.txt", "photo.jpg", "🖼️ Images/photo.jpg"]);
+
+    std::fs::remove_file(sandbox.path().join("notes.txt")).unwrap();
+    sandbox.organize(&["--on-conflict", "rename", "--plan-id", &plan_id]).assert().success();
+    assert_eq!(sandbox.files(), ["🖼️ Images/photo (1).jpg", "🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_existing_destination_is_not_overwritten() {
+    let sandbox = Sandbox::new();
+    sandbox.file("🖼️ Images/photo.jpg", b"already sorted");
+    sandbox.file("photo.jpg", b"new download");
+
+    sandbox.organize(&[]).assert().failure().code(1);
+
+    assert_eq!(fs::read(sandbox.path().join("🖼️ Images/photo.jpg")).unwrap(), b"already sorted");
+    assert_eq!(fs::read(sandbox.path().join("photo.jpg")).unwrap(), b"new download");
+}
+
+#[test]
+fn test_conflict_policies() {
+    let sandbox = Sandbox::new();
+    sandbox.file("🖼️ Images/photo.jpg", b"already sorted");
+    sandbox.file("photo.jpg", b"new download");
+
+    sandbox.organize(&["--on-conflict", "skip"]).assert().success();
+    assert_eq!(sandbox.files(), ["photo.jpg", "🖼️ Images/photo.jpg"]);
+
+    sandbox.organize(&["--on-conflict", "rename"]).assert().success();
+    assert_eq!(sandbox.files(), ["🖼️ Images/photo (1).jpg", "🖼️ Images/photo.jpg"]);
+    assert_eq!(fs::read(sandbox.path().join("🖼️ Images/photo (1).jpg")).unwrap(), b"new download");
+}
+
+#[test]
+fn test_missing_directory_fails() {
+    let sandbox = Sandbox::new();
+    let missing = sandbox.path().join("does-not-exist");
+
+    let output = cargo_bin_cmd!("file-organizer")
+        .env("XDG_CONFIG_HOME", sandbox.config_home.path())
+        .arg("organize")
+        .arg(&missing)
+        .assert()
+        .failure()
+        .code(1)
+        .get_output()
+        .clone();
+
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Directory does not exist"));
+}
+
+#[test]
+fn test_invalid_config_fails() {
+    let sandbox = Sandbox::new();
+    sandbox.config("[[rules]]\nname = \"no destination\"\n");
+    sandbox.file("photo.jpg", b"jpg");
+
+    sandbox.organize(&["--mode", "custom"]).assert().failure().code(1);
+
+    assert_eq!(sandbox.files(), ["photo.jpg"]);
+}
+
+#[test]
+fn test_clean_after_recursive_organize() {
+    let sandbox = Sandbox::new();
+    sandbox.file("downloads/2024/photo.jpg", b"jpg");
+    fs::create_dir_all(sandbox.path().join("empty/also-empty")).unwrap();
+
+    sandbox.organize(&["--recursive"]).assert().success();
+    assert_eq!(sandbox.dirs(), ["downloads", "downloads/2024", "empty", "empty/also-empty", "🖼️ Images"]);
+
+    sandbox.command(&["clean", "--dry-run"]).assert().success();
+    assert_eq!(sandbox.dirs().len(), 5);
+
+    sandbox.command(&["clean"]).assert().success();
+    assert_eq!(sandbox.dirs(), ["🖼️ Images"]);
+    assert_eq!(sandbox.files(), ["🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_clean_only_organizer_dirs() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    fs::create_dir_all(sandbox.path().join("mnt/usb")).unwrap();
+
+    sandbox.organize(&[]).assert().success();
+    sandbox.command(&["undo"]).assert().success();
+    assert_eq!(sandbox.dirs(), ["mnt", "mnt/usb", "🖼️ Images"]);
+
+    sandbox.command(&["clean", "--only-organizer-dirs"]).assert().success();
+    assert_eq!(sandbox.dirs(), ["mnt", "mnt/usb"]);
+    assert_eq!(sandbox.files(), ["photo.jpg"]);
+}
+
+#[test]
+fn test_undo_restores_layout() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.file("nested/notes.txt", b"txt");
+    let before = sandbox.files();
+
+    sandbox.organize(&["--recursive"]).assert().success();
+    assert_ne!(sandbox.files(), before);
+    assert_eq!(sandbox.journals(), 1);
+
+    sandbox.command(&["undo"]).assert().success();
+    assert_eq!(sandbox.files(), before);
+    assert_eq!(sandbox.journals(), 0);
+    sandbox.command(&["undo"]).assert().success().stdout(predicates::str::contains("Nothing to undo"));
+}
+
+#[test]
+fn test_undo_operation_id() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    let output = sandbox.organize(&[]).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let line = stdout.lines().find(|line| line.contains("to revert")).unwrap_or_else(|| panic!("{}", stdout));
+    let id = line.split("--operation ").nth(1).unwrap().split('`').next().unwrap();
+    assert!(line.contains(&format!("Operation #{}", id)));
+    sandbox.file("notes.txt", b"txt");
+    sandbox.organize(&[]).assert().success();
+
+    sandbox.command(&["undo", "--operation", id]).assert().success();
+    assert_eq!(sandbox.files(), ["photo.jpg", "📄 Documents/notes.txt"]);
+    sandbox
+        .command(&["undo", "--operation", id])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(format!("No operation {} in the history", id)));
+}
+
+#[test]
+fn test_undo_older_operation_keeps_newer_ids() {
+    let sandbox = Sandbox::new();
+    let organize = || {
+        let output = sandbox.organize(&[]).output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let line = stdout.lines().find(|line| line.contains("to revert")).unwrap_or_else(|| panic!("{}", stdout));
+        line.split("--operation ").nth(1).unwrap().split('`').next().unwrap().to_string()
+    };
+    sandbox.file("a.jpg", b"jpg");
+    let first = organize();
+    sandbox.file("b.pdf", b"pdf");
+    let second = organize();
+    sandbox.command(&["undo", "--operation", &first]).assert().success();
+
+    // The next run doesn't take the id of the one still in the history
+    sandbox.file("c.txt", b"txt");
+    let third = organize();
+    assert!(third != first && third != second, "{} reused", third);
+    assert_eq!(sandbox.journals(), 2);
+
+    sandbox.command(&["undo"]).assert().success();
+    sandbox.command(&["undo"]).assert().success();
+    assert_eq!(sandbox.files(), ["a.jpg", "b.pdf", "c.txt"]);
+}
+
+#[test]
+fn test_undo_conflict_is_not_overwritten() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"first download");
+    sandbox.organize(&[]).assert().success();
+    sandbox.file("photo.jpg", b"second download");
+
+    sandbox
+        .command(&["undo"])
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(predicates::str::contains("1 of 1 files could not be restored"));
+    assert_eq!(fs::read(sandbox.path().join("photo.jpg")).unwrap(), b"second download");
+    assert_eq!(sandbox.journals(), 1);
+
+    // The refused entry is still in the journal, so it can be retried with another policy
+    sandbox.command(&["undo", "--on-conflict", "rename"]).assert().success();
+    assert_eq!(sandbox.files(), ["photo (1).jpg", "photo.jpg"]);
+    assert_eq!(fs::read(sandbox.path().join("photo (1).jpg")).unwrap(), b"first download");
+    assert_eq!(sandbox.journals(), 0);
+}
+
+#[test]
+fn test_undo_skipped_conflict_can_be_retried() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"first download");
+    sandbox.organize(&[]).assert().success();
+    sandbox.file("photo.jpg", b"second download");
+
+    sandbox.command(&["undo", "--on-conflict", "skip"]).assert().success();
+    assert_eq!(fs::read(sandbox.path().join("photo.jpg")).unwrap(), b"second download");
+    assert_eq!(sandbox.journals(), 1);
+
+    // Once the way is clear, undoing again restores it
+    fs::remove_file(sandbox.path().join("photo.jpg")).unwrap();
+    sandbox.command(&["undo"]).assert().success();
+    assert_eq!(sandbox.files(), ["photo.jpg"]);
+    assert_eq!(fs::read(sandbox.path().join("photo.jpg")).unwrap(), b"first download");
+    assert_eq!(sandbox.journals(), 0);
+}
+
+#[test]
+fn test_history_lists_and_migrates_journals() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.organize(&[]).assert().success();
+    sandbox
+        .command(&["history"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Extension mode, 1 files"));
+
+    // A journal from before formats were versioned
+    sandbox.file(
+        ".file-organizer/history/2000-01-01-0001.json",
+        br#"{"id":"2000-01-01-0001","created_at":"2000-01-01T00:00:00Z","mode":"Size","moves":[]}"#,
+    );
+    sandbox
+        .command(&["history", "migrate", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("1 journals would be migrated"));
+    sandbox.command(&["history", "migrate"]).assert().success().stdout(predicates::str::contains("Migrated 1 journals"));
+    sandbox.command(&["history", "migrate"]).assert().success().stdout(predicates::str::contains("already up to date"));
+
+    let migrated = fs::read_to_string(sandbox.path().join(".file-organizer/history/2000-01-01-0001.json")).unwrap();
+    assert!(migrated.contains("\"version\": 2"), "{}", migrated);
+}
+
+#[test]
+fn test_global_history_across_directories() {
+    let sandbox = Sandbox::new();
+    let downloads = sandbox.path().join("Downloads");
+    let camera = sandbox.path().join("Camera");
+    sandbox.file("Downloads/report.pdf", b"pdf");
+    sandbox.file("Camera/photo.jpg", b"jpg");
+    sandbox.command_in(&downloads, &["organize"]).assert().success();
+    sandbox.command_in(&camera, &["organize", "--mode", "date"]).assert().success();
+
+    let listed = sandbox.command(&["history", "--global"]).assert().success();
+    let stdout = String::from_utf8(listed.get_output().stdout.clone()).unwrap();
+    assert!(stdout.contains("Extension mode, 1 files"), "{}", stdout);
+    assert!(stdout.contains("Date mode, 1 files"), "{}", stdout);
+
+    sandbox
+        .command(&["history", "--global", "--mode", "date"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Camera").and(predicates::str::contains("Downloads").not()));
+    sandbox
+        .command(&["history", "--global", "--until", "2000-01-01"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("No operations found"));
+    sandbox
+        .command_in(&downloads, &["history", "--global"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Extension mode").and(predicates::str::contains("Date mode").not()));
+}
+
+#[test]
+fn test_only_categories() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.file("clip.mp4", b"mp4");
+    sandbox.file("report.pdf", b"pdf");
+
+    sandbox.organize(&["--only-categories", "images,Videos"]).assert().success();
+    assert_eq!(sandbox.files(), ["report.pdf", "🎬 Videos/clip.mp4", "🖼️ Images/photo.jpg"]);
+
+    sandbox
+        .organize(&["--only-categories", "Pictures"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("unknown category 'Pictures'"));
+}
+
+#[test]
+fn test_exclude_filters() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.file("main.rs", b"fn main() {}");
+    sandbox.file("backup.zip", b"zip");
+    sandbox.file("report.pdf", b"pdf");
+
+    sandbox
+        .organize(&["--filter", "jpg", "--exclude-categories", "Images"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("--filter jpg can't match anything"));
+    sandbox.organize(&["--exclude-categories", "code,archives", "--exclude-ext", "PDF"]).assert().success();
+    assert_eq!(sandbox.files(), ["backup.zip", "main.rs", "report.pdf", "🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_merge_and_undo() {
+    let sandbox = Sandbox::new();
+    let old = tempfile::tempdir().unwrap();
+    fs::create_dir_all(old.path().join("Images")).unwrap();
+    fs::write(old.path().join("Images/same.jpg"), b"same").unwrap();
+    fs::write(old.path().join("Images/differs.jpg"), b"old").unwrap();
+    fs::write(old.path().join("notes.txt"), b"notes").unwrap();
+    sandbox.file("Images/same.jpg", b"same");
+    sandbox.file("Images/differs.jpg", b"new");
+    let old_path = old.path().to_str().unwrap();
+
+    sandbox
+        .command(&["merge", old_path, "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("2 files would be moved (1 renamed), 1 identical"));
+    sandbox.command(&["merge", old_path]).assert().success().stdout(predicates::str::contains("Undo with"));
+    assert_eq!(sandbox.files(), ["Images/differs (1).jpg", "Images/differs.jpg", "Images/same.jpg", "notes.txt"]);
+    assert!(old.path().join("Images/same.jpg").exists());
+    assert!(!old.path().join("notes.txt").exists());
+
+    sandbox.command(&["undo"]).assert().success();
+    assert_eq!(sandbox.files(), ["Images/differs.jpg", "Images/same.jpg"]);
+    assert_eq!(fs::read(old.path().join("Images/differs.jpg")).unwrap(), b"old");
+    assert!(old.path().join("notes.txt").exists());
+}
+
+#[test]
+fn test_enforce_leaves_hidden_files() {
+    let sandbox = Sandbox::new();
+    sandbox.file(".env", b"SECRET=1");
+    sandbox.file("setup.iso", b"iso");
+
+    sandbox.command(&["enforce", "--max-size", "0"]).assert().success();
+    assert_eq!(sandbox.files(), [".env", "Archive/setup.iso"]);
+    sandbox.command(&["undo"]).assert().success();
+    sandbox.command(&["enforce", "--max-size", "0", "--include-hidden"]).assert().success();
+    assert_eq!(sandbox.files(), ["Archive/.env", "Archive/setup.iso"]);
+}
+
+#[test]
+fn test_enforce_size_budget() {
+    let sandbox = Sandbox::new();
+    let now = std::time::SystemTime::now();
+    for (name, days_ago) in [("old.iso", 30), ("older.zip", 60), ("new.pdf", 0)] {
+        let file = sandbox.file(name, &[b'x'; 1000]);
+        let modified = now - std::time::Duration::from_secs(days_ago * 86_400);
+        fs::File::options().write(true).open(&file).unwrap().set_modified(modified).unwrap();
+    }
+
+    sandbox
+        .command(&["enforce", "--max-size", "2000", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("older.zip (1000B) → Archive/older.zip"))
+        .stdout(predicates::str::contains("1 files (1000B) would be archived, leaving 1.95KB"));
+    assert_eq!(sandbox.files(), ["new.pdf", "old.iso", "older.zip"]);
+
+    sandbox.command(&["enforce", "--max-size", "1000"]).assert().success().stdout(predicates::str::contains("Archived 2 files"));
+    assert_eq!(sandbox.files(), ["Archive/old.iso", "Archive/older.zip", "new.pdf"]);
+    sandbox.command(&["enforce", "--max-size", "1000"]).assert().success().stdout(predicates::str::contains("within the budget"));
+
+    sandbox.command(&["undo"]).assert().success();
+    assert_eq!(sandbox.files(), ["new.pdf", "old.iso", "older.zip"]);
+
+    sandbox.command(&["enforce", "--max-size", "2500", "--strategy", "trash-oldest"]).assert().success();
+    assert_eq!(sandbox.files(), ["new.pdf", "old.iso"]);
+}
+
+#[test]
+fn test_enforce_keep_newest() {
+    let sandbox = Sandbox::new();
+    let now = std::time::SystemTime::now();
+    for minutes_ago in 1..=3 {
+        let file = sandbox.file(&format!("Screenshot {}.png", minutes_ago), b"png");
+        let modified = now - std::time::Duration::from_secs(minutes_ago * 60);
+        fs::File::options().write(true).open(&file).unwrap().set_modified(modified).unwrap();
+    }
+    let archive = tempfile::tempdir().unwrap();
+    let archive_path = archive.path().to_str().unwrap();
+
+    sandbox.command(&["enforce"]).assert().failure().stderr(predicates::str::contains("--max-size"));
+    sandbox.command(&["enforce", "--keep-newest", "0"]).assert().failure().stderr(predicates::str::contains("--keep-newest"));
+    sandbox
+        .command(&["enforce", "--keep-newest", "1", "--archive-dir", archive_path, "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Keeping the newest 1 files of each folder"))
+        .stdout(predicates::str::contains("2 files (6B) would be archived"));
+    assert_eq!(sandbox.files(), ["Screenshot 1.png", "Screenshot 2.png", "Screenshot 3.png"]);
+
+    sandbox.command(&["enforce", "--keep-newest", "1", "--archive-dir", archive_path]).assert().success();
+    assert_eq!(sandbox.files(), ["Screenshot 1.png"]);
+    assert!(archive.path().join("Screenshot 2.png").exists() && archive.path().join("Screenshot 3.png").exists());
+
+    sandbox.command(&["undo"]).assert().success();
+    assert_eq!(sandbox.files(), ["Screenshot 1.png", "Screenshot 2.png", "Screenshot 3.png"]);
+}
+
+#[test]
+fn test_regex_rule_captures() {
+    let sandbox = Sandbox::new();
+    sandbox.config(
+        "[[rules]]\nname = \"invoices\"\nregex = 'Invoice-(?P<year>\\d{4})-(?P<client>\\w+)'\ndestination = \"Invoices/{client}/{year}\"\n",
+    );
+    sandbox.file("Invoice-2024-acme.pdf", b"pdf");
+    sandbox.file("Invoice-2023-globex.pdf", b"pdf");
+    sandbox.file("notes.txt", b"txt");
+
+    sandbox.organize(&["--mode", "custom"]).assert().success();
+    assert_eq!(
+        sandbox.files(),
+        ["Invoices/acme/2024/Invoice-2024-acme.pdf", "Invoices/globex/2023/Invoice-2023-globex.pdf", "📄 Documents/notes.txt"]
+    );
+}
+
+#[test]
+fn test_rule_renames_and_undo_restores_names() {
+    let sandbox = Sandbox::new();
+    sandbox.config(
+        "[[rules]]\nname = \"photos\"\nextensions = [\"jpg\"]\ndestination = \"Photos/{yyyy}/{mm}\"\n\
+         rename = \"{yyyy}{mm}{dd}_{hhmmss}.{ext}\"\n",
+    );
+    sandbox.file("IMG_0001.jpg", b"jpg");
+    let taken = chrono::NaiveDate::from_ymd_opt(2024, 3, 9).unwrap().and_hms_opt(18, 4, 2).unwrap().and_utc();
+    let file = fs::File::options().write(true).open(sandbox.path().join("IMG_0001.jpg")).unwrap();
+    file.set_modified(taken.into()).unwrap();
+
+    sandbox.organize(&["--mode", "custom"]).assert().success();
+    assert_eq!(sandbox.files(), ["Photos/2024/03/20240309_180402.jpg"]);
+    sandbox.command(&["undo"]).assert().success();
+    assert_eq!(sandbox.files(), ["IMG_0001.jpg"]);
+}
+
+#[test]
+fn test_skip_rule_leaves_files_in_place() {
+    let sandbox = Sandbox::new();
+    sandbox.config(
+        "[[rules]]\nname = \"disk images\"\nextensions = [\"iso\"]\naction = \"ignore\"\n\n\
+         [[rules]]\nname = \"everything else\"\ndestination = \"Sorted\"\n",
+    );
+    sandbox.file("ubuntu.iso", b"iso");
+    sandbox.file("notes.txt", b"txt");
+
+    sandbox
+        .organize(&["--mode", "custom"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("1 files stay put because a skip rule matched them"));
+    assert_eq!(sandbox.files(), ["Sorted/notes.txt", "ubuntu.iso"]);
+}
+
+#[test]
+fn test_continue_rules_and_quarantine() {
+    let sandbox = Sandbox::new();
+    sandbox.config(
+        "[default_rule]\naction = \"quarantine\"\ndestination = \"Inbox\"\n\n\
+         [[rules]]\nname = \"tag drafts\"\npattern = \"draft*\"\nrename = \"DRAFT {name}.{ext}\"\ncontinue = true\n\n\
+         [[rules]]\nname = \"documents\"\nextensions = [\"txt\"]\ndestination = \"Docs\"\n",
+    );
+    sandbox.file("draft-plan.txt", b"d");
+    sandbox.file("draft-logo.png", b"p");
+    sandbox.file("notes.txt", b"n");
+
+    sandbox.organize(&["--mode", "custom"]).assert().success();
+    assert_eq!(sandbox.files(), ["Docs/DRAFT draft-plan.txt", "Docs/notes.txt", "Inbox/DRAFT draft-logo.png"]);
+}
+
+#[test]
+fn test_rules_lint() {
+    let sandbox = Sandbox::new();
+    sandbox.config(
+        "[[rules]]\nname = \"recent-photos\"\nwhen = \"ext in [jpg, png] AND age < 30d\"\ndestination = \"Recent\"\n\n\
+         [[rules]]\nname = \"recent-jpgs\"\nwhen = \"ext = jpg AND age < 7d\"\ndestination = \"Week\"\n",
+    );
+    sandbox.file("new.jpg", b"jpg");
+    sandbox.file("new.pdf", b"pdf");
+
+    sandbox
+        .command_without_path(&["rules", "lint"])
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("Rule 'recent-jpgs' is unreachable: rule 'recent-photos' comes first"));
+    sandbox.organize(&["--mode", "custom"]).assert().success();
+    assert_eq!(sandbox.files(), ["Recent/new.jpg", "📄 Documents/new.pdf"]);
+
+    sandbox.config("[[rules]]\nname = \"broken\"\nwhen = \"size > 5 AND\"\ndestination = \"Big\"\n");
+    sandbox
+        .command_without_path(&["rules", "lint"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid condition 'size > 5 AND': expected a test"));
+}
+
+#[test]
+fn test_manifest_check() {
+    let sandbox = Sandbox::new();
+    sandbox.file("Images/a.jpg", b"a");
+    sandbox.file("Documents/b.pdf", b"b");
+    let out = tempfile::tempdir().unwrap();
+    let manifest = out.path().join("library.csv");
+    let manifest_path = manifest.to_str().unwrap();
+
+    sandbox.command(&["manifest", "export", manifest_path]).assert().success().stdout(predicates::str::contains("Listed 2 files"));
+    assert!(fs::read_to_string(&manifest).unwrap().starts_with("path,size,hash,category\n"));
+    sandbox.command(&["manifest", "check", manifest_path]).assert().success().stdout(predicates::str::contains("2 intact"));
+
+    sandbox.file("Documents/b.pdf", b"corrupted");
+    sandbox
+        .command(&["manifest", "check", manifest_path])
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("changed Documents/b.pdf"));
+
+    // SHA-256 manifests are checked with SHA-256
+    sandbox.command(&["manifest", "export", "--hash", "sha256", manifest_path]).assert().success();
+    assert!(fs::read_to_string(&manifest).unwrap().starts_with("path,size,sha256,category\n"));
+    sandbox.command(&["manifest", "check", manifest_path]).assert().success().stdout(predicates::str::contains("2 intact"));
+}
+
+#[test]
+fn test_check_exit_code() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.file("report.pdf", b"pdf");
+
+    sandbox
+        .organize(&["--check"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("2 files in"));
+    assert_eq!(sandbox.files(), ["photo.jpg", "report.pdf"]);
+
+    sandbox.organize(&[]).assert().success();
+    sandbox.organize(&["--check"]).assert().success().stdout(predicates::str::contains("Everything is already organized"));
+    // Files already in their category folders aren't planned again
+    sandbox.organize(&["--check", "--recursive"]).assert().success();
+
+    sandbox.file("new.txt", b"txt");
+    sandbox.organize(&["--check", "--recursive"]).assert().failure();
+}
+
+#[test]
+fn test_preview_order() {
+    let sandbox = Sandbox::new();
+    for name in ["z.mp3", "b.jpg", "a.pdf", "c.jpg", "a.jpg", "main.rs", "backup.zip"] {
+        sandbox.file(name, b"x");
+    }
+
+    let output = sandbox.organize(&["--dry-run"]).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let position = |text: &str| stdout.find(text).unwrap_or_else(|| panic!("{} missing from {}", text, stdout));
+    // Folders by name whatever their emoji, files by name within each
+    assert!(position("Archives") < position("Audio") && position("Audio") < position("Code"));
+    assert!(position("Code") < position("Documents") && position("Documents") < position("Images"));
+    assert!(position("→ a.jpg") < position("→ b.jpg") && position("→ b.jpg") < position("→ c.jpg"));
+    // With their sizes, and each folder's total
+    assert!(stdout.contains("→ a.jpg (1B)") && stdout.contains("Images (3 files, 3B)"));
+    // Run after run
+    assert_eq!(String::from_utf8(sandbox.organize(&["--dry-run"]).output().unwrap().stdout).unwrap(), stdout);
+}
+
+#[test]
+fn test_preview_limits() {
+    let sandbox = Sandbox::new();
+    for name in ["a.jpg", "b.jpg", "c.jpg", "d.jpg", "song.mp3", "notes.pdf"] {
+        sandbox.file(name, b"x");
+    }
+
+    sandbox
+        .organize(&["--dry-run", "--preview-limit", "1"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("and 2 more folders (--preview-full lists them all)"))
+        .stdout(predicates::str::contains("Audio").and(predicates::str::contains("Images").not()))
+        .stdout(predicates::str::contains("Total: 6 files will be organized"));
+    sandbox
+        .organize(&["--dry-run", "--preview-full"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("→ d.jpg").and(predicates::str::contains("more files").not()))
+        .stdout(predicates::str::contains("more folders").not());
+    sandbox.organize(&["--dry-run", "--preview-full", "--preview-limit", "1"]).assert().failure();
+}
+
+#[test]
+fn test_no_extension_policy() {
+    let sandbox = Sandbox::new();
+    sandbox.file("LICENSE", b"MIT License");
+    sandbox.file("deploy", b"#!/bin/sh\n");
+    sandbox.file("photo.jpg", b"jpg");
+
+    sandbox
+        .organize(&["--dry-run", "--no-extension", "skip"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Skipped: 2 without an extension"));
+    sandbox.config("[categories]\nno_extension = \"folder\"\n");
+    sandbox.organize(&[]).assert().success();
+    assert_eq!(sandbox.files(), ["❔ No Extension/LICENSE", "💻 Code/deploy", "🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_skipped_summary() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.file(".env", b"secret");
+    sandbox.file("setup.iso", b"iso");
+
+    sandbox
+        .organize(&["--exclude-ext", "iso"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Skipped: 1 hidden, 1 excluded"))
+        .stdout(predicates::str::contains("setup.iso").not());
+
+    sandbox.file("notes.txt", b"txt");
+    sandbox
+        .command(&["--verbose", "organize", "--filter", "iso"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Skipped files:"))
+        .stdout(predicates::str::contains("notes.txt (filtered out)"));
+
+    let output = sandbox.organize(&["--dry-run", "--progress-format", "ndjson"]).output().unwrap();
+    let skipped: Vec<serde_json::Value> = String::from_utf8(output.stderr)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .filter(|event| event["event"] == "file_skipped")
+        .collect();
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0]["reason"], "hidden");
+    assert!(skipped[0]["source"].as_str().unwrap().ends_with(".env"));
+}
+
+#[test]
+fn test_log_levels() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.file("setup.iso", b"iso");
+    let stderr = |args: &[&str]| {
+        let output = sandbox.command(args).env_remove("RUST_LOG").output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+    let ruled_out = "--filter iso is ruled out by --exclude-ext";
+
+    let default = stderr(&["organize", "--dry-run", "--filter", "jpg,iso", "--exclude-ext", "iso"]);
+    assert!(default.contains(ruled_out) && !default.contains("Planned"));
+    let debug = stderr(&["-vv", "organize", "--dry-run", "--filter", "jpg,iso", "--exclude-ext", "iso"]);
+    assert!(debug.contains("Planned") && debug.contains("photo.jpg"));
+    assert!(stderr(&["-q", "organize", "--dry-run", "--filter", "jpg,iso", "--exclude-ext", "iso"]).is_empty());
+
+    sandbox.config("[execution]\n");
+    assert!(stderr(&["-v", "organize", "--dry-run"]).contains("Using config"));
+    sandbox.command(&["-v", "-q", "organize"]).assert().failure();
+}
+
+#[test]
+fn test_quiet() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"new");
+    sandbox.file("🖼️ Images/photo.jpg", b"old");
+    sandbox.file("report.pdf", b"pdf");
+
+    sandbox.command(&["-q", "organize", "--dry-run"]).assert().success().stdout("");
+    sandbox
+        .command(&["-q", "organize"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicates::str::contains("Failed to move photo.jpg: "));
+    assert_eq!(sandbox.files(), ["photo.jpg", "📄 Documents/report.pdf", "🖼️ Images/photo.jpg"]);
+
+    // Every other command is just as quiet
+    for args in [&["-q", "history"][..], &["-q", "enforce", "--max-size", "1GB", "--dry-run"], &["-q", "clean", "--dry-run"], &["-q", "undo"]] {
+        sandbox.command(args).assert().success().stdout("");
+    }
+    assert_eq!(sandbox.files(), ["photo.jpg", "report.pdf", "🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_verbose_timings() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+
+    sandbox.organize(&["--dry-run"]).assert().success().stdout(predicates::str::contains("Scan ").not());
+    sandbox
+        .command(&["-v", "organize", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicates::str::is_match(r"Scan \d+\.\d\ds, planning \d+\.\d\ds").unwrap())
+        .stdout(predicates::str::contains("moves").not());
+    sandbox.command(&["-v", "organize"]).assert().success().stdout(predicates::str::contains(", moves "));
+}
+
+#[test]
+fn test_ndjson_progress() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.file("report.pdf", b"pdf");
+
+    let output = sandbox.organize(&["--progress-format", "ndjson"]).output().unwrap();
+    assert!(output.status.success());
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stderr)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("{}: {}", line, e)))
+        .collect();
+    let names: Vec<&str> = events.iter().map(|event| event["event"].as_str().unwrap()).collect();
+    assert_eq!(names, ["scan_started", "file_planned", "file_planned", "file_moved", "file_moved", "summary"]);
+    assert!(events[3]["destination"].as_str().unwrap().contains("Documents"));
+    assert_eq!(events[3]["operation_type"], "move");
+    assert_eq!(events[5]["total_files"], 2);
+    assert_eq!(events[5]["folders_created"], 2);
+    assert_eq!(events[5]["folders_reused"], 0);
+    assert_eq!(events[5]["dry_run"], false);
+    assert_eq!(events[5]["timings"]["files"], 2);
+    assert_eq!(events[5]["timings"]["bytes"], 6);
+    // The pretty output still goes to stdout
+    assert!(String::from_utf8(output.stdout).unwrap().contains("completed successfully"));
+}
+
+#[test]
+fn test_ndjson_failing_run() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"new");
+    sandbox.file("🖼️ Images/photo.jpg", b"old");
+    sandbox.file("report.pdf", b"pdf");
+
+    let output = sandbox.command(&["-v", "organize", "--progress-format", "ndjson"]).output().unwrap();
+    assert!(!output.status.success());
+    // Every line of stderr is an event, even with failures and logs
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stderr)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("{}: {}", line, e)))
+        .collect();
+    assert!(events.iter().any(|event| event["event"] == "error" && event["source"].as_str().is_some_and(|source| source.ends_with("photo.jpg"))));
+    let last = events.last().unwrap();
+    assert_eq!(last["event"], "error");
+    assert!(last["message"].as_str().unwrap().contains("1 of 2 files could not be moved"));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Failed to move photo.jpg: ") && stdout.contains("1 files failed to move"), "{}", stdout);
+}
+
+#[test]
+fn test_spanish_messages() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+
+    sandbox
+        .organize(&[])
+        .env("LANG", "es_ES.UTF-8")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Se encontraron 1 archivos para procesar"))
+        .stdout(predicates::str::contains("¡Organización de archivos completada con éxito!"));
+    sandbox
+        .organize(&["--check"])
+        .env("LC_ALL", "C")
+        .env("LANG", "es_ES.UTF-8")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Everything is already organized"));
+}
+
+#[test]
+fn test_localized_category_folders() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.organize(&[]).assert().success();
+
+    sandbox.config("[categories]\nlanguage = \"es\"\n\n[categories.names]\nDocuments = \"Papeles\"\n");
+    sandbox.file("new.jpg", b"new");
+    sandbox.file("report.pdf", b"pdf");
+    sandbox.organize(&["--recursive"]).assert().success();
+    // The folder organized under the English name is recognized and left alone
+    assert_eq!(sandbox.files(), ["📄 Papeles/report.pdf", "🖼️ Images/photo.jpg", "🖼️ Imágenes/new.jpg"]);
+    sandbox.organize(&["--check", "--recursive"]).assert().success();
+}
+
+#[test]
+fn test_hidden_files() {
+    let sandbox = Sandbox::new();
+    sandbox.file(".notes.txt", b"hidden");
+    sandbox.file("Thumbs.db", b"system");
+    sandbox.file("todo.txt", b"todo");
+
+    sandbox.organize(&[]).assert().success();
+    assert_eq!(sandbox.files(), [".notes.txt", "Thumbs.db", "📄 Documents/todo.txt"]);
+
+    sandbox.organize(&["--include-hidden"]).assert().success();
+    assert_eq!(sandbox.files(), ["Thumbs.db", "📄 Documents/.notes.txt", "📄 Documents/todo.txt"]);
+}
+
+#[test]
+fn test_cloud_placeholders() {
+    let sandbox = Sandbox::new();
+    sandbox.file(".photo.jpg.icloud", b"stub");
+
+    sandbox
+        .organize(&["--include-hidden"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("1 cloud files aren't downloaded and stay put"));
+    assert_eq!(sandbox.files(), [".photo.jpg.icloud"]);
+
+    sandbox.organize(&["--include-hidden", "--hydrate"]).assert().success();
+    assert_eq!(sandbox.files(), ["📂 Other/.photo.jpg.icloud"]);
+}
+
+#[test]
+fn test_twin_folders_are_merged() {
+    let sandbox = Sandbox::new();
+    sandbox.file("Images/photo.jpg", b"old");
+    sandbox.file("photo.jpg", b"new");
+    sandbox.file("other.jpg", b"other");
+
+    sandbox
+        .organize(&["--on-conflict", "skip"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("2 files for 🖼️ Images go to Images"));
+    // No `🖼️ Images` next to `Images`; the name taken in `Images` goes by the conflict policy
+    assert_eq!(sandbox.files(), ["Images/other.jpg", "Images/photo.jpg", "photo.jpg"]);
+}
+
+#[test]
+fn test_metadata_dir_is_never_touched() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.organize(&[]).assert().success();
+    let history = sandbox.path().join(".file-organizer/history");
+    let journals: Vec<_> = fs::read_dir(&history).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    fs::create_dir_all(sandbox.path().join(".file-organizer/empty")).unwrap();
+    fs::copy(history.join(&journals[0]), sandbox.path().join(".file-organizer/copy.json")).unwrap();
+
+    sandbox.organize(&["--recursive"]).assert().success().stdout(predicates::str::contains("Found 1 files"));
+    assert!(sandbox.path().join(".file-organizer/copy.json").exists());
+    sandbox.command(&["clean"]).assert().success();
+    assert!(sandbox.path().join(".file-organizer/empty").is_dir());
+    sandbox.command(&["dedupe"]).assert().success().stdout(predicates::str::contains("copies").not());
+
+    let metadata = sandbox.path().join(".file-organizer");
+    for subcommand in ["organize", "clean", "dedupe"] {
+        sandbox
+            .command_without_path(&[subcommand, metadata.to_str().unwrap()])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("holds the organizer's own history"));
+    }
+    assert_eq!(sandbox.journals(), 1);
+}
+
+#[test]
+fn test_dedupe_folders() {
+    let sandbox = Sandbox::new();
+    for folder in ["album", "album (1)"] {
+        sandbox.file(&format!("{}/a.jpg", folder), b"aaaa");
+        sandbox.file(&format!("{}/b.jpg", folder), b"bbbb");
+    }
+    sandbox.file("single.txt", b"aaaa");
+
+    sandbox
+        .command(&["dedupe", "--folders"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("keep album").and(predicates::str::contains("copy album (1)")))
+        .stdout(predicates::str::contains("would reclaim 8B"));
+    sandbox.command(&["dedupe"]).assert().success().stdout(predicates::str::contains("3 copies"));
+
+    // Declining (no terminal here) deletes nothing
+    sandbox.command(&["dedupe", "--folders", "--remove"]).assert().success().stdout(predicates::str::contains("Nothing was deleted"));
+    assert_eq!(sandbox.files().len(), 5);
+    sandbox.command(&["dedupe", "--folders", "--remove", "--force"]).assert().success();
+    assert_eq!(sandbox.files(), ["album/a.jpg", "album/b.jpg", "single.txt"]);
+}
+
+#[test]
+fn test_learn_unknown() {
+    let sandbox = Sandbox::new();
+    sandbox.file("a.xyz", b"a");
+    sandbox.file("b.xyz", b"b");
+    sandbox.file("notes.abc", b"c");
+    sandbox.file("photo.jpg", b"jpg");
+
+    // The first answer is saved; the input ends before the second question
+    sandbox
+        .organize(&["--dry-run", "--learn-unknown"])
+        .write_stdin("documents\n")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(".xyz  2 files").and(predicates::str::contains(".abc  1 files")))
+        .stdout(predicates::str::contains("Saved 1 extensions to"))
+        .stdout(predicates::str::contains("[categories.extensions]\nabc = \"Other\"  # 1 files"));
+    let config = fs::read_to_string(sandbox.config_home.path().join("file-organizer/config.toml")).unwrap();
+    assert_eq!(config, "[categories.extensions]\nxyz = \"Documents\"\n");
+
+    sandbox.organize(&[]).assert().success();
+    assert_eq!(sandbox.files(), ["📂 Other/notes.abc", "📄 Documents/a.xyz", "📄 Documents/b.xyz", "🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_hash_cache() {
+    let sandbox = Sandbox::new();
+    sandbox.file("a.jpg", b"same");
+    sandbox.file("b.jpg", b"same");
+
+    let hashed = |expected: &str| {
+        sandbox.command(&["--verbose", "dedupe"]).assert().success().stdout(predicates::str::contains(expected.to_string()));
+    };
+    hashed("Hash cache: 0 hits, 2 files hashed");
+    hashed("Hash cache: 2 hits, 0 files hashed");
+    // A changed file is hashed again
+    sandbox.file("b.jpg", b"diff");
+    hashed("Hash cache: 1 hits, 1 files hashed");
+
+    sandbox.command_without_path(&["cache", "clear"]).assert().success().stdout(predicates::str::contains("Forgot 2 cached hashes"));
+    hashed("Hash cache: 0 hits, 2 files hashed");
+
+    // BLAKE3 hashes don't stand in for SHA-256 ones
+    sandbox
+        .command(&["--verbose", "dedupe", "--hash", "sha256"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Hash cache: 0 hits, 2 files hashed"));
+}
+
+#[test]
+fn test_import_from_card() {
+    let sandbox = Sandbox::new();
+    let card = tempfile::tempdir().unwrap();
+    fs::create_dir_all(card.path().join("DCIM/100CANON")).unwrap();
+    fs::write(card.path().join("DCIM/100CANON/IMG_0001.JPG"), b"first").unwrap();
+    fs::write(card.path().join("DCIM/100CANON/IMG_0002.JPG"), b"second").unwrap();
+    let media = card.path().to_str().unwrap();
+
+    sandbox
+        .command(&["import", media, "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("2 new files would be copied"));
+    assert!(sandbox.files().is_empty());
+
+    sandbox.command(&["import", media, "--date-buckets", "year"]).assert().success().stdout(predicates::str::contains("Copied 2 new files"));
+    assert_eq!(sandbox.files().len(), 2);
+    assert!(sandbox.files().iter().all(|file| file.starts_with("📅 ")));
+    assert!(card.path().join("DCIM/100CANON/IMG_0001.JPG").exists());
+
+    fs::write(card.path().join("DCIM/100CANON/IMG_0003.JPG"), b"third").unwrap();
+    sandbox
+        .command(&["import", media, "--date-buckets", "year"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Copied 1 new files, 2 were imported before"));
+    assert_eq!(sandbox.files().len(), 3);
+}
+
+#[test]
+fn test_analyze_is_read_only() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.file("notes.txt", b"txt");
+
+    sandbox
+        .organize(&["--analyze"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("ANALYSIS MODE").and(predicates::str::contains("photo.jpg")));
+    assert_eq!(sandbox.files(), ["notes.txt", "photo.jpg"]);
+    assert!(!sandbox.path().join(".file-organizer").exists());
+    assert!(!sandbox.config_home.path().join("file-organizer").exists());
+    sandbox.organize(&["--analyze", "--resume"]).assert().failure();
+}
+
+#[test]
+fn test_min_free_space() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+
+    sandbox
+        .organize(&["--dry-run", "--min-free", "1000TB"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Not enough room"));
+    sandbox
+        .organize(&["--min-free", "1000TB"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Not enough room").and(predicates::str::contains("nothing was moved")));
+    assert_eq!(sandbox.files(), ["photo.jpg"]);
+
+    sandbox.config("[execution]\nmin_free = \"1KB\"\n");
+    sandbox.organize(&[]).assert().success();
+    assert_eq!(sandbox.files(), ["🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_weird_names() {
+    let sandbox = Sandbox::new();
+    sandbox.file("plain.txt", b"txt");
+    sandbox.file("naïve.txt", b"txt");
+    sandbox.file("sub/an-unusually-long-name.txt", b"txt");
+
+    sandbox
+        .command(&["find", "weird-names", "--longer-than", "20"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("2 files with weird names"))
+        .stdout(predicates::str::contains("26 characters long"))
+        .stdout(predicates::str::contains("plain.txt").not());
+
+    // Quarantine them with a catch-all rule
+    sandbox.config("[[rules]]\nname = \"quarantine\"\npattern = \"*\"\ndestination = \"Quarantine\"\n");
+    sandbox
+        .organize(&["--recursive", "--mode", "custom", "--weird-names", "--name-longer-than", "20"])
+        .assert()
+        .success();
+    assert_eq!(sandbox.files(), ["Quarantine/an-unusually-long-name.txt", "Quarantine/naïve.txt", "plain.txt"]);
+    sandbox.command(&["find", "weird-names"]).assert().success().stdout(predicates::str::contains("Quarantine/naïve.txt"));
+}
+
+#[test]
+fn test_resume_interrupted_run() {
+    let sandbox = Sandbox::new();
+    sandbox.command(&["organize", "--resume"]).assert().success().stdout(predicates::str::contains("No interrupted run"));
+
+    // Checkpointed after a.jpg; b.jpg moved before the crash, gone.txt was deleted since
+    sandbox.file("🖼️ Images/a.jpg", b"a");
+    sandbox.file("🖼️ Images/b.jpg", b"b");
+    sandbox.file("c.pdf", b"c");
+    sandbox.file(
+        ".file-organizer/resume.json",
+        r#"{
+  "version": 1,
+  "mode": "Extension",
+  "started_at": "2024-06-01T10:00:00Z",
+  "moved": [{ "source": "a.jpg", "destination": "🖼️ Images/a.jpg" }],
+  "remaining": [
+    { "source": "b.jpg", "destination": "🖼️ Images/b.jpg" },
+    { "source": "c.pdf", "destination": "📄 Documents/c.pdf" },
+    { "source": "gone.txt", "destination": "📄 Documents/gone.txt" }
+  ]
+}"#
+        .as_bytes(),
+    );
+    sandbox.organize(&[]).assert().failure().stderr(predicates::str::contains("--resume"));
+
+    sandbox
+        .command(&["organize", "--resume"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("2 files already moved, 1 to go").and(predicates::str::contains("1 planned files no longer exist")));
+    assert_eq!(sandbox.files(), ["📄 Documents/c.pdf", "🖼️ Images/a.jpg", "🖼️ Images/b.jpg"]);
+    assert!(!sandbox.path().join(".file-organizer/resume.json").exists());
+
+    // The journal covers the whole run, so undo restores every file
+    sandbox.command(&["undo"]).assert().success();
+    assert_eq!(sandbox.files(), ["a.jpg", "b.jpg", "c.pdf"]);
+}
+
+#[test]
+fn test_error_policy_from_config_and_flags() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"new");
+    sandbox.file("🖼️ Images/photo.jpg", b"old");
+    sandbox.file("report.pdf", b"pdf");
+    sandbox.config("[execution]\non_error = \"rollback\"\n");
+
+    // Whichever file goes first, nothing stays moved
+    sandbox.organize(&[]).assert().failure().stderr(predicates::str::contains("Stopped after a file could not be moved"));
+    assert_eq!(sandbox.files(), ["photo.jpg", "report.pdf", "🖼️ Images/photo.jpg"]);
+    assert_eq!(sandbox.journals(), 0);
+
+    sandbox
+        .organize(&["--on-error", "keep-going"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("1 of 2 files could not be moved"))
+        .stderr(predicates::str::contains("Failed to move photo.jpg: "));
+    assert_eq!(sandbox.files(), ["photo.jpg", "📄 Documents/report.pdf", "🖼️ Images/photo.jpg"]);
+    sandbox.command(&["organize", "--fail-fast", "--on-error", "rollback"]).assert().failure();
+}
+
+#[test]
+fn test_dry_run_previews_conflicts() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"new");
+    sandbox.file("🖼️ Images/photo.jpg", b"old");
+    sandbox.file("report.pdf", b"pdf");
+
+    sandbox
+        .organize(&["--dry-run", "--on-conflict", "rename"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Conflicts (1)").and(predicates::str::contains("already exists: will be renamed")));
+    sandbox.organize(&["--dry-run"]).assert().success().stdout(predicates::str::contains("will fail"));
+    assert_eq!(sandbox.files(), ["photo.jpg", "report.pdf", "🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_ask_per_category() {
+    let sandbox = Sandbox::new();
+    sandbox.file("song.mp3", b"mp3");
+    sandbox.file("report.pdf", b"pdf");
+    sandbox.file("photo.jpg", b"jpg");
+
+    // Folders are asked about in name order: Audio, Documents, Images
+    sandbox
+        .organize(&["--ask-per-category"])
+        .write_stdin("y\nn\nq\n")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Move 1 files to 🎵 Audio? [y/n/a/q]"));
+    assert_eq!(sandbox.files(), ["photo.jpg", "report.pdf", "🎵 Audio/song.mp3"]);
+
+    sandbox.organize(&["--ask-per-category"]).write_stdin("n\na\n").assert().success();
+    assert_eq!(sandbox.files(), ["report.pdf", "🎵 Audio/song.mp3", "🖼️ Images/photo.jpg"]);
+}
+
+#[test]
+fn test_pinned_files_stay_put() {
+    let sandbox = Sandbox::new();
+    let installer = sandbox.file("Setup.exe", b"exe");
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.file("notes.txt", b"txt");
+    sandbox.config("[pins]\npatterns = [\"notes.*\"]\n");
+
+    let pinned = sandbox.command_in(&installer, &["pin"]).assert();
+    if !pinned.get_output().status.success() {
+        // No user extended attributes where the temp dir lives
+        return;
+    }
+    sandbox.organize(&[]).assert().success();
+    assert_eq!(sandbox.files(), ["Setup.exe", "notes.txt", "🖼️ Images/photo.jpg"]);
+
+    sandbox.command_in(&installer, &["pin", "--remove"]).assert().success();
+    sandbox.organize(&[]).assert().success();
+    assert!(!sandbox.path().join("Setup.exe").exists());
+}
+
+#[test]
+fn test_stats_trend_across_runs() {
+    let sandbox = Sandbox::new();
+    sandbox.command(&["stats"]).assert().success().stdout(predicates::str::contains("No runs with statistics"));
+
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.file("blob.xyz", b"?");
+    sandbox.organize(&[]).assert().success();
+    sandbox.file("song.mp3", b"mp3");
+    sandbox.organize(&[]).assert().success();
+
+    sandbox
+        .command(&["stats"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("moved 1 files").and(predicates::str::contains("Audio")));
+    sandbox
+        .command(&["stats", "--trend"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("1 other").and(predicates::str::contains("went from 2 to 1 over 2 runs")));
+}
+
+#[test]
+fn test_other_by_extension() {
+    let sandbox = Sandbox::new();
+    sandbox.file("a.xyz", b"12");
+    sandbox.file("b.xyz", b"34");
+    sandbox.file("c.tar.qq", b"5");
+    sandbox.file("photo.jpg", b"jpg");
+
+    sandbox
+        .organize(&[])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(".xyz").and(predicates::str::contains("--learn-unknown")));
+    sandbox
+        .command(&["stats"])
+        .assert()
+        .success()
+        .stdout(
+            predicates::str::contains("Unknown types by extension")
+                .and(predicates::str::contains("[categories.extensions]\nxyz = \"Other\"  # 2 files, 4B\n\"tar.qq\" = \"Other\"")),
+        );
+}
+
+#[test]
+fn test_history_export_and_import() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.organize(&[]).assert().success();
+    let export = sandbox.config_home.path().join("history.json");
+    let export_arg = export.to_str().unwrap();
+
+    sandbox
+        .command(&["history", "export", export_arg])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Exported 1 operations"));
+    fs::remove_dir_all(sandbox.path().join(".file-organizer")).unwrap();
+    sandbox
+        .command(&["history", "import", export_arg])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Imported 1 operations"));
+
+    sandbox.command(&["undo"]).assert().success();
+    assert_eq!(sandbox.files(), ["photo.jpg"]);
+}
+
+#[test]
+fn test_undo_dry_run_changes_nothing() {
+    let sandbox = Sandbox::new();
+    sandbox.file("photo.jpg", b"jpg");
+    sandbox.organize(&[]).assert().success();
+
+    sandbox.command(&["undo", "--dry-run"]).assert().success();
+
+    assert_eq!(sandbox.files(), ["🖼️ Images/photo.jpg"]);
+}